@@ -0,0 +1,30 @@
+//! Click the first element whose accessible name matches a regex, without
+//! showing anything; builds with `--no-default-features`
+//!
+//! ```sh
+//! cargo run --no-default-features --example auto-click-by-name -- '^Save$'
+//! ```
+
+use anyhow::Context;
+use regex::Regex;
+use vimium_linux::config::Config;
+use vimium_linux::provider::{self, ElementKind};
+use vimium_linux::{atspi, click};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let pattern = std::env::args().nth(1).context("usage: auto-click-by-name <name regex>")?;
+    let name = Regex::new(&pattern)?;
+
+    let config = Config::load();
+    let mut elements = provider::collect(ElementKind::Clickable, &config).await?;
+    atspi::resolve_names(&mut elements).await?;
+
+    let element = elements
+        .iter()
+        .find(|e| name.is_match(&e.name))
+        .with_context(|| format!("No element named {:?}", pattern))?;
+    let (x, y) = element.center();
+    println!("Clicking {} {:?} at ({}, {})", element.role, element.name, x, y);
+    click::click_at(x, y)
+}
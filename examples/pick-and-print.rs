@@ -0,0 +1,25 @@
+//! Show hints over every clickable element and print the picked one as JSON
+//!
+//! ```sh
+//! cargo run --example pick-and-print
+//! ```
+
+use vimium_linux::config::Config;
+use vimium_linux::provider::{self, ElementKind};
+use vimium_linux::{atspi, hints, outcome, overlay};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Config::load();
+    let elements = provider::collect(ElementKind::Clickable, &config).await?;
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+
+    let Some((picked, _action)) = overlay::show_and_select(hinted, config, None).await? else {
+        eprintln!("Cancelled");
+        std::process::exit(1);
+    };
+    let mut element = picked.element;
+    atspi::resolve_names(std::slice::from_mut(&mut element)).await?;
+    println!("{}", outcome::element_json(&element));
+    Ok(())
+}
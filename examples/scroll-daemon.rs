@@ -0,0 +1,42 @@
+//! Scroll the focused window's main area by commands read from stdin, one per
+//! line: `down`, `up 5`, `left`, `right 2`; builds with `--no-default-features`
+//!
+//! ```sh
+//! mkfifo /tmp/scroll && cargo run --no-default-features --example scroll-daemon < /tmp/scroll
+//! echo "down 5" > /tmp/scroll
+//! ```
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use vimium_linux::atspi;
+use vimium_linux::click::{self, ScrollDirection};
+use vimium_linux::config::Config;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Config::load();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let mut words = line.split_whitespace();
+        let Some(direction) = words.next() else {
+            continue;
+        };
+        let direction: ScrollDirection = match direction.parse() {
+            Ok(direction) => direction,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let amount = words.next().and_then(|a| a.parse().ok()).unwrap_or(3);
+
+        // Look the area up every time: focus may have moved since the last command
+        match atspi::get_focused_scrollable(&config.scan).await? {
+            Some(area) => {
+                let (x, y) = area.center();
+                click::scroll_at(x, y, direction, amount)?;
+            }
+            None => eprintln!("No focused window to scroll"),
+        }
+    }
+    Ok(())
+}
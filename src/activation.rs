@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+/// Token the compositor issued for the last selection, until a program is started with it
+static TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keep the token issued for a selection, or forget the last one with `None`
+pub fn set_token(token: Option<String>) {
+    *TOKEN.lock().unwrap_or_else(|e| e.into_inner()) = token;
+}
+
+/// Environment handing the last selection's token to a program started for it
+///
+/// Tokens are single-use, so only the first program gets it. Both the
+/// xdg-activation variable and the older startup-notification one are set,
+/// which is what GTK, Qt and browsers look at when mapping their first window.
+pub fn take_environment() -> Vec<(&'static str, String)> {
+    let token = TOKEN.lock().unwrap_or_else(|e| e.into_inner()).take();
+    token
+        .map(|token| vec![("XDG_ACTIVATION_TOKEN", token.clone()), ("DESKTOP_STARTUP_ID", token)])
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_environment() {
+        set_token(Some("abc".to_string()));
+        let env = take_environment();
+        assert_eq!(env.len(), 2);
+        assert!(env.iter().all(|(_, value)| value == "abc"));
+        assert!(take_environment().is_empty());
+
+        set_token(Some("stale".to_string()));
+        set_token(None);
+        assert!(take_environment().is_empty());
+    }
+}
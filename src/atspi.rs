@@ -1,19 +1,33 @@
+use crate::bus_cache;
+use crate::config::ScanConfig;
+use crate::metrics;
+use crate::timings;
 use anyhow::{Context, Result};
+use atspi::proxy::action::ActionProxy;
 use atspi::proxy::component::ComponentProxy;
+use atspi::proxy::text::TextProxy;
 use atspi::Role;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use futures_util::StreamExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, info, warn};
 use zbus::{Address, Connection};
 
 /// Represents a clickable UI element with screen coordinates
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ClickableElement {
+    /// Accessible name (empty until fetched, see `fetch_names`)
     pub name: String,
     pub role: String,
     pub x: i32,
     pub y: i32,
     pub width: i32,
     pub height: i32,
+    /// Bus name of the application owning the element
+    pub bus_name: String,
+    /// Object path of the element on the accessibility bus
+    pub path: String,
 }
 
 impl ClickableElement {
@@ -21,6 +35,22 @@ impl ClickableElement {
     pub fn center(&self) -> (i32, i32) {
         (self.x + self.width / 2, self.y + self.height / 2)
     }
+
+    /// Identifies the element on the accessibility bus ("bus_name:path")
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.bus_name, self.path)
+    }
+
+    /// Short ID for the element, stable for as long as the element exists
+    ///
+    /// This is a 64-bit FNV-1a hash of `key()`, so it is the same across runs
+    /// and builds, unlike `std`'s default hasher.
+    pub fn id(&self) -> String {
+        let hash = self.key().bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{:016x}", hash)
+    }
 }
 
 /// Roles that are typically clickable/actionable
@@ -74,24 +104,662 @@ fn is_text_input_role(role: Role) -> bool {
     )
 }
 
+/// Roles marking out a document's structure, for `read`
+fn is_heading_role(role: Role) -> bool {
+    matches!(
+        role,
+        Role::Heading | Role::Label | Role::Static | Role::Paragraph | Role::Caption
+    )
+}
+
+/// Roles showing text worth searching
+fn is_readable_role(role: Role) -> bool {
+    matches!(
+        role,
+        Role::Label
+            | Role::Static
+            | Role::Text
+            | Role::Paragraph
+            | Role::Heading
+            | Role::Caption
+            | Role::DocumentText
+            | Role::Link
+            | Role::ListItem
+            | Role::TableCell
+            | Role::Entry
+            | Role::Terminal
+    )
+}
+
 /// Query AT-SPI for all clickable elements
-pub async fn get_clickable_elements() -> Result<Vec<ClickableElement>> {
-    collect_elements(|role| is_actionable_role(role)).await
+pub async fn get_clickable_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(is_actionable_role, scan).await
 }
 
 /// Query AT-SPI for scrollable elements
-pub async fn get_scrollable_elements() -> Result<Vec<ClickableElement>> {
-    collect_elements(|role| is_scrollable_role(role)).await
+pub async fn get_scrollable_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(is_scrollable_role, scan).await
 }
 
 /// Query AT-SPI for text input elements
-pub async fn get_text_elements() -> Result<Vec<ClickableElement>> {
-    collect_elements(|role| is_text_input_role(role)).await
+pub async fn get_text_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(is_text_input_role, scan).await
+}
+
+/// Query AT-SPI for headings, labels and static text, for `read`
+pub async fn get_heading_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(is_heading_role, scan).await
+}
+
+/// Query AT-SPI for browser and notebook tabs, for `tabs`
+pub async fn get_tab_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(|role| role == Role::PageTab, scan).await
+}
+
+/// Query AT-SPI for links, for `open-link`
+pub async fn get_link_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(|role| role == Role::Link, scan).await
+}
+
+/// Query AT-SPI for elements with text to copy, for `yank`
+pub async fn get_yank_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(|role| is_actionable_role(role) || is_readable_role(role), scan).await
+}
+
+/// Query AT-SPI for elements showing text, for `search`
+pub async fn get_readable_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(is_readable_role, scan).await
+}
+
+/// Query AT-SPI for clickable elements inside a container, such as a scrolled pane
+///
+/// Only the container's subtree is walked. Elements scrolled out of view may
+/// still be reported, with positions outside the container.
+pub async fn get_clickable_elements_in(
+    container: &ClickableElement,
+    scan: &ScanConfig,
+) -> Result<Vec<ClickableElement>> {
+    collect_elements_in(container, is_actionable_role, scan).await
+}
+
+/// Query AT-SPI for text input elements inside a container, such as a dialog
+pub async fn get_text_elements_in(container: &ClickableElement, scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements_in(container, is_text_input_role, scan).await
+}
+
+/// Collect elements matching `role_filter` from a container's subtree
+async fn collect_elements_in(
+    container: &ClickableElement,
+    role_filter: fn(Role) -> bool,
+    scan: &ScanConfig,
+) -> Result<Vec<ClickableElement>> {
+    with_reconnect("container scan", || async move {
+        let conn = a11y_connection()
+            .await
+            .context("Failed to connect to accessibility bus")?;
+        let mut walk = Walk::new(&conn, &role_filter, scan);
+        collect_from_accessible(&mut walk, &container.bus_name, &container.path, 0).await;
+        walk.finish()
+    })
+    .await
+}
+
+/// A top-level window that became active
+#[derive(Debug, Clone)]
+pub struct ActivatedWindow {
+    /// The window, without its extents
+    pub window: ClickableElement,
+    /// Accessible name of the application owning it
+    pub app: String,
+}
+
+/// Ask applications to report window activations and send each one on `windows`
+///
+/// Runs until the accessibility bus goes away or `windows` is closed.
+pub async fn watch_window_activations(windows: mpsc::UnboundedSender<ActivatedWindow>) -> Result<()> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    // Applications only emit the events some client registered for
+    atspi::proxy::registry::RegistryProxy::new(&conn)
+        .await?
+        .register_event("window:activate")
+        .await
+        .context("Failed to register for window events")?;
+    let rule = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.a11y.atspi.Event.Window")?
+        .member("Activate")?
+        .build();
+    let mut events = zbus::MessageStream::for_match_rule(rule, &conn, None).await?;
+    info!("Watching for window activations");
+
+    while let Some(event) = events.next().await {
+        let Ok(event) = event else {
+            continue;
+        };
+        let header = event.header();
+        let (Some(sender), Some(path)) = (header.sender(), header.path()) else {
+            continue;
+        };
+        let (bus_name, path) = (sender.to_string(), path.to_string());
+        let Ok(proxy) = accessible_proxy(&conn, &bus_name, &path).await else {
+            continue;
+        };
+        let role = proxy.get_role().await.map(|r| format!("{:?}", r)).unwrap_or_default();
+        let name = proxy.name().await.unwrap_or_default();
+        let app = match proxy.get_application().await {
+            Ok(app) => match accessible_proxy(&conn, app.name.as_str(), app.path.as_str()).await {
+                Ok(app) => app.name().await.unwrap_or_default(),
+                Err(_) => String::new(),
+            },
+            Err(_) => String::new(),
+        };
+        debug!("Window activated: {:?} {:?} of {:?}", role, name, app);
+        let window = ClickableElement {
+            name,
+            role,
+            bus_name,
+            path,
+            ..Default::default()
+        };
+        if windows.send(ActivatedWindow { window, app }).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// How long window moves are gathered before elements are measured again
+const MOVE_SETTLE: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Follow moves and resizes of the windows owning `elements`, reporting each
+/// element whose bounds changed as (key, (x, y, width, height))
+///
+/// Only the elements of applications that moved something are measured
+/// again, without a rescan. Runs until `on_moved` returns `false` or the
+/// accessibility bus goes away.
+pub async fn watch_bounds<F>(elements: Vec<ClickableElement>, mut on_moved: F) -> Result<()>
+where
+    F: FnMut(String, (i32, i32, i32, i32)) -> bool,
+{
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let registry = atspi::proxy::registry::RegistryProxy::new(&conn).await?;
+    for event in ["window:move", "window:resize", "object:bounds-changed"] {
+        registry
+            .register_event(event)
+            .await
+            .with_context(|| format!("Failed to register for {} events", event))?;
+    }
+    let windows = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.a11y.atspi.Event.Window")?
+        .build();
+    let objects = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.a11y.atspi.Event.Object")?
+        .member("BoundsChanged")?
+        .build();
+    let mut events = futures_util::stream::select(
+        zbus::MessageStream::for_match_rule(windows, &conn, None).await?,
+        zbus::MessageStream::for_match_rule(objects, &conn, None).await?,
+    );
+    let mut bounds: HashMap<String, (i32, i32, i32, i32)> =
+        elements.iter().map(|e| (e.key(), (e.x, e.y, e.width, e.height))).collect();
+    debug!("Following moves of {} elements", elements.len());
+
+    while let Some(event) = events.next().await {
+        // A drag sends a stream of moves; measure once they pause
+        let mut moved = HashSet::new();
+        let mut note = |event: zbus::Result<zbus::Message>| {
+            let Ok(event) = event else {
+                return;
+            };
+            let header = event.header();
+            let member = header.member().map(|m| m.as_str());
+            if let (Some("Move" | "Resize" | "BoundsChanged"), Some(sender)) = (member, header.sender()) {
+                moved.insert(sender.to_string());
+            }
+        };
+        note(event);
+        let deadline = tokio::time::Instant::now() + MOVE_SETTLE;
+        while let Ok(Some(event)) = tokio::time::timeout_at(deadline, events.next()).await {
+            note(event);
+        }
+
+        for element in elements.iter().filter(|e| moved.contains(&e.bus_name)) {
+            let Ok(component) = ComponentProxy::builder(&conn)
+                .destination(element.bus_name.as_str())
+                .and_then(|b| b.path(element.path.as_str()))
+            else {
+                continue;
+            };
+            let Ok(component) = component.build().await else {
+                continue;
+            };
+            let Ok(rect) = component.get_extents(atspi::CoordType::Screen).await else {
+                continue;
+            };
+            let key = element.key();
+            if bounds.get(&key) != Some(&rect) {
+                bounds.insert(key.clone(), rect);
+                if !on_moved(key, rect) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn accessible_proxy(
+    conn: &Connection,
+    dest: &str,
+    path: &str,
+) -> Result<atspi::proxy::accessible::AccessibleProxy<'static>> {
+    Ok(atspi::proxy::accessible::AccessibleProxy::builder(conn)
+        .destination(dest.to_string())?
+        .path(path.to_string())?
+        .build()
+        .await?)
+}
+
+/// The focused window's main scrollable area: its largest scroll pane or document
+///
+/// Falls back to the window itself when it has neither.
+pub async fn get_focused_scrollable(scan: &ScanConfig) -> Result<Option<ClickableElement>> {
+    with_reconnect("scrollable lookup", || async move {
+        let conn = a11y_connection()
+            .await
+            .context("Failed to connect to accessibility bus")?;
+        let Some(window) = active_window(&conn, scan).await? else {
+            return Ok(None);
+        };
+        debug!("Active window is {}:{}", window.0, window.1);
+
+        let role_filter = |role| {
+            matches!(
+                role,
+                Role::ScrollPane | Role::DocumentFrame | Role::DocumentWeb | Role::Frame | Role::Window
+            )
+        };
+        let mut walk = Walk::new(&conn, &role_filter, scan);
+        collect_from_accessible(&mut walk, &window.0, &window.1, 0).await;
+        Ok(main_scrollable(walk.finish()?))
+    })
+    .await
+}
+
+/// The top-level window with the Active state, as (bus name, path)
+async fn active_window(conn: &Connection, scan: &ScanConfig) -> Result<Option<(String, String)>> {
+    let Some(apps) = registry_children(conn, scan).await? else {
+        return Ok(None);
+    };
+    Ok(active_window_of(conn, &apps).await)
+}
+
+/// The top-level window with the Active state among those of `apps`
+async fn active_window_of(conn: &Connection, apps: &[(String, String)]) -> Option<(String, String)> {
+    for (dest, path) in apps {
+        let Ok(app) = atspi::proxy::accessible::AccessibleProxy::builder(conn)
+            .destination(dest.as_str())
+            .and_then(|b| b.path(path.as_str()))
+        else {
+            continue;
+        };
+        let Ok(app) = app.build().await else {
+            continue;
+        };
+        for window in app.get_children().await.unwrap_or_default() {
+            let Ok(proxy) = atspi::proxy::accessible::AccessibleProxy::builder(conn)
+                .destination(window.name.clone())
+                .and_then(|b| b.path(window.path.clone()))
+            else {
+                continue;
+            };
+            let Ok(proxy) = proxy.build().await else {
+                continue;
+            };
+            if proxy.get_state().await.is_ok_and(|s| s.contains(atspi::State::Active)) {
+                return Some((window.name.to_string(), window.path.to_string()));
+            }
+        }
+    }
+    None
+}
+
+/// The largest scroll pane or document, else the largest window
+fn main_scrollable(elements: Vec<ClickableElement>) -> Option<ClickableElement> {
+    let area = |e: &ClickableElement| e.width as i64 * e.height as i64;
+    let (windows, panes): (Vec<_>, Vec<_>) = elements
+        .into_iter()
+        .partition(|e| e.role == format!("{:?}", Role::Frame) || e.role == format!("{:?}", Role::Window));
+    panes
+        .into_iter()
+        .max_by_key(area)
+        .or_else(|| windows.into_iter().max_by_key(area))
+}
+
+/// Query AT-SPI for terminal emulators
+pub async fn get_terminal_elements(scan: &ScanConfig) -> Result<Vec<ClickableElement>> {
+    collect_elements(|role| role == Role::Terminal, scan).await
+}
+
+/// Text proxy for an element implementing the Text interface
+async fn text_proxy(element: &ClickableElement) -> Result<TextProxy<'static>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    Ok(TextProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?)
+}
+
+async fn action_proxy(element: &ClickableElement) -> Result<ActionProxy<'static>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    Ok(ActionProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?)
+}
+
+/// The keyboard shortcut of an element's first action, as reported by the app
+pub async fn key_binding(element: &ClickableElement) -> Option<String> {
+    let action = action_proxy(element).await.ok()?;
+    let binding = action.get_key_binding(0).await.ok()?;
+    (!binding.is_empty()).then_some(binding)
+}
+
+/// Give `element` keyboard focus within its window, if its application has the
+/// active window; returns whether keys sent now reach the element's window
+pub async fn focus_for_keys(element: &ClickableElement) -> bool {
+    let Ok(conn) = a11y_connection().await else {
+        return false;
+    };
+    let app = (element.bus_name.clone(), "/org/a11y/atspi/accessible/root".to_string());
+    if active_window_of(&conn, std::slice::from_ref(&app)).await.is_none() {
+        return false;
+    }
+    // The window's shortcuts work without it, so a refusal is fine
+    let _ = grab_focus(element).await;
+    true
+}
+
+/// Give `element` keyboard focus, returning whether the application took it
+pub async fn grab_focus(element: &ClickableElement) -> Result<bool> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let component = ComponentProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?;
+    Ok(component.grab_focus().await?)
+}
+
+/// The URI a link points to, through the Hyperlink interface
+pub async fn link_uri(element: &ClickableElement) -> Result<String> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let link = atspi::proxy::hyperlink::HyperlinkProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?;
+    let uri = link.get_uri(0).await?;
+    if uri.is_empty() {
+        anyhow::bail!("The link has no URI");
+    }
+    Ok(uri)
+}
+
+/// Invoke an element's first action, which toolkits make its default
+pub async fn do_default_action(element: &ClickableElement) -> Result<()> {
+    let action = action_proxy(element).await?;
+    if action.nactions().await? == 0 {
+        anyhow::bail!("{} has no accessible action", element.role);
+    }
+    if !action.do_action(0).await? {
+        anyhow::bail!("{} refused its default action", element.role);
+    }
+    Ok(())
+}
+
+/// Names of actions that toggle or click a control, as toolkits report them
+const TOGGLE_ACTIONS: &[&str] = &["toggle", "click", "press", "check", "uncheck", "activate"];
+
+/// Toggle or click an element through its Action interface, without the pointer
+pub async fn do_toggle_action(element: &ClickableElement) -> Result<()> {
+    let action = action_proxy(element).await?;
+    let actions = action.get_actions().await?;
+    let Some(index) = actions
+        .iter()
+        .position(|(name, ..)| TOGGLE_ACTIONS.contains(&name.to_lowercase().as_str()))
+    else {
+        anyhow::bail!("{} has no toggle or click action", element.role);
+    };
+    if !action.do_action(index as i32).await? {
+        anyhow::bail!("{} refused its {} action", element.role, actions[index].0);
+    }
+    Ok(())
+}
+
+/// Read the full text of an element through the Text interface
+pub async fn get_text(element: &ClickableElement) -> Result<String> {
+    let text = text_proxy(element).await?;
+    let count = text.character_count().await?;
+    Ok(text.get_text(0, count).await?)
+}
+
+/// Screen bounds of the characters `start..end` of an element's text
+pub async fn get_text_range_extents(
+    element: &ClickableElement,
+    start: i32,
+    end: i32,
+) -> Result<(i32, i32, i32, i32)> {
+    let text = text_proxy(element).await?;
+    Ok(text
+        .get_range_extents(start, end, atspi::CoordType::Screen)
+        .await?)
+}
+
+/// Ask the application to scroll the characters `start..end` of an element's text into view
+pub async fn scroll_text_into_view(element: &ClickableElement, start: i32, end: i32) -> Result<()> {
+    let text = text_proxy(element).await?;
+    text.scroll_substring_to(start, end, atspi::ScrollType::Anywhere as u32)
+        .await?;
+    Ok(())
+}
+
+/// Move the text caret to the start of an element, e.g. a heading in a document
+pub async fn place_caret(element: &ClickableElement) -> Result<()> {
+    set_caret_offset(element, 0).await
+}
+
+/// Caret offset in an element's text, in characters
+pub async fn caret_offset(element: &ClickableElement) -> Result<i32> {
+    Ok(text_proxy(element).await?.caret_offset().await?)
+}
+
+/// Move the text caret of an element to `offset` characters
+pub async fn set_caret_offset(element: &ClickableElement, offset: i32) -> Result<()> {
+    let text = text_proxy(element).await?;
+    if !text.set_caret_offset(offset).await? {
+        anyhow::bail!("{} refused the caret", element.role);
+    }
+    Ok(())
+}
+
+/// Insert `text` at the caret of an editable element through the EditableText interface
+pub async fn insert_text(element: &ClickableElement, text: &str) -> Result<()> {
+    let caret = text_proxy(element).await?.caret_offset().await?.max(0);
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let editable = atspi::proxy::editable_text::EditableTextProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?;
+    if !editable.insert_text(caret, text, text.len() as i32).await? {
+        anyhow::bail!("{} refused the text", element.role);
+    }
+    Ok(())
+}
+
+/// Select the characters `start..end` of an element's text, replacing its first selection
+pub async fn set_selection(element: &ClickableElement, start: i32, end: i32) -> Result<()> {
+    let text = text_proxy(element).await?;
+    let selected = if text.get_nselections().await? > 0 {
+        text.set_selection(0, start, end).await?
+    } else {
+        text.add_selection(start, end).await?
+    };
+    if !selected {
+        anyhow::bail!("{} refused the selection", element.role);
+    }
+    Ok(())
+}
+
+/// Accessibility bus connection shared by the scan and background name fetches
+static A11Y_CONNECTION: Mutex<Option<Connection>> = Mutex::const_new(None);
+
+/// Names looked up at once by `fetch_names`
+static NAME_CONCURRENCY: AtomicUsize = AtomicUsize::new(8);
+
+/// Set how many accessible names are looked up at once (`scan.name_concurrency`)
+pub fn set_name_concurrency(limit: usize) {
+    NAME_CONCURRENCY.store(limit.max(1), Ordering::Relaxed);
+}
+
+/// Get the shared accessibility bus connection, connecting on first use
+async fn a11y_connection() -> Result<Connection> {
+    let mut cached = A11Y_CONNECTION.lock().await;
+    if let Some(conn) = cached.as_ref() {
+        metrics::inc_counter(metrics::CONNECTION_CACHE, &[("result", "hit")]);
+        return Ok(conn.clone());
+    }
+    metrics::inc_counter(metrics::CONNECTION_CACHE, &[("result", "miss")]);
+    let started = std::time::Instant::now();
+    let conn = get_a11y_connection().await?;
+    timings::record(timings::CONNECT, started.elapsed());
+    *cached = Some(conn.clone());
+    Ok(conn)
+}
+
+/// Connect to the accessibility bus ahead of the first scan
+pub async fn warm_up() -> Result<()> {
+    let _conn = a11y_connection().await?;
+    Ok(())
+}
+
+/// Times an operation is attempted when the accessibility bus keeps dropping
+const RECONNECT_ATTEMPTS: u32 = 4;
+
+/// Wait before the first reconnection, doubled after each further failure
+const RECONNECT_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// The accessibility bus went away while walking the tree
+#[derive(Debug)]
+struct BusLost;
+
+impl std::fmt::Display for BusLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Lost the accessibility bus connection")
+    }
+}
+
+impl std::error::Error for BusLost {}
+
+/// Whether a D-Bus error means the connection itself is gone, not just one application
+fn is_bus_lost(e: &zbus::Error) -> bool {
+    match e {
+        zbus::Error::InputOutput(_) => true,
+        zbus::Error::FDO(e) => matches!(**e, zbus::fdo::Error::Disconnected(_) | zbus::fdo::Error::IOError(_)),
+        zbus::Error::MethodError(name, _, _) => name.as_str() == "org.freedesktop.DBus.Error.Disconnected",
+        _ => false,
+    }
+}
+
+/// Whether a D-Bus error from the registry means it is gone, e.g. restarting
+fn is_registry_lost(e: &zbus::Error) -> bool {
+    const GONE: &[&str] = &[
+        "org.freedesktop.DBus.Error.ServiceUnknown",
+        "org.freedesktop.DBus.Error.NameHasNoOwner",
+    ];
+    is_bus_lost(e)
+        || match e {
+            zbus::Error::FDO(e) => matches!(
+                **e,
+                zbus::fdo::Error::ServiceUnknown(_) | zbus::fdo::Error::NameHasNoOwner(_)
+            ),
+            zbus::Error::MethodError(name, _, _) => GONE.contains(&name.as_str()),
+            _ => false,
+        }
+}
+
+/// Whether a failed scan is worth retrying on a fresh connection
+fn is_connection_lost(e: &anyhow::Error) -> bool {
+    e.chain()
+        .any(|cause| cause.is::<BusLost>() || cause.downcast_ref::<zbus::Error>().is_some_and(is_registry_lost))
+}
+
+/// Forget the shared connection and everything cached about the old bus
+async fn reset_connection() {
+    A11Y_CONNECTION.lock().await.take();
+    bus_cache::invalidate();
+}
+
+/// Run `op`, reconnecting with backoff and starting over when the bus drops
+///
+/// Covers the registry restarting or the session bus changing under a
+/// running scan; any other error is returned as is.
+async fn with_reconnect<T, F, Fut>(what: &str, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut delay = RECONNECT_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(e) if attempt < RECONNECT_ATTEMPTS && is_connection_lost(&e) => {
+                warn!("{:#} during {}, reconnecting in {:?}", e, what, delay);
+                metrics::inc_counter(metrics::RECONNECTS, &[]);
+                reset_connection().await;
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
 /// Get the accessibility bus connection
 async fn get_a11y_connection() -> Result<Connection> {
-    // First, try to get the a11y bus address from the session bus
+    // An address resolved by an earlier invocation skips the session bus
+    if let Some(addr_str) = bus_cache::cached_address() {
+        if let Ok(addr) = addr_str.parse::<Address>() {
+            if let Ok(conn) = zbus::ConnectionBuilder::address(addr)?.build().await {
+                info!("Connected to accessibility bus via cached address");
+                return Ok(conn);
+            }
+        }
+        debug!("Cached a11y bus address {} is stale", addr_str);
+        bus_cache::invalidate();
+    }
+
+    // Otherwise, try to get the a11y bus address from the session bus
     let session_bus = Connection::session()
         .await
         .context("Failed to connect to session bus")?;
@@ -105,6 +773,7 @@ async fn get_a11y_connection() -> Result<Connection> {
             if let Ok(addr) = addr_str.parse::<Address>() {
                 if let Ok(conn) = zbus::ConnectionBuilder::address(addr)?.build().await {
                     info!("Connected to accessibility bus via org.a11y.Bus");
+                    bus_cache::store_address(&addr_str);
                     return Ok(conn);
                 }
             }
@@ -116,9 +785,8 @@ async fn get_a11y_connection() -> Result<Connection> {
     let socket_path = if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
         format!("unix:path={}/at-spi/bus_0", runtime_dir)
     } else {
-        // Fallback to uid-based path
-        let uid = std::process::id(); // This is PID, not UID, but we'll try common paths
-        format!("unix:path=/run/user/1000/at-spi/bus_0") // Common default
+        // Fallback to the common default for the first user
+        "unix:path=/run/user/1000/at-spi/bus_0".to_string()
     };
     debug!("Trying direct socket connection: {}", socket_path);
 
@@ -134,21 +802,230 @@ async fn get_a11y_connection() -> Result<Connection> {
     Ok(session_bus)
 }
 
+/// Roles whose subtrees are skipped during traversal
+struct DescentTable {
+    /// Normalized role names (see `normalize_role_name`)
+    skip: Vec<String>,
+}
+
+impl DescentTable {
+    fn from_config(scan: &ScanConfig) -> Self {
+        let allow: Vec<String> = scan.always_descend.iter().map(|r| normalize_role_name(r)).collect();
+        let skip = scan
+            .skip_descent
+            .iter()
+            .map(|r| normalize_role_name(r))
+            .filter(|r| !allow.contains(r))
+            .collect();
+        Self { skip }
+    }
+
+    /// Whether children of an element with this role can be ignored
+    fn skips(&self, role: Role) -> bool {
+        !self.skip.is_empty() && self.skip.contains(&normalize_role_name(&format!("{:?}", role)))
+    }
+}
+
+/// Normalize a role name so "push_button", "push button" and "PushButton" compare equal
+pub fn normalize_role_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// State of a single walk over the accessibility tree
+struct Walk<'a, F> {
+    conn: &'a Connection,
+    role_filter: &'a F,
+    descent: DescentTable,
+    elements: Vec<ClickableElement>,
+    visited: HashSet<String>,
+    /// Subtrees not descended into because of their role
+    pruned: usize,
+    /// Bus names of applications whose root has no children
+    silent: Vec<String>,
+    /// The connection dropped, so the rest of the walk would find nothing
+    lost: bool,
+}
+
+impl<'a, F> Walk<'a, F> {
+    fn new(conn: &'a Connection, role_filter: &'a F, scan: &ScanConfig) -> Self {
+        Self {
+            conn,
+            role_filter,
+            descent: DescentTable::from_config(scan),
+            elements: Vec::new(),
+            visited: HashSet::new(),
+            pruned: 0,
+            silent: Vec::new(),
+            lost: false,
+        }
+    }
+
+    /// Note a failed call, stopping the walk if the bus itself went away
+    fn failed(&mut self, e: &zbus::Error) {
+        if is_bus_lost(e) {
+            self.lost = true;
+        }
+    }
+
+    /// The elements found, unless the walk was cut short by a lost connection
+    fn finish(self) -> Result<Vec<ClickableElement>> {
+        if self.lost {
+            return Err(BusLost.into());
+        }
+        Ok(self.elements)
+    }
+}
+
 /// Collect elements from AT-SPI
-async fn collect_elements<F>(role_filter: F) -> Result<Vec<ClickableElement>>
+async fn collect_elements<F>(role_filter: F, scan: &ScanConfig) -> Result<Vec<ClickableElement>>
 where
     F: Fn(Role) -> bool + Send + Sync + 'static,
+{
+    let started = std::time::Instant::now();
+    // One deadline for the whole scan, reconnections included
+    let deadline = (scan.timeout_ms > 0)
+        .then(|| tokio::time::Instant::now() + std::time::Duration::from_millis(scan.timeout_ms));
+    let elements = with_reconnect("scan", || scan_desktop(&role_filter, scan, deadline)).await?;
+    metrics::observe_seconds(metrics::SCAN_SECONDS, started.elapsed().as_secs_f64());
+    timings::record(timings::TRAVERSAL, started.elapsed());
+    metrics::set_gauge(metrics::SCAN_ELEMENTS, elements.len() as f64);
+    Ok(elements)
+}
+
+/// Walk every application once over the shared connection
+///
+/// When `deadline` passes first, the walk stops and returns the elements
+/// found until then, so one unresponsive application can't hold up the hints.
+async fn scan_desktop<F>(
+    role_filter: &F,
+    scan: &ScanConfig,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<Vec<ClickableElement>>
+where
+    F: Fn(Role) -> bool,
 {
     // Connect to the accessibility bus
-    let conn = get_a11y_connection()
+    let conn = a11y_connection()
         .await
         .context("Failed to connect to accessibility bus")?;
 
-    let mut elements = Vec::new();
-    let mut visited = HashSet::new();
+    let mut walk = Walk::new(&conn, role_filter, scan);
+
+    let Some(mut children) = registry_children(&conn, scan).await? else {
+        return Ok(walk.elements);
+    };
+    // Picking the applications asks each of them too, so it counts against the deadline
+    let scan_children = async {
+        if !scan.apps.is_empty() {
+            children = scanned_apps(&conn, children, scan).await;
+        }
+        if scan.focused_only {
+            match active_window_of(&conn, &children).await {
+                Some(window) => {
+                    debug!("Scanning only the active window {}:{}", window.0, window.1);
+                    children = vec![window];
+                }
+                None => debug!("No active window found, scanning every application"),
+            }
+        }
+
+        debug!("Desktop has {} children (applications)", children.len());
+        walk_applications(&mut walk, children).await
+    };
+
+    match deadline {
+        Some(deadline) => {
+            if tokio::time::timeout_at(deadline, scan_children).await.is_err() {
+                warn!(
+                    "Scan timed out after {} ms, hinting the {} elements found so far",
+                    scan.timeout_ms,
+                    walk.elements.len()
+                );
+            }
+        }
+        None => scan_children.await,
+    }
+    if !walk.silent.is_empty() {
+        debug!("Applications without an accessible tree: {:?}", walk.silent);
+    }
+    *SILENT_APPS.lock().unwrap_or_else(|e| e.into_inner()) = walk.silent.clone();
+
+    debug!(
+        "Found {} total elements ({} nodes visited, {} subtrees pruned)",
+        walk.elements.len(),
+        walk.visited.len(),
+        walk.pruned
+    );
+    walk.finish()
+}
+
+/// The applications among `children` that `scan.apps` names
+///
+/// Applications are named by their root accessible, so this costs a call per
+/// application but spares walking the others.
+async fn scanned_apps(conn: &Connection, children: Vec<(String, String)>, scan: &ScanConfig) -> Vec<(String, String)> {
+    let mut scanned = Vec::new();
+    for (dest, path) in children {
+        let Ok(app) = accessible_proxy(conn, &dest, &path).await else {
+            continue;
+        };
+        if app.name().await.is_ok_and(|name| scan.scans_app(&name)) {
+            scanned.push((dest, path));
+        }
+    }
+    debug!("Scanning {} applications named {:?}", scanned.len(), scan.apps);
+    scanned
+}
+
+/// Walk each application's tree in turn, noting those that expose nothing
+async fn walk_applications<F>(walk: &mut Walk<'_, F>, children: Vec<(String, String)>)
+where
+    F: Fn(Role) -> bool,
+{
+    for (dest, path) in children {
+        collect_from_accessible(walk, &dest, &path, 0).await;
+        if walk.lost {
+            break;
+        }
+    }
+}
+
+/// Bus names of the applications the last scan found no accessible tree in
+static SILENT_APPS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Applications the last scan found registered but without any accessible
+/// children, typically because accessibility is off in them, as (name, process)
+///
+/// Applications that don't answer with a name are given by their bus name.
+pub async fn silent_apps() -> Vec<(String, Option<u32>)> {
+    let bus_names = SILENT_APPS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut names = app_names(&bus_names).await.unwrap_or_default();
+    let pids = app_pids(&bus_names).await.unwrap_or_default();
+    bus_names
+        .into_iter()
+        .map(|bus_name| {
+            let pid = pids.get(&bus_name).copied();
+            (names.remove(&bus_name).unwrap_or(bus_name), pid)
+        })
+        .collect()
+}
+
+/// Applications registered on the desktop, as (bus name, path)
+///
+/// A listing saved by a recent invocation is reused; applications that
+/// exited since then simply fail to answer during the walk.
+async fn registry_children(conn: &Connection, scan: &ScanConfig) -> Result<Option<Vec<(String, String)>>> {
+    let ttl = std::time::Duration::from_secs(scan.app_cache_secs);
+    if let Some(children) = bus_cache::cached_children(ttl) {
+        debug!("Using cached application list");
+        return Ok(Some(children));
+    }
 
     // Get the registry proxy (root of AT-SPI tree)
-    let registry = atspi::proxy::accessible::AccessibleProxy::builder(&conn)
+    let registry = atspi::proxy::accessible::AccessibleProxy::builder(conn)
         .destination("org.a11y.atspi.Registry")?
         .path("/org/a11y/atspi/accessible/root")?
         .build()
@@ -156,61 +1033,43 @@ where
         .context("Failed to connect to AT-SPI registry")?;
 
     // Get all children (applications) from the registry
-    let children = match registry.get_children().await {
-        Ok(kids) => kids,
+    let children: Vec<(String, String)> = match registry.get_children().await {
+        Ok(kids) => kids
+            .into_iter()
+            .map(|app_ref| (app_ref.name.to_string(), app_ref.path.to_string()))
+            .collect(),
+        Err(e) if is_registry_lost(&e) => return Err(e).context("The AT-SPI registry went away"),
         Err(e) => {
             warn!("Failed to get desktop children: {}", e);
-            return Ok(elements);
+            return Ok(None);
         }
     };
 
-    debug!("Desktop has {} children (applications)", children.len());
-
-    // Iterate through applications
-    for app_ref in children {
-        let dest = app_ref.name.to_string();
-        let path = app_ref.path.to_string();
-
-        collect_from_accessible(
-            &conn,
-            &dest,
-            &path,
-            &mut elements,
-            &mut visited,
-            0,
-            &role_filter,
-        )
-        .await;
+    if !ttl.is_zero() {
+        bus_cache::store_children(children.clone());
     }
-
-    debug!("Found {} total elements", elements.len());
-    Ok(elements)
+    Ok(Some(children))
 }
 
 /// Recursively collect elements from an accessible
-async fn collect_from_accessible<F>(
-    conn: &Connection,
-    dest: &str,
-    path: &str,
-    elements: &mut Vec<ClickableElement>,
-    visited: &mut HashSet<String>,
-    depth: usize,
-    role_filter: &F,
-) where
+async fn collect_from_accessible<F>(walk: &mut Walk<'_, F>, dest: &str, path: &str, depth: usize)
+where
     F: Fn(Role) -> bool,
 {
     const MAX_DEPTH: usize = 20;
     const MAX_ELEMENTS: usize = 500;
 
-    if depth > MAX_DEPTH || elements.len() >= MAX_ELEMENTS {
+    if walk.lost || depth > MAX_DEPTH || walk.elements.len() >= MAX_ELEMENTS {
         return;
     }
 
     let key = format!("{}:{}", dest, path);
-    if visited.contains(&key) {
+    if walk.visited.contains(&key) {
         return;
     }
-    visited.insert(key.clone());
+    walk.visited.insert(key.clone());
+
+    let conn = walk.conn;
 
     // Create a proxy for this accessible
     let proxy = match atspi::proxy::accessible::AccessibleProxy::builder(conn)
@@ -219,7 +1078,7 @@ async fn collect_from_accessible<F>(
     {
         Ok(builder) => match builder.build().await {
             Ok(p) => p,
-            Err(_) => return,
+            Err(e) => return walk.failed(&e),
         },
         Err(_) => return,
     };
@@ -227,11 +1086,11 @@ async fn collect_from_accessible<F>(
     // Get role
     let role = match proxy.get_role().await {
         Ok(r) => r,
-        Err(_) => return,
+        Err(e) => return walk.failed(&e),
     };
 
     // Check if element matches filter
-    if role_filter(role) {
+    if (walk.role_filter)(role) {
         // Try to get extents using the Component interface
         // Create a ComponentProxy for the same object to access Component interface
         if let Ok(component) = ComponentProxy::builder(conn)
@@ -244,20 +1103,22 @@ async fn collect_from_accessible<F>(
                     if w > 0 && h > 0 && x >= 0 && y >= 0 {
                         // Skip very large elements (backgrounds)
                         if w < 3000 && h < 2000 {
-                            let name = proxy.name().await.unwrap_or_default();
-
-                            elements.push(ClickableElement {
-                                name: name.clone(),
+                            // Names are fetched later (see `fetch_names`) so hints
+                            // can be shown without waiting on a round trip per element
+                            walk.elements.push(ClickableElement {
+                                name: String::new(),
                                 role: format!("{:?}", role),
                                 x,
                                 y,
                                 width: w,
                                 height: h,
+                                bus_name: dest.to_string(),
+                                path: path.to_string(),
                             });
 
                             debug!(
-                                "Found element: {} ({:?}) at ({}, {}) {}x{}",
-                                name, role, x, y, w, h
+                                "Found element: {}:{} ({:?}) at ({}, {}) {}x{}",
+                                dest, path, role, x, y, w, h
                             );
                         }
                     }
@@ -266,22 +1127,287 @@ async fn collect_from_accessible<F>(
         }
     }
 
+    // Don't descend where the role rules out actionable descendants
+    if walk.descent.skips(role) {
+        walk.pruned += 1;
+        return;
+    }
+
     // Recurse into children
-    if let Ok(children) = proxy.get_children().await {
-        for child_ref in children {
-            let child_dest = child_ref.name.to_string();
-            let child_path = child_ref.path.to_string();
-
-            Box::pin(collect_from_accessible(
-                conn,
-                &child_dest,
-                &child_path,
-                elements,
-                visited,
-                depth + 1,
-                role_filter,
-            ))
-            .await;
+    match proxy.get_children().await {
+        Ok(children) => {
+            if children.is_empty() && role == Role::Application {
+                walk.silent.push(dest.to_string());
+            }
+            for child_ref in children {
+                let child_dest = child_ref.name.to_string();
+                let child_path = child_ref.path.to_string();
+
+                Box::pin(collect_from_accessible(walk, &child_dest, &child_path, depth + 1)).await;
+            }
         }
+        Err(e) => walk.failed(&e),
+    }
+}
+
+/// Fetch accessible names for elements, reporting each as it arrives
+///
+/// Up to `scan.name_concurrency` lookups run at once, so names may arrive
+/// out of order. `on_name` receives the element's index and its name;
+/// returning `false` stops the fetch (e.g. because the overlay has already closed).
+pub async fn fetch_names<F>(elements: Vec<ClickableElement>, mut on_name: F) -> Result<()>
+where
+    F: FnMut(usize, String) -> bool,
+{
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+
+    let limit = NAME_CONCURRENCY.load(Ordering::Relaxed);
+    let mut pending = elements.into_iter().enumerate().filter(|(_, e)| e.name.is_empty());
+    let mut lookups = tokio::task::JoinSet::new();
+    let mut fetched = 0;
+    loop {
+        while lookups.len() < limit {
+            let Some((index, element)) = pending.next() else {
+                break;
+            };
+            let conn = conn.clone();
+            lookups.spawn(async move { (index, fetch_name(&conn, &element).await) });
+        }
+        let Some(result) = lookups.join_next().await else {
+            break;
+        };
+        let Ok((index, Some(name))) = result else {
+            continue;
+        };
+        fetched += 1;
+        if !on_name(index, name) {
+            debug!("Name fetch stopped after {} names", fetched);
+            lookups.abort_all();
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// The accessible name of one element, if it has a non-empty one
+async fn fetch_name(conn: &Connection, element: &ClickableElement) -> Option<String> {
+    let proxy = atspi::proxy::accessible::AccessibleProxy::builder(conn)
+        .destination(element.bus_name.as_str())
+        .and_then(|b| b.path(element.path.as_str()))
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let name = proxy.name().await.unwrap_or_default();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Accessible names of the applications owning these bus names
+///
+/// Applications that don't answer are left out.
+pub async fn app_names(bus_names: &[String]) -> Result<HashMap<String, String>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let mut names = HashMap::new();
+    for bus_name in bus_names {
+        let Ok(app) = accessible_proxy(&conn, bus_name, "/org/a11y/atspi/accessible/root").await else {
+            continue;
+        };
+        if let Ok(name) = app.name().await {
+            names.insert(bus_name.clone(), name);
+        }
+    }
+    Ok(names)
+}
+
+/// Processes of the applications owning these bus names
+///
+/// Applications whose process can't be told are left out.
+pub async fn app_pids(bus_names: &[String]) -> Result<HashMap<String, u32>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let dbus = zbus::fdo::DBusProxy::new(&conn).await?;
+    let mut pids = HashMap::new();
+    for bus_name in bus_names {
+        let Ok(name) = zbus::names::BusName::try_from(bus_name.as_str()) else {
+            continue;
+        };
+        if let Ok(pid) = dbus.get_connection_unix_process_id(name).await {
+            pids.insert(bus_name.clone(), pid);
+        }
+    }
+    Ok(pids)
+}
+
+/// Turn on the session's accessibility flag, which toolkits read when they start
+pub async fn enable() -> Result<()> {
+    let session_bus = Connection::session()
+        .await
+        .context("Failed to connect to session bus")?;
+    atspi::proxy::bus::StatusProxy::new(&session_bus)
+        .await
+        .context("No org.a11y.Bus on the session bus")?
+        .set_is_enabled(true)
+        .await
+        .context("Failed to set org.a11y.Status.IsEnabled")?;
+    Ok(())
+}
+
+/// Names of the applications registered with the AT-SPI registry, listed afresh
+///
+/// Applications without a name are given by their bus name.
+pub async fn applications() -> Result<Vec<String>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let uncached = ScanConfig {
+        app_cache_secs: 0,
+        ..Default::default()
+    };
+    let children = registry_children(&conn, &uncached)
+        .await?
+        .context("The AT-SPI registry did not list its applications")?;
+    let bus_names: Vec<String> = children.into_iter().map(|(bus_name, _)| bus_name).collect();
+    let mut names = app_names(&bus_names).await?;
+    Ok(bus_names
+        .into_iter()
+        .map(|bus_name| names.remove(&bus_name).unwrap_or(bus_name))
+        .collect())
+}
+
+/// An element's tooltip: its accessible description and help text, if either is set
+pub async fn tooltip(element: &ClickableElement) -> Result<Option<String>> {
+    let conn = a11y_connection()
+        .await
+        .context("Failed to connect to accessibility bus")?;
+    let proxy = accessible_proxy(&conn, &element.bus_name, &element.path).await?;
+    let description = proxy.description().await.unwrap_or_default();
+
+    // Help text is newer than the proxies and read as a plain property
+    let properties = zbus::fdo::PropertiesProxy::builder(&conn)
+        .destination(element.bus_name.clone())?
+        .path(element.path.clone())?
+        .build()
+        .await?;
+    let interface = zbus::names::InterfaceName::from_static_str("org.a11y.atspi.Accessible")?;
+    let help = match properties.get(interface, "HelpText").await {
+        Ok(value) => String::try_from(value).unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+    Ok(tooltip_text(&description, &help))
+}
+
+/// Description and help text joined, skipping empty and repeated parts
+fn tooltip_text(description: &str, help: &str) -> Option<String> {
+    let (description, help) = (description.trim(), help.trim());
+    match (description.is_empty(), help.is_empty()) {
+        (true, true) => None,
+        (false, true) => Some(description.to_string()),
+        (true, false) => Some(help.to_string()),
+        (false, false) if description == help => Some(description.to_string()),
+        (false, false) => Some(format!("{} - {}", description, help)),
+    }
+}
+
+/// Fetch names for all elements up front, for filters that match on names
+pub async fn resolve_names(elements: &mut [ClickableElement]) -> Result<()> {
+    let started = std::time::Instant::now();
+    let mut names = Vec::new();
+    fetch_names(elements.to_vec(), |index, name| {
+        names.push((index, name));
+        true
+    })
+    .await?;
+
+    for (index, name) in names {
+        elements[index].name = name;
+    }
+    timings::record(timings::NAMES, started.elapsed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_role_name() {
+        assert_eq!(normalize_role_name("PushButton"), "pushbutton");
+        assert_eq!(normalize_role_name("push_button"), "pushbutton");
+        assert_eq!(normalize_role_name("Push Button"), "pushbutton");
+    }
+
+    #[test]
+    fn test_element_id_is_stable() {
+        let element = ClickableElement {
+            bus_name: ":1.42".to_string(),
+            path: "/org/a11y/atspi/accessible/7".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(element.id(), element.clone().id());
+        assert_eq!(element.id().len(), 16);
+        // FNV-1a of the empty key ":" is fixed by the algorithm
+        assert_eq!(ClickableElement::default().id(), "af63b74c8601adad");
+    }
+
+    #[test]
+    fn test_main_scrollable() {
+        let element = |role: Role, width, height| ClickableElement {
+            role: format!("{:?}", role),
+            width,
+            height,
+            ..Default::default()
+        };
+        let elements = vec![
+            element(Role::Frame, 1000, 800),
+            element(Role::ScrollPane, 200, 600),
+            element(Role::DocumentWeb, 900, 700),
+        ];
+        assert_eq!(main_scrollable(elements).unwrap().role, "DocumentWeb");
+        let windows = vec![element(Role::Frame, 1000, 800)];
+        assert_eq!(main_scrollable(windows).unwrap().role, "Frame");
+        assert!(main_scrollable(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_is_connection_lost() {
+        let io = zbus::Error::InputOutput(std::sync::Arc::new(std::io::ErrorKind::BrokenPipe.into()));
+        assert!(is_bus_lost(&io));
+        let unknown = zbus::Error::FDO(Box::new(zbus::fdo::Error::ServiceUnknown("registry".into())));
+        assert!(!is_bus_lost(&unknown));
+        assert!(is_registry_lost(&unknown));
+
+        let scan_error = anyhow::Error::new(unknown).context("The AT-SPI registry went away");
+        assert!(is_connection_lost(&scan_error));
+        assert!(is_connection_lost(&anyhow::Error::new(BusLost)));
+        assert!(!is_connection_lost(&anyhow::anyhow!("No Wayland display")));
+        assert!(!is_connection_lost(&anyhow::Error::new(zbus::Error::Unsupported)));
+    }
+
+    #[test]
+    fn test_tooltip_text() {
+        assert_eq!(tooltip_text("", " "), None);
+        assert_eq!(tooltip_text("Bold", ""), Some("Bold".to_string()));
+        assert_eq!(tooltip_text("", "Ctrl+B"), Some("Ctrl+B".to_string()));
+        assert_eq!(tooltip_text("Bold", "Bold"), Some("Bold".to_string()));
+        assert_eq!(tooltip_text("Bold", "Ctrl+B"), Some("Bold - Ctrl+B".to_string()));
+    }
+
+    #[test]
+    fn test_descent_table() {
+        let scan = ScanConfig {
+            skip_descent: vec!["image".to_string(), "separator".to_string()],
+            always_descend: vec!["Separator".to_string()],
+            ..Default::default()
+        };
+        let table = DescentTable::from_config(&scan);
+        assert!(table.skips(Role::Image));
+        assert!(!table.skips(Role::Separator));
+        assert!(!table.skips(Role::Panel));
     }
 }
@@ -0,0 +1,254 @@
+use crate::atspi::ClickableElement;
+use crate::config::Config;
+use crate::provider::{ElementFuture, ElementKind, ElementProvider};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// How long the extension gets to answer a query
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Browsers cap messages from the host at 1 MB, but replies can be larger
+const MAX_MESSAGE_BYTES: u32 = 64 * 1024 * 1024;
+
+/// An element reported by the browser extension, in screen coordinates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserElement {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    #[serde(default)]
+    pub role: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Message sent from the host to the extension
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HostMessage {
+    Query { id: u64, kind: ElementKind },
+}
+
+/// Message sent from the extension to the host
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ExtensionMessage {
+    Elements { id: u64, elements: Vec<BrowserElement> },
+}
+
+/// Request sent by the provider to the host over the bridge socket
+#[derive(Debug, Serialize, Deserialize)]
+struct BridgeRequest {
+    kind: ElementKind,
+}
+
+/// Socket shared by the native messaging host and the provider
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("vimium-linux-browser.sock")
+}
+
+/// Elements reported by the companion browser extension
+pub struct BrowserProvider;
+
+impl ElementProvider for BrowserProvider {
+    fn name(&self) -> &'static str {
+        "browser"
+    }
+
+    fn elements<'a>(&'a self, kind: ElementKind, _config: &'a Config) -> ElementFuture<'a> {
+        Box::pin(async move {
+            // The page's own tabs aren't the browser's
+            if kind == ElementKind::Tab {
+                return Ok(Vec::new());
+            }
+            tokio::time::timeout(QUERY_TIMEOUT * 2, query_bridge(kind))
+                .await
+                .context("Browser bridge timed out")?
+        })
+    }
+}
+
+/// Ask the native messaging host for the current page's elements
+async fn query_bridge(kind: ElementKind) -> Result<Vec<ClickableElement>> {
+    let stream = UnixStream::connect(socket_path())
+        .await
+        .context("Browser bridge is not running (is the extension installed?)")?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut request = serde_json::to_string(&BridgeRequest { kind })?;
+    request.push('\n');
+    writer.write_all(request.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let elements: Vec<BrowserElement> =
+        serde_json::from_str(&line).context("Invalid reply from browser bridge")?;
+
+    Ok(elements
+        .into_iter()
+        .enumerate()
+        .map(|(index, e)| ClickableElement {
+            name: e.name,
+            role: e.role,
+            x: e.x,
+            y: e.y,
+            width: e.width,
+            height: e.height,
+            bus_name: String::new(),
+            path: format!("/browser/{}", index),
+        })
+        .collect())
+}
+
+/// Run the native messaging host the browser starts for the extension
+///
+/// Queries arriving on the bridge socket are forwarded to the extension over
+/// stdout and its replies, read from stdin, are passed back. The host exits
+/// when the browser closes stdin.
+pub async fn run_native_host() -> Result<()> {
+    let path = socket_path();
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).with_context(|| format!("Failed to bind {:?}", path))?;
+    info!("Browser bridge listening on {:?}", path);
+
+    let (reply_tx, mut reply_rx) = mpsc::channel(4);
+    let mut reader = tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        loop {
+            match read_message(&mut stdin).await {
+                Ok(Some(bytes)) => match serde_json::from_slice::<ExtensionMessage>(&bytes) {
+                    Ok(message) => {
+                        if reply_tx.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Ignoring message from extension: {}", e),
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to read from extension: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut stdout = tokio::io::stdout();
+    let mut next_id = 0u64;
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Bridge accept failed: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut reader => break,
+        };
+
+        next_id += 1;
+        if let Err(e) = answer_query(stream, next_id, &mut stdout, &mut reply_rx).await {
+            debug!("Bridge query failed: {}", e);
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+    info!("Browser closed the native messaging connection");
+    Ok(())
+}
+
+/// Forward one bridge request to the extension and send back its reply
+async fn answer_query<W: AsyncWrite + Unpin>(
+    stream: UnixStream,
+    id: u64,
+    stdout: &mut W,
+    replies: &mut mpsc::Receiver<ExtensionMessage>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let request: BridgeRequest = serde_json::from_str(&line).context("Invalid bridge request")?;
+
+    let query = serde_json::to_vec(&HostMessage::Query { id, kind: request.kind })?;
+    write_message(stdout, &query).await?;
+
+    // Replies to earlier, timed-out queries are discarded
+    let elements = tokio::time::timeout(QUERY_TIMEOUT, async {
+        while let Some(ExtensionMessage::Elements { id: reply_id, elements }) = replies.recv().await {
+            if reply_id == id {
+                return Some(elements);
+            }
+        }
+        None
+    })
+    .await
+    .context("Extension did not answer in time")?
+    .context("Extension disconnected")?;
+
+    debug!("Extension reported {} elements", elements.len());
+    let mut reply = serde_json::to_string(&elements)?;
+    reply.push('\n');
+    writer.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+/// Read a native messaging frame (native-endian u32 length, then JSON)
+async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE_BYTES {
+        anyhow::bail!("Message of {} bytes is too large", len);
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes).await?;
+    Ok(Some(bytes))
+}
+
+/// Write a native messaging frame
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_ne_bytes()).await?;
+    writer.write_all(bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_message_framing_round_trip() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, br#"{"type":"query"}"#).await.unwrap();
+        assert_eq!(&buf[..4], &16u32.to_ne_bytes());
+
+        let mut reader = buf.as_slice();
+        let message = read_message(&mut reader).await.unwrap();
+        assert_eq!(message.as_deref(), Some(&br#"{"type":"query"}"#[..]));
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_extension_reply() {
+        let json = r#"{"type":"elements","id":3,"elements":[{"x":10,"y":20,"width":100,"height":30,"role":"link"}]}"#;
+        let ExtensionMessage::Elements { id, elements } = serde_json::from_str(json).unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(elements[0].role, "link");
+        assert!(elements[0].name.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// The a11y bus address only changes when the session restarts
+const ADDRESS_TTL: Duration = Duration::from_secs(300);
+
+/// Accessibility bus details saved between one-shot invocations
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusCache {
+    /// Address of the accessibility bus and when it was resolved
+    pub address: Option<(String, u64)>,
+    /// Registry children as (bus name, path) and when they were listed
+    pub children: Option<(Vec<(String, String)>, u64)>,
+}
+
+/// Cache file in the runtime dir, which is private to the user
+pub fn cache_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("vimium-linux-a11y.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn is_fresh(written: u64, ttl: Duration, now: u64) -> bool {
+    now >= written && now - written < ttl.as_secs()
+}
+
+impl BusCache {
+    /// Read the cache, treating a missing or corrupt file as empty
+    pub fn load(path: &Path) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the cache atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?).with_context(|| format!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to rename {:?}", tmp))?;
+        Ok(())
+    }
+
+    fn fresh_address(&self, now: u64) -> Option<&str> {
+        match &self.address {
+            Some((address, written)) if is_fresh(*written, ADDRESS_TTL, now) => Some(address),
+            _ => None,
+        }
+    }
+
+    fn fresh_children(&self, ttl: Duration, now: u64) -> Option<&[(String, String)]> {
+        match &self.children {
+            Some((children, written)) if is_fresh(*written, ttl, now) => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// Cached a11y bus address, if resolved recently
+pub fn cached_address() -> Option<String> {
+    let cache = BusCache::load(&cache_path());
+    cache.fresh_address(now()).map(str::to_string)
+}
+
+/// Remember the a11y bus address for later invocations
+pub fn store_address(address: &str) {
+    update(|cache| cache.address = Some((address.to_string(), now())));
+}
+
+/// Cached registry children, if listed less than `ttl` ago
+pub fn cached_children(ttl: Duration) -> Option<Vec<(String, String)>> {
+    if ttl.is_zero() {
+        return None;
+    }
+    let cache = BusCache::load(&cache_path());
+    cache.fresh_children(ttl, now()).map(<[_]>::to_vec)
+}
+
+/// Remember the registry children for later invocations
+pub fn store_children(children: Vec<(String, String)>) {
+    update(|cache| cache.children = Some((children, now())));
+}
+
+/// Drop everything cached, e.g. after the cached address stopped working
+pub fn invalidate() {
+    let _ = fs::remove_file(cache_path());
+}
+
+fn update(f: impl FnOnce(&mut BusCache)) {
+    let path = cache_path();
+    let mut cache = BusCache::load(&path);
+    f(&mut cache);
+    if let Err(e) = cache.save(&path) {
+        debug!("Failed to save bus cache: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_expiry() {
+        let cache = BusCache {
+            address: Some(("unix:path=/run/user/1000/at-spi/bus_0".to_string(), 1000)),
+            children: Some((vec![(":1.5".to_string(), "/org/a11y/atspi/accessible/root".to_string())], 1000)),
+        };
+        let ttl = Duration::from_secs(10);
+        assert!(cache.fresh_children(ttl, 1009).is_some());
+        assert!(cache.fresh_children(ttl, 1010).is_none());
+        assert!(cache.fresh_address(1200).is_some());
+        // A clock that went backwards does not make old entries fresh
+        assert!(cache.fresh_address(900).is_none());
+
+        let path = std::env::temp_dir().join(format!("vimium-bus-cache-{}.json", std::process::id()));
+        cache.save(&path).unwrap();
+        assert_eq!(BusCache::load(&path), cache);
+        fs::remove_file(&path).unwrap();
+    }
+}
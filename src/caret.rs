@@ -0,0 +1,432 @@
+use crate::config::{parse_color, Config};
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::overlay::{cancel_source, clear_cancel, wayland_connection};
+use anyhow::{Context, Result};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    reexports::{calloop::EventLoop, calloop_wayland_source::WaylandSource},
+    shm::{Shm, ShmHandler},
+};
+use tracing::info;
+use wayland_client::{
+    delegate_noop,
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+
+/// Width of the bar drawn at the caret
+const BAR_WIDTH: i32 = 2;
+
+/// Screen extents (x, y, width, height) of the character after the caret
+pub type Extents = (i32, i32, i32, i32);
+
+/// Where the caret was left, and the selection made with `v`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaretEnd {
+    /// Caret offset in characters
+    pub caret: usize,
+    /// Where the selection started, if one was made
+    pub anchor: Option<usize>,
+    /// Whether `y` ended the mode to copy the selection
+    pub yank: bool,
+}
+
+impl CaretEnd {
+    /// Selected characters as `start..end`, if any
+    pub fn selection(&self) -> Option<std::ops::Range<usize>> {
+        let anchor = self.anchor?;
+        (anchor != self.caret).then(|| anchor.min(self.caret)..anchor.max(self.caret))
+    }
+}
+
+/// A vim motion moving the caret
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    /// `w`: start of the next word
+    WordForward,
+    /// `b`: start of this or the previous word
+    WordBack,
+    /// `0`: start of the line
+    LineStart,
+    /// `$`: end of the line
+    LineEnd,
+    /// `gg`: start of the text
+    Start,
+    /// `G`: end of the text
+    End,
+}
+
+/// What a character is, for word motions: spaces, word characters or punctuation
+fn class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+impl Motion {
+    /// The motion a key stands for; `g` only moves when pressed twice
+    fn from_key(key: Keysym, pending_g: &mut bool) -> Option<Motion> {
+        let after_g = std::mem::take(pending_g);
+        match key {
+            Keysym::h | Keysym::Left => Some(Motion::Left),
+            Keysym::l | Keysym::Right => Some(Motion::Right),
+            Keysym::w => Some(Motion::WordForward),
+            Keysym::b => Some(Motion::WordBack),
+            Keysym::_0 | Keysym::Home => Some(Motion::LineStart),
+            Keysym::dollar | Keysym::End => Some(Motion::LineEnd),
+            Keysym::G => Some(Motion::End),
+            Keysym::g if after_g => Some(Motion::Start),
+            Keysym::g => {
+                *pending_g = true;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Caret offset after the motion, in characters
+    fn apply(self, text: &[char], caret: usize) -> usize {
+        let caret = caret.min(text.len());
+        match self {
+            Motion::Left => caret.saturating_sub(1),
+            Motion::Right => (caret + 1).min(text.len()),
+            Motion::WordForward => {
+                let mut i = caret;
+                if let Some(&c) = text.get(i) {
+                    let current = class(c);
+                    while i < text.len() && class(text[i]) == current {
+                        i += 1;
+                    }
+                }
+                while i < text.len() && class(text[i]) == 0 {
+                    i += 1;
+                }
+                i
+            }
+            Motion::WordBack => {
+                let mut i = caret;
+                while i > 0 && class(text[i - 1]) == 0 {
+                    i -= 1;
+                }
+                if i > 0 {
+                    let current = class(text[i - 1]);
+                    while i > 0 && class(text[i - 1]) == current {
+                        i -= 1;
+                    }
+                }
+                i
+            }
+            Motion::LineStart => text[..caret].iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1),
+            Motion::LineEnd => text[caret..].iter().position(|&c| c == '\n').map_or(text.len(), |i| caret + i),
+            Motion::Start => 0,
+            Motion::End => text.len(),
+        }
+    }
+}
+
+/// Move the caret of a text from `caret` with vim motions until the user is done
+///
+/// h/l move by a character, w/b by a word, 0/$ to the ends of the line and
+/// gg/G to the ends of the text. `v` starts or drops a selection from the
+/// caret, and `y` ends the mode to copy it. `move_caret` is called with every
+/// new offset and selection anchor and returns where to draw the caret, if
+/// the application says. Enter, Escape or q leave the caret where it is.
+pub fn run_caret_overlay(
+    text: &str,
+    caret: usize,
+    config: &Config,
+    move_caret: impl FnMut(usize, Option<usize>) -> Option<Extents> + 'static,
+) -> Result<CaretEnd> {
+    let conn = wayland_connection()?;
+
+    let (globals, event_queue) = registry_queue_init(&conn).context("Failed to init registry")?;
+    let qh = event_queue.handle();
+
+    let compositor = CompositorState::bind(&globals, &qh).context("wl_compositor not available")?;
+    let layer_shell = LayerShell::bind(&globals, &qh).context("layer_shell not available")?;
+    let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
+
+    let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
+    let region = Region::new(&compositor).context("Failed to create an input region")?;
+    surface.set_input_region(Some(region.wl_region()));
+    let layer_surface = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("vimium-caret"), None);
+    layer_surface.set_anchor(Anchor::TOP | Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT);
+    layer_surface.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface.commit();
+
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
+
+    let mut move_caret = move_caret;
+    let extents = move_caret(caret, None);
+    let mut state = CaretState {
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh),
+        shm,
+        buffers,
+        layer_surface: Some(layer_surface),
+        text: text.chars().collect(),
+        caret,
+        anchor: None,
+        yank: false,
+        extents,
+        pending_g: false,
+        move_caret: Box::new(move_caret),
+        color: parse_color(&config.colors.hint_bg),
+        configured: false,
+        width: 0,
+        height: 0,
+        exit: false,
+        keyboard: None,
+    };
+
+    let mut event_loop: EventLoop<CaretState> = EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Caret mode cancelled externally");
+            state.exit = true;
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
+    info!("Moving the caret. h/l, w/b, 0/$ and gg/G move it, v selects, y copies, Enter or Escape leaves it.");
+
+    while !state.exit {
+        event_loop.dispatch(None, &mut state).context("Wayland dispatch failed")?;
+    }
+
+    // Same explicit teardown as the hint overlay
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
+
+    Ok(CaretEnd {
+        caret: state.caret,
+        anchor: state.anchor,
+        yank: state.yank,
+    })
+}
+
+struct CaretState {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    shm: Shm,
+    buffers: FrameBuffers,
+    layer_surface: Option<LayerSurface>,
+    text: Vec<char>,
+    /// Caret offset in characters
+    caret: usize,
+    /// Where the selection started, set by `v`
+    anchor: Option<usize>,
+    /// Set by `y`
+    yank: bool,
+    /// Where the caret is drawn, in global coordinates
+    extents: Option<Extents>,
+    /// Set by the first `g` of `gg`
+    pending_g: bool,
+    move_caret: Box<dyn FnMut(usize, Option<usize>) -> Option<Extents> + 'static>,
+    color: (u8, u8, u8, u8),
+    configured: bool,
+    width: u32,
+    height: u32,
+    exit: bool,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+}
+
+impl CaretState {
+    /// Draw a bar at the caret on an otherwise clear surface
+    fn draw(&mut self) {
+        if !self.configured || self.width == 0 || self.height == 0 {
+            return;
+        }
+        let Some(layer_surface) = &self.layer_surface else {
+            return;
+        };
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
+        };
+        let (canvas, width, height, scale) = (frame.canvas, frame.width, frame.height, frame.scale as i32);
+        canvas.fill(0);
+
+        // Text extents share the overlay's output-local coordinates
+        if let Some((left, top, _, h)) = self.extents {
+            let (r, g, b, a) = self.color;
+            for py in (top / scale).max(0)..((top + h.max(1)) / scale).min(height as i32) {
+                for px in (left / scale).max(0)..((left + BAR_WIDTH) / scale).min(width as i32) {
+                    let idx = ((py as u32 * width + px as u32) * 4) as usize;
+                    canvas[idx..idx + 4].copy_from_slice(&[b, g, r, a]);
+                }
+            }
+        }
+
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
+        layer_surface.commit();
+    }
+
+    fn handle_key(&mut self, key: Keysym) {
+        match key {
+            Keysym::Escape | Keysym::q | Keysym::Return | Keysym::KP_Enter => {
+                info!("Leaving the caret at {}", self.caret);
+                self.exit = true;
+                return;
+            }
+            Keysym::y => {
+                self.yank = true;
+                self.exit = true;
+                return;
+            }
+            Keysym::v => {
+                self.anchor = match self.anchor {
+                    Some(_) => None,
+                    None => Some(self.caret),
+                };
+                self.extents = (self.move_caret)(self.caret, self.anchor);
+                return;
+            }
+            _ => {}
+        }
+        let Some(motion) = Motion::from_key(key, &mut self.pending_g) else {
+            return;
+        };
+        let caret = motion.apply(&self.text, self.caret);
+        if caret != self.caret {
+            self.caret = caret;
+            self.extents = (self.move_caret)(caret, self.anchor);
+        }
+    }
+}
+
+impl CompositorHandler for CaretState {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
+    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+}
+
+impl OutputHandler for CaretState {
+    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl LayerShellHandler for CaretState {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        self.exit = true;
+    }
+
+    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
+        self.width = configure.new_size.0;
+        self.height = configure.new_size.1;
+        self.configured = true;
+        self.draw();
+    }
+}
+
+impl SeatHandler for CaretState {
+    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+        }
+    }
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard { self.keyboard = None; }
+    }
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for CaretState {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        self.handle_key(event.keysym);
+        self.draw();
+    }
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: Modifiers, _: u32) {}
+}
+
+impl ShmHandler for CaretState {
+    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+}
+
+impl ProvidesRegistryState for CaretState {
+    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(CaretState);
+delegate_output!(CaretState);
+delegate_shm!(CaretState);
+delegate_seat!(CaretState);
+delegate_keyboard!(CaretState);
+delegate_layer!(CaretState);
+delegate_registry!(CaretState);
+delegate_noop!(CaretState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(CaretState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_motions() {
+        let text: Vec<char> = "let x = foo(1);\nnext line".chars().collect();
+        assert_eq!(Motion::WordForward.apply(&text, 0), 4);
+        assert_eq!(Motion::WordForward.apply(&text, 8), 11);
+        assert_eq!(Motion::WordBack.apply(&text, 8), 6);
+        assert_eq!(Motion::WordBack.apply(&text, 10), 8);
+        assert_eq!(Motion::LineEnd.apply(&text, 3), 15);
+        assert_eq!(Motion::LineStart.apply(&text, 20), 16);
+        assert_eq!(Motion::End.apply(&text, 0), text.len());
+        assert_eq!(Motion::Left.apply(&text, 0), 0);
+
+        let mut pending_g = false;
+        assert_eq!(Motion::from_key(Keysym::g, &mut pending_g), None);
+        assert_eq!(Motion::from_key(Keysym::g, &mut pending_g), Some(Motion::Start));
+        assert_eq!(Motion::from_key(Keysym::dollar, &mut pending_g), Some(Motion::LineEnd));
+
+        let end = CaretEnd {
+            caret: 4,
+            anchor: Some(9),
+            yank: true,
+        };
+        assert_eq!(end.selection(), Some(4..9));
+        assert_eq!(CaretEnd { anchor: Some(4), ..end }.selection(), None);
+        assert_eq!(CaretEnd { anchor: None, ..end }.selection(), None);
+    }
+}
@@ -1,160 +1,79 @@
+use crate::metrics;
+use crate::protocols;
+use crate::touchpad::{Gesture, Touchpad};
+use crate::ydotoold;
 use anyhow::{Context, Result};
 use std::io::Write;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
-use tracing::{debug, info, warn};
+use tracing::{debug, info};
 
 /// Detect if running on Hyprland
 fn is_hyprland() -> bool {
     std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok()
 }
 
-/// Get the focused monitor's offset from Hyprland
-/// Returns (x_offset, y_offset) for coordinate adjustment
-fn get_hyprland_monitor_offset() -> (i32, i32) {
-    let output = match Command::new("hyprctl")
-        .args(["monitors", "-j"])
-        .output()
-    {
-        Ok(o) => o,
-        Err(_) => return (0, 0),
-    };
-
-    let json_str = match String::from_utf8(output.stdout) {
-        Ok(s) => s,
-        Err(_) => return (0, 0),
-    };
-
-    // Simple JSON parsing - track current monitor's x,y and check for focused
-    let mut current_x = 0i32;
-    let mut current_y = 0i32;
-    let mut found_focused = false;
-
-    for line in json_str.lines() {
-        let line = line.trim();
-
-        // Reset on new monitor object
-        if line == "{" {
-            current_x = 0;
-            current_y = 0;
-        }
-
-        // Parse x coordinate
-        if line.starts_with("\"x\":") {
-            if let Ok(val) = line.trim_start_matches("\"x\":").trim().trim_end_matches(',').parse::<i32>() {
-                current_x = val;
-            }
-        }
-
-        // Parse y coordinate
-        if line.starts_with("\"y\":") {
-            if let Ok(val) = line.trim_start_matches("\"y\":").trim().trim_end_matches(',').parse::<i32>() {
-                current_y = val;
-            }
-        }
-
-        // Check if this is the focused monitor
-        if line.contains("\"focused\": true") {
-            found_focused = true;
-            break;
-        }
-    }
-
-    if found_focused {
-        debug!("Hyprland focused monitor offset: ({}, {})", current_x, current_y);
-        (current_x, current_y)
-    } else {
-        (0, 0)
-    }
-}
-
-/// Click at the given screen coordinates
-/// Tries multiple methods: hyprctl (Hyprland), ydotool, wlrctl, dotool
-pub fn click_at(x: i32, y: i32) -> Result<()> {
-    info!("Clicking at ({}, {})", x, y);
-
-    // Try hyprctl first (for Hyprland - handles coordinates correctly)
-    if is_hyprland() {
-        if try_hyprctl_click(x, y, ClickButton::Left).is_ok() {
-            return Ok(());
-        }
-    }
-
-    // Try ydotool (most common on Wayland)
-    if try_ydotool_click(x, y, ClickButton::Left).is_ok() {
-        return Ok(());
-    }
-
-    // Try wlrctl (for wlroots compositors)
-    if try_wlrctl_click(x, y, ClickButton::Left).is_ok() {
-        return Ok(());
-    }
-
-    // Try dotool
-    if try_dotool_click(x, y, ClickButton::Left).is_ok() {
-        return Ok(());
-    }
-
-    // Try wtype + cursor positioning
-    if try_wtype_click(x, y, ClickButton::Left).is_ok() {
-        return Ok(());
-    }
-
-    anyhow::bail!(
-        "No click method available. Please install one of: ydotool, wlrctl, dotool, or wtype"
-    )
+/// Whether `program` is an executable on PATH
+pub(crate) fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
 }
 
-/// Perform a right-click at the given coordinates
-pub fn right_click_at(x: i32, y: i32) -> Result<()> {
-    info!("Right-clicking at ({}, {})", x, y);
-    perform_click(x, y, ClickButton::Right)
+/// Record a backend attempt and report whether it succeeded
+fn tried(backend: &str, result: &Result<()>) -> bool {
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    metrics::inc_counter(metrics::BACKEND_ATTEMPTS, &[("backend", backend), ("result", outcome)]);
+    result.is_ok()
 }
 
-/// Perform a middle-click at the given coordinates
-pub fn middle_click_at(x: i32, y: i32) -> Result<()> {
-    info!("Middle-clicking at ({}, {})", x, y);
-    perform_click(x, y, ClickButton::Middle)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickButton {
+    Left,
+    Right,
+    Middle,
 }
 
-/// Scroll at the given position
-pub fn scroll_at(x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
-    debug!("Scrolling {:?} by {} at ({}, {})", direction, amount, x, y);
-
-    // Try hyprctl for positioning on Hyprland
-    if is_hyprland() {
-        if try_hyprctl_scroll(x, y, direction, amount).is_ok() {
-            return Ok(());
+impl ClickButton {
+    /// Button name used by wlrctl and dotool
+    fn name(self) -> &'static str {
+        match self {
+            ClickButton::Left => "left",
+            ClickButton::Right => "right",
+            ClickButton::Middle => "middle",
         }
     }
 
-    // Try ydotool
-    if try_ydotool_scroll(x, y, direction, amount).is_ok() {
-        return Ok(());
+    /// ydotool button code: left=0xC0, right=0xC1, middle=0xC2 for a full click,
+    /// 0x40.. for down only and 0x80.. for up only
+    fn ydotool_code(self, down: bool, up: bool) -> String {
+        let button = match self {
+            ClickButton::Left => 0,
+            ClickButton::Right => 1,
+            ClickButton::Middle => 2,
+        };
+        let flags = if down { 0x40 } else { 0 } | if up { 0x80 } else { 0 };
+        format!("0x{:X}", flags | button)
     }
+}
 
-    // Try dotool
-    if try_dotool_scroll(x, y, direction, amount).is_ok() {
-        return Ok(());
-    }
+impl std::str::FromStr for ClickButton {
+    type Err = String;
 
-    // Try wlrctl
-    if try_wlrctl_scroll(direction, amount).is_ok() {
-        return Ok(());
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "left" => Ok(ClickButton::Left),
+            "right" => Ok(ClickButton::Right),
+            "middle" => Ok(ClickButton::Middle),
+            _ => Err(format!("unknown button '{}' (expected left, right or middle)", s)),
+        }
     }
-
-    anyhow::bail!("No scroll method available")
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum ClickButton {
-    Left,
-    Right,
-    Middle,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ScrollDirection {
     Up,
     Down,
@@ -162,454 +81,552 @@ pub enum ScrollDirection {
     Right,
 }
 
-fn perform_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    // Try hyprctl first (for Hyprland - handles coordinates correctly)
-    if is_hyprland() {
-        if try_hyprctl_click(x, y, button).is_ok() {
-            return Ok(());
+impl std::str::FromStr for ScrollDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "up" => Ok(ScrollDirection::Up),
+            "down" => Ok(ScrollDirection::Down),
+            "left" => Ok(ScrollDirection::Left),
+            "right" => Ok(ScrollDirection::Right),
+            _ => Err(format!("unknown direction '{}' (expected up, down, left or right)", s)),
         }
     }
-    if try_ydotool_click(x, y, button).is_ok() {
-        return Ok(());
-    }
-    if try_wlrctl_click(x, y, button).is_ok() {
-        return Ok(());
-    }
-    if try_dotool_click(x, y, button).is_ok() {
-        return Ok(());
+}
+
+impl ScrollDirection {
+    fn is_horizontal(self) -> bool {
+        matches!(self, ScrollDirection::Left | ScrollDirection::Right)
     }
-    if try_wtype_click(x, y, button).is_ok() {
-        return Ok(());
+
+    /// Signed wheel amount, positive for up and right
+    fn signed(self, amount: i32) -> i32 {
+        match self {
+            ScrollDirection::Up | ScrollDirection::Right => amount,
+            ScrollDirection::Down | ScrollDirection::Left => -amount,
+        }
     }
-    anyhow::bail!("No click method available for {:?} button", button)
 }
 
-/// Try clicking using hyprctl (for Hyprland)
-fn try_hyprctl_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    debug!("Trying hyprctl...");
+/// Direction of a pinch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zoom {
+    In,
+    Out,
+}
+
+/// What a pointer backend can do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Move the pointer to global coordinates
+    pub absolute_move: bool,
+    /// Press and release mouse buttons
+    pub buttons: bool,
+    /// Vertical scrolling
+    pub wheel: bool,
+    /// Horizontal scrolling
+    pub hwheel: bool,
+}
 
-    // Get the focused monitor's offset and apply it to coordinates
-    let (offset_x, offset_y) = get_hyprland_monitor_offset();
-    let adjusted_x = x + offset_x;
-    let adjusted_y = y + offset_y;
+/// A way of driving the pointer, usually an external tool
+pub trait PointerBackend: Send + Sync {
+    /// Name used in logs and metrics
+    fn name(&self) -> &'static str;
 
-    debug!("Adjusted coordinates: ({}, {}) -> ({}, {})", x, y, adjusted_x, adjusted_y);
+    fn capabilities(&self) -> Capabilities;
 
-    // Move cursor using hyprctl
-    let status = Command::new("hyprctl")
-        .args(["dispatch", "movecursor", &adjusted_x.to_string(), &adjusted_y.to_string()])
-        .status()
-        .context("Failed to run hyprctl movecursor")?;
+    /// Move the pointer to global coordinates
+    fn move_to(&self, x: i32, y: i32) -> Result<()>;
 
-    if !status.success() {
-        anyhow::bail!("hyprctl movecursor failed");
-    }
+    /// Move to global coordinates and click
+    fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()>;
 
-    // Small delay to ensure cursor moved
-    thread::sleep(Duration::from_millis(10));
+    /// Move to global coordinates and scroll
+    fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()>;
 
-    // Click using ydotool (cursor is now in correct position)
-    let button_code = match button {
-        ClickButton::Left => "0xC0",
-        ClickButton::Right => "0xC1",
-        ClickButton::Middle => "0xC2",
-    };
+    /// Hold down or release a button where the pointer is
+    fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()>;
+}
 
-    let status = Command::new("ydotool")
-        .args(["click", button_code])
+/// Run a command, failing unless it exits successfully
+pub(crate) fn run(program: &str, args: &[&str]) -> Result<()> {
+    let mut command = Command::new(program);
+    if program == "ydotool" {
+        if let Some(socket) = ydotoold::socket() {
+            command.env("YDOTOOL_SOCKET", socket);
+        }
+    }
+    let status = command
+        .args(args)
         .status()
-        .context("Failed to run ydotool click")?;
-
+        .with_context(|| format!("Failed to run {}", program))?;
     if !status.success() {
-        anyhow::bail!("ydotool click failed");
+        anyhow::bail!("{} {} failed", program, args.first().unwrap_or(&""));
     }
-
-    info!("Clicked using hyprctl + ydotool ({:?})", button);
     Ok(())
 }
 
-/// Try clicking using ydotool
-fn try_ydotool_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    debug!("Trying ydotool...");
-
-    // ydotool needs ydotoold daemon running
-    // Move to absolute position
-    let status = Command::new("ydotool")
-        .args(["mousemove", "--absolute", "-x", &x.to_string(), "-y", &y.to_string()])
-        .status()
-        .context("Failed to run ydotool mousemove")?;
-
-    if !status.success() {
-        anyhow::bail!("ydotool mousemove failed");
+/// Run a command with `input` on stdin, failing unless it exits successfully
+pub(crate) fn run_with_input(program: &str, input: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .with_context(|| format!("Failed to write to {}", program))?;
     }
-
-    // Button codes: left=0xC0, right=0xC1, middle=0xC2
-    let button_code = match button {
-        ClickButton::Left => "0xC0",
-        ClickButton::Right => "0xC1",
-        ClickButton::Middle => "0xC2",
-    };
-
-    let status = Command::new("ydotool")
-        .args(["click", button_code])
-        .status()
-        .context("Failed to run ydotool click")?;
-
+    let status = child.wait().with_context(|| format!("Failed to wait for {}", program))?;
     if !status.success() {
-        anyhow::bail!("ydotool click failed");
+        anyhow::bail!("{} failed", program);
     }
-
-    info!("Clicked using ydotool ({:?})", button);
     Ok(())
 }
 
-/// Try clicking using wlrctl (for wlroots compositors like Sway)
-fn try_wlrctl_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    debug!("Trying wlrctl...");
-
-    let status = Command::new("wlrctl")
-        .args(["pointer", "move", &x.to_string(), &y.to_string()])
-        .status()
-        .context("Failed to run wlrctl")?;
+/// ydotool (needs the ydotoold daemon running)
+struct Ydotool;
 
-    if !status.success() {
-        anyhow::bail!("wlrctl move failed");
+impl Ydotool {
+    fn press(&self, code: &str) -> Result<()> {
+        run("ydotool", &["click", code])
     }
 
-    let button_name = match button {
-        ClickButton::Left => "left",
-        ClickButton::Right => "right",
-        ClickButton::Middle => "middle",
-    };
-
-    let status = Command::new("wlrctl")
-        .args(["pointer", "click", button_name])
-        .status()
-        .context("Failed to run wlrctl click")?;
-
-    if !status.success() {
-        anyhow::bail!("wlrctl click failed");
+    fn wheel(&self, direction: ScrollDirection, amount: i32) -> Result<()> {
+        let arg = if direction.is_horizontal() { "--hwheel" } else { "--wheel" };
+        run("ydotool", &["mousemove", arg, &direction.signed(amount).to_string()])
     }
-
-    info!("Clicked using wlrctl ({:?})", button);
-    Ok(())
 }
 
-/// Try clicking using dotool
-fn try_dotool_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    debug!("Trying dotool...");
-
-    let button_name = match button {
-        ClickButton::Left => "left",
-        ClickButton::Right => "right",
-        ClickButton::Middle => "middle",
-    };
-
-    // dotool reads commands from stdin
-    let input = format!("mouseto {} {}\nclick {}\n", x, y, button_name);
+impl PointerBackend for Ydotool {
+    fn name(&self) -> &'static str {
+        "ydotool"
+    }
 
-    let mut child = Command::new("dotool")
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .context("Failed to run dotool")?;
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { absolute_move: true, buttons: true, wheel: true, hwheel: true }
+    }
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(input.as_bytes()).context("Failed to write to dotool")?;
+    fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        run("ydotool", &["mousemove", "--absolute", "-x", &x.to_string(), "-y", &y.to_string()])
     }
 
-    let status = child.wait().context("Failed to wait for dotool")?;
+    fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+        self.move_to(x, y)?;
+        self.press(&button.ydotool_code(true, true))
+    }
 
-    if !status.success() {
-        anyhow::bail!("dotool failed");
+    fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+        self.move_to(x, y)?;
+        self.wheel(direction, amount)
     }
 
-    info!("Clicked using dotool ({:?})", button);
-    Ok(())
+    fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()> {
+        self.press(&button.ydotool_code(pressed, !pressed))
+    }
 }
 
-/// Try clicking using wtype (keyboard-focused but can do mouse)
-fn try_wtype_click(x: i32, y: i32, button: ClickButton) -> Result<()> {
-    debug!("Trying wtype...");
-
-    // wtype doesn't directly support mouse, but we can try via ydotool for positioning
-    // This is a fallback that might work on some systems
+/// hyprctl for positioning (handles Hyprland's coordinates correctly), with
+/// ydotool for buttons and wheels
+struct Hyprctl {
+    ydotool: bool,
+}
 
-    // First try to move cursor with ydotool (if available)
-    let move_result = Command::new("ydotool")
-        .args(["mousemove", "--absolute", "-x", &x.to_string(), "-y", &y.to_string()])
-        .status();
+impl PointerBackend for Hyprctl {
+    fn name(&self) -> &'static str {
+        "hyprctl"
+    }
 
-    if move_result.is_err() {
-        anyhow::bail!("wtype method requires ydotool for cursor positioning");
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            absolute_move: true,
+            buttons: self.ydotool,
+            wheel: self.ydotool,
+            hwheel: self.ydotool,
+        }
     }
 
-    // Then click with wlrctl as fallback
-    let button_name = match button {
-        ClickButton::Left => "left",
-        ClickButton::Right => "right",
-        ClickButton::Middle => "middle",
-    };
+    fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        // Coordinates are already global (see `layout`), as movecursor expects
+        run("hyprctl", &["dispatch", "movecursor", &x.to_string(), &y.to_string()])?;
+        // Small delay to ensure cursor moved
+        thread::sleep(Duration::from_millis(10));
+        Ok(())
+    }
 
-    let status = Command::new("wlrctl")
-        .args(["pointer", "click", button_name])
-        .status()?;
+    fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+        self.move_to(x, y)?;
+        Ydotool.press(&button.ydotool_code(true, true))
+    }
 
-    if !status.success() {
-        anyhow::bail!("wtype click failed");
+    fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+        self.move_to(x, y)?;
+        Ydotool.wheel(direction, amount)
     }
 
-    info!("Clicked using wtype fallback ({:?})", button);
-    Ok(())
+    fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()> {
+        Ydotool.set_button(button, pressed)
+    }
 }
 
-/// Try scrolling using hyprctl for positioning (Hyprland)
-fn try_hyprctl_scroll(x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
-    debug!("Trying hyprctl scroll...");
+/// wlrctl (wlroots compositors like Sway)
+struct Wlrctl;
 
-    // Get the focused monitor's offset and apply it to coordinates
-    let (offset_x, offset_y) = get_hyprland_monitor_offset();
-    let adjusted_x = x + offset_x;
-    let adjusted_y = y + offset_y;
+impl PointerBackend for Wlrctl {
+    fn name(&self) -> &'static str {
+        "wlrctl"
+    }
 
-    debug!("Adjusted scroll coordinates: ({}, {}) -> ({}, {})", x, y, adjusted_x, adjusted_y);
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { absolute_move: true, buttons: true, wheel: true, hwheel: false }
+    }
 
-    // Move cursor to position using hyprctl
-    let status = Command::new("hyprctl")
-        .args(["dispatch", "movecursor", &adjusted_x.to_string(), &adjusted_y.to_string()])
-        .status()
-        .context("Failed to run hyprctl movecursor")?;
+    fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        run("wlrctl", &["pointer", "move", &x.to_string(), &y.to_string()])
+    }
 
-    if !status.success() {
-        anyhow::bail!("hyprctl movecursor failed");
+    fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+        self.move_to(x, y)?;
+        run("wlrctl", &["pointer", "click", button.name()])
     }
 
-    thread::sleep(Duration::from_millis(10));
+    fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+        let scroll_dir = match direction {
+            ScrollDirection::Up => "up",
+            ScrollDirection::Down => "down",
+            _ => anyhow::bail!("wlrctl doesn't support horizontal scroll"),
+        };
+        self.move_to(x, y)?;
+        // Repeat scroll commands for the amount
+        let clicks = (amount.abs() / 15).max(1);
+        for _ in 0..clicks {
+            run("wlrctl", &["pointer", "scroll", scroll_dir])?;
+        }
+        Ok(())
+    }
 
-    // Scroll using ydotool (cursor is now in correct position)
-    let (wheel_arg, wheel_amount) = match direction {
-        ScrollDirection::Up => ("--wheel", amount.to_string()),
-        ScrollDirection::Down => ("--wheel", (-amount).to_string()),
-        ScrollDirection::Left => ("--hwheel", (-amount).to_string()),
-        ScrollDirection::Right => ("--hwheel", amount.to_string()),
-    };
+    fn set_button(&self, _button: ClickButton, _pressed: bool) -> Result<()> {
+        anyhow::bail!("wlrctl can't hold buttons")
+    }
+}
 
-    let status = Command::new("ydotool")
-        .args(["mousemove", wheel_arg, &wheel_amount])
-        .status()?;
+/// dotool, which reads commands from stdin
+struct Dotool;
 
-    if !status.success() {
-        anyhow::bail!("ydotool scroll failed");
+impl PointerBackend for Dotool {
+    fn name(&self) -> &'static str {
+        "dotool"
     }
 
-    Ok(())
-}
-
-/// Try scrolling using ydotool
-fn try_ydotool_scroll(x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
-    debug!("Trying ydotool scroll...");
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { absolute_move: true, buttons: true, wheel: true, hwheel: true }
+    }
 
-    // Move to position first
-    Command::new("ydotool")
-        .args(["mousemove", "--absolute", "-x", &x.to_string(), "-y", &y.to_string()])
-        .status()?;
+    fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        run_with_input("dotool", &format!("mouseto {} {}\n", x, y))
+    }
 
-    // Scroll - ydotool uses wheel direction
-    let (wheel_arg, wheel_amount) = match direction {
-        ScrollDirection::Up => ("--wheel", amount.to_string()),
-        ScrollDirection::Down => ("--wheel", (-amount).to_string()),
-        ScrollDirection::Left => ("--hwheel", (-amount).to_string()),
-        ScrollDirection::Right => ("--hwheel", amount.to_string()),
-    };
+    fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+        run_with_input("dotool", &format!("mouseto {} {}\nclick {}\n", x, y, button.name()))
+    }
 
-    let status = Command::new("ydotool")
-        .args(["mousemove", wheel_arg, &wheel_amount])
-        .status()?;
+    fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+        let command = if direction.is_horizontal() { "hscroll" } else { "scroll" };
+        let input = format!("mouseto {} {}\n{} {}\n", x, y, command, direction.signed(amount));
+        run_with_input("dotool", &input)
+    }
 
-    if !status.success() {
-        anyhow::bail!("ydotool scroll failed");
+    fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()> {
+        let command = if pressed { "buttondown" } else { "buttonup" };
+        run_with_input("dotool", &format!("{} {}\n", command, button.name()))
     }
+}
 
-    Ok(())
+/// Backends installed on this system, in order of preference
+fn discover() -> Vec<Box<dyn PointerBackend>> {
+    let ydotool = on_path("ydotool");
+    let mut backends: Vec<Box<dyn PointerBackend>> = Vec::new();
+    if is_hyprland() && on_path("hyprctl") {
+        backends.push(Box::new(Hyprctl { ydotool }));
+    }
+    if ydotool {
+        backends.push(Box::new(Ydotool));
+    }
+    if on_path("wlrctl") {
+        if protocols::has(protocols::VIRTUAL_POINTER) {
+            backends.push(Box::new(Wlrctl));
+        } else {
+            debug!("Skipping wlrctl: the compositor lacks {}", protocols::VIRTUAL_POINTER);
+        }
+    }
+    if on_path("dotool") {
+        backends.push(Box::new(Dotool));
+    }
+    backends
 }
 
-/// Try scrolling using dotool
-fn try_dotool_scroll(x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
-    debug!("Trying dotool scroll...");
+/// Tries each pointer backend able to perform an operation until one works
+pub struct Pointer {
+    backends: Vec<Box<dyn PointerBackend>>,
+}
 
-    let scroll_cmd = match direction {
-        ScrollDirection::Up => format!("scroll {}", amount),
-        ScrollDirection::Down => format!("scroll -{}", amount),
-        ScrollDirection::Left => format!("hscroll -{}", amount),
-        ScrollDirection::Right => format!("hscroll {}", amount),
-    };
+impl Pointer {
+    pub fn new(backends: Vec<Box<dyn PointerBackend>>) -> Self {
+        Self { backends }
+    }
+
+    fn attempt(
+        &self,
+        what: &str,
+        supports: impl Fn(Capabilities) -> bool,
+        op: impl Fn(&dyn PointerBackend) -> Result<()>,
+    ) -> Result<()> {
+        let mut last_error = None;
+        for backend in self.backends.iter().filter(|b| supports(b.capabilities())) {
+            debug!("Trying {} for {}...", backend.name(), what);
+            let result = op(backend.as_ref());
+            if tried(backend.name(), &result) {
+                info!("{} using {}", what, backend.name());
+                return Ok(());
+            }
+            if let Err(e) = result {
+                debug!("{} failed: {}", backend.name(), e);
+                last_error = Some(e);
+            }
+        }
+        match last_error {
+            Some(e) => Err(e.context(format!("No {} method worked", what))),
+            None => anyhow::bail!(
+                "No {} method available. Please install one of: ydotool, wlrctl or dotool",
+                what
+            ),
+        }
+    }
 
-    let input = format!("mouseto {} {}\n{}\n", x, y, scroll_cmd);
+    pub fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        self.attempt("move", |c| c.absolute_move, |b| b.move_to(x, y))
+    }
 
-    let mut child = Command::new("dotool")
-        .stdin(std::process::Stdio::piped())
-        .spawn()?;
+    pub fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+        self.attempt("click", |c| c.absolute_move && c.buttons, |b| b.click(x, y, button))
+    }
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(input.as_bytes())?;
+    pub fn scroll(&self, x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+        let supports = |c: Capabilities| {
+            c.absolute_move && if direction.is_horizontal() { c.hwheel } else { c.wheel }
+        };
+        self.attempt("scroll", supports, |b| b.scroll(x, y, direction, amount))
     }
 
-    let status = child.wait()?;
-    if !status.success() {
-        anyhow::bail!("dotool scroll failed");
+    pub fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()> {
+        self.attempt("button", |c| c.buttons, |b| b.set_button(button, pressed))
     }
+}
 
-    Ok(())
+/// Backends discovered on first use
+fn pointer() -> &'static Pointer {
+    static POINTER: OnceLock<Pointer> = OnceLock::new();
+    POINTER.get_or_init(|| {
+        let backends = discover();
+        let names: Vec<_> = backends.iter().map(|b| b.name()).collect();
+        debug!("Pointer backends: {:?}", names);
+        Pointer::new(backends)
+    })
 }
 
-/// Try scrolling using wlrctl
-fn try_wlrctl_scroll(direction: ScrollDirection, amount: i32) -> Result<()> {
-    debug!("Trying wlrctl scroll...");
+/// Click at the given global screen coordinates
+/// Tries multiple methods: hyprctl (Hyprland), ydotool, wlrctl, dotool
+pub fn click_at(x: i32, y: i32) -> Result<()> {
+    info!("Clicking at ({}, {})", x, y);
+    pointer().click(x, y, ClickButton::Left)
+}
 
-    // wlrctl has limited scroll support
-    let scroll_dir = match direction {
-        ScrollDirection::Up => "up",
-        ScrollDirection::Down => "down",
-        _ => anyhow::bail!("wlrctl doesn't support horizontal scroll"),
-    };
+/// Perform a right-click at the given coordinates
+pub fn right_click_at(x: i32, y: i32) -> Result<()> {
+    info!("Right-clicking at ({}, {})", x, y);
+    pointer().click(x, y, ClickButton::Right)
+}
 
-    // Repeat scroll commands for the amount
-    let clicks = (amount.abs() / 15).max(1);
-    for _ in 0..clicks {
-        let status = Command::new("wlrctl")
-            .args(["pointer", "scroll", scroll_dir])
-            .status()?;
+/// Perform a middle-click at the given coordinates
+pub fn middle_click_at(x: i32, y: i32) -> Result<()> {
+    info!("Middle-clicking at ({}, {})", x, y);
+    pointer().click(x, y, ClickButton::Middle)
+}
 
-        if !status.success() {
-            anyhow::bail!("wlrctl scroll failed");
-        }
+/// Click the given button at the given coordinates
+pub fn press_at(x: i32, y: i32, button: ClickButton) -> Result<()> {
+    match button {
+        ClickButton::Left => click_at(x, y),
+        ClickButton::Right => right_click_at(x, y),
+        ClickButton::Middle => middle_click_at(x, y),
     }
+}
 
-    Ok(())
+/// Scroll at the given position
+pub fn scroll_at(x: i32, y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+    debug!("Scrolling {:?} by {} at ({}, {})", direction, amount, x, y);
+    pointer().scroll(x, y, direction, amount)
 }
 
-/// Move cursor to position without clicking
-pub fn move_cursor_to(x: i32, y: i32) -> Result<()> {
-    debug!("Moving cursor to ({}, {})", x, y);
+/// Virtual touchpad kept for later gestures, as libinput takes a moment to pick up a new one
+static TOUCHPAD: Mutex<Option<Touchpad>> = Mutex::new(None);
 
-    // Try hyprctl first (for Hyprland)
-    if is_hyprland() {
-        // Apply monitor offset for correct positioning
-        let (offset_x, offset_y) = get_hyprland_monitor_offset();
-        let adjusted_x = x + offset_x;
-        let adjusted_y = y + offset_y;
-
-        debug!("Adjusted cursor move: ({}, {}) -> ({}, {})", x, y, adjusted_x, adjusted_y);
-
-        if Command::new("hyprctl")
-            .args(["dispatch", "movecursor", &adjusted_x.to_string(), &adjusted_y.to_string()])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
-        {
-            return Ok(());
-        }
+/// Perform a gesture on the virtual touchpad, creating it on first use
+fn touchpad_gesture(gesture: Gesture) -> Result<()> {
+    let mut touchpad = TOUCHPAD.lock().unwrap_or_else(|e| e.into_inner());
+    if touchpad.is_none() {
+        *touchpad = Some(Touchpad::create()?);
     }
+    touchpad.as_mut().expect("created above").perform(gesture)
+}
 
-    // Try ydotool
-    if Command::new("ydotool")
-        .args(["mousemove", "--absolute", "-x", &x.to_string(), "-y", &y.to_string()])
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-    {
+/// Pinch at the given position to zoom documents, images and maps
+///
+/// Emulated with a virtual touchpad when /dev/uinput is writable, otherwise
+/// with Ctrl+wheel, which most zoomable views also accept.
+pub fn pinch_at(x: i32, y: i32, zoom: Zoom) -> Result<()> {
+    info!("Zooming {:?} at ({}, {})", zoom, x, y);
+    move_cursor_to(x, y)?;
+    let result = touchpad_gesture(Gesture::Pinch(zoom == Zoom::In));
+    if tried("touchpad", &result) {
         return Ok(());
     }
-
-    // Try wlrctl
-    if Command::new("wlrctl")
-        .args(["pointer", "move", &x.to_string(), &y.to_string()])
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-    {
-        return Ok(());
+    if let Err(e) = result {
+        debug!("Touchpad pinch failed ({:#}), zooming with Ctrl+wheel", e);
     }
-
-    // Try dotool
-    let input = format!("mouseto {} {}\n", x, y);
-    let mut child = Command::new("dotool")
-        .stdin(std::process::Stdio::piped())
-        .spawn()?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(input.as_bytes())?;
+    let direction = if zoom == Zoom::In { ScrollDirection::Up } else { ScrollDirection::Down };
+    if on_path("dotool") {
+        return run_with_input("dotool", &format!("keydown ctrl\nscroll {}\nkeyup ctrl\n", direction.signed(1)));
+    }
+    if on_path("ydotool") {
+        // 29 is KEY_LEFTCTRL
+        run("ydotool", &["key", "29:1"])?;
+        let wheel = Ydotool.wheel(direction, 1);
+        run("ydotool", &["key", "29:0"])?;
+        return wheel;
     }
-    child.wait()?;
+    anyhow::bail!("Zooming needs write access to /dev/uinput, dotool or ydotool")
+}
 
-    Ok(())
+/// Swipe two fingers at the given position, scrolling with the toolkit's
+/// touchpad handling (kinetic scrolling, swipe navigation)
+pub fn swipe_at(x: i32, y: i32, direction: ScrollDirection) -> Result<()> {
+    const DISTANCE: i32 = 600;
+    info!("Swiping {:?} at ({}, {})", direction, x, y);
+    move_cursor_to(x, y)?;
+    // Fingers move against the scroll direction, as on a touchpad without natural scrolling
+    let (dx, dy) = match direction {
+        ScrollDirection::Up => (0, DISTANCE),
+        ScrollDirection::Down => (0, -DISTANCE),
+        ScrollDirection::Left => (DISTANCE, 0),
+        ScrollDirection::Right => (-DISTANCE, 0),
+    };
+    let result = touchpad_gesture(Gesture::Swipe(dx, dy));
+    tried("touchpad", &result);
+    result
+}
+
+/// Move cursor to position without clicking
+pub fn move_cursor_to(x: i32, y: i32) -> Result<()> {
+    debug!("Moving cursor to ({}, {})", x, y);
+    pointer().move_to(x, y)
 }
 
 /// Hold mouse button down (for drag operations)
 pub fn button_down(button: ClickButton) -> Result<()> {
-    let button_code = match button {
-        ClickButton::Left => "0x40",   // down only
-        ClickButton::Right => "0x41",
-        ClickButton::Middle => "0x42",
-    };
+    pointer().set_button(button, true)
+}
 
-    if Command::new("ydotool")
-        .args(["click", button_code])
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-    {
-        return Ok(());
+/// Release mouse button (for drag operations)
+pub fn button_up(button: ClickButton) -> Result<()> {
+    pointer().set_button(button, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Backend recording the operations it was asked to perform
+    struct Mock {
+        name: &'static str,
+        caps: Capabilities,
+        fails: bool,
+        log: Arc<Mutex<Vec<String>>>,
     }
 
-    // dotool alternative
-    let button_name = match button {
-        ClickButton::Left => "left",
-        ClickButton::Right => "right",
-        ClickButton::Middle => "middle",
-    };
-    let input = format!("buttondown {}\n", button_name);
-    let mut child = Command::new("dotool")
-        .stdin(std::process::Stdio::piped())
-        .spawn()?;
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(input.as_bytes())?;
+    impl Mock {
+        fn record(&self, op: String) -> Result<()> {
+            self.log.lock().unwrap().push(format!("{} {}", self.name, op));
+            if self.fails {
+                anyhow::bail!("{} failed", self.name);
+            }
+            Ok(())
+        }
     }
-    child.wait()?;
 
-    Ok(())
-}
+    impl PointerBackend for Mock {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        fn capabilities(&self) -> Capabilities {
+            self.caps
+        }
+        fn move_to(&self, x: i32, y: i32) -> Result<()> {
+            self.record(format!("move {},{}", x, y))
+        }
+        fn click(&self, x: i32, y: i32, button: ClickButton) -> Result<()> {
+            self.record(format!("click {},{} {}", x, y, button.name()))
+        }
+        fn scroll(&self, _x: i32, _y: i32, direction: ScrollDirection, amount: i32) -> Result<()> {
+            self.record(format!("scroll {:?} {}", direction, amount))
+        }
+        fn set_button(&self, button: ClickButton, pressed: bool) -> Result<()> {
+            self.record(format!("button {} {}", button.name(), pressed))
+        }
+    }
 
-/// Release mouse button (for drag operations)
-pub fn button_up(button: ClickButton) -> Result<()> {
-    let button_code = match button {
-        ClickButton::Left => "0x80",   // up only
-        ClickButton::Right => "0x81",
-        ClickButton::Middle => "0x82",
-    };
+    fn pointer_with(mocks: &[(&'static str, Capabilities, bool)]) -> (Pointer, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let backends = mocks
+            .iter()
+            .map(|&(name, caps, fails)| Box::new(Mock { name, caps, fails, log: log.clone() }) as Box<dyn PointerBackend>)
+            .collect();
+        (Pointer::new(backends), log)
+    }
 
-    if Command::new("ydotool")
-        .args(["click", button_code])
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-    {
-        return Ok(());
+    const ALL: Capabilities = Capabilities { absolute_move: true, buttons: true, wheel: true, hwheel: true };
+    const NO_HWHEEL: Capabilities = Capabilities { hwheel: false, ..ALL };
+
+    #[test]
+    fn test_failed_backend_falls_through() {
+        let (pointer, log) = pointer_with(&[("a", ALL, true), ("b", ALL, false), ("c", ALL, false)]);
+        pointer.click(10, 20, ClickButton::Right).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["a click 10,20 right", "b click 10,20 right"]);
     }
 
-    // dotool alternative
-    let button_name = match button {
-        ClickButton::Left => "left",
-        ClickButton::Right => "right",
-        ClickButton::Middle => "middle",
-    };
-    let input = format!("buttonup {}\n", button_name);
-    let mut child = Command::new("dotool")
-        .stdin(std::process::Stdio::piped())
-        .spawn()?;
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(input.as_bytes())?;
+    #[test]
+    fn test_backends_filtered_by_capability() {
+        let (pointer, log) = pointer_with(&[("wlr", NO_HWHEEL, false), ("full", ALL, false)]);
+        pointer.scroll(0, 0, ScrollDirection::Left, 3).unwrap();
+        pointer.scroll(0, 0, ScrollDirection::Down, 3).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["full scroll Left 3", "wlr scroll Down 3"]);
+
+        let (pointer, _) = pointer_with(&[("wlr", NO_HWHEEL, false)]);
+        assert!(pointer.scroll(0, 0, ScrollDirection::Right, 3).is_err());
     }
-    child.wait()?;
 
-    Ok(())
+    #[test]
+    fn test_parse_direction() {
+        assert_eq!("Up".parse(), Ok(ScrollDirection::Up));
+        assert!("sideways".parse::<ScrollDirection>().is_err());
+    }
+
+    #[test]
+    fn test_ydotool_codes() {
+        assert_eq!(ClickButton::Left.ydotool_code(true, true), "0xC0");
+        assert_eq!(ClickButton::Right.ydotool_code(true, false), "0x41");
+        assert_eq!(ClickButton::Middle.ydotool_code(false, true), "0x82");
+    }
 }
@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Put `text` on the Wayland clipboard with wl-copy
+///
+/// The text goes through stdin, so it can be of any length and start with a dash.
+pub fn copy(text: &str) -> Result<()> {
+    let mut child = Command::new("wl-copy")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run wl-copy (install wl-clipboard)")?;
+    child
+        .stdin
+        .take()
+        .context("wl-copy has no stdin")?
+        .write_all(text.as_bytes())
+        .context("Failed to pass the text to wl-copy")?;
+    if !child.wait().context("Failed to run wl-copy")?.success() {
+        anyhow::bail!("wl-copy failed");
+    }
+    Ok(())
+}
@@ -1,16 +1,40 @@
+use crate::atspi::normalize_role_name;
+use crate::contrast::{best_text_color, contrast_ratio, MIN_CONTRAST};
+use crate::filter::RoleFilter;
+use crate::hints::NameMatch;
+use crate::keys::KeySequence;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use tracing::warn;
 
 /// Main configuration structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     pub hints: HintConfig,
     pub colors: ColorConfig,
     pub behavior: BehaviorConfig,
     pub scroll: ScrollConfig,
+    pub overlay: OverlayConfig,
+    pub scan: ScanConfig,
+    pub role_picker: RolePickerConfig,
+    pub metrics: MetricsConfig,
+    pub providers: ProvidersConfig,
+    pub hotkey: HotkeyConfig,
+    pub power: PowerConfig,
+    pub window: WindowConfig,
+    pub nudge: NudgeConfig,
+    pub tabs: TabsConfig,
+    pub links: LinksConfig,
+    pub auto_text: AutoTextConfig,
+    pub hooks: HooksConfig,
+    pub history: HistoryConfig,
+    pub click: ClickConfig,
+    /// Elements to act on by name with `vimium-linux bookmark <name>`
+    pub bookmarks: BTreeMap<String, Bookmark>,
 }
 
 /// Hint display configuration
@@ -25,6 +49,95 @@ pub struct HintConfig {
     pub font_family: String,
     /// Padding inside hint box
     pub padding: u32,
+    /// Regex patterns; elements whose name matches any of them get no hint
+    pub exclude_names: Vec<String>,
+    /// Give password fields no hint, so hint keys can't end up typed into one
+    pub exclude_passwords: bool,
+    /// Roles hinted when `--filter` isn't given, e.g. "!table_cell"
+    pub filter: RoleFilter,
+    /// Draw hint labels in uppercase (typing stays lowercase)
+    pub uppercase_labels: bool,
+    /// Give each application's hints a border in its own color
+    pub group_by_app: bool,
+    /// Above this many elements, pick a region first and then an element in it (0 disables)
+    pub two_stage_threshold: usize,
+    /// How hint labels are assigned to elements
+    pub strategy: HintStrategy,
+    /// Characters for hints on the left half of the screen (`split_hands` strategy)
+    pub left_chars: String,
+    /// Characters for hints on the right half of the screen (`split_hands` strategy)
+    pub right_chars: String,
+    /// Style rules applied in order to matching elements; later rules win
+    pub rules: Vec<HintRule>,
+}
+
+/// Hint assignment strategies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HintStrategy {
+    /// Every hint uses `chars`
+    #[default]
+    Alphabet,
+    /// Left-hand keys on the left half of the screen, right-hand keys on the right
+    SplitHands,
+}
+
+/// A style rule, e.g. `{ match = { role = "link" }, style = { bg = "#224", position = "left" } }`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HintRule {
+    #[serde(rename = "match")]
+    pub matches: RuleMatch,
+    pub style: HintStyle,
+}
+
+/// Elements a rule applies to; an empty match applies to every element
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleMatch {
+    /// Roles, as for `--filter`
+    pub role: RoleFilter,
+    /// Regex on the accessible name (applied once names are known)
+    pub name: Option<String>,
+}
+
+/// Style properties a rule sets; unset ones keep their earlier value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HintStyle {
+    /// Label background
+    pub bg: Option<String>,
+    /// Label text
+    pub text: Option<String>,
+    /// Typed characters
+    pub matched: Option<String>,
+    /// Padding inside the label
+    pub padding: Option<u32>,
+    /// Font size of the label in pixels
+    pub size: Option<u32>,
+    /// Where the label sits relative to the element
+    pub position: Option<HintPosition>,
+    /// Don't hint matching elements
+    pub hidden: Option<bool>,
+}
+
+/// Placement of a hint label relative to its element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HintPosition {
+    /// Over the element's top-left corner
+    #[default]
+    TopLeft,
+    /// Centered over the element
+    Center,
+    /// Outside the element, to its left
+    Left,
+    /// Outside the element, to its right
+    Right,
+    /// Outside the element, above it
+    Above,
+    /// Outside the element, below it
+    Below,
 }
 
 /// Color configuration (hex strings like "#RRGGBB" or "#RRGGBBAA")
@@ -43,6 +156,36 @@ pub struct ColorConfig {
     pub input_bg: String,
     /// Input display text
     pub input_text: String,
+    /// Built-in palette; colors set explicitly in the file still win
+    pub theme: Theme,
+    /// What to do when text falls below the WCAG AA contrast ratio
+    pub contrast_check: ContrastCheck,
+}
+
+/// Built-in color palettes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// The stock white-on-dark palette
+    #[default]
+    Default,
+    /// Blue/amber palette avoiding red-green distinctions (green-weak)
+    Deuteranopia,
+    /// Blue/yellow palette avoiding dark reds (red-weak)
+    Protanopia,
+}
+
+/// Handling of low-contrast color combinations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContrastCheck {
+    /// Don't check
+    Off,
+    /// Log a warning
+    #[default]
+    Warn,
+    /// Replace the text color with black or white
+    Adjust,
 }
 
 /// Behavior configuration
@@ -57,6 +200,45 @@ pub struct BehaviorConfig {
     pub default_mode: ActionMode,
     /// Show element names in hints
     pub show_element_names: bool,
+    /// Ask for an element class (see `[role_picker]`) before showing hints
+    pub role_picker: bool,
+    /// Key cycling the pending action (Click, Right, Middle, Hover) while typing a hint
+    pub cycle_action_key: char,
+    /// Key cycling through showing one application's hints at a time
+    pub cycle_app_key: char,
+    /// Key repeating the last action on the element it was taken on, if it's hinted
+    pub repeat_key: char,
+    /// Key followed by a hint and a letter saving the hinted element as a mark
+    pub mark_key: char,
+    /// Key followed by a letter selecting the marked element, if it's hinted
+    pub jump_key: char,
+    /// Milliseconds to wait after acting on an element
+    pub post_action_delay_ms: u64,
+    /// Click by pressing the element's own keyboard shortcut when the app reports one
+    pub use_key_bindings: bool,
+    /// Roles clicked through the element's own toggle or click action instead of the
+    /// pointer, which works when another window covers them; empty to always use the pointer
+    pub action_roles: RoleFilter,
+    /// Give focus back to the previously focused window when the overlay closes
+    /// (Hyprland and Sway)
+    pub restore_focus: bool,
+    /// How hints are picked: the overlay, a menu from rofi, dmenu or fuzzel,
+    /// or a list in the terminal
+    pub picker: Picker,
+}
+
+/// Where the user picks a hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Picker {
+    /// The layer-shell hint overlay
+    #[default]
+    Overlay,
+    Rofi,
+    Dmenu,
+    Fuzzel,
+    /// A list with fuzzy filtering in the terminal
+    Tui,
 }
 
 /// Scroll mode configuration
@@ -69,11 +251,301 @@ pub struct ScrollConfig {
     pub page_step: i32,
     /// Smooth scrolling (multiple small steps)
     pub smooth: bool,
+    /// Keep faded hints for the scrolled area's elements; press f to pick one
+    pub show_hints: bool,
+    /// Send hjkl presses made within this many milliseconds as one scroll (0 sends each)
+    pub batch_ms: u64,
+    /// How hjkl presses repeated in one direction speed up
+    pub accel_curve: AccelCurve,
+    /// Growth per repeated press: added to the multiplier (linear) or multiplying it (exponential)
+    pub accel_rate: f32,
+    /// Largest multiplier of `scroll_step`
+    pub accel_max: f32,
+    /// Presses at most this many milliseconds apart count as repeats
+    pub accel_window_ms: u64,
 }
 
-/// Action modes
+/// Growth of the scroll step over repeated presses
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
+pub enum AccelCurve {
+    /// Every press scrolls `scroll_step`
+    Off,
+    #[default]
+    Linear,
+    Exponential,
+}
+
+/// Overlay surface configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayConfig {
+    /// Memory ceiling for an overlay frame in MiB; larger frames are
+    /// rendered at reduced resolution (0 disables the limit)
+    pub max_buffer_mb: u32,
+    /// Hide the mouse cursor while hints are shown so it doesn't cover them
+    pub hide_cursor: bool,
+    /// Redraw hints as their names arrive; otherwise names show on the next key press
+    pub live_names: bool,
+    /// Minimum milliseconds between redraws not caused by a key press (0 disables)
+    pub redraw_interval_ms: u64,
+    /// Draw scan timing, application names and why nearby elements got no hint
+    pub debug: bool,
+    /// Show the best match's name, role and tooltip in a panel while typing
+    pub preview: bool,
+    /// Corner of the "Input:" box
+    pub input_position: PanelPosition,
+    /// Corner of the mode indicator; beside the input box when they share one
+    pub mode_position: PanelPosition,
+    /// Move hints along when their window is moved or resized while they are shown
+    pub follow_windows: bool,
+}
+
+/// Where an overlay panel is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// Not drawn at all
+    Hidden,
+}
+
+/// AT-SPI tree traversal configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Roles whose children are never visited (e.g. "image", "separator")
+    pub skip_descent: Vec<String>,
+    /// Roles always descended into, overriding `skip_descent`
+    pub always_descend: Vec<String>,
+    /// Seconds the list of accessible applications is reused across invocations (0 disables)
+    pub app_cache_secs: u64,
+    /// Accessible names looked up at once
+    pub name_concurrency: usize,
+    /// Milliseconds a scan may take before hints are shown for what it found so far (0 waits forever)
+    pub timeout_ms: u64,
+    /// Accessible application names to scan, ignoring case; empty scans every application
+    pub apps: Vec<String>,
+    /// Only scan the active window, falling back to every window when none is active
+    pub focused_only: bool,
+    /// Drop elements of windows on hidden workspaces or minimized, as Hyprland's or sway's IPC tells
+    pub visible_only: bool,
+}
+
+impl ScanConfig {
+    /// Whether the application named `app` is scanned
+    pub fn scans_app(&self, app: &str) -> bool {
+        self.apps.is_empty() || self.apps.iter().any(|a| a.eq_ignore_ascii_case(app))
+    }
+}
+
+/// Metrics export configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Write Prometheus metrics here on exit, and every 15 seconds while the daemon runs, for
+    /// node_exporter's textfile collector
+    pub textfile: Option<PathBuf>,
+}
+
+/// Global hotkeys read from /dev/input by the daemon
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Chords and the modes they start
+    pub bindings: Vec<HotkeyBinding>,
+    /// Register through the GlobalShortcuts portal instead of reading /dev/input
+    pub portal: bool,
+}
+
+/// A key chord starting a mode, e.g. `{ chord = "super+semicolon", mode = "click" }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub chord: String,
+    #[serde(default)]
+    pub mode: ActionMode,
+}
+
+/// Throttling while on battery or in power-saver mode (detected through UPower)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    /// Check the power state at startup and throttle when constrained
+    pub enabled: bool,
+    /// `scan.name_concurrency` while constrained
+    pub name_concurrency: usize,
+    /// Cache lifetimes are multiplied by this while constrained
+    pub cache_ttl_factor: u64,
+    /// Keep smooth scrolling while constrained
+    pub smooth_scroll: bool,
+}
+
+/// Text mode started by the daemon when a prompt appears
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoTextConfig {
+    /// Focus the text field of a dialog with a single one when it becomes active
+    pub enabled: bool,
+    /// Applications (accessible names, e.g. "gcr-prompter") it applies to; empty means all
+    pub apps: Vec<String>,
+    /// Applications it never applies to
+    pub exclude_apps: Vec<String>,
+}
+
+impl AutoTextConfig {
+    /// Whether prompts from `app` get their field focused
+    pub fn applies_to(&self, app: &str) -> bool {
+        let listed = |apps: &[String]| apps.iter().any(|a| a.eq_ignore_ascii_case(app));
+        self.enabled && (self.apps.is_empty() || listed(&self.apps)) && !listed(&self.exclude_apps)
+    }
+}
+
+/// Shell commands run and keys sent when a mode ends, see `hooks`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Run after an element was selected, with its details in `VIMIUM_*` variables
+    pub on_select: Option<String>,
+    /// Run when the overlay was closed without a selection
+    pub on_cancel: Option<String>,
+    /// Keys sent after an element was selected, as for `send-keys`, e.g. "ctrl+a"
+    pub on_select_keys: Option<KeySequence>,
+}
+
+/// Pointer tool configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickConfig {
+    pub ydotool: YdotoolConfig,
+}
+
+/// ydotool configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YdotoolConfig {
+    /// Start a private ydotoold the first time ydotool is needed and none is
+    /// running, restart it if it dies, and stop it on exit
+    pub manage_daemon: bool,
+}
+
+/// An element found by application and name, e.g. `play = { app = "spotify", name = "Play" }`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmark {
+    /// Application name, ignoring case; empty for any application
+    pub app: String,
+    /// Element name
+    pub name: String,
+    /// How `name` is compared: exact, substring or regex
+    pub name_match: NameMatch,
+    /// Roles the element may have, e.g. "button"
+    pub role: RoleFilter,
+    /// What to do with the element
+    pub action: ActionMode,
+}
+
+impl Bookmark {
+    /// Whether an element of the application named `app` can be this bookmark
+    pub fn in_app(&self, app: &str) -> bool {
+        self.app.is_empty() || self.app.eq_ignore_ascii_case(app)
+    }
+}
+
+/// Selections remembered for `repeat-last`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Selections to keep; 0 records nothing
+    pub size: usize,
+}
+
+/// Window move/resize mode configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Pixels to move or resize per key press
+    pub step: i32,
+    /// Pixels to move or resize per key press with Ctrl held
+    pub large_step: i32,
+}
+
+/// Pointer adjustment after `move` picks an element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NudgeConfig {
+    /// Pixels to move the pointer per key press
+    pub step: i32,
+    /// Pixels to move the pointer per key press with Ctrl held
+    pub large_step: i32,
+}
+
+/// Tab switcher (`vimium-linux tabs`) configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabsConfig {
+    /// Font size of tab hints in pixels, larger than `hints.font_size` as tabs are few
+    pub font_size: u32,
+}
+
+/// Link opening (`vimium-linux open-link`) configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinksConfig {
+    /// Command the link's URI is appended to, run with `sh -c`, e.g. "firefox --new-tab"
+    pub open_command: String,
+}
+
+/// Element provider configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProvidersConfig {
+    /// Providers queried for elements; earlier ones win when results overlap
+    pub enabled: Vec<String>,
+}
+
+/// Role picker configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolePickerConfig {
+    /// Element classes offered by the picker, in display order
+    pub classes: Vec<RoleClass>,
+}
+
+/// An element class selectable with a single key in the role picker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleClass {
+    /// Key selecting this class
+    pub key: char,
+    /// Label shown in the picker
+    pub label: String,
+    /// Roles in this class (e.g. "push_button"); empty means all roles
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl RoleClass {
+    fn new(key: char, label: &str, roles: &[&str]) -> Self {
+        Self {
+            key,
+            label: label.to_string(),
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    /// Whether an element role (as reported by AT-SPI) belongs to this class
+    pub fn matches(&self, role: &str) -> bool {
+        let role = normalize_role_name(role);
+        self.roles.is_empty() || self.roles.iter().any(|r| normalize_role_name(r) == role)
+    }
+}
+
+/// Action modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum ActionMode {
     /// Normal click mode
     #[default]
@@ -88,15 +560,42 @@ pub enum ActionMode {
     Text,
     /// Drag mode
     Drag,
+    /// Move the pointer without clicking
+    Hover,
+    /// Invoke the element's default accessible action, leaving the pointer alone
+    Activate,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            hints: HintConfig::default(),
-            colors: ColorConfig::default(),
-            behavior: BehaviorConfig::default(),
-            scroll: ScrollConfig::default(),
+impl ActionMode {
+    /// Next action for the cycle key: Click, Right, Middle, Hover, Click, ...
+    pub fn next_in_cycle(self) -> ActionMode {
+        match self {
+            ActionMode::Click => ActionMode::RightClick,
+            ActionMode::RightClick => ActionMode::MiddleClick,
+            ActionMode::MiddleClick => ActionMode::Hover,
+            _ => ActionMode::Click,
+        }
+    }
+
+    /// Name on the command line, e.g. "right-click"
+    pub fn name(self) -> String {
+        use clap::ValueEnum;
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Label shown in the overlay's mode indicator
+    pub fn label(self) -> &'static str {
+        match self {
+            ActionMode::Click => "Click",
+            ActionMode::RightClick => "Right-Click",
+            ActionMode::MiddleClick => "Middle-Click",
+            ActionMode::Scroll => "Scroll",
+            ActionMode::Text => "Text",
+            ActionMode::Drag => "Drag",
+            ActionMode::Hover => "Hover",
+            ActionMode::Activate => "Activate",
         }
     }
 }
@@ -108,6 +607,16 @@ impl Default for HintConfig {
             font_size: 14,
             font_family: "monospace".to_string(),
             padding: 4,
+            exclude_names: Vec::new(),
+            exclude_passwords: false,
+            filter: RoleFilter::default(),
+            uppercase_labels: true,
+            group_by_app: false,
+            two_stage_threshold: 0,
+            strategy: HintStrategy::Alphabet,
+            left_chars: "asdfgqwertzxcvb".to_string(),
+            right_chars: "hjklyuiopnm".to_string(),
+            rules: Vec::new(),
         }
     }
 }
@@ -121,6 +630,81 @@ impl Default for ColorConfig {
             hint_text_matched: "#888888".to_string(),
             input_bg: "#ffffffee".to_string(),
             input_text: "#000000".to_string(),
+            theme: Theme::Default,
+            contrast_check: ContrastCheck::Warn,
+        }
+    }
+}
+
+impl Theme {
+    /// Colors for this theme, or None for the stock defaults
+    fn palette(self) -> Option<ColorConfig> {
+        let colors = |background: &str, hint_bg: &str, hint_text: &str, matched: &str| ColorConfig {
+            background: background.to_string(),
+            hint_bg: hint_bg.to_string(),
+            hint_text: hint_text.to_string(),
+            hint_text_matched: matched.to_string(),
+            input_bg: "#ffffffee".to_string(),
+            input_text: "#000000".to_string(),
+            theme: self,
+            contrast_check: ContrastCheck::default(),
+        };
+        match self {
+            Theme::Default => None,
+            Theme::Deuteranopia => Some(colors("#00000080", "#ffb000", "#000000", "#0050a0")),
+            Theme::Protanopia => Some(colors("#00000080", "#1e64c8", "#ffffff", "#ffd84a")),
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Fill in theme colors for every key not listed in `explicit`
+    pub fn apply_theme(&mut self, explicit: &[String]) {
+        let Some(palette) = self.theme.palette() else {
+            return;
+        };
+        let fields = [
+            ("background", &mut self.background, palette.background),
+            ("hint_bg", &mut self.hint_bg, palette.hint_bg),
+            ("hint_text", &mut self.hint_text, palette.hint_text),
+            ("hint_text_matched", &mut self.hint_text_matched, palette.hint_text_matched),
+            ("input_bg", &mut self.input_bg, palette.input_bg),
+            ("input_text", &mut self.input_text, palette.input_text),
+        ];
+        for (key, field, value) in fields {
+            if !explicit.iter().any(|k| k == key) {
+                *field = value;
+            }
+        }
+    }
+
+    /// Check text/background pairs against WCAG AA, warning or adjusting
+    pub fn check_contrast(&mut self) {
+        if self.contrast_check == ContrastCheck::Off {
+            return;
+        }
+        let pairs = [
+            ("hint_text", &mut self.hint_text, &self.hint_bg),
+            ("input_text", &mut self.input_text, &self.input_bg),
+        ];
+        for (key, text, bg) in pairs {
+            let ratio = contrast_ratio(parse_color(text), parse_color(bg));
+            if ratio >= MIN_CONTRAST {
+                continue;
+            }
+            if self.contrast_check == ContrastCheck::Adjust {
+                let fixed = best_text_color(parse_color(bg));
+                warn!(
+                    "colors.{} {} on {} has contrast {:.1}:1, using {} instead",
+                    key, text, bg, ratio, fixed
+                );
+                *text = fixed.to_string();
+            } else {
+                warn!(
+                    "colors.{} {} on {} has contrast {:.1}:1, below the recommended {}:1",
+                    key, text, bg, ratio, MIN_CONTRAST
+                );
+            }
         }
     }
 }
@@ -132,6 +716,17 @@ impl Default for BehaviorConfig {
             exit_on_click: true,
             default_mode: ActionMode::Click,
             show_element_names: false,
+            role_picker: false,
+            cycle_action_key: ',',
+            cycle_app_key: '\t',
+            repeat_key: '.',
+            mark_key: 'M',
+            jump_key: '\'',
+            post_action_delay_ms: 0,
+            use_key_bindings: false,
+            action_roles: "check_box,toggle_button,radio_button".parse().expect("valid role filter"),
+            restore_focus: false,
+            picker: Picker::Overlay,
         }
     }
 }
@@ -142,11 +737,208 @@ impl Default for ScrollConfig {
             scroll_step: 50,
             page_step: 500,
             smooth: true,
+            show_hints: false,
+            batch_ms: 0,
+            accel_curve: AccelCurve::Linear,
+            accel_rate: 0.5,
+            accel_max: 4.0,
+            accel_window_ms: 250,
+        }
+    }
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            max_buffer_mb: 64,
+            hide_cursor: false,
+            live_names: true,
+            redraw_interval_ms: 0,
+            debug: false,
+            preview: false,
+            input_position: PanelPosition::TopLeft,
+            mode_position: PanelPosition::TopLeft,
+            follow_windows: false,
+        }
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        let skip = [
+            "image",
+            "icon",
+            "separator",
+            "progress_bar",
+            "level_bar",
+            "animation",
+            "terminal",
+        ];
+        Self {
+            skip_descent: skip.iter().map(|r| r.to_string()).collect(),
+            always_descend: Vec::new(),
+            app_cache_secs: 5,
+            name_concurrency: 8,
+            timeout_ms: 5000,
+            apps: Vec::new(),
+            focused_only: false,
+            visible_only: false,
+        }
+    }
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name_concurrency: 1,
+            cache_ttl_factor: 4,
+            smooth_scroll: false,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            step: 20,
+            large_step: 100,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            size: 20,
+        }
+    }
+}
+
+impl Default for TabsConfig {
+    fn default() -> Self {
+        Self {
+            font_size: 20,
+        }
+    }
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        Self {
+            open_command: "xdg-open".to_string(),
+        }
+    }
+}
+
+impl Default for NudgeConfig {
+    fn default() -> Self {
+        Self {
+            step: 5,
+            large_step: 50,
+        }
+    }
+}
+
+impl Default for ProvidersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: vec!["atspi".to_string()],
         }
     }
 }
 
+impl Default for RolePickerConfig {
+    fn default() -> Self {
+        Self {
+            classes: vec![
+                RoleClass::new('b', "buttons", &["push_button", "toggle_button", "radio_button", "check_box"]),
+                RoleClass::new('l', "links", &["link"]),
+                RoleClass::new('i', "inputs", &["entry", "password_text", "combo_box", "spin_button"]),
+                RoleClass::new('t', "tabs", &["page_tab"]),
+                RoleClass::new('m', "menus", &["menu_item"]),
+                RoleClass::new('a', "all", &[]),
+            ],
+        }
+    }
+}
+
+impl OverlayConfig {
+    /// Memory ceiling in bytes
+    pub fn max_buffer_bytes(&self) -> usize {
+        self.max_buffer_mb as usize * 1024 * 1024
+    }
+}
+
+/// Adjustments for laggy remote desktops (VNC, RDP), see `--safe-mode`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SafeMode {
+    /// No smooth scrolling and no redraws as names arrive
+    pub no_animations: bool,
+    /// A faint solid background and no per-application colors
+    pub low_color: bool,
+    /// Fewer redraws and scroll events, at the cost of responsiveness
+    pub coarse: bool,
+}
+
+/// Settings given on the command line for one invocation, e.g. `--hint-chars`
+///
+/// `trigger` sends them to the daemon, which applies them to the mode it runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overrides {
+    pub hint_chars: Option<String>,
+    /// Turn off `behavior.auto_select`
+    pub no_auto_select: bool,
+    /// Alpha of the overlay background, keeping its color
+    pub bg_alpha: Option<u8>,
+    pub scroll_step: Option<i32>,
+    /// Applications to scan instead of `scan.apps`, unless empty
+    pub apps: Vec<String>,
+    /// Turn on `scan.focused_only`
+    pub focused_only: bool,
+}
+
 impl Config {
+    /// Apply per-invocation overrides on top of the loaded config
+    pub fn apply_overrides(&mut self, overrides: Overrides) {
+        if let Some(chars) = overrides.hint_chars {
+            self.hints.chars = chars;
+        }
+        if overrides.no_auto_select {
+            self.behavior.auto_select = false;
+        }
+        if let Some(alpha) = overrides.bg_alpha {
+            let (r, g, b, _) = parse_color(&self.colors.background);
+            self.colors.background = format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, alpha);
+        }
+        if let Some(step) = overrides.scroll_step {
+            self.scroll.scroll_step = step;
+        }
+        if !overrides.apps.is_empty() {
+            self.scan.apps = overrides.apps;
+        }
+        if overrides.focused_only {
+            self.scan.focused_only = true;
+        }
+    }
+
+    /// Apply the `--safe-mode` adjustments on top of the loaded config
+    pub fn apply_safe_mode(&mut self, safe: SafeMode) {
+        if safe.no_animations {
+            self.scroll.smooth = false;
+            self.overlay.live_names = false;
+        }
+        if safe.low_color {
+            self.colors.background = "#00000030".to_string();
+            self.hints.group_by_app = false;
+        }
+        if safe.coarse {
+            self.overlay.redraw_interval_ms = self.overlay.redraw_interval_ms.max(100);
+            self.scroll.batch_ms = self.scroll.batch_ms.max(100);
+        }
+    }
+
     /// Load config from default location or return defaults
     pub fn load() -> Self {
         Self::load_from_path(Self::config_path()).unwrap_or_default()
@@ -156,7 +948,51 @@ impl Config {
     pub fn load_from_path(path: PathBuf) -> Result<Self> {
         let content = fs::read_to_string(&path)
             .with_context(|| format!("Failed to read config from {:?}", path))?;
-        toml::from_str(&content).context("Failed to parse config file")
+        Self::parse(&content)
+    }
+
+    /// Parse config file contents, applying the selected color theme
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config: Config = toml::from_str(content).context("Failed to parse config file")?;
+
+        // Theme colors must not override colors the user set explicitly
+        let raw: toml::Table = toml::from_str(content).context("Failed to parse config file")?;
+        let explicit: Vec<String> = raw
+            .get("colors")
+            .and_then(|c| c.as_table())
+            .map(|t| t.keys().cloned().collect())
+            .unwrap_or_default();
+        config.colors.apply_theme(&explicit);
+        config.check_colors()?;
+
+        Ok(config)
+    }
+
+    /// Fail on colors `parse_color` can't read, naming the setting
+    fn check_colors(&self) -> Result<()> {
+        let colors = &self.colors;
+        let mut settings = vec![
+            ("colors.background".to_string(), &colors.background),
+            ("colors.hint_bg".to_string(), &colors.hint_bg),
+            ("colors.hint_text".to_string(), &colors.hint_text),
+            ("colors.hint_text_matched".to_string(), &colors.hint_text_matched),
+            ("colors.input_bg".to_string(), &colors.input_bg),
+            ("colors.input_text".to_string(), &colors.input_text),
+        ];
+        for (i, rule) in self.hints.rules.iter().enumerate() {
+            let style = &rule.style;
+            for (key, color) in [("bg", &style.bg), ("text", &style.text), ("matched", &style.matched)] {
+                if let Some(color) = color {
+                    settings.push((format!("hints.rules[{}].style.{}", i, key), color));
+                }
+            }
+        }
+        for (setting, color) in settings {
+            if try_parse_color(color).is_none() {
+                anyhow::bail!("Invalid color {:?} for {} (use #RGB, #RGBA, #RRGGBB or #RRGGBBAA)", color, setting);
+            }
+        }
+        Ok(())
     }
 
     /// Get the default config file path
@@ -189,26 +1025,29 @@ impl Config {
 }
 
 /// Parse a hex color string to RGBA components (0-255)
+///
+/// Invalid colors are opaque black; `Config::parse` rejects them before they get here.
 pub fn parse_color(hex: &str) -> (u8, u8, u8, u8) {
+    try_parse_color(hex).unwrap_or((0, 0, 0, 255))
+}
+
+/// Parse `#RGB`, `#RGBA`, `#RRGGBB` or `#RRGGBBAA` (the `#` is optional)
+pub fn try_parse_color(hex: &str) -> Option<(u8, u8, u8, u8)> {
     let hex = hex.trim_start_matches('#');
-    let len = hex.len();
-
-    if len == 6 {
-        // RGB
-        let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-        let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-        let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-        (r, g, b, 255)
-    } else if len == 8 {
-        // RGBA
-        let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-        let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-        let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-        let a = u8::from_str_radix(&hex[6..8], 16).unwrap_or(255);
-        (r, g, b, a)
-    } else {
-        // Invalid, return black
-        (0, 0, 0, 255)
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, digits: usize| {
+        let value = u8::from_str_radix(&hex[i * digits..(i + 1) * digits], 16).ok()?;
+        // A short digit stands for itself repeated, e.g. `a` for `aa`
+        Some(if digits == 1 { value * 0x11 } else { value })
+    };
+    match hex.len() {
+        3 => Some((channel(0, 1)?, channel(1, 1)?, channel(2, 1)?, 255)),
+        4 => Some((channel(0, 1)?, channel(1, 1)?, channel(2, 1)?, channel(3, 1)?)),
+        6 => Some((channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, 255)),
+        8 => Some((channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, channel(3, 2)?)),
+        _ => None,
     }
 }
 
@@ -230,10 +1069,134 @@ mod tests {
         assert_eq!(parse_color("#000000b4"), (0, 0, 0, 180));
     }
 
+    #[test]
+    fn test_parse_short_colors() {
+        assert_eq!(parse_color("#224"), (0x22, 0x22, 0x44, 255));
+        assert_eq!(parse_color("#f008"), (255, 0, 0, 0x88));
+        assert_eq!(try_parse_color("#12345"), None);
+        assert_eq!(try_parse_color("#gg0000"), None);
+        assert!(Config::parse("[colors]\nhint_bg = \"#224\"").is_ok());
+        assert!(Config::parse("[colors]\nhint_bg = \"blue\"").is_err());
+        assert!(Config::parse("[[hints.rules]]\nstyle = { bg = \"#12\" }").is_err());
+    }
+
+    #[test]
+    fn test_theme_keeps_explicit_colors() {
+        let config = Config::parse(
+            r##"
+            [colors]
+            theme = "deuteranopia"
+            hint_text = "#111111"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.colors.hint_bg, "#ffb000");
+        assert_eq!(config.colors.hint_text, "#111111");
+    }
+
+    #[test]
+    fn test_contrast_adjust() {
+        let mut colors = ColorConfig {
+            hint_bg: "#ffffff".to_string(),
+            hint_text: "#eeeeee".to_string(),
+            contrast_check: ContrastCheck::Adjust,
+            ..ColorConfig::default()
+        };
+        colors.check_contrast();
+        assert_eq!(colors.hint_text, "#000000");
+    }
+
+    #[test]
+    fn test_action_cycle() {
+        let mut action = ActionMode::Click;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            action = action.next_in_cycle();
+            seen.push(action);
+        }
+        assert_eq!(
+            seen,
+            [ActionMode::RightClick, ActionMode::MiddleClick, ActionMode::Hover, ActionMode::Click]
+        );
+    }
+
+    #[test]
+    fn test_role_class_matches() {
+        let links = RoleClass::new('l', "links", &["link"]);
+        assert!(links.matches("Link"));
+        assert!(!links.matches("PushButton"));
+
+        let buttons = RoleClass::new('b', "buttons", &["push_button"]);
+        assert!(buttons.matches("PushButton"));
+
+        let all = RoleClass::new('a', "all", &[]);
+        assert!(all.matches("TableCell"));
+    }
+
     #[test]
     fn test_default_config() {
         let config = Config::default();
         assert!(config.behavior.auto_select);
         assert_eq!(config.hints.font_size, 14);
+        assert!(config.behavior.action_roles.matches("CheckBox"));
+        assert!(config.behavior.action_roles.matches("ToggleButton"));
+        assert!(!config.behavior.action_roles.matches("PushButton"));
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let config = Config::parse(
+            r#"
+            [bookmarks]
+            play = { app = "spotify", name = "Play" }
+            new = { name = "^New", name_match = "regex", role = "button", action = "hover" }
+            "#,
+        )
+        .unwrap();
+        let play = &config.bookmarks["play"];
+        assert!(play.in_app("Spotify"));
+        assert!(!play.in_app("Firefox"));
+        assert_eq!(play.action, ActionMode::Click);
+        let new = &config.bookmarks["new"];
+        assert!(new.in_app("gedit"));
+        assert_eq!(new.name_match, NameMatch::Regex);
+        assert!(new.role.matches("PushButton"));
+        assert_eq!(new.action, ActionMode::Hover);
+    }
+
+    #[test]
+    fn test_apply_safe_mode() {
+        let mut config = Config::default();
+        config.overlay.redraw_interval_ms = 250;
+        config.apply_safe_mode(SafeMode {
+            coarse: true,
+            ..Default::default()
+        });
+        assert_eq!(config.overlay.redraw_interval_ms, 250);
+        assert_eq!(config.scroll.batch_ms, 100);
+        assert!(config.overlay.live_names);
+        assert_eq!(config.colors.background, "#00000080");
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut config = Config::default();
+        config.colors.background = "#102030".to_string();
+        config.apply_overrides(Overrides {
+            hint_chars: Some("jkl".to_string()),
+            no_auto_select: true,
+            bg_alpha: Some(0x40),
+            scroll_step: None,
+            apps: vec!["firefox".to_string()],
+            focused_only: true,
+        });
+        assert_eq!(config.hints.chars, "jkl");
+        assert!(!config.behavior.auto_select);
+        assert_eq!(config.colors.background, "#10203040");
+        assert_eq!(config.scroll.scroll_step, Config::default().scroll.scroll_step);
+        assert!(config.scan.scans_app("Firefox"));
+        assert!(!config.scan.scans_app("Thunderbird"));
+        assert!(Config::default().scan.scans_app("Thunderbird"));
+        assert!(config.scan.focused_only);
     }
 }
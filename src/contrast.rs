@@ -0,0 +1,60 @@
+/// Minimum contrast ratio for normal text (WCAG 2.x level AA)
+pub const MIN_CONTRAST: f64 = 4.5;
+
+/// Relative luminance of an sRGB color as defined by WCAG
+pub fn relative_luminance(color: (u8, u8, u8, u8)) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b, _) = color;
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+/// Contrast ratio between two colors, from 1.0 (identical) to 21.0 (black on white)
+pub fn contrast_ratio(a: (u8, u8, u8, u8), b: (u8, u8, u8, u8)) -> f64 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    let (lighter, darker) = if la > lb { (la, lb) } else { (lb, la) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Black or white, whichever reads better on `background`
+pub fn best_text_color(background: (u8, u8, u8, u8)) -> &'static str {
+    let black = contrast_ratio(background, (0, 0, 0, 255));
+    let white = contrast_ratio(background, (255, 255, 255, 255));
+    if black >= white {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contrast_ratio_extremes() {
+        let black = (0, 0, 0, 255);
+        let white = (255, 255, 255, 255);
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(white, white) - 1.0).abs() < 0.01);
+        assert_eq!(contrast_ratio(black, white), contrast_ratio(white, black));
+    }
+
+    #[test]
+    fn test_gray_on_white_fails() {
+        // The default matched-text gray is deliberately low contrast
+        assert!(contrast_ratio((0x88, 0x88, 0x88, 255), (255, 255, 255, 255)) < MIN_CONTRAST);
+    }
+
+    #[test]
+    fn test_best_text_color() {
+        assert_eq!(best_text_color((255, 255, 0, 255)), "#000000");
+        assert_eq!(best_text_color((0, 0, 128, 255)), "#ffffff");
+    }
+}
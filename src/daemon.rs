@@ -0,0 +1,271 @@
+use crate::atspi;
+use crate::config::{ActionMode, AutoTextConfig, Config, Overrides};
+use crate::filter::RoleFilter;
+use crate::overlay;
+use crate::provider;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Request sent by `trigger` to the daemon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TriggerRequest {
+    pub mode: ActionMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<RoleFilter>,
+    /// Global options given to `trigger`, applied for this mode only
+    #[serde(default)]
+    pub overrides: Overrides,
+}
+
+/// The daemon's answer once the mode finished
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TriggerReply {
+    /// Error message if the mode failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Socket the daemon listens on
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("vimium-linux.sock")
+}
+
+/// The socket passed by systemd socket activation (`LISTEN_FDS`), if any
+fn activated_listener() -> Result<Option<UnixListener>> {
+    /// First file descriptor passed by systemd
+    const LISTEN_FDS_START: i32 = 3;
+
+    let for_us = std::env::var("LISTEN_PID").is_ok_and(|pid| pid == std::process::id().to_string());
+    let count: u32 = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse().ok()).unwrap_or(0);
+    if !for_us || count == 0 {
+        return Ok(None);
+    }
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+
+    use std::os::fd::FromRawFd;
+    // SAFETY: systemd hands this descriptor to us and nothing else owns it
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    info!("Using the socket passed by systemd");
+    Ok(Some(UnixListener::from_std(listener)?))
+}
+
+/// Keep the accessibility and Wayland connections open and run modes on request
+///
+/// Modes are requested by `trigger` over the socket or sent on `hotkeys`.
+/// Requests are handled one at a time; one arriving while an overlay is up
+/// waits for it to close.
+pub async fn run<F, Fut>(mut hotkeys: mpsc::UnboundedReceiver<ActionMode>, mut run_mode: F) -> Result<()>
+where
+    F: FnMut(TriggerRequest) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let path = socket_path();
+    let listener = match activated_listener()? {
+        Some(listener) => listener,
+        None => {
+            if UnixStream::connect(&path).await.is_ok() {
+                anyhow::bail!("A daemon is already listening on {:?}", path);
+            }
+            let _ = std::fs::remove_file(&path);
+            UnixListener::bind(&path).with_context(|| format!("Failed to bind {:?}", path))?
+        }
+    };
+
+    if let Err(e) = atspi::warm_up().await {
+        warn!("Accessibility bus not available yet: {}", e);
+    }
+    if let Err(e) = overlay::wayland_connection() {
+        warn!("Wayland not available yet: {}", e);
+    }
+    info!("Daemon listening on {:?}", path);
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            Some(mode) = hotkeys.recv() => {
+                let request = TriggerRequest {
+                    mode,
+                    filter: None,
+                    overrides: Overrides::default(),
+                };
+                if let Err(e) = run_mode(request).await {
+                    warn!("{:?} mode failed: {:#}", mode, e);
+                }
+                continue;
+            }
+        };
+        let (stream, _) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Daemon accept failed: {}", e);
+                continue;
+            }
+        };
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        if let Err(e) = BufReader::new(reader).read_line(&mut line).await {
+            debug!("Failed to read trigger: {}", e);
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<TriggerRequest>(&line) {
+            Ok(request) => {
+                info!("Triggered {:?} mode", request.mode);
+                let mode = request.mode;
+                match run_mode(request).await {
+                    Ok(()) => TriggerReply::default(),
+                    Err(e) => {
+                        warn!("{:?} mode failed: {:#}", mode, e);
+                        TriggerReply { error: Some(format!("{:#}", e)) }
+                    }
+                }
+            }
+            Err(e) => TriggerReply { error: Some(format!("Invalid trigger: {}", e)) },
+        };
+
+        let mut reply = serde_json::to_string(&reply)?;
+        reply.push('\n');
+        if let Err(e) = writer.write_all(reply.as_bytes()).await {
+            debug!("Trigger client went away: {}", e);
+        }
+    }
+}
+
+/// Ask a running daemon to run `mode`, waiting until it finishes
+pub async fn trigger(request: &TriggerRequest) -> Result<()> {
+    let path = socket_path();
+    let stream = UnixStream::connect(&path)
+        .await
+        .with_context(|| format!("No daemon listening on {:?} (start `vimium-linux daemon`)", path))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut request = serde_json::to_string(request)?;
+    request.push('\n');
+    writer.write_all(request.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let reply: TriggerReply = serde_json::from_str(&line).context("Invalid reply from daemon")?;
+    match reply.error {
+        Some(error) => anyhow::bail!("Daemon: {}", error),
+        None => Ok(()),
+    }
+}
+
+/// Window roles of prompts such as password dialogs
+const PROMPT_ROLES: &[&str] = &["Dialog", "Alert"];
+
+/// Whether an activated window may be a prompt whose field should get focus
+fn is_prompt_candidate(activated: &atspi::ActivatedWindow, config: &AutoTextConfig) -> bool {
+    PROMPT_ROLES.contains(&activated.window.role.as_str()) && config.applies_to(&activated.app)
+}
+
+/// Focus the text field of a dialog with a single one whenever it becomes active
+///
+/// With one field there is nothing to pick, so it is focused directly rather
+/// than through text mode, whose scan would hint fields of every window. Each
+/// prompt is handled once, so focus moved elsewhere isn't taken back when the
+/// dialog is activated again.
+pub async fn watch_prompts(config: Config) {
+    let (windows_tx, mut windows) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        if let Err(e) = atspi::watch_window_activations(windows_tx).await {
+            warn!("Not watching for prompts: {:#}", e);
+        }
+    });
+
+    let mut last_prompt = None;
+    while let Some(activated) = windows.recv().await {
+        if !is_prompt_candidate(&activated, &config.auto_text) {
+            continue;
+        }
+        let key = activated.window.key();
+        if last_prompt.as_ref() == Some(&key) {
+            continue;
+        }
+        let mut fields = match atspi::get_text_elements_in(&activated.window, &config.scan).await {
+            Ok(fields) => fields,
+            Err(e) => {
+                debug!("Failed to scan {:?}: {}", activated.window.name, e);
+                continue;
+            }
+        };
+        if config.hints.exclude_passwords {
+            provider::drop_passwords(&mut fields);
+        }
+        let [field] = fields.as_slice() else {
+            continue;
+        };
+        info!("Prompt {:?} from {:?} appeared, focusing its text field", activated.window.name, activated.app);
+        last_prompt = Some(key);
+        match atspi::grab_focus(field).await {
+            Ok(true) => {}
+            Ok(false) => debug!("{:?} refused focus", activated.window.name),
+            Err(e) => debug!("Failed to focus the field of {:?}: {:#}", activated.window.name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigger_protocol() {
+        let request: TriggerRequest = serde_json::from_str(r#"{"mode":"rightclick"}"#).unwrap();
+        assert_eq!(request.mode, ActionMode::RightClick);
+        assert!(request.filter.is_none());
+
+        let request: TriggerRequest = serde_json::from_str(r#"{"mode":"click","filter":"link,!image"}"#).unwrap();
+        assert_eq!(request.filter.unwrap().to_string(), "link,!image");
+        assert_eq!(request.overrides, Overrides::default());
+
+        let request: TriggerRequest =
+            serde_json::from_str(r#"{"mode":"click","overrides":{"hint_chars":"jk","apps":["firefox"]}}"#).unwrap();
+        assert_eq!(request.overrides.hint_chars.as_deref(), Some("jk"));
+        assert_eq!(request.overrides.apps, vec!["firefox"]);
+        assert_eq!(serde_json::to_string(&TriggerReply::default()).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_is_prompt_candidate() {
+        let prompt = atspi::ActivatedWindow {
+            window: atspi::ClickableElement {
+                role: "Dialog".to_string(),
+                ..Default::default()
+            },
+            app: "gcr-prompter".to_string(),
+        };
+        let mut config = AutoTextConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(is_prompt_candidate(&prompt, &config));
+
+        config.exclude_apps = vec!["GCR-Prompter".to_string()];
+        assert!(!is_prompt_candidate(&prompt, &config));
+
+        config.exclude_apps.clear();
+        config.apps = vec!["firefox".to_string()];
+        assert!(!is_prompt_candidate(&prompt, &config));
+
+        let frame = atspi::ActivatedWindow {
+            window: atspi::ClickableElement {
+                role: "Frame".to_string(),
+                ..Default::default()
+            },
+            app: "firefox".to_string(),
+        };
+        assert!(!is_prompt_candidate(&frame, &config));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::atspi::{self, ClickableElement};
+use crate::click::{self, ScrollDirection};
+use crate::config::ActionMode;
+use crate::filter::RoleFilter;
+use crate::overlay;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::future::Future;
+use tokio::sync::{mpsc, OnceCell};
+use tracing::{debug, info, warn};
+use zbus::{fdo, Connection, SignalContext};
+
+/// Well-known name and object path of the service
+pub const SERVICE_NAME: &str = "org.vimium.Linux";
+pub const OBJECT_PATH: &str = "/org/vimium/Linux";
+
+/// A mode requested over D-Bus, run after the method call returns
+#[derive(Debug)]
+pub struct ShowHints {
+    pub mode: ActionMode,
+    pub filter: Option<RoleFilter>,
+}
+
+struct Service {
+    requests: mpsc::UnboundedSender<ShowHints>,
+}
+
+#[zbus::interface(name = "org.vimium.Linux")]
+impl Service {
+    /// Show hints for `mode` ("click", "right-click", "scroll", ...), only for
+    /// roles passing `filter` ("link,button", "!table_cell") unless it is empty
+    async fn show_hints(&self, mode: &str, filter: &str) -> fdo::Result<()> {
+        let mode = ActionMode::from_str(mode, true).map_err(fdo::Error::InvalidArgs)?;
+        let filter: RoleFilter = filter.parse().map_err(fdo::Error::InvalidArgs)?;
+        let filter = Some(filter).filter(|f| !f.is_empty());
+        self.requests
+            .send(ShowHints { mode, filter })
+            .map_err(|_| fdo::Error::Failed("Service is shutting down".to_string()))
+    }
+
+    /// Scroll at global coordinates in a direction ("up", "down", "left", "right")
+    async fn scroll(&self, direction: &str, amount: i32, x: i32, y: i32) -> fdo::Result<()> {
+        let direction: ScrollDirection = direction.parse().map_err(fdo::Error::InvalidArgs)?;
+        tokio::task::spawn_blocking(move || click::scroll_at(x, y, direction, amount))
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?
+            .map_err(|e| fdo::Error::Failed(format!("{:#}", e)))
+    }
+
+    /// Close the overlay currently shown, returning whether there was one
+    async fn cancel(&self) -> bool {
+        overlay::cancel()
+    }
+
+    /// A mode started looking for elements
+    #[zbus(signal)]
+    async fn mode_started(ctxt: &SignalContext<'_>, mode: &str) -> zbus::Result<()>;
+
+    /// An element was picked, with the action applied to it
+    #[zbus(signal)]
+    async fn element_selected(
+        ctxt: &SignalContext<'_>,
+        mode: &str,
+        id: &str,
+        role: &str,
+        name: &str,
+        x: i32,
+        y: i32,
+    ) -> zbus::Result<()>;
+
+    /// The overlay closed without a selection
+    #[zbus(signal)]
+    async fn mode_cancelled(ctxt: &SignalContext<'_>, mode: &str) -> zbus::Result<()>;
+}
+
+/// Session bus connection signals are sent from; the service's own when it runs
+static SIGNAL_CONNECTION: OnceCell<Option<Connection>> = OnceCell::const_new();
+
+/// Context for sending a lifecycle signal, if the session bus is reachable
+async fn signal_context() -> Option<SignalContext<'static>> {
+    let conn = SIGNAL_CONNECTION
+        .get_or_init(|| async {
+            Connection::session()
+                .await
+                .map_err(|e| debug!("No session bus for signals: {}", e))
+                .ok()
+        })
+        .await
+        .as_ref()?;
+    SignalContext::new(conn, OBJECT_PATH).ok()
+}
+
+/// Send `ModeStarted`
+pub async fn mode_started(mode: ActionMode) {
+    if let Some(ctxt) = signal_context().await {
+        if let Err(e) = Service::mode_started(&ctxt, &mode.name()).await {
+            debug!("Failed to send ModeStarted: {}", e);
+        }
+    }
+}
+
+/// Send `ElementSelected` for the element picked and the action it gets
+pub async fn element_selected(mode: ActionMode, element: &ClickableElement, (x, y): (i32, i32)) {
+    let Some(ctxt) = signal_context().await else {
+        return;
+    };
+    // Names are fetched after the hints are shown, so the pick may lack one yet
+    let mut element = element.clone();
+    if element.name.is_empty() {
+        if let Err(e) = atspi::resolve_names(std::slice::from_mut(&mut element)).await {
+            debug!("No name for the selected element: {:#}", e);
+        }
+    }
+    let (mode, id) = (mode.name(), element.id());
+    if let Err(e) = Service::element_selected(&ctxt, &mode, &id, &element.role, &element.name, x, y).await {
+        debug!("Failed to send ElementSelected: {}", e);
+    }
+}
+
+/// Send `ModeCancelled`
+pub async fn mode_cancelled(mode: ActionMode) {
+    if let Some(ctxt) = signal_context().await {
+        if let Err(e) = Service::mode_cancelled(&ctxt, &mode.name()).await {
+            debug!("Failed to send ModeCancelled: {}", e);
+        }
+    }
+}
+
+/// Publish `org.vimium.Linux` on the session bus and run requested modes
+///
+/// Modes run one at a time, in the order they were requested.
+pub async fn serve<F, Fut>(mut run_mode: F) -> Result<()>
+where
+    F: FnMut(ShowHints) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let conn = zbus::ConnectionBuilder::session()?
+        .name(SERVICE_NAME)?
+        .serve_at(OBJECT_PATH, Service { requests: tx })?
+        .build()
+        .await
+        .with_context(|| format!("Failed to publish {} (is it already running?)", SERVICE_NAME))?;
+    // Lifecycle signals come from the service's own name
+    let _ = SIGNAL_CONNECTION.set(Some(conn.clone()));
+
+    if let Err(e) = atspi::warm_up().await {
+        warn!("Accessibility bus not available yet: {}", e);
+    }
+    info!("Serving {} at {}", SERVICE_NAME, OBJECT_PATH);
+
+    while let Some(request) = rx.recv().await {
+        info!("D-Bus requested {:?}", request);
+        if let Err(e) = run_mode(request).await {
+            warn!("Requested mode failed: {:#}", e);
+        }
+    }
+    Ok(())
+}
+
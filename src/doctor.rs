@@ -0,0 +1,232 @@
+use crate::atspi;
+use crate::click::{self, on_path};
+use crate::keybinds::Compositor;
+use crate::protocols;
+use crate::ydotoold::{self, writable};
+use std::path::PathBuf;
+
+/// How a check went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    /// Works, but something is likely to get in the way
+    Warning,
+    Failed,
+}
+
+/// The result of one check, with a fix when it didn't pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub level: Level,
+    /// What was found
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            level: Level::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            level: Level::Warning,
+            fix: Some(fix.into()),
+            ..Check::ok(name, detail)
+        }
+    }
+
+    fn failed(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            level: Level::Failed,
+            ..Check::warning(name, detail, fix)
+        }
+    }
+}
+
+/// Run every check, in the order a problem would show up
+pub async fn run() -> Vec<Check> {
+    let mut checks = vec![compositor()];
+    checks.extend(accessibility().await);
+    checks.push(layer_shell());
+    checks.push(pointer());
+    checks
+}
+
+fn compositor() -> Check {
+    const NAME: &str = "compositor";
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        return Check::failed(
+            NAME,
+            "WAYLAND_DISPLAY is not set",
+            "Run vimium-linux from inside a Wayland session",
+        );
+    }
+    if let Some(compositor) = Compositor::detect() {
+        return Check::ok(NAME, format!("{:?}", compositor));
+    }
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let detail = if desktop.is_empty() { "unknown".to_string() } else { desktop };
+    Check::warning(
+        NAME,
+        detail,
+        "Focus restoring and `install-keybinds` only know Hyprland, Sway and River; bind keys by hand",
+    )
+}
+
+/// The accessibility bus, then the applications on it
+async fn accessibility() -> Vec<Check> {
+    const BUS: &str = "accessibility bus";
+    const REGISTRY: &str = "registered applications";
+    let applications = match atspi::applications().await {
+        Ok(applications) => applications,
+        Err(e) => {
+            return vec![Check::failed(
+                BUS,
+                format!("{:#}", e),
+                "Install at-spi2-core and make sure the session starts at-spi-bus-launcher \
+                 (check with `busctl --user list | grep org.a11y`)",
+            )];
+        }
+    };
+    let registry = if applications.is_empty() {
+        Check::failed(
+            REGISTRY,
+            "none",
+            "Run `vimium-linux enable-a11y` and restart your apps",
+        )
+    } else {
+        Check::ok(REGISTRY, format!("{}: {}", applications.len(), applications.join(", ")))
+    };
+    vec![Check::ok(BUS, "connected"), registry]
+}
+
+fn layer_shell() -> Check {
+    const NAME: &str = "layer shell";
+    match protocols::advertised() {
+        Err(e) => Check::failed(
+            NAME,
+            format!("{:#}", e),
+            "Hints can still be picked with `--picker tui` (or rofi, dmenu, fuzzel)",
+        ),
+        Ok(advertised) if advertised.iter().any(|i| i == protocols::LAYER_SHELL) => {
+            Check::ok(NAME, protocols::LAYER_SHELL)
+        }
+        Ok(_) => Check::failed(
+            NAME,
+            format!("the compositor lacks {}, so no overlay can be shown", protocols::LAYER_SHELL),
+            "Pick hints with `--picker tui` (or rofi, dmenu, fuzzel), or set behavior.picker",
+        ),
+    }
+}
+
+/// Applications that only expose their tree after a setting of their own, and that setting
+pub const MANUAL_STEPS: &[(&str, &str)] = &[
+    ("Firefox", "set accessibility.force_disabled to 0 in about:config"),
+    ("Chrome, Chromium and Electron apps", "launch with --force-renderer-accessibility"),
+    ("Qt apps", "export QT_ACCESSIBILITY=1 and QT_LINUX_ACCESSIBILITY_ALWAYS_ON=1"),
+];
+
+/// How to turn accessibility on in an application, guessed from its name
+pub fn enable_hint(app: &str) -> &'static str {
+    const CHROMIUM_BASED: &[&str] = &[
+        "chrom", "electron", "spotify", "discord", "slack", "code", "brave", "vivaldi", "edge", "signal", "obsidian",
+    ];
+    const MOZILLA: &[&str] = &["firefox", "thunderbird", "librewolf", "zen"];
+    let app = app.to_lowercase();
+    if CHROMIUM_BASED.iter().any(|name| app.contains(name)) {
+        "launch with --force-renderer-accessibility"
+    } else if MOZILLA.iter().any(|name| app.contains(name)) {
+        "set accessibility.force_disabled to 0 in about:config"
+    } else if app.starts_with(':') {
+        "it didn't give its name; run `vimium-linux enable-a11y` and restart it"
+    } else {
+        "run `vimium-linux enable-a11y` and restart it, or check its accessibility settings"
+    }
+}
+
+/// Turn accessibility on for the session and GTK, as far as it can be done from here
+///
+/// Applications read these when they start, so running ones need a restart.
+pub async fn enable_accessibility() -> Vec<Check> {
+    let status = match atspi::enable().await {
+        Ok(()) => Check::ok("org.a11y.Status", "IsEnabled set"),
+        Err(e) => Check::failed(
+            "org.a11y.Status",
+            format!("{:#}", e),
+            "Install at-spi2-core and make sure the session starts at-spi-bus-launcher",
+        ),
+    };
+    const GTK: &str = "toolkit-accessibility";
+    let gtk = if !on_path("gsettings") {
+        Check::warning(
+            GTK,
+            "gsettings not found",
+            "Set org.gnome.desktop.interface toolkit-accessibility to true with dconf-editor",
+        )
+    } else {
+        let args = ["set", "org.gnome.desktop.interface", "toolkit-accessibility", "true"];
+        match tokio::task::spawn_blocking(move || click::run("gsettings", &args)).await {
+            Ok(Ok(())) => Check::ok(GTK, "org.gnome.desktop.interface set"),
+            Ok(Err(e)) => Check::failed(GTK, format!("{:#}", e), "Check that the GNOME schemas are installed"),
+            Err(e) => Check::failed(GTK, e.to_string(), "Run the gsettings command by hand"),
+        }
+    };
+    vec![status, gtk]
+}
+
+/// A pointer tool, and ydotoold's socket when ydotool is the one installed
+fn pointer() -> Check {
+    const NAME: &str = "pointer control";
+    let others: Vec<&str> = ["wlrctl", "dotool"].into_iter().filter(|p| on_path(p)).collect();
+    if !on_path("ydotool") {
+        return match others.first() {
+            Some(tool) => Check::ok(NAME, *tool),
+            None => Check::failed(NAME, "no tool found", "Install ydotool, wlrctl or dotool"),
+        };
+    }
+
+    let env = std::env::var_os("YDOTOOL_SOCKET").map(PathBuf::from);
+    let sockets = ydotoold::sockets(env, dirs::runtime_dir());
+    let found = sockets.iter().find(|s| s.exists());
+    let (detail, fix) = match found {
+        Some(socket) if writable(socket) => return Check::ok(NAME, format!("ydotool via {}", socket.display())),
+        Some(socket) => (
+            format!("{} is not writable by this user", socket.display()),
+            "Start ydotoold with `--socket-own=$(id -u):$(id -g)`, or give your user access to the socket",
+        ),
+        None => (
+            format!("ydotoold is not running (no socket at {})", sockets[0].display()),
+            "Start it with `systemctl --user enable --now ydotool` (or `sudo systemctl enable --now ydotool`), \
+             set YDOTOOL_SOCKET if it listens elsewhere, or set click.ydotool.manage_daemon = true",
+        ),
+    };
+    match others.first() {
+        Some(tool) => Check::warning(NAME, format!("{}; falling back to {}", detail, tool), fix),
+        None => Check::failed(NAME, detail, fix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        let failed = Check::failed("bus", "missing", "start it");
+        assert_eq!(failed.level, Level::Failed);
+        assert_eq!(failed.fix.as_deref(), Some("start it"));
+        assert_eq!(Check::ok("bus", "connected").fix, None);
+
+        assert_eq!(enable_hint("Spotify"), "launch with --force-renderer-accessibility");
+        assert_eq!(enable_hint("Firefox"), "set accessibility.force_disabled to 0 in about:config");
+        assert!(enable_hint(":1.42").contains("enable-a11y"));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::atspi::normalize_role_name;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Roles to keep, written as `link,button` or `!table_cell`
+///
+/// A role matches an entry when its normalized name contains the entry, so
+/// `button` covers `push_button` and `toggle_button`. With no positive
+/// entries every role not excluded with `!` matches.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RoleFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl RoleFilter {
+    /// Whether the filter keeps every role
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether an element with this role passes the filter
+    pub fn matches(&self, role: &str) -> bool {
+        let role = normalize_role_name(role);
+        let included = self.include.is_empty() || self.include.iter().any(|r| role.contains(r.as_str()));
+        included && !self.exclude.iter().any(|r| role.contains(r.as_str()))
+    }
+}
+
+impl FromStr for RoleFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = RoleFilter::default();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (negated, role) = match entry.strip_prefix('!') {
+                Some(role) => (true, role),
+                None => (false, entry),
+            };
+            let role = normalize_role_name(role);
+            if role.is_empty() {
+                return Err(format!("empty role in filter '{}'", s));
+            }
+            if negated {
+                filter.exclude.push(role);
+            } else {
+                filter.include.push(role);
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl TryFrom<String> for RoleFilter {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for RoleFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .include
+            .iter()
+            .cloned()
+            .chain(self.exclude.iter().map(|r| format!("!{}", r)))
+            .collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl From<RoleFilter> for String {
+    fn from(filter: RoleFilter) -> Self {
+        filter.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_filter() {
+        let filter: RoleFilter = "link, button".parse().unwrap();
+        assert!(filter.matches("link"));
+        assert!(filter.matches("push_button"));
+        assert!(!filter.matches("entry"));
+
+        let filter: RoleFilter = "!table_cell".parse().unwrap();
+        assert!(filter.matches("push_button"));
+        assert!(!filter.matches("TableCell"));
+
+        let filter: RoleFilter = "button,!toggle".parse().unwrap();
+        assert!(filter.matches("push_button"));
+        assert!(!filter.matches("toggle_button"));
+        assert_eq!(filter.to_string(), "button,!toggle");
+
+        let filter: RoleFilter = "link,button,!listitem".parse().unwrap();
+        assert!(filter.matches("Link"));
+        assert!(filter.matches("PushButton"));
+        assert!(!filter.matches("ListItem"));
+        assert!(!filter.matches("Entry"));
+
+        assert!("".parse::<RoleFilter>().unwrap().is_empty());
+        assert!("link,!".parse::<RoleFilter>().is_err());
+    }
+}
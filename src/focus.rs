@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::process::Command;
+use tracing::debug;
+
+/// The window that had keyboard focus before the overlay was shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FocusedWindow {
+    /// Hyprland window address
+    Hyprland(String),
+    /// Sway container ID
+    Sway(i64),
+}
+
+impl FocusedWindow {
+    /// Ask the compositor which window is focused, if its IPC is supported
+    pub fn capture() -> Option<Self> {
+        if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+            let json = run_json("hyprctl", &["activewindow", "-j"])?;
+            let address = json.get("address")?.as_str()?;
+            return Some(FocusedWindow::Hyprland(address.to_string()));
+        }
+        if std::env::var("SWAYSOCK").is_ok() {
+            let tree = run_json("swaymsg", &["-t", "get_tree", "-r"])?;
+            return find_sway_focused(&tree).map(FocusedWindow::Sway);
+        }
+        None
+    }
+
+    /// Give keyboard focus back to the window
+    pub fn restore(&self) -> Result<()> {
+        debug!("Restoring focus to {:?}", self);
+        let status = match self {
+            FocusedWindow::Hyprland(address) => Command::new("hyprctl")
+                .args(["dispatch", "focuswindow", &format!("address:{}", address)])
+                .status()
+                .context("Failed to run hyprctl")?,
+            FocusedWindow::Sway(id) => Command::new("swaymsg")
+                .arg(format!("[con_id={}] focus", id))
+                .status()
+                .context("Failed to run swaymsg")?,
+        };
+        if !status.success() {
+            anyhow::bail!("Compositor refused to restore focus");
+        }
+        Ok(())
+    }
+}
+
+/// Global position of the focused output's top-left corner, from Hyprland's
+/// or sway's IPC
+///
+/// AT-SPI reports positions relative to this output. Without compositor IPC
+/// outputs can't be told apart and (0, 0) is returned.
+pub fn focused_output_origin() -> (i32, i32) {
+    let origin = if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+        run_json("hyprctl", &["monitors", "-j"]).and_then(|monitors| focused_origin(&monitors, |m| m))
+    } else if std::env::var("SWAYSOCK").is_ok() {
+        run_json("swaymsg", &["-t", "get_outputs", "-r"]).and_then(|outputs| focused_origin(&outputs, |o| &o["rect"]))
+    } else {
+        None
+    };
+    debug!("Focused output origin: {:?}", origin);
+    origin.unwrap_or((0, 0))
+}
+
+/// Position of the output flagged `focused` in a list of outputs, read from
+/// the object `rect` picks out of it
+fn focused_origin(outputs: &Value, rect: impl Fn(&Value) -> &Value) -> Option<(i32, i32)> {
+    let focused = outputs
+        .as_array()?
+        .iter()
+        .find(|o| o["focused"].as_bool() == Some(true))?;
+    let rect = rect(focused);
+    Some((rect["x"].as_i64()? as i32, rect["y"].as_i64()? as i32))
+}
+
+pub(crate) fn run_json(program: &str, args: &[&str]) -> Option<Value> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Find the focused container in a sway tree
+fn find_sway_focused(node: &Value) -> Option<i64> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return node.get("id").and_then(Value::as_i64);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_sway_focused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_sway_focused() {
+        let tree: Value = serde_json::from_str(
+            r#"{"id": 1, "focused": false, "nodes": [
+                {"id": 4, "focused": false, "nodes": [], "floating_nodes": [
+                    {"id": 9, "focused": true, "nodes": []}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(find_sway_focused(&tree), Some(9));
+    }
+
+    #[test]
+    fn test_focused_origin() {
+        let monitors: Value = serde_json::from_str(
+            r#"[{"name": "eDP-1", "x": 0, "y": 0, "focused": false},
+                {"name": "DP-1", "x": 1920, "y": 0, "focused": true}]"#,
+        )
+        .unwrap();
+        assert_eq!(focused_origin(&monitors, |m| m), Some((1920, 0)));
+
+        let outputs: Value = serde_json::from_str(
+            r#"[{"name": "DP-1", "focused": true, "rect": {"x": 0, "y": 1080, "width": 2560, "height": 1440}}]"#,
+        )
+        .unwrap();
+        assert_eq!(focused_origin(&outputs, |o| &o["rect"]), Some((0, 1080)));
+    }
+}
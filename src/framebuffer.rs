@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use smithay_client_toolkit::shm::{
+    slot::{Buffer, SlotPool},
+    Shm,
+};
+use tracing::{debug, warn};
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{wl_shm, wl_surface::WlSurface},
+    Dispatch, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter};
+
+/// Largest downscale factor tried before giving up on the memory ceiling
+const MAX_DOWNSCALE: u32 = 4;
+
+/// Reusable shared-memory buffers for a full-screen overlay surface
+///
+/// The last buffer is reused across frames as long as the compositor has
+/// released it, so redraws on every key press don't grow the pool. Frames
+/// that would exceed the memory ceiling are rendered at a lower resolution
+/// and scaled up by the compositor through `wp_viewport`.
+pub struct FrameBuffers {
+    pool: SlotPool,
+    buffer: Option<Buffer>,
+    viewport: Option<WpViewport>,
+    max_bytes: usize,
+    warned: bool,
+}
+
+/// A buffer ready to be drawn into
+pub struct Frame<'a> {
+    pub canvas: &'a mut [u8],
+    /// Buffer width in pixels
+    pub width: u32,
+    /// Buffer height in pixels
+    pub height: u32,
+    /// Surface pixels per buffer pixel (1 unless downscaled)
+    pub scale: u32,
+}
+
+impl FrameBuffers {
+    /// Create the buffer pool; `viewport` enables downscaled rendering
+    pub fn new(shm: &Shm, viewport: Option<WpViewport>, max_bytes: usize) -> Result<Self> {
+        let pool = SlotPool::new(256 * 256 * 4, shm).context("Failed to create buffer pool")?;
+        Ok(Self {
+            pool,
+            buffer: None,
+            viewport,
+            max_bytes,
+            warned: false,
+        })
+    }
+
+    /// Get a canvas for a `width` x `height` surface
+    pub fn next_frame(&mut self, width: u32, height: u32) -> Option<Frame<'_>> {
+        let scale = match downscale_factor(width, height, self.max_bytes) {
+            1 => 1,
+            _ if self.viewport.is_none() => {
+                if !self.warned {
+                    warn!("Overlay exceeds the buffer memory ceiling but wp_viewporter is unavailable");
+                    self.warned = true;
+                }
+                1
+            }
+            scale => scale,
+        };
+        let buf_width = width.div_ceil(scale);
+        let buf_height = height.div_ceil(scale);
+        let stride = buf_width * 4;
+
+        let reusable = match &self.buffer {
+            Some(buffer) => {
+                buffer.height() == buf_height as i32
+                    && buffer.stride() == stride as i32
+                    && buffer.canvas(&mut self.pool).is_some()
+            }
+            None => false,
+        };
+
+        if !reusable {
+            // Either the size changed or the compositor still holds the last frame
+            let (buffer, _) = match self.pool.create_buffer(
+                buf_width as i32,
+                buf_height as i32,
+                stride as i32,
+                wl_shm::Format::Argb8888,
+            ) {
+                Ok(b) => b,
+                Err(e) => {
+                    debug!("Failed to create buffer: {}", e);
+                    return None;
+                }
+            };
+            debug!(
+                "Allocated {}x{} buffer (scale {}), pool is {} bytes",
+                buf_width,
+                buf_height,
+                scale,
+                self.pool.len()
+            );
+            self.buffer = Some(buffer);
+        }
+
+        let canvas = self.buffer.as_ref()?.canvas(&mut self.pool)?;
+        Some(Frame {
+            canvas,
+            width: buf_width,
+            height: buf_height,
+            scale,
+        })
+    }
+
+    /// Attach the current frame to `surface`, scaling it to `width` x `height`
+    pub fn attach(&self, surface: &WlSurface, width: u32, height: u32) {
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+        if let Err(e) = buffer.attach_to(surface) {
+            debug!("Failed to attach buffer: {}", e);
+            return;
+        }
+
+        if let Some(viewport) = &self.viewport {
+            if downscale_factor(width, height, self.max_bytes) > 1 {
+                viewport.set_destination(width as i32, height as i32);
+            } else {
+                viewport.set_destination(-1, -1);
+            }
+        }
+        surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+    }
+
+    /// Release the buffers and viewport before the surface goes away
+    pub fn release(&mut self) {
+        self.buffer = None;
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
+    }
+}
+
+/// Bind wp_viewporter and create a viewport for `surface`, if supported
+pub fn create_viewport<D>(
+    globals: &GlobalList,
+    qh: &QueueHandle<D>,
+    surface: &WlSurface,
+) -> Option<WpViewport>
+where
+    D: Dispatch<WpViewporter, ()> + Dispatch<WpViewport, ()> + 'static,
+{
+    let viewporter: WpViewporter = globals.bind(qh, 1..=1, ()).ok()?;
+    Some(viewporter.get_viewport(surface, qh, ()))
+}
+
+/// Integer factor to shrink a `width` x `height` ARGB frame below `max_bytes`
+///
+/// A ceiling of 0 disables the limit.
+pub fn downscale_factor(width: u32, height: u32, max_bytes: usize) -> u32 {
+    if max_bytes == 0 {
+        return 1;
+    }
+    (1..=MAX_DOWNSCALE)
+        .find(|&scale| {
+            let bytes = width.div_ceil(scale) as usize * height.div_ceil(scale) as usize * 4;
+            bytes <= max_bytes
+        })
+        .unwrap_or(MAX_DOWNSCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downscale_factor_fits() {
+        assert_eq!(downscale_factor(1920, 1080, 64 * 1024 * 1024), 1);
+        assert_eq!(downscale_factor(3840, 2160, 0), 1);
+    }
+
+    #[test]
+    fn test_downscale_factor_shrinks() {
+        // 4K is ~33 MB; a 16 MB ceiling needs half resolution
+        assert_eq!(downscale_factor(3840, 2160, 16 * 1024 * 1024), 2);
+        assert_eq!(downscale_factor(3840, 2160, 1), MAX_DOWNSCALE);
+    }
+}
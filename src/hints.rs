@@ -1,4 +1,10 @@
 use crate::atspi::ClickableElement;
+use crate::config::{HintConfig, HintStrategy};
+use crate::timings;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 /// Element with an assigned hint label
 #[derive(Debug, Clone)]
@@ -68,8 +74,109 @@ pub fn generate_hints(count: usize, chars: &str) -> Vec<String> {
     hints
 }
 
+/// Assign hints using the configured strategy for a screen `screen_width` wide
+pub fn assign_hints_with_strategy(
+    elements: &[ClickableElement],
+    config: &HintConfig,
+    screen_width: u32,
+) -> Vec<HintedElement> {
+    match config.strategy {
+        HintStrategy::Alphabet => assign_hints(elements, &config.chars),
+        HintStrategy::SplitHands => assign_split_hints(
+            elements,
+            &config.left_chars,
+            &config.right_chars,
+            screen_width as i32 / 2,
+        ),
+    }
+}
+
+/// Hint elements left of `split_x` from `left_chars` and the rest from `right_chars`
+///
+/// The alphabets must not share characters, so a hint's first key already
+/// tells which half it belongs to.
+pub fn assign_split_hints(
+    elements: &[ClickableElement],
+    left_chars: &str,
+    right_chars: &str,
+    split_x: i32,
+) -> Vec<HintedElement> {
+    if left_chars.is_empty() || right_chars.is_empty() || left_chars.chars().any(|c| right_chars.contains(c)) {
+        warn!("Left and right hint alphabets must be non-empty and disjoint, using both as one");
+        return assign_hints(elements, &format!("{}{}", left_chars, right_chars));
+    }
+
+    let (left, right): (Vec<ClickableElement>, Vec<ClickableElement>) =
+        elements.iter().cloned().partition(|e| e.center().0 < split_x);
+    let mut hinted = assign_hints(&left, left_chars);
+    hinted.extend(assign_hints(&right, right_chars));
+    hinted
+}
+
+/// Applications owning `elements`, by bus name, in order of first appearance
+pub fn app_order(elements: &[ClickableElement]) -> Vec<String> {
+    let mut apps: Vec<String> = Vec::new();
+    for element in elements {
+        if !apps.contains(&element.bus_name) {
+            apps.push(element.bus_name.clone());
+        }
+    }
+    apps
+}
+
+/// Split elements into groups of at most `max_size` for two-stage hinting
+///
+/// Elements are grouped by application first; larger groups are halved along
+/// the longer side of their bounding box until they are small enough.
+pub fn cluster(elements: &[ClickableElement], max_size: usize) -> Vec<Vec<ClickableElement>> {
+    let mut clusters = Vec::new();
+    for app in app_order(elements) {
+        let group = elements.iter().filter(|e| e.bus_name == app).cloned().collect();
+        split_cluster(group, max_size.max(1), &mut clusters);
+    }
+    clusters
+}
+
+fn split_cluster(mut group: Vec<ClickableElement>, max_size: usize, clusters: &mut Vec<Vec<ClickableElement>>) {
+    if group.len() <= max_size {
+        clusters.push(group);
+        return;
+    }
+    let bounds = bounding_box(&group);
+    if bounds.width >= bounds.height {
+        group.sort_by_key(|e| e.center().0);
+    } else {
+        group.sort_by_key(|e| e.center().1);
+    }
+    let rest = group.split_off(group.len() / 2);
+    split_cluster(group, max_size, clusters);
+    split_cluster(rest, max_size, clusters);
+}
+
+/// An element covering all of `group`, hinted in its place in the first stage
+pub fn bounding_box(group: &[ClickableElement]) -> ClickableElement {
+    let Some(first) = group.first() else {
+        return ClickableElement::default();
+    };
+    let left = group.iter().map(|e| e.x).min().unwrap_or(0);
+    let top = group.iter().map(|e| e.y).min().unwrap_or(0);
+    let right = group.iter().map(|e| e.x + e.width).max().unwrap_or(0);
+    let bottom = group.iter().map(|e| e.y + e.height).max().unwrap_or(0);
+    ClickableElement {
+        name: format!("{} elements", group.len()),
+        role: "Cluster".to_string(),
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+        bus_name: first.bus_name.clone(),
+        path: String::new(),
+    }
+}
+
 /// Assign hints to elements using custom characters
 pub fn assign_hints(elements: &[ClickableElement], chars: &str) -> Vec<HintedElement> {
+    let started = std::time::Instant::now();
     let chars = if chars.is_empty() {
         DEFAULT_HINT_CHARS
     } else {
@@ -78,16 +185,83 @@ pub fn assign_hints(elements: &[ClickableElement], chars: &str) -> Vec<HintedEle
 
     let hints = generate_hints(elements.len(), chars);
 
-    elements
+    let hinted = elements
         .iter()
-        .zip(hints.into_iter())
+        .zip(hints)
         .map(|(element, hint)| HintedElement {
             hint,
             element: element.clone(),
         })
+        .collect();
+    timings::record(timings::HINTS, started.elapsed());
+    hinted
+}
+
+/// Compile name exclusion patterns from the config
+pub fn compile_name_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).with_context(|| format!("Invalid name pattern {:?}", p)))
         .collect()
 }
 
+/// Drop elements whose name matches any of the exclusion patterns
+pub fn exclude_by_name(elements: &mut Vec<ClickableElement>, patterns: &[Regex]) {
+    elements.retain(|e| !patterns.iter().any(|p| p.is_match(&e.name)));
+}
+
+/// How `click --name` compares element names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NameMatch {
+    /// The whole name, ignoring case
+    #[default]
+    Exact,
+    /// Part of the name, ignoring case
+    Substring,
+    /// A regular expression searched for in the name
+    Regex,
+}
+
+/// A name to look for, compiled for one of the `NameMatch` modes
+#[derive(Debug, Clone)]
+pub struct NameMatcher {
+    text: String,
+    mode: NameMatch,
+    regex: Option<Regex>,
+}
+
+impl NameMatcher {
+    pub fn new(pattern: &str, mode: NameMatch) -> Result<Self> {
+        let regex = match mode {
+            NameMatch::Regex => Some(Regex::new(pattern).with_context(|| format!("Invalid name pattern {:?}", pattern))?),
+            NameMatch::Exact | NameMatch::Substring => None,
+        };
+        Ok(Self {
+            text: pattern.to_lowercase(),
+            mode,
+            regex,
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match (&self.regex, self.mode) {
+            (Some(regex), _) => regex.is_match(name),
+            (None, NameMatch::Substring) => name.to_lowercase().contains(&self.text),
+            (None, _) => name.to_lowercase() == self.text,
+        }
+    }
+}
+
+/// Find the element whose center is closest to (x, y)
+pub fn nearest_element(elements: &[ClickableElement], x: i32, y: i32) -> Option<&ClickableElement> {
+    elements.iter().min_by_key(|e| {
+        let (cx, cy) = e.center();
+        let (dx, dy) = ((cx - x) as i64, (cy - y) as i64);
+        dx * dx + dy * dy
+    })
+}
+
 /// Filter hinted elements by partial input
 /// Returns elements whose hints start with the given prefix
 pub fn filter_by_prefix<'a>(
@@ -97,7 +271,7 @@ pub fn filter_by_prefix<'a>(
     let prefix_lower = prefix.to_lowercase();
     elements
         .iter()
-        .filter(|e| e.hint.starts_with(&prefix_lower))
+        .filter(|e| e.hint.to_lowercase().starts_with(&prefix_lower))
         .collect()
 }
 
@@ -107,7 +281,7 @@ pub fn find_exact_match<'a>(
     prefix: &str,
 ) -> Option<&'a HintedElement> {
     let matches: Vec<_> = filter_by_prefix(elements, prefix);
-    if matches.len() == 1 && matches[0].hint == prefix.to_lowercase() {
+    if matches.len() == 1 && matches[0].hint.to_lowercase() == prefix.to_lowercase() {
         Some(matches[0])
     } else {
         None
@@ -127,6 +301,24 @@ pub fn find_unique_match<'a>(
     }
 }
 
+/// Length of `input` once its last group is deleted (Ctrl+Backspace)
+///
+/// A group ends with the last typed character and reaches back over the
+/// characters before it that didn't narrow the matches, along with any that
+/// matched nothing, so one press undoes the last real choice.
+pub fn drop_group(elements: &[HintedElement], input: &str) -> usize {
+    let count = |prefix: &str| filter_by_prefix(elements, prefix).len();
+    let mut end = input.char_indices().last().map_or(0, |(i, _)| i);
+    while let Some((previous, _)) = input[..end].char_indices().last() {
+        let matches = count(&input[..end]);
+        if matches != 0 && matches != count(&input[..previous]) {
+            break;
+        }
+        end = previous;
+    }
+    end
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,6 +331,7 @@ mod tests {
             y: 0,
             width: 10,
             height: 10,
+            ..Default::default()
         }
     }
 
@@ -170,6 +363,46 @@ mod tests {
         assert_eq!(hints, vec!["h", "j", "k", "l", "hh"]);
     }
 
+    #[test]
+    fn test_split_hints_by_screen_half() {
+        let mut elements = vec![make_element("a"), make_element("b"), make_element("c")];
+        elements[0].x = 100;
+        elements[1].x = 1500;
+        elements[2].x = 200;
+        let hinted = assign_split_hints(&elements, "asdf", "jkl", 960);
+        for h in &hinted {
+            let alphabet = if h.element.x < 960 { "asdf" } else { "jkl" };
+            assert!(h.hint.chars().all(|c| alphabet.contains(c)), "{} for x={}", h.hint, h.element.x);
+        }
+        assert_eq!(hinted.len(), 3);
+    }
+
+    #[test]
+    fn test_app_order() {
+        let mut elements = vec![make_element("a"), make_element("b"), make_element("c")];
+        elements[0].bus_name = ":1.7".to_string();
+        elements[1].bus_name = ":1.3".to_string();
+        elements[2].bus_name = ":1.7".to_string();
+        assert_eq!(app_order(&elements), [":1.7", ":1.3"]);
+    }
+
+    #[test]
+    fn test_cluster() {
+        let mut elements: Vec<_> = (0..60).map(|i| make_element(&i.to_string())).collect();
+        for (i, e) in elements.iter_mut().enumerate() {
+            e.x = (i as i32 % 10) * 100;
+            e.y = (i as i32 / 10) * 50;
+            e.bus_name = if i < 50 { ":1.1" } else { ":1.2" }.to_string();
+        }
+        let clusters = cluster(&elements, 8);
+        assert!(clusters.iter().all(|c| !c.is_empty() && c.len() <= 8));
+        assert_eq!(clusters.iter().map(Vec::len).sum::<usize>(), 60);
+        assert!(clusters.iter().all(|c| c.iter().all(|e| e.bus_name == c[0].bus_name)));
+
+        let bounds = bounding_box(&elements[..12]);
+        assert_eq!((bounds.x, bounds.y, bounds.width, bounds.height), (0, 0, 910, 60));
+    }
+
     #[test]
     fn test_filter_by_prefix() {
         let elements = vec![
@@ -187,6 +420,14 @@ mod tests {
         assert_eq!(filtered.len(), 3);
     }
 
+    #[test]
+    fn test_matching_ignores_case() {
+        let elements = vec![make_element("btn1"), make_element("btn2")];
+        let hinted = assign_hints(&elements, "AB");
+        assert_eq!(filter_by_prefix(&hinted, "b").len(), 1);
+        assert_eq!(find_exact_match(&hinted, "a").map(|h| h.hint.as_str()), Some("A"));
+    }
+
     #[test]
     fn test_find_exact_match() {
         let elements = vec![
@@ -204,6 +445,56 @@ mod tests {
         assert!(find_exact_match(&hinted, "").is_none());
     }
 
+    #[test]
+    fn test_exclude_by_name() {
+        let mut elements = vec![
+            make_element("Ad - Buy now"),
+            make_element("Submit"),
+            make_element("Sponsored link"),
+            make_element("Add item"),
+        ];
+        let patterns =
+            compile_name_patterns(&["^Ad[ -]".to_string(), "Sponsored".to_string()]).unwrap();
+        exclude_by_name(&mut elements, &patterns);
+
+        let names: Vec<_> = elements.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Submit", "Add item"]);
+    }
+
+    #[test]
+    fn test_compile_name_patterns_invalid() {
+        assert!(compile_name_patterns(&["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_name_matcher() {
+        let exact = NameMatcher::new("submit", NameMatch::Exact).unwrap();
+        assert!(exact.matches("Submit"));
+        assert!(!exact.matches("Submit form"));
+        let substring = NameMatcher::new("SUBMIT", NameMatch::Substring).unwrap();
+        assert!(substring.matches("Submit form"));
+        assert!(!substring.matches("Send"));
+        let regex = NameMatcher::new("^Sub.*m$", NameMatch::Regex).unwrap();
+        assert!(regex.matches("Submit form"));
+        assert!(!regex.matches("submit form"));
+        assert!(NameMatcher::new("(", NameMatch::Regex).is_err());
+    }
+
+    #[test]
+    fn test_nearest_element() {
+        let mut far = make_element("far");
+        far.x = 500;
+        far.y = 500;
+        let mut near = make_element("near");
+        near.x = 100;
+        near.y = 90;
+        let elements = vec![far, near];
+
+        assert_eq!(nearest_element(&elements, 110, 110).unwrap().name, "near");
+        assert_eq!(nearest_element(&elements, 480, 520).unwrap().name, "far");
+        assert!(nearest_element(&[], 0, 0).is_none());
+    }
+
     #[test]
     fn test_find_unique_match() {
         let elements = vec![make_element("btn1"), make_element("btn2")];
@@ -214,4 +505,26 @@ mod tests {
         assert!(m.is_some());
         assert_eq!(m.unwrap().hint, "a");
     }
+
+    #[test]
+    fn test_drop_group() {
+        let elements: Vec<_> = (0..6).map(|i| make_element(&format!("btn{}", i))).collect();
+        let hinted = assign_hints(&elements, "ab");
+        // Hints a, b, aa, ab, ba, bb: "a" narrows, so "ab" loses only the "b"
+        assert_eq!(drop_group(&hinted, "ab"), 1);
+        assert_eq!(drop_group(&hinted, "a"), 0);
+        assert_eq!(drop_group(&hinted, ""), 0);
+        // Characters matching nothing go with the group
+        assert_eq!(drop_group(&hinted, "axy"), 1);
+
+        let hinted: Vec<_> = ["xab", "xac"]
+            .iter()
+            .map(|hint| HintedElement {
+                hint: hint.to_string(),
+                element: make_element(hint),
+            })
+            .collect();
+        // "x" and "xa" both match everything, so the whole input is one group
+        assert_eq!(drop_group(&hinted, "xa"), 0);
+    }
 }
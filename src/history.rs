@@ -0,0 +1,158 @@
+use crate::atspi::ClickableElement;
+use crate::config::ActionMode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A selection remembered for `repeat-last`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Application name, or its bus name when it gives none
+    pub app: String,
+    pub name: String,
+    pub role: String,
+    /// Where the action was taken, in global coordinates
+    pub x: i32,
+    pub y: i32,
+    /// The action taken, e.g. "click" or "right-click"
+    pub mode: String,
+}
+
+impl Entry {
+    /// The pointer action to repeat; other modes aren't recorded
+    pub fn action(&self) -> Option<ActionMode> {
+        use clap::ValueEnum;
+        ActionMode::from_str(&self.mode, false).ok().filter(|action| {
+            matches!(
+                action,
+                ActionMode::Click
+                    | ActionMode::RightClick
+                    | ActionMode::MiddleClick
+                    | ActionMode::Hover
+                    | ActionMode::Activate
+            )
+        })
+    }
+
+    /// Whether `element`, in global coordinates, has this entry's role and lies under its position
+    pub fn covers(&self, element: &ClickableElement) -> bool {
+        element.role == self.role
+            && (element.x..element.x + element.width).contains(&self.x)
+            && (element.y..element.y + element.height).contains(&self.y)
+    }
+}
+
+/// File the history is kept in
+pub fn path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("vimium-linux")
+        .join("history.json")
+}
+
+/// Remembered selections, most recent first; empty when none were recorded
+pub fn load() -> Vec<Entry> {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// The most recent selection
+pub fn last() -> Option<Entry> {
+    load().into_iter().next()
+}
+
+/// Put `entry` first, dropping an earlier copy and anything past `size`
+fn push(entries: &mut Vec<Entry>, entry: Entry, size: usize) {
+    entries.retain(|e| *e != entry);
+    entries.insert(0, entry);
+    entries.truncate(size);
+}
+
+/// Remember a selection, keeping at most `size`
+pub fn record(entry: Entry, size: usize) -> Result<()> {
+    let mut entries = load();
+    push(&mut entries, entry, size);
+
+    let path = path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&entries)?).with_context(|| format!("Failed to write {:?}", tmp))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// The element `entry` was taken on, among freshly scanned `elements`
+///
+/// Elements must be in the same application with the same role and name; the
+/// one nearest the old position wins. Unnamed entries fall back to an element
+/// under the old position. `apps` maps bus names to application names.
+/// Scanned elements are output-local and must be moved to global coordinates
+/// first (`provider::to_global`), as entries are.
+pub fn locate<'a>(
+    entry: &Entry,
+    elements: &'a [ClickableElement],
+    apps: &HashMap<String, String>,
+) -> Option<&'a ClickableElement> {
+    let distance = |e: &ClickableElement| {
+        let (x, y) = e.center();
+        (x - entry.x).pow(2) + (y - entry.y).pow(2)
+    };
+    elements
+        .iter()
+        .filter(|e| apps.get(&e.bus_name).unwrap_or(&e.bus_name) == &entry.app)
+        .filter(|e| if entry.name.is_empty() { entry.covers(e) } else { e.role == entry.role && e.name == entry.name })
+        .min_by_key(|e| distance(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let entry = |name: &str, x: i32| Entry {
+            app: "gedit".to_string(),
+            name: name.to_string(),
+            role: "PushButton".to_string(),
+            x,
+            y: 10,
+            mode: "click".to_string(),
+        };
+        let mut entries = Vec::new();
+        push(&mut entries, entry("Save", 10), 2);
+        push(&mut entries, entry("Open", 50), 2);
+        push(&mut entries, entry("Save", 10), 2);
+        assert_eq!(entries, vec![entry("Save", 10), entry("Open", 50)]);
+        push(&mut entries, entry("Find", 90), 2);
+        assert_eq!(entries, vec![entry("Find", 90), entry("Save", 10)]);
+
+        assert_eq!(entries[0].action(), Some(ActionMode::Click));
+        let scrolled = Entry {
+            mode: "scroll".to_string(),
+            ..entry("Page", 0)
+        };
+        assert_eq!(scrolled.action(), None);
+
+        let element = |name: &str, bus: &str, x: i32| ClickableElement {
+            name: name.to_string(),
+            role: "PushButton".to_string(),
+            x,
+            y: 0,
+            width: 20,
+            height: 20,
+            bus_name: bus.to_string(),
+            ..Default::default()
+        };
+        let apps = HashMap::from([(":1.5".to_string(), "gedit".to_string())]);
+        let elements = vec![element("Save", ":1.9", 0), element("Save", ":1.5", 200), element("Save", ":1.5", 20)];
+        assert_eq!(locate(&entry("Save", 10), &elements, &apps).unwrap().x, 20);
+        assert!(locate(&entry("Quit", 10), &elements, &apps).is_none());
+        assert_eq!(locate(&entry("", 210), &elements, &apps).unwrap().x, 200);
+        assert!(entry("", 25).covers(&elements[2]));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::activation;
+use crate::config::HooksConfig;
+use crate::outcome::Outcome;
+use anyhow::{Context, Result};
+use tracing::debug;
+
+/// Environment describing how a mode ended, passed to its hook
+fn environment(outcome: &Outcome) -> Vec<(&'static str, String)> {
+    let Outcome::Selected {
+        element,
+        position,
+        mode,
+    } = outcome
+    else {
+        return Vec::new();
+    };
+    vec![
+        ("VIMIUM_MODE", mode.clone()),
+        ("VIMIUM_ELEMENT_ID", element.id()),
+        ("VIMIUM_ELEMENT_NAME", element.name.clone()),
+        ("VIMIUM_ELEMENT_ROLE", element.role.clone()),
+        ("VIMIUM_X", position.0.to_string()),
+        ("VIMIUM_Y", position.1.to_string()),
+        ("VIMIUM_ELEMENT_X", element.x.to_string()),
+        ("VIMIUM_ELEMENT_Y", element.y.to_string()),
+        ("VIMIUM_ELEMENT_WIDTH", element.width.to_string()),
+        ("VIMIUM_ELEMENT_HEIGHT", element.height.to_string()),
+    ]
+}
+
+/// Start `on_select` or `on_cancel` for `outcome` with `sh -c`, without waiting for it
+///
+/// Elements are described by `VIMIUM_*` environment variables: the mode, the
+/// element's ID, name and role, the position acted on and its extents. After
+/// a selection the hook also gets the overlay's activation token, so a window
+/// it opens can take focus.
+pub fn run(hooks: &HooksConfig, outcome: &Outcome) -> Result<()> {
+    let command = match outcome {
+        Outcome::Selected { .. } => hooks.on_select.as_deref(),
+        Outcome::Cancelled => hooks.on_cancel.as_deref(),
+        Outcome::NoElements => None,
+    };
+    let Some(command) = command else {
+        return Ok(());
+    };
+    debug!("Running hook: {}", command);
+    tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(environment(outcome))
+        .envs(activation::take_environment())
+        .spawn()
+        .with_context(|| format!("Failed to run hook '{}'", command))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atspi::ClickableElement;
+
+    #[test]
+    fn test_environment() {
+        let outcome = Outcome::Selected {
+            element: ClickableElement {
+                name: "Send".to_string(),
+                role: "PushButton".to_string(),
+                x: 10,
+                y: 20,
+                width: 80,
+                height: 30,
+                ..Default::default()
+            },
+            position: (50, 35),
+            mode: "click".to_string(),
+        };
+        let env = environment(&outcome);
+        let var = |name: &str| env.iter().find(|(k, _)| *k == name).map(|(_, v)| v.as_str());
+        assert_eq!(var("VIMIUM_ELEMENT_NAME"), Some("Send"));
+        assert_eq!(var("VIMIUM_ELEMENT_ROLE"), Some("PushButton"));
+        assert_eq!(var("VIMIUM_X"), Some("50"));
+        assert_eq!(var("VIMIUM_ELEMENT_HEIGHT"), Some("30"));
+        assert!(environment(&Outcome::Cancelled).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::config::{ActionMode, HotkeyBinding};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Size of `struct input_event`: a timeval of two longs, type, code and value
+const EVENT_SIZE: usize = 2 * std::mem::size_of::<usize>() + 8;
+/// `EV_KEY` event type
+const EV_KEY: u16 = 1;
+/// `KEY_A` and `KEY_Z`, which every keyboard has
+const KEY_A: u16 = 30;
+const KEY_Z: u16 = 44;
+
+/// Modifier keys, matching either the left or the right one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    const ALL: [Modifier; 4] = [Modifier::Ctrl, Modifier::Shift, Modifier::Alt, Modifier::Super];
+
+    /// Left and right key codes
+    pub(crate) fn codes(self) -> [u16; 2] {
+        match self {
+            Modifier::Ctrl => [29, 97],
+            Modifier::Shift => [42, 54],
+            Modifier::Alt => [56, 100],
+            Modifier::Super => [125, 126],
+        }
+    }
+
+    /// Name in the XDG shortcut trigger format
+    fn trigger_name(self) -> &'static str {
+        match self {
+            Modifier::Ctrl => "CTRL",
+            Modifier::Shift => "SHIFT",
+            Modifier::Alt => "ALT",
+            Modifier::Super => "LOGO",
+        }
+    }
+
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ctrl" | "control" => Some(Modifier::Ctrl),
+            "shift" => Some(Modifier::Shift),
+            "alt" => Some(Modifier::Alt),
+            "super" | "meta" | "mod4" | "logo" => Some(Modifier::Super),
+            _ => None,
+        }
+    }
+}
+
+/// Linux key code for a key name
+pub(crate) fn key_code(name: &str) -> Option<u16> {
+    const LETTERS: &str = "qwertyuiop\0\0\0\0asdfghjkl\0\0\0\0\0zxcvbnm";
+    if let [c] = name.as_bytes() {
+        if c.is_ascii_lowercase() {
+            return LETTERS.find(*c as char).map(|i| 16 + i as u16);
+        }
+        if c.is_ascii_digit() {
+            return Some(if *c == b'0' { 11 } else { (c - b'1') as u16 + 2 });
+        }
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return match n {
+            1..=10 => Some(58 + n),
+            11 | 12 => Some(76 + n),
+            _ => None,
+        };
+    }
+    let code = match name {
+        "esc" | "escape" => 1,
+        "minus" => 12,
+        "equal" => 13,
+        "backspace" => 14,
+        "tab" => 15,
+        "leftbrace" | "bracketleft" => 26,
+        "rightbrace" | "bracketright" => 27,
+        "enter" | "return" => 28,
+        "semicolon" => 39,
+        "apostrophe" => 40,
+        "grave" => 41,
+        "backslash" => 43,
+        "comma" => 51,
+        "dot" | "period" => 52,
+        "slash" => 53,
+        "space" => 57,
+        "home" => 102,
+        "up" => 103,
+        "pageup" => 104,
+        "left" => 105,
+        "right" => 106,
+        "end" => 107,
+        "down" => 108,
+        "pagedown" => 109,
+        "insert" => 110,
+        "delete" => 111,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// XKB keysym name for a key name accepted by `key_code`
+pub(crate) fn keysym(name: &str) -> String {
+    let sym = match name {
+        "esc" | "escape" => "Escape",
+        "backspace" => "BackSpace",
+        "tab" => "Tab",
+        "leftbrace" | "bracketleft" => "bracketleft",
+        "rightbrace" | "bracketright" => "bracketright",
+        "enter" | "return" => "Return",
+        "dot" | "period" => "period",
+        "home" => "Home",
+        "up" => "Up",
+        "pageup" => "Page_Up",
+        "left" => "Left",
+        "right" => "Right",
+        "end" => "End",
+        "down" => "Down",
+        "pagedown" => "Page_Down",
+        "insert" => "Insert",
+        "delete" => "Delete",
+        _ if name.len() > 1 && name.starts_with('f') && name[1..].parse::<u8>().is_ok() => {
+            return name.to_uppercase();
+        }
+        _ => name,
+    };
+    sym.to_string()
+}
+
+/// A chord in the XDG shortcut trigger format, e.g. `super+semicolon` as `LOGO+semicolon`
+pub fn portal_trigger(chord: &str) -> Result<String, String> {
+    let parsed: Chord = chord.parse()?;
+    let key = chord.rsplit('+').next().unwrap_or_default().trim().to_lowercase();
+    let mut parts: Vec<String> = parsed.modifiers.iter().map(|m| m.trigger_name().to_string()).collect();
+    parts.push(keysym(&key));
+    Ok(parts.join("+"))
+}
+
+/// A key with modifiers, written like `super+semicolon` or `ctrl+alt+f`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    modifiers: Vec<Modifier>,
+    key: u16,
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<String> = s.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let (key, modifiers) = parts.split_last().ok_or_else(|| "empty chord".to_string())?;
+        let modifiers = modifiers
+            .iter()
+            .map(|m| Modifier::parse(m).ok_or_else(|| format!("unknown modifier '{}' in '{}'", m, s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = key_code(key).ok_or_else(|| format!("unknown key '{}' in '{}'", key, s))?;
+        Ok(Chord { modifiers, key })
+    }
+}
+
+/// Tracks held keys and reports when a bound chord is pressed
+pub struct ChordMatcher {
+    bindings: Vec<(Chord, ActionMode)>,
+    held: HashSet<u16>,
+}
+
+impl ChordMatcher {
+    pub fn new(bindings: &[HotkeyBinding]) -> Result<Self> {
+        let bindings = bindings
+            .iter()
+            .map(|b| Ok((b.chord.parse::<Chord>().map_err(anyhow::Error::msg)?, b.mode)))
+            .collect::<Result<_>>()?;
+        Ok(Self { bindings, held: HashSet::new() })
+    }
+
+    /// Feed a key event (value 1 = press, 0 = release, 2 = autorepeat)
+    pub fn key_event(&mut self, code: u16, value: i32) -> Option<ActionMode> {
+        match value {
+            0 => {
+                self.held.remove(&code);
+                None
+            }
+            1 => {
+                self.held.insert(code);
+                self.bindings
+                    .iter()
+                    .find(|(chord, _)| chord.key == code && self.modifiers_match(chord))
+                    .map(|&(_, mode)| mode)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether exactly the chord's modifiers are held
+    fn modifiers_match(&self, chord: &Chord) -> bool {
+        Modifier::ALL.iter().all(|m| {
+            let held = m.codes().iter().any(|c| self.held.contains(c));
+            held == chord.modifiers.contains(m)
+        })
+    }
+}
+
+/// Whether a sysfs key capability bitmap (hex words, most significant first) has `code`
+fn has_key(bitmap: &str, code: u16) -> bool {
+    let words: Vec<&str> = bitmap.split_whitespace().collect();
+    let index = code as usize / 64;
+    words
+        .len()
+        .checked_sub(index + 1)
+        .and_then(|i| u64::from_str_radix(words[i], 16).ok())
+        .is_some_and(|word| word & (1 << (code % 64)) != 0)
+}
+
+/// Event devices that look like keyboards
+pub fn keyboards() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/dev/input") else {
+        return Vec::new();
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("event")))
+        .filter(|p| is_keyboard(p))
+        .collect();
+    devices.sort();
+    devices
+}
+
+fn is_keyboard(device: &Path) -> bool {
+    let Some(name) = device.file_name() else {
+        return false;
+    };
+    let caps = Path::new("/sys/class/input").join(name).join("device/capabilities/key");
+    fs::read_to_string(caps)
+        .map(|bitmap| has_key(&bitmap, KEY_A) && has_key(&bitmap, KEY_Z))
+        .unwrap_or(false)
+}
+
+/// Watch keyboards for the bound chords and send the matching modes
+///
+/// Devices are read, not grabbed, so keys still reach the compositor.
+/// Reading /dev/input needs membership in the `input` group.
+pub fn listen(bindings: &[HotkeyBinding], modes: mpsc::UnboundedSender<ActionMode>) -> Result<()> {
+    let mut matcher = ChordMatcher::new(bindings)?;
+    let devices = keyboards();
+    if devices.is_empty() {
+        anyhow::bail!("No keyboards found in /dev/input");
+    }
+
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+    let mut opened = 0;
+    let mut denied = false;
+    for device in devices {
+        let file = match std::fs::File::open(&device) {
+            Ok(f) => f,
+            Err(e) => {
+                denied |= e.kind() == ErrorKind::PermissionDenied;
+                debug!("Cannot read {:?}: {}", device, e);
+                continue;
+            }
+        };
+        opened += 1;
+        info!("Listening for hotkeys on {:?}", device);
+        let key_tx = key_tx.clone();
+        tokio::spawn(async move {
+            let mut file = tokio::fs::File::from_std(file);
+            let mut event = [0u8; EVENT_SIZE];
+            while file.read_exact(&mut event).await.is_ok() {
+                let kind = u16::from_ne_bytes([event[EVENT_SIZE - 8], event[EVENT_SIZE - 7]]);
+                if kind != EV_KEY {
+                    continue;
+                }
+                let code = u16::from_ne_bytes([event[EVENT_SIZE - 6], event[EVENT_SIZE - 5]]);
+                let value = i32::from_ne_bytes(event[EVENT_SIZE - 4..].try_into().expect("4 bytes"));
+                if key_tx.send((code, value)).is_err() {
+                    break;
+                }
+            }
+            debug!("Stopped reading {:?}", device);
+        });
+    }
+    if opened == 0 {
+        if denied {
+            anyhow::bail!("Permission denied reading /dev/input; add yourself to the input group");
+        }
+        anyhow::bail!("Could not open any keyboard");
+    }
+
+    tokio::spawn(async move {
+        while let Some((code, value)) = key_rx.recv().await {
+            if let Some(mode) = matcher.key_event(code, value) {
+                info!("Hotkey pressed for {:?} mode", mode);
+                if modes.send(mode).is_err() {
+                    break;
+                }
+            }
+        }
+        warn!("Hotkey listener stopped");
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(chord: &str, mode: ActionMode) -> HotkeyBinding {
+        HotkeyBinding {
+            chord: chord.to_string(),
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_parse_chord() {
+        let chord: Chord = "Super+Semicolon".parse().unwrap();
+        assert_eq!(chord, Chord { modifiers: vec![Modifier::Super], key: 39 });
+        assert_eq!("ctrl+alt+f".parse::<Chord>().unwrap().key, 33);
+        assert_eq!("f12".parse::<Chord>().unwrap().key, 88);
+        assert_eq!("0".parse::<Chord>().unwrap().key, 11);
+        assert!("hyper+x".parse::<Chord>().is_err());
+        assert!("ctrl+".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn test_chord_matcher() {
+        let bindings = [binding("super+f", ActionMode::Click), binding("super+shift+f", ActionMode::Scroll)];
+        let mut matcher = ChordMatcher::new(&bindings).unwrap();
+        assert_eq!(matcher.key_event(33, 1), None);
+        matcher.key_event(33, 0);
+
+        matcher.key_event(126, 1);
+        assert_eq!(matcher.key_event(33, 1), Some(ActionMode::Click));
+        assert_eq!(matcher.key_event(33, 2), None);
+        matcher.key_event(33, 0);
+        matcher.key_event(42, 1);
+        assert_eq!(matcher.key_event(33, 1), Some(ActionMode::Scroll));
+    }
+
+    #[test]
+    fn test_portal_trigger() {
+        assert_eq!(portal_trigger("super+semicolon").unwrap(), "LOGO+semicolon");
+        assert_eq!(portal_trigger("Ctrl+Shift+F5").unwrap(), "CTRL+SHIFT+F5");
+        assert_eq!(portal_trigger("alt+pageup").unwrap(), "ALT+Page_Up");
+        assert!(portal_trigger("hyper+x").is_err());
+    }
+
+    #[test]
+    fn test_has_key() {
+        // KEY_A (30) and KEY_Z (44) in the lowest word, plus bit 64 in the next
+        let bitmap = "1 100040000000";
+        assert!(has_key(bitmap, KEY_A));
+        assert!(has_key(bitmap, KEY_Z));
+        assert!(has_key(bitmap, 64));
+        assert!(!has_key(bitmap, 31));
+        assert!(!has_key(bitmap, 200));
+    }
+}
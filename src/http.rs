@@ -0,0 +1,287 @@
+use crate::click::{self, ClickButton, ScrollDirection};
+use crate::config::ActionMode;
+use crate::filter::RoleFilter;
+use crate::overlay;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+/// Request bodies larger than this are refused
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Clients must send the whole request within this time
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request answered by the caller of `serve`
+#[derive(Debug)]
+pub enum Request {
+    /// `GET /elements`: clickable elements, as `list --format json` prints them
+    Elements,
+    /// `POST /hints`: show hints and wait for a selection, answered with its `--json-result`
+    Hints { mode: ActionMode, filter: Option<RoleFilter> },
+    /// `POST /act`: act on an element by its ID
+    Act { id: String, action: ActionMode },
+}
+
+#[derive(Deserialize)]
+struct HintsBody {
+    #[serde(default)]
+    mode: ActionMode,
+    #[serde(default)]
+    filter: Option<RoleFilter>,
+}
+
+#[derive(Deserialize)]
+struct ActBody {
+    id: String,
+    #[serde(default)]
+    action: ActionMode,
+}
+
+#[derive(Deserialize)]
+struct ClickBody {
+    x: i32,
+    y: i32,
+    #[serde(default)]
+    button: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScrollBody {
+    x: i32,
+    y: i32,
+    direction: String,
+    #[serde(default)]
+    amount: Option<i32>,
+}
+
+/// An HTTP error status with a message for the JSON body
+#[derive(Debug)]
+struct HttpError(u16, String);
+
+/// Where a request goes once parsed
+#[derive(Debug)]
+enum Routed {
+    /// Answered on the spot (pointer actions, `/cancel`)
+    Reply(Value),
+    /// Passed to the caller of `serve`
+    Handle(Request),
+}
+
+/// A request waiting for the caller of `serve`, with where its answer goes
+type Job = (Request, oneshot::Sender<Result<Value, HttpError>>);
+
+impl From<anyhow::Error> for HttpError {
+    fn from(e: anyhow::Error) -> Self {
+        HttpError(500, format!("{:#}", e))
+    }
+}
+
+fn bad_request(e: impl std::fmt::Display) -> HttpError {
+    HttpError(400, e.to_string())
+}
+
+/// Serve the JSON automation API on `addr`
+///
+/// Each connection is read in its own task, so a slow client doesn't hold up
+/// the others and `/cancel` gets through while hints are shown. Pointer
+/// actions are handled there; listing, hinting and acting on elements are
+/// passed to `handle`, one request at a time.
+pub async fn serve<F, Fut>(addr: SocketAddr, scroll_step: i32, mut handle: F) -> Result<()>
+where
+    F: FnMut(Request) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    if !addr.ip().is_loopback() {
+        warn!("HTTP API on {} is reachable from other machines and has no authentication", addr);
+    }
+    let listener = TcpListener::bind(addr).await.with_context(|| format!("Failed to bind {}", addr))?;
+    info!("HTTP API listening on http://{}", addr);
+
+    let (jobs_tx, mut jobs) = mpsc::unbounded_channel::<Job>();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    tokio::spawn(serve_connection(stream, peer, scroll_step, jobs_tx.clone()));
+                }
+                Err(e) => warn!("HTTP accept failed: {}", e),
+            }
+        }
+    });
+
+    while let Some((request, reply)) = jobs.recv().await {
+        let result = handle(request).await.map_err(HttpError::from);
+        let _ = reply.send(result);
+    }
+    Ok(())
+}
+
+/// Read one request from `stream`, answer it and close the connection
+async fn serve_connection(mut stream: TcpStream, peer: SocketAddr, scroll_step: i32, jobs: mpsc::UnboundedSender<Job>) {
+    let result = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok((method, path, body))) => {
+            debug!("{} {} from {}", method, path, peer);
+            match route(&method, &path, &body, scroll_step).await {
+                Ok(Routed::Reply(value)) => Ok(value),
+                Ok(Routed::Handle(request)) => {
+                    let (reply_tx, reply) = oneshot::channel();
+                    if jobs.send((request, reply_tx)).is_err() {
+                        Err(HttpError(500, "The server is shutting down".to_string()))
+                    } else {
+                        reply
+                            .await
+                            .unwrap_or_else(|_| Err(HttpError(500, "The request was dropped".to_string())))
+                    }
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Ok(Err(e)) => Err(bad_request(format!("{:#}", e))),
+        Err(_) => Err(HttpError(408, format!("No complete request within {:?}", READ_TIMEOUT))),
+    };
+    let (status, body) = match result {
+        Ok(value) => (200, value),
+        Err(HttpError(status, message)) => (status, json!({ "error": message })),
+    };
+    if let Err(e) = write_response(&mut stream, status, &body).await {
+        debug!("HTTP client {} went away: {}", peer, e);
+    }
+}
+
+async fn route(method: &str, path: &str, body: &[u8], scroll_step: i32) -> Result<Routed, HttpError> {
+    let request = match (method, path) {
+        ("GET", "/elements") => Request::Elements,
+        ("POST", "/hints") => {
+            let body: HintsBody = parse_body(body)?;
+            Request::Hints { mode: body.mode, filter: body.filter }
+        }
+        ("POST", "/act") => {
+            let body: ActBody = parse_body(body)?;
+            Request::Act { id: body.id, action: body.action }
+        }
+        ("POST", "/click") => {
+            let body: ClickBody = parse_body(body)?;
+            let button = match body.button {
+                Some(b) => b.parse().map_err(bad_request)?,
+                None => ClickButton::Left,
+            };
+            tokio::task::spawn_blocking(move || click::press_at(body.x, body.y, button))
+                .await
+                .map_err(|e| HttpError(500, e.to_string()))??;
+            return Ok(Routed::Reply(json!({})));
+        }
+        ("POST", "/scroll") => {
+            let body: ScrollBody = parse_body(body)?;
+            let direction: ScrollDirection = body.direction.parse().map_err(bad_request)?;
+            let amount = body.amount.unwrap_or(scroll_step);
+            tokio::task::spawn_blocking(move || click::scroll_at(body.x, body.y, direction, amount))
+                .await
+                .map_err(|e| HttpError(500, e.to_string()))??;
+            return Ok(Routed::Reply(json!({})));
+        }
+        ("POST", "/cancel") => return Ok(Routed::Reply(json!({ "cancelled": overlay::cancel() }))),
+        (_, "/elements" | "/hints" | "/act" | "/click" | "/scroll" | "/cancel") => {
+            return Err(HttpError(405, format!("{} not allowed on {}", method, path)));
+        }
+        _ => return Err(HttpError(404, format!("No endpoint {}", path))),
+    };
+    Ok(Routed::Handle(request))
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, HttpError> {
+    let body = if body.is_empty() { b"{}".as_slice() } else { body };
+    serde_json::from_slice(body).map_err(bad_request)
+}
+
+/// Read the request line, headers and body of an HTTP/1.1 request
+async fn read_request(stream: &mut TcpStream) -> Result<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let (method, path) = parse_request_line(&line).context("Malformed request line")?;
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().context("Invalid Content-Length")?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        anyhow::bail!("Request body of {} bytes is too large", content_length);
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok((method, path, body))
+}
+
+/// Method and path (without query string) of a request line
+fn parse_request_line(line: &str) -> Option<(String, String)> {
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts.next()?.starts_with("HTTP/").then_some(())?;
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method.to_string(), path.to_string()))
+}
+
+async fn write_response(stream: &mut TcpStream, status: u16, body: &Value) -> Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        _ => "Internal Server Error",
+    };
+    let body = serde_json::to_string(body)?;
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(
+            parse_request_line("GET /elements?x=1 HTTP/1.1\r\n"),
+            Some(("GET".to_string(), "/elements".to_string()))
+        );
+        assert_eq!(parse_request_line("GET /elements\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_route_parses_bodies() {
+        let body = br#"{"mode":"rightclick","filter":"link,!image"}"#;
+        let routed = route("POST", "/hints", body, 3).await.unwrap();
+        assert!(matches!(routed, Routed::Handle(Request::Hints { mode: ActionMode::RightClick, .. })));
+        assert!(matches!(route("POST", "/act", b"{}", 3).await, Err(HttpError(400, _))));
+        assert!(matches!(route("DELETE", "/act", b"", 3).await, Err(HttpError(405, _))));
+        assert!(matches!(route("GET", "/nope", b"", 3).await, Err(HttpError(404, _))));
+        assert!(matches!(route("POST", "/cancel", b"", 3).await, Ok(Routed::Reply(_))));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::config::HotkeyBinding;
+use crate::hotkey::{keysym, Modifier};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// First line of the generated snippet, used to spot an earlier install
+const MARKER: &str = "# vimium-linux keybindings";
+
+/// Compositors with a known keybinding syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compositor {
+    Hyprland,
+    Sway,
+    River,
+}
+
+/// Modes bound by the snippet, with whether Shift is added to Super and the key
+const BINDINGS: &[(&str, bool, &str)] = &[
+    ("click", false, "semicolon"),
+    ("scroll", true, "semicolon"),
+    ("text", false, "apostrophe"),
+];
+
+/// Key written for modes that have neither a chord nor a default binding
+const PLACEHOLDER_KEY: &str = "KEY";
+
+impl Compositor {
+    /// The compositor of the current session, from its environment variables
+    pub fn detect() -> Option<Self> {
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return Some(Compositor::Hyprland);
+        }
+        if std::env::var_os("SWAYSOCK").is_some() {
+            return Some(Compositor::Sway);
+        }
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+        desktop.contains("river").then_some(Compositor::River)
+    }
+
+    /// The compositor's configuration file
+    pub fn config_path(self) -> Option<PathBuf> {
+        let file = match self {
+            Compositor::Hyprland => "hypr/hyprland.conf",
+            Compositor::Sway => "sway/config",
+            Compositor::River => "river/init",
+        };
+        dirs::config_dir().map(|dir| dir.join(file))
+    }
+
+    fn modifier_name(self, modifier: Modifier) -> &'static str {
+        match (self, modifier) {
+            (Compositor::Hyprland, Modifier::Super) => "SUPER",
+            (Compositor::Hyprland, Modifier::Shift) => "SHIFT",
+            (Compositor::Hyprland, Modifier::Ctrl) => "CTRL",
+            (Compositor::Hyprland, Modifier::Alt) => "ALT",
+            (Compositor::Sway, Modifier::Super) => "Mod4",
+            (Compositor::Sway, Modifier::Alt) => "Mod1",
+            (Compositor::River, Modifier::Super) => "Super",
+            (Compositor::River, Modifier::Alt) => "Alt",
+            (_, Modifier::Shift) => "Shift",
+            (_, Modifier::Ctrl) => "Control",
+        }
+    }
+
+    fn binding(self, modifiers: &[Modifier], key: &str, command: &str) -> String {
+        let names: Vec<&str> = modifiers.iter().map(|&m| self.modifier_name(m)).collect();
+        match self {
+            Compositor::Hyprland => format!("bind = {}, {}, exec, {}", names.join(" "), key, command),
+            Compositor::Sway => {
+                let chord: Vec<&str> = names.into_iter().chain([key]).collect();
+                format!("bindsym {} exec {}", chord.join("+"), command)
+            }
+            Compositor::River => {
+                let modifiers = if names.is_empty() { "None".to_string() } else { names.join("+") };
+                format!("riverctl map normal {} {} spawn '{}'", modifiers, key, command)
+            }
+        }
+    }
+}
+
+/// Modifiers and keysym of a `[hotkey]` chord such as `super+shift+semicolon`
+fn parse_chord(chord: &str) -> Result<(Vec<Modifier>, String), String> {
+    let parts: Vec<String> = chord.split('+').map(|p| p.trim().to_lowercase()).collect();
+    let (key, modifiers) = parts.split_last().ok_or_else(|| "empty chord".to_string())?;
+    let modifiers = modifiers
+        .iter()
+        .map(|m| Modifier::parse(m).ok_or_else(|| format!("unknown modifier '{}' in '{}'", m, chord)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((modifiers, keysym(key)))
+}
+
+/// The command a key runs for `mode`
+fn mode_command(mode: &str, daemon: bool) -> String {
+    if daemon {
+        format!("vimium-linux trigger {}", mode)
+    } else {
+        format!("vimium-linux {}", mode)
+    }
+}
+
+/// The default modifiers for a `BINDINGS` entry
+fn default_modifiers(shift: bool) -> Vec<Modifier> {
+    if shift {
+        vec![Modifier::Super, Modifier::Shift]
+    } else {
+        vec![Modifier::Super]
+    }
+}
+
+/// Bindings for click, scroll and text modes
+///
+/// With `daemon`, the keys run `vimium-linux trigger` and the daemon is
+/// started with the session.
+pub fn snippet(compositor: Compositor, daemon: bool) -> String {
+    let mut lines = vec![MARKER.to_string()];
+    if daemon {
+        lines.push(match compositor {
+            Compositor::Hyprland => "exec-once = vimium-linux daemon".to_string(),
+            Compositor::Sway => "exec vimium-linux daemon".to_string(),
+            Compositor::River => "riverctl spawn 'vimium-linux daemon'".to_string(),
+        });
+    }
+    for &(mode, shift, key) in BINDINGS {
+        lines.push(compositor.binding(&default_modifiers(shift), key, &mode_command(mode, daemon)));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Bindings for every mode in `modes`, using the chords from `[hotkey]`
+///
+/// Modes without a chord get the snippet's default key; the others are
+/// written commented out with a placeholder key to fill in.
+pub fn mode_bindings(compositor: Compositor, modes: &[String], hotkeys: &[HotkeyBinding], daemon: bool) -> String {
+    let mut lines = vec![MARKER.to_string()];
+    for mode in modes {
+        let command = mode_command(mode, daemon);
+        let mut bound = false;
+        for hotkey in hotkeys.iter().filter(|h| h.mode.name() == *mode) {
+            match parse_chord(&hotkey.chord) {
+                Ok((modifiers, key)) => {
+                    lines.push(compositor.binding(&modifiers, &key, &command));
+                    bound = true;
+                }
+                Err(e) => lines.push(format!("# Skipped hotkey: {}", e)),
+            }
+        }
+        if bound {
+            continue;
+        }
+        match BINDINGS.iter().find(|(name, _, _)| name == mode) {
+            Some(&(_, shift, key)) => lines.push(compositor.binding(&default_modifiers(shift), key, &command)),
+            None => lines.push(format!(
+                "# {}",
+                compositor.binding(&[Modifier::Super], PLACEHOLDER_KEY, &command)
+            )),
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Append the snippet to the compositor's config, returning the file written
+pub fn write(compositor: Compositor, daemon: bool) -> Result<PathBuf> {
+    let path = compositor.config_path().context("No config directory")?;
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    if existing.contains(MARKER) {
+        anyhow::bail!("{:?} already has vimium-linux keybindings", path);
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let separator = if existing.is_empty() || existing.ends_with('\n') { "\n" } else { "\n\n" };
+    write!(file, "{}{}", separator, snippet(compositor, daemon)).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet() {
+        let sway = snippet(Compositor::Sway, false);
+        assert!(sway.starts_with(MARKER));
+        assert!(sway.contains("bindsym Mod4+semicolon exec vimium-linux click\n"));
+        assert!(sway.contains("bindsym Mod4+Shift+semicolon exec vimium-linux scroll\n"));
+
+        let hyprland = snippet(Compositor::Hyprland, true);
+        assert!(hyprland.contains("exec-once = vimium-linux daemon\n"));
+        assert!(hyprland.contains("bind = SUPER, apostrophe, exec, vimium-linux trigger text\n"));
+
+        let river = snippet(Compositor::River, false);
+        assert!(river.contains("riverctl map normal Super+Shift semicolon spawn 'vimium-linux scroll'\n"));
+    }
+
+    #[test]
+    fn test_mode_bindings() {
+        let modes = ["click".to_string(), "right-click".to_string(), "hover".to_string()];
+        let hotkeys = [HotkeyBinding {
+            chord: "ctrl+alt+r".to_string(),
+            mode: crate::config::ActionMode::RightClick,
+        }];
+        let sway = mode_bindings(Compositor::Sway, &modes, &hotkeys, false);
+        assert!(sway.contains("bindsym Mod4+semicolon exec vimium-linux click\n"));
+        assert!(sway.contains("bindsym Control+Mod1+r exec vimium-linux right-click\n"));
+        assert!(sway.contains("# bindsym Mod4+KEY exec vimium-linux hover\n"));
+
+        let hyprland = mode_bindings(Compositor::Hyprland, &modes, &hotkeys, true);
+        assert!(hyprland.contains("bind = CTRL ALT, r, exec, vimium-linux trigger right-click\n"));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::click::{on_path, run, run_with_input};
+use crate::hotkey::{key_code, keysym, Modifier};
+use crate::protocols;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// A key with modifiers, e.g. `ctrl+shift+t`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCombo {
+    modifiers: Vec<Modifier>,
+    /// Lowercase key name, as accepted by `[hotkey]` chords or an XKB keysym
+    key: String,
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<String> = s.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let (key, modifiers) = parts.split_last().ok_or_else(|| "empty key".to_string())?;
+        if key.is_empty() {
+            return Err(format!("missing key in '{}'", s));
+        }
+        let modifiers = modifiers
+            .iter()
+            .map(|m| Modifier::parse(m).ok_or_else(|| format!("unknown modifier '{}' in '{}'", m, s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeyCombo {
+            modifiers,
+            key: key.replace('_', ""),
+        })
+    }
+}
+
+impl KeyCombo {
+    /// Parse an AT-SPI key binding such as `<Control>s` or GTK's
+    /// `mnemonic;menu path;accelerator` triple
+    ///
+    /// The accelerator is preferred. A mnemonic without modifiers only works
+    /// while its menu is open, so it is ignored.
+    pub fn from_atspi(binding: &str) -> Option<Self> {
+        let fields: Vec<&str> = binding.split(';').collect();
+        let accelerator = fields.get(2).copied();
+        let mnemonic = fields.first().copied();
+        [accelerator, mnemonic]
+            .into_iter()
+            .flatten()
+            .filter_map(Self::parse_atspi_field)
+            .find(|combo| !combo.modifiers.is_empty())
+    }
+
+    fn parse_atspi_field(field: &str) -> Option<Self> {
+        let mut rest = field.trim();
+        if rest.is_empty() {
+            return None;
+        }
+        if !rest.starts_with('<') {
+            // Qt style, e.g. "Ctrl+S"
+            return rest.parse().ok();
+        }
+        let mut modifiers = Vec::new();
+        while let Some(tail) = rest.strip_prefix('<') {
+            let (name, tail) = tail.split_once('>')?;
+            let modifier = match name.to_lowercase().as_str() {
+                "control" | "ctrl" | "primary" => Modifier::Ctrl,
+                "mod1" => Modifier::Alt,
+                "mod4" => Modifier::Super,
+                other => Modifier::parse(other)?,
+            };
+            modifiers.push(modifier);
+            rest = tail;
+        }
+        if rest.is_empty() {
+            return None;
+        }
+        Some(KeyCombo {
+            modifiers,
+            key: rest.to_lowercase().replace('_', ""),
+        })
+    }
+
+    fn keysym(&self) -> String {
+        keysym(&self.key)
+    }
+}
+
+impl std::fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier_name(*modifier))?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// One step of a key sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyStroke {
+    /// Text typed as is
+    Text(String),
+    Combo(KeyCombo),
+}
+
+/// Keys sent in order, written as one combination (`ctrl+shift+t`, `tab`) or as
+/// text with combinations in angle brackets (`Hello<Enter>`, `<ctrl+a>new<tab>`)
+///
+/// Without angle brackets, anything with a `+` or naming a single key is a
+/// combination. A `<` without a closing `>` is typed as is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeySequence(Vec<KeyStroke>);
+
+impl FromStr for KeySequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("empty key sequence".to_string());
+        }
+        if !s.contains('<') && (s.contains('+') || is_key_name(&s.to_lowercase())) {
+            return Ok(KeySequence(vec![KeyStroke::Combo(s.parse()?)]));
+        }
+        let mut strokes = Vec::new();
+        let mut rest = s;
+        while let Some((text, tail)) = rest.split_once('<') {
+            let Some((name, tail)) = tail.split_once('>') else {
+                break;
+            };
+            if !text.is_empty() {
+                strokes.push(KeyStroke::Text(text.to_string()));
+            }
+            strokes.push(KeyStroke::Combo(name.parse()?));
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            strokes.push(KeyStroke::Text(rest.to_string()));
+        }
+        Ok(KeySequence(strokes))
+    }
+}
+
+impl TryFrom<String> for KeySequence {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for stroke in &self.0 {
+            match stroke {
+                KeyStroke::Text(text) => write!(f, "{}", text)?,
+                KeyStroke::Combo(combo) => write!(f, "<{}>", combo)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<KeySequence> for String {
+    fn from(sequence: KeySequence) -> Self {
+        sequence.to_string()
+    }
+}
+
+/// Whether `name` names a key rather than being text to type
+fn is_key_name(name: &str) -> bool {
+    key_code(name).is_some() || keysym(name) != name
+}
+
+/// Send a key sequence, typing its text and pressing its combinations in order
+pub fn send_sequence(sequence: &KeySequence) -> Result<()> {
+    for stroke in &sequence.0 {
+        match stroke {
+            KeyStroke::Text(text) => type_text(text)?,
+            KeyStroke::Combo(combo) => send(combo)?,
+        }
+    }
+    Ok(())
+}
+
+/// Modifier name understood by wtype and dotool
+fn modifier_name(modifier: Modifier) -> &'static str {
+    match modifier {
+        Modifier::Ctrl => "ctrl",
+        Modifier::Shift => "shift",
+        Modifier::Alt => "alt",
+        Modifier::Super => "super",
+    }
+}
+
+/// Press a key combination through wtype, dotool or ydotool
+pub fn send(combo: &KeyCombo) -> Result<()> {
+    info!("Sending {}", combo);
+    let mut last_error = None;
+    for (tool, send) in [
+        ("wtype", send_wtype as fn(&KeyCombo) -> Result<()>),
+        ("dotool", send_dotool),
+        ("ydotool", send_ydotool),
+    ] {
+        if !on_path(tool) {
+            continue;
+        }
+        if tool == "wtype" && !protocols::has(protocols::VIRTUAL_KEYBOARD) {
+            debug!("Skipping wtype: the compositor lacks {}", protocols::VIRTUAL_KEYBOARD);
+            continue;
+        }
+        match send(combo) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                debug!("{} could not send {}: {}", tool, combo, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No key sending tool found (install wtype, dotool or ydotool)")))
+}
+
+/// Type text into the focused window through wtype, dotool or ydotool
+pub fn type_text(text: &str) -> Result<()> {
+    info!("Typing {} characters", text.chars().count());
+    let mut last_error = None;
+    for tool in ["wtype", "dotool", "ydotool"] {
+        if !on_path(tool) {
+            continue;
+        }
+        if tool == "wtype" && !protocols::has(protocols::VIRTUAL_KEYBOARD) {
+            debug!("Skipping wtype: the compositor lacks {}", protocols::VIRTUAL_KEYBOARD);
+            continue;
+        }
+        let result = match tool {
+            "wtype" => run("wtype", &["--", text]),
+            // dotool reads one command per line, so line breaks become Enter
+            "dotool" => {
+                let lines: Vec<String> = text.split('\n').map(|line| format!("type {}\n", line)).collect();
+                run_with_input("dotool", &lines.join("key enter\n"))
+            }
+            _ => run("ydotool", &["type", "--", text]),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                debug!("{} could not type text: {}", tool, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No key sending tool found (install wtype, dotool or ydotool)")))
+}
+
+fn send_wtype(combo: &KeyCombo) -> Result<()> {
+    let modifiers: Vec<&str> = combo
+        .modifiers
+        .iter()
+        .map(|&m| if m == Modifier::Super { "logo" } else { modifier_name(m) })
+        .collect();
+    let keysym = combo.keysym();
+    let mut args = Vec::new();
+    for m in &modifiers {
+        args.extend(["-M", m]);
+    }
+    args.extend(["-k", keysym.as_str()]);
+    for m in modifiers.iter().rev() {
+        args.extend(["-m", m]);
+    }
+    run("wtype", &args)
+}
+
+fn send_dotool(combo: &KeyCombo) -> Result<()> {
+    let key = match key_code(&combo.key) {
+        Some(code) => format!("k:{}", code),
+        None => format!("x:{}", combo.keysym()),
+    };
+    let mut chord: Vec<String> = combo.modifiers.iter().map(|&m| modifier_name(m).to_string()).collect();
+    chord.push(key);
+    run_with_input("dotool", &format!("key {}\n", chord.join("+")))
+}
+
+fn send_ydotool(combo: &KeyCombo) -> Result<()> {
+    let key = key_code(&combo.key).ok_or_else(|| anyhow::anyhow!("ydotool has no key code for '{}'", combo.key))?;
+    let codes: Vec<u16> = combo.modifiers.iter().map(|m| m.codes()[0]).chain([key]).collect();
+    let events: Vec<String> = codes
+        .iter()
+        .map(|c| format!("{}:1", c))
+        .chain(codes.iter().rev().map(|c| format!("{}:0", c)))
+        .collect();
+    let mut args = vec!["key"];
+    args.extend(events.iter().map(String::as_str));
+    run("ydotool", &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_atspi() {
+        let combo = KeyCombo::from_atspi("<Control><Shift>t").unwrap();
+        assert_eq!(combo.to_string(), "ctrl+shift+t");
+        let combo = KeyCombo::from_atspi("<Alt>o;_File:_Open;<Primary>o").unwrap();
+        assert_eq!(combo.to_string(), "ctrl+o");
+        assert_eq!(KeyCombo::from_atspi("<Alt>o;_File:_Open;").unwrap().to_string(), "alt+o");
+        assert_eq!(KeyCombo::from_atspi("Ctrl+Page_Up").unwrap().to_string(), "ctrl+pageup");
+        assert!(KeyCombo::from_atspi("n").is_none());
+        assert!(KeyCombo::from_atspi("").is_none());
+    }
+
+    #[test]
+    fn test_key_sequence() {
+        let combo = |s: &str| KeyStroke::Combo(s.parse().unwrap());
+        let text = |s: &str| KeyStroke::Text(s.to_string());
+        let parse = |s: &str| s.parse::<KeySequence>().unwrap().0;
+        assert_eq!(parse("ctrl+shift+t"), [combo("ctrl+shift+t")]);
+        assert_eq!(parse("Hello<Enter>"), [text("Hello"), combo("enter")]);
+        assert_eq!(parse("<ctrl+a>new<tab>"), [combo("ctrl+a"), text("new"), combo("tab")]);
+        assert_eq!(parse("Tab"), [combo("tab")]);
+        assert_eq!(parse("hello"), [text("hello")]);
+        assert_eq!(parse("1+1 < 3"), [text("1+1 < 3")]);
+        assert!("hyper+x".parse::<KeySequence>().is_err());
+        assert!("<hyper+x>".parse::<KeySequence>().is_err());
+        assert!("".parse::<KeySequence>().is_err());
+        assert_eq!("Hi<Enter>".parse::<KeySequence>().unwrap().to_string(), "Hi<enter>");
+    }
+}
@@ -0,0 +1,125 @@
+use smithay_client_toolkit::output::OutputInfo;
+
+/// An output's area in the global compositor space, in logical pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputRect {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl OutputRect {
+    /// Logical rectangle of an output, preferring xdg-output's values
+    pub fn from_info(info: &OutputInfo) -> Option<Self> {
+        let (x, y) = info.logical_position.unwrap_or(info.location);
+        let (width, height) = match info.logical_size {
+            Some(size) => size,
+            None => {
+                // Without xdg-output, derive the logical size from the mode
+                let mode = info.modes.iter().find(|m| m.current)?;
+                let scale = info.scale_factor.max(1);
+                (mode.dimensions.0 / scale, mode.dimensions.1 / scale)
+            }
+        };
+        Some(Self {
+            name: info.name.clone().unwrap_or_else(|| format!("output-{}", info.id)),
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn same_area(&self, other: &OutputRect) -> bool {
+        (self.x, self.y, self.width, self.height) == (other.x, other.y, other.width, other.height)
+    }
+}
+
+/// The outputs an overlay surface is currently shown on
+///
+/// Overlay surfaces draw in output-local coordinates while pointer backends
+/// take global ones. Mirrored outputs report the same area and are kept
+/// once, so their offsets are never applied twice.
+#[derive(Debug, Default)]
+pub struct OutputLayout {
+    outputs: Vec<OutputRect>,
+}
+
+impl OutputLayout {
+    /// Record that the surface entered an output
+    pub fn enter(&mut self, rect: OutputRect) {
+        self.leave(&rect.name);
+        if !self.outputs.iter().any(|o| o.same_area(&rect)) {
+            self.outputs.push(rect);
+        }
+    }
+
+    /// Record that the surface left an output
+    pub fn leave(&mut self, name: &str) {
+        self.outputs.retain(|o| o.name != name);
+    }
+
+    /// Global position of the surface's top-left corner
+    ///
+    /// A layer surface covering its output has the output's size, which
+    /// tells it apart from overlapping outputs it also touches.
+    pub fn surface_origin(&self, surface_width: u32, surface_height: u32) -> (i32, i32) {
+        let size = (surface_width as i32, surface_height as i32);
+        self.outputs
+            .iter()
+            .find(|o| (o.width, o.height) == size)
+            .or_else(|| self.outputs.first())
+            .map(|o| (o.x, o.y))
+            .unwrap_or((0, 0))
+    }
+
+    /// Convert a surface-local position to global coordinates
+    pub fn to_global(&self, (x, y): (i32, i32), surface_width: u32, surface_height: u32) -> (i32, i32) {
+        let (ox, oy) = self.surface_origin(surface_width, surface_height);
+        (x + ox, y + oy)
+    }
+
+    /// Convert a global position to surface-local coordinates
+    pub fn to_local(&self, (x, y): (i32, i32), surface_width: u32, surface_height: u32) -> (i32, i32) {
+        let (ox, oy) = self.surface_origin(surface_width, surface_height);
+        (x - ox, y - oy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(name: &str, x: i32, y: i32, width: i32, height: i32) -> OutputRect {
+        OutputRect {
+            name: name.to_string(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_mirrored_outputs_counted_once() {
+        let mut layout = OutputLayout::default();
+        layout.enter(rect("DP-1", 1920, 0, 2560, 1440));
+        layout.enter(rect("HDMI-A-1", 1920, 0, 2560, 1440));
+        assert_eq!(layout.to_global((100, 50), 2560, 1440), (2020, 50));
+        assert_eq!(layout.to_local((2020, 50), 2560, 1440), (100, 50));
+    }
+
+    #[test]
+    fn test_overlapping_outputs_pick_surface_output() {
+        let mut layout = OutputLayout::default();
+        layout.enter(rect("eDP-1", 0, 0, 1920, 1080));
+        layout.enter(rect("DP-1", 1000, 0, 2560, 1440));
+        assert_eq!(layout.surface_origin(2560, 1440), (1000, 0));
+        assert_eq!(layout.surface_origin(1920, 1080), (0, 0));
+
+        layout.leave("DP-1");
+        assert_eq!(layout.surface_origin(2560, 1440), (0, 0));
+    }
+}
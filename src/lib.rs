@@ -0,0 +1,133 @@
+//! Keyboard-driven navigation for Wayland
+//!
+//! The `vimium-linux` binary is a thin CLI over this library. Element
+//! discovery, hint assignment and pointer control can be used on their own,
+//! and without the default `gui` feature the crate builds without any
+//! Wayland client code (see `examples/` for both):
+//!
+//! ```no_run
+//! use vimium_linux::config::Config;
+//! use vimium_linux::provider::{self, ElementKind};
+//! use vimium_linux::{click, hints};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::load();
+//! let elements = provider::collect(ElementKind::Clickable, &config).await?;
+//! let hinted = hints::assign_hints(&elements, &config.hints.chars);
+//! if let Some(first) = hinted.first() {
+//!     let (x, y) = first.click_position();
+//!     click::click_at(x, y)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+/// xdg-activation tokens handed to the programs vimium-linux starts for a selection
+pub mod activation;
+/// Element discovery through the AT-SPI accessibility tree
+pub mod atspi;
+/// Native messaging bridge to the companion browser extension
+pub mod browser;
+/// Moving a text caret and selecting text with vim motions, for `text --caret` and `select`
+#[cfg(feature = "gui")]
+pub mod caret;
+/// Pointer control through external tools (ydotool, wlrctl, dotool, hyprctl)
+pub mod click;
+/// Copying text to the Wayland clipboard
+pub mod clipboard;
+/// Configuration file and defaults
+pub mod config;
+/// Resident daemon triggered over a Unix socket
+#[cfg(feature = "gui")]
+pub mod daemon;
+/// The `org.vimium.Linux` D-Bus service
+#[cfg(feature = "gui")]
+pub mod dbus;
+/// Environment checks behind `vimium-linux doctor`
+pub mod doctor;
+/// Role filters such as `link,button` or `!table_cell`
+pub mod filter;
+/// Saving and restoring the focused window through compositor IPC
+pub mod focus;
+/// Hint label assignment and matching
+pub mod hints;
+/// Selections remembered for `repeat-last`
+pub mod history;
+/// Shell hooks run when a mode ends
+pub mod hooks;
+/// Global hotkeys read from /dev/input
+pub mod hotkey;
+/// HTTP/JSON automation API
+#[cfg(feature = "http")]
+pub mod http;
+/// Compositor keybinding snippets
+pub mod keybinds;
+/// Key combinations sent through wtype, dotool or ydotool
+pub mod keys;
+/// Opening link URIs with xdg-open or a configured browser
+pub mod links;
+/// Elements saved under a letter per application, for `goto`
+pub mod marks;
+/// Picking hints from a rofi, dmenu or fuzzel menu
+pub mod menu;
+/// Prometheus metrics
+pub mod metrics;
+/// The hint modes and what they do with the element picked
+#[cfg(feature = "gui")]
+pub mod modes;
+/// Exit codes and JSON results of a mode
+pub mod outcome;
+/// The layer-shell hint overlay
+#[cfg(feature = "gui")]
+pub mod overlay;
+/// Nudging the pointer from the keyboard after `move` picks an element
+#[cfg(feature = "gui")]
+pub mod nudge;
+/// Fuzzy element picker in the terminal, used when no overlay can be shown
+pub mod picker;
+/// Lua plugins filtering elements, picking actions and adding overlay keys
+#[cfg(feature = "lua")]
+pub mod plugins;
+/// Shortcuts registered through the GlobalShortcuts portal
+pub mod portal;
+/// A line of text typed on a layer-shell surface, e.g. the `search` query
+#[cfg(feature = "gui")]
+pub mod prompt;
+/// Throttling on battery and in power-saver mode
+pub mod power;
+/// Wayland protocols the compositor advertises
+pub mod protocols;
+/// Element providers and merging their results
+pub mod provider;
+/// Automation scripts run by `vimium-linux run`
+pub mod script;
+/// Scroll mode overlay
+#[cfg(feature = "gui")]
+pub mod scroll;
+/// Desktop-wide text search for `vimium-linux search`
+pub mod search;
+/// systemd user units for the daemon
+pub mod service;
+/// Overlay status published for Waybar and eww
+pub mod status;
+/// Per-element hint styles from `[[hints.rules]]`
+pub mod style;
+/// URLs and paths found in terminal emulators
+pub mod terminal;
+/// Per-stage durations reported with `--timings`
+pub mod timings;
+/// Hint selection on stdin when no Wayland display is available
+pub mod tty;
+/// Window move/resize mode through compositor IPC
+#[cfg(feature = "gui")]
+pub mod window;
+/// A private ydotoold started when none is running
+pub mod ydotoold;
+
+mod bus_cache;
+mod contrast;
+#[cfg(feature = "gui")]
+mod framebuffer;
+#[cfg(feature = "gui")]
+mod layout;
+mod touchpad;
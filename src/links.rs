@@ -0,0 +1,45 @@
+use crate::activation;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tracing::info;
+
+/// Command opening `uri` with `opener`, which gets the URI as its last argument
+///
+/// `opener` is run by `sh -c`, so it may carry arguments of its own, such as
+/// `firefox --new-tab`. The URI is passed as `$1` and never parsed by the shell.
+fn command(opener: &str, uri: &str) -> Command {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("{} \"$1\"", opener))
+        .arg("sh")
+        .arg(uri);
+    command
+}
+
+/// Open `uri` with `opener` (`links.open_command`), without waiting for it
+///
+/// The runtime reaps the process once it exits, so none are left behind by
+/// a long-running daemon.
+pub fn open(opener: &str, uri: &str) -> Result<()> {
+    info!("Opening {} with {}", uri, opener);
+    command(opener, uri)
+        .envs(activation::take_environment())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", opener))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let command = command("firefox --new-tab", "https://example.org/?a=1&b=$HOME");
+        let command = command.as_std();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(command.get_program(), "sh");
+        assert_eq!(args, ["-c", "firefox --new-tab \"$1\"", "sh", "https://example.org/?a=1&b=$HOME"]);
+    }
+}
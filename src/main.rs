@@ -1,13 +1,19 @@
-mod atspi;
-mod click;
-mod config;
-mod hints;
-mod overlay;
-mod scroll;
+#[cfg(feature = "http")]
+use vimium_linux::http;
+#[cfg(feature = "lua")]
+use vimium_linux::plugins;
+use vimium_linux::{
+    atspi, browser, click, config, daemon, dbus, doctor, filter, focus, hints, hotkey, keybinds, keys, metrics, modes,
+    outcome, overlay, portal, power, prompt, protocols, provider, script, service, status, timings, ydotoold,
+};
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use config::{ActionMode, Config};
+use clap::{Parser, Subcommand, ValueEnum};
+use config::{ActionMode, Config, Picker};
+use modes::{parse_point, parse_seconds, HintArgs, PICK_MODE};
+use outcome::Outcome;
+use provider::ElementKind;
+use std::time::Duration;
 use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
@@ -25,39 +31,500 @@ struct Cli {
     /// Verbose output (can be repeated: -v, -vv, -vvv)
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
+
+    /// Modes to try in order when the selected mode finds no elements
+    /// (also written as `click,scroll`)
+    #[arg(long, global = true, value_enum, value_delimiter = ',')]
+    fallback_mode: Vec<ActionMode>,
+
+    /// Print what happened (selected element, cancelled, ...) as JSON on stdout
+    #[arg(long, global = true)]
+    json_result: bool,
+
+    /// Tune for laggy remote desktops (VNC, RDP): --no-animations --low-color --coarse
+    #[arg(long, global = true)]
+    safe_mode: bool,
+
+    /// No smooth scrolling and no redraws as element names arrive
+    #[arg(long, global = true)]
+    no_animations: bool,
+
+    /// Faint solid background without per-application colors
+    #[arg(long, global = true)]
+    low_color: bool,
+
+    /// Fewer overlay redraws and batched scroll events
+    #[arg(long, global = true)]
+    coarse: bool,
+
+    /// Show scan timing, application names and the filter that rejected each
+    /// element without a hint
+    #[arg(long, global = true)]
+    debug_overlay: bool,
+
+    /// Print how long connecting, scanning, hinting and showing the overlay took, on exit
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Pick hints from a rofi, dmenu or fuzzel menu or a list in the terminal
+    /// instead of the overlay
+    #[arg(long, global = true, value_enum)]
+    picker: Option<Picker>,
+
+    /// Characters hint labels are made of, instead of `hints.chars`
+    #[arg(long, global = true, value_name = "CHARS", value_parser = parse_hint_chars)]
+    hint_chars: Option<String>,
+
+    /// Wait for Enter even when the typed hint matches exactly one element
+    #[arg(long, global = true)]
+    no_auto_select: bool,
+
+    /// Opacity of the overlay background, 0-255, keeping its color
+    #[arg(long, global = true, value_name = "ALPHA")]
+    bg_alpha: Option<u8>,
+
+    /// Pixels scrolled per hjkl press, instead of `scroll.scroll_step`
+    #[arg(long, global = true, value_name = "PIXELS")]
+    scroll_step: Option<i32>,
+
+    /// Only scan and hint elements of these applications (accessible names, e.g. `firefox`),
+    /// instead of `scan.apps`
+    #[arg(long, global = true, value_name = "APP", value_delimiter = ',')]
+    app: Vec<String>,
+
+    /// Only scan and hint the active window, instead of `scan.focused_only`
+    #[arg(long, global = true)]
+    focused_only: bool,
+}
+
+impl Cli {
+    /// The global options that override config settings for this invocation
+    fn overrides(&self) -> config::Overrides {
+        config::Overrides {
+            hint_chars: self.hint_chars.clone(),
+            no_auto_select: self.no_auto_select,
+            bg_alpha: self.bg_alpha,
+            scroll_step: self.scroll_step,
+            apps: self.app.clone(),
+            focused_only: self.focused_only,
+        }
+    }
+
+    /// Apply the global options that tune this invocation (safe mode, overrides,
+    /// debug overlay, picker) to `config` and start keeping `--timings`
+    ///
+    /// Returns the overrides, which `trigger` passes on to the daemon.
+    fn configure(&self, config: &mut Config) -> config::Overrides {
+        config.apply_safe_mode(config::SafeMode {
+            no_animations: self.safe_mode || self.no_animations,
+            low_color: self.safe_mode || self.low_color,
+            coarse: self.safe_mode || self.coarse,
+        });
+        let overrides = self.overrides();
+        config.apply_overrides(overrides.clone());
+        config.overlay.debug |= self.debug_overlay;
+        if let Some(picker) = self.picker {
+            config.behavior.picker = picker;
+        }
+        if self.timings {
+            timings::enable();
+        }
+        overrides
+    }
+}
+
+/// Subcommands that can be chained as `click,scroll`
+const MODE_NAMES: &[&str] = &["click", "right-click", "middle-click", "activate", "scroll", "text"];
+
+/// Rewrite a `click,scroll` mode list into `click --fallback-mode scroll`
+///
+/// Only the subcommand is rewritten: the first argument after the program
+/// name that is neither a global option nor the value one takes.
+fn expand_mode_list(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    use clap::CommandFactory;
+    let command = Cli::command();
+    let takes_value = |option: &str| {
+        let found = match option.strip_prefix("--") {
+            Some(long) => command.get_arguments().find(|a| a.get_long() == Some(long)),
+            None => {
+                let mut short = option.chars().skip(1);
+                match (short.next(), short.next()) {
+                    (Some(c), None) => command.get_arguments().find(|a| a.get_short() == Some(c)),
+                    _ => None,
+                }
+            }
+        };
+        found.is_some_and(|a| a.get_action().takes_values())
+    };
+
+    let mut expanded = Vec::new();
+    expanded.extend(args.next());
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') && arg != "-" {
+            let value = !arg.contains('=') && takes_value(&arg);
+            expanded.push(arg);
+            if value {
+                expanded.extend(args.next());
+            }
+            continue;
+        }
+        match arg.split_once(',') {
+            Some((first, rest)) if MODE_NAMES.contains(&first) => {
+                expanded.push(first.to_string());
+                expanded.push("--fallback-mode".to_string());
+                expanded.push(rest.to_string());
+            }
+            _ => expanded.push(arg),
+        }
+        break;
+    }
+    expanded.extend(args);
+    expanded
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Click mode - show hints and click selected element (default)
     Click {
-        /// Filter by element role (button, link, input, etc.)
-        #[arg(short, long)]
-        filter: Option<String>,
+        #[command(flatten)]
+        args: HintArgs,
     },
     /// Right-click mode
     RightClick {
-        #[arg(short, long)]
-        filter: Option<String>,
+        #[command(flatten)]
+        args: HintArgs,
     },
     /// Middle-click mode
     MiddleClick {
-        #[arg(short, long)]
-        filter: Option<String>,
+        #[command(flatten)]
+        args: HintArgs,
+    },
+    /// Activate mode - invoke the selected element's default accessible action without moving the pointer,
+    /// which works when it is covered or partly off-screen
+    Activate {
+        #[command(flatten)]
+        args: HintArgs,
+    },
+    /// Pick an element and print it as JSON without clicking (same as `click --print`)
+    Pick {
+        #[command(flatten)]
+        args: HintArgs,
     },
     /// Scroll mode - select area then use hjkl to scroll
-    Scroll,
+    Scroll {
+        /// Scroll the focused window's main area without picking one
+        #[arg(long)]
+        focused: bool,
+    },
     /// Text mode - jump to and focus text input fields
-    Text,
+    Text {
+        /// Keep scanning up to this many seconds until a text field appears
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        wait: Option<Duration>,
+        /// Text to insert into the field once it is focused
+        #[arg(long, value_name = "TEXT")]
+        then_type: Option<String>,
+        /// Press Enter after `--then-type`, e.g. to submit a search
+        #[arg(long, requires = "then_type")]
+        enter: bool,
+        /// Then move the field's caret with vim motions (h/l, w/b, 0/$, gg/G); v selects and y copies
+        #[arg(long)]
+        caret: bool,
+    },
+    /// Save the last selection under a letter, for its application (see `goto`)
+    Mark {
+        /// Letter or digit naming the mark
+        letter: char,
+    },
+    /// Act on the element marked with a letter in whichever application shows it
+    Goto {
+        /// Letter or digit naming the mark
+        letter: char,
+        /// What to do with the element
+        #[arg(long, value_enum, default_value_t = ActionMode::Click)]
+        action: ActionMode,
+    },
+    /// Repeat the last click, right-click, middle-click or hover on the same element
+    RepeatLast,
+    /// Act on an element named in `[bookmarks]`; lists them without a name
+    Bookmark {
+        /// Bookmark name from the config
+        name: Option<String>,
+    },
+    /// Window mode - pick a window, then move it with hjkl, resize it with HJKL, maximize it with m,
+    /// close it with x or send it to workspace 1-9
+    Window,
+    /// Tab mode - hint the tabs of the focused window with larger labels, then switch to one
+    Tabs,
+    /// Reading mode - hint headings, labels and static text, then move the caret to the one picked
+    Read,
+    /// Hint links, then open the URI of the one picked with `links.open_command` instead of clicking it
+    OpenLink,
+    /// Selection mode - hint elements showing text, then move the caret of the one picked with vim motions,
+    /// start a selection with v and copy it with y
+    Select {
+        /// Copy the selection when leaving with Enter too
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Yank mode - hint labels, text and controls, then copy the text of the one picked to the clipboard
+    Yank,
+    /// Hint every visible occurrence of some text, then scroll to and click the chosen one
+    Search {
+        /// Text to find; case is ignored unless it has capitals. Typed in a prompt when left out, like Vimium's `/`
+        query: Option<String>,
+        /// Only scroll the occurrence into view, without clicking it
+        #[arg(long)]
+        scroll_only: bool,
+    },
+    /// Click at screen coordinates directly (no hints)
+    Press {
+        /// Position to click, as x,y
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+        at: (i32, i32),
+        /// Mouse button: left, right or middle
+        #[arg(short, long, default_value = "left")]
+        button: click::ClickButton,
+    },
+    /// Click at screen coordinates, trying each pointer tool in turn (no hints)
+    ///
+    /// Coordinates are global, the space spanning all outputs, unless `--local` is given.
+    ClickAt {
+        /// X coordinate
+        #[arg(allow_negative_numbers = true)]
+        x: i32,
+        /// Y coordinate
+        #[arg(allow_negative_numbers = true)]
+        y: i32,
+        /// Mouse button: left, right or middle
+        #[arg(short, long, default_value = "left")]
+        button: click::ClickButton,
+        /// Coordinates are relative to the focused output, as AT-SPI reports them, and are offset by its
+        /// position (from Hyprland's or sway's IPC)
+        #[arg(long)]
+        local: bool,
+    },
+    /// Send keys to the focused window: a combination (`ctrl+shift+t`) or text with
+    /// combinations in angle brackets (`Hello<Enter>`)
+    SendKeys {
+        keys: keys::KeySequence,
+    },
+    /// Move mode - pick an element, move the pointer over it, then nudge it with hjkl
+    /// and press Enter to click (or move to coordinates directly with --at)
+    Move {
+        /// Position to move to, as x,y, without hints
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+        at: Option<(i32, i32)>,
+        #[command(flatten)]
+        args: HintArgs,
+    },
+    /// Native messaging host for the browser extension (started by the browser)
+    NativeHost {
+        /// Arguments passed by the browser (manifest path, extension origin)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+        browser_args: Vec<String>,
+    },
+    /// List clickable elements without showing hints
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+        /// Filter by element role, as for `click --filter`
+        #[arg(short, long)]
+        filter: Option<filter::RoleFilter>,
+    },
+    /// Act on an element by the ID printed by `list`
+    Act {
+        /// Element ID from `list`
+        #[arg(long)]
+        id: String,
+        /// What to do with the element
+        #[arg(long, value_enum, default_value_t = ActionMode::Click)]
+        action: ActionMode,
+    },
+    /// Run an automation script of steps (TOML or JSON)
+    Run {
+        /// Script file, or `-` for stdin
+        script: std::path::PathBuf,
+        /// How long steps wait for their element unless they set `timeout_ms`
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        wait: Option<Duration>,
+    },
+    /// Run commands read from stdin, one per line (e.g. `click --filter button`)
+    Batch,
+    /// Keep connections open and run modes requested with `trigger`
+    Daemon,
+    /// Ask the running daemon to run a mode
+    Trigger {
+        /// Mode to run
+        #[arg(value_enum, default_value_t = ActionMode::Click)]
+        mode: ActionMode,
+        /// Filter by element role, as for `click --filter`
+        #[arg(short, long)]
+        filter: Option<filter::RoleFilter>,
+    },
+    /// Write a systemd user service for the daemon and enable it
+    InstallService {
+        /// Also write a socket unit so the daemon starts on the first `trigger`
+        #[arg(long)]
+        socket: bool,
+        /// Only write the units
+        #[arg(long)]
+        no_enable: bool,
+    },
+    /// Print keybindings for click, scroll and text modes for a compositor's config
+    InstallKeybinds {
+        /// Compositor to generate bindings for (detected when omitted)
+        #[arg(value_enum)]
+        compositor: Option<keybinds::Compositor>,
+        /// Bind `trigger` and start the daemon with the session
+        #[arg(long)]
+        daemon: bool,
+        /// Append the bindings to the compositor's config file
+        #[arg(long)]
+        write: bool,
+    },
+    /// Print bind lines for every mode, using the chords from `[hotkey]`
+    Keybindings {
+        /// Compositor to generate bindings for (detected when omitted)
+        #[arg(long, value_enum)]
+        compositor: Option<keybinds::Compositor>,
+        /// Bind `trigger` for a running daemon, which also offers drag and hover
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Report which Wayland protocols the compositor offers and what needs them
+    Protocols,
+    /// Check the accessibility bus, overlay support and pointer tools, and suggest fixes
+    Doctor,
+    /// Turn on accessibility for the session and GTK, and list what other apps need
+    EnableA11y,
+    /// Print the overlay's mode, input and match count for Waybar or eww
+    Status {
+        /// Keep running and print a line whenever the status changes
+        #[arg(long)]
+        watch: bool,
+        /// Print the raw status instead of a Waybar module line
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve the org.vimium.Linux D-Bus interface
+    DbusService,
+    /// Serve a local HTTP/JSON automation API
+    #[cfg(feature = "http")]
+    ServeHttp {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: std::net::SocketAddr,
+    },
     /// Generate default config file
     InitConfig,
     /// Show current config
     ShowConfig,
 }
 
+impl Commands {
+    /// Whether the command keeps running modes until it is stopped
+    fn serves(&self) -> bool {
+        match self {
+            Commands::Daemon | Commands::DbusService => true,
+            #[cfg(feature = "http")]
+            Commands::ServeHttp { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the command runs until it is stopped instead of finishing
+    fn keeps_running(&self) -> bool {
+        self.serves()
+            || matches!(
+                self,
+                Commands::Batch | Commands::NativeHost { .. } | Commands::Status { watch: true, .. }
+            )
+    }
+}
+
+/// Output format for `list`
+#[derive(Clone, Copy, ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+}
+
+/// An element as printed by `list --format json`
+#[derive(serde::Serialize)]
+struct ListedElement<'a> {
+    id: String,
+    role: &'a str,
+    name: &'a str,
+    /// Name of the owning application, if it has one
+    app: Option<&'a str>,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+/// Parse hint characters: two or more, none repeated, so labels can be told apart
+fn parse_hint_chars(s: &str) -> Result<String, String> {
+    let distinct: std::collections::HashSet<char> = s.chars().collect();
+    if distinct.len() != s.chars().count() || distinct.len() < 2 {
+        return Err(format!("expected two or more characters without repeats but got '{}'", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Print `doctor` checks with their fixes, failing if any failed
+fn print_checks(checks: &[doctor::Check]) -> Result<()> {
+    for check in checks {
+        let level = match check.level {
+            doctor::Level::Ok => "ok",
+            doctor::Level::Warning => "warning",
+            doctor::Level::Failed => "failed",
+        };
+        println!("{:<8} {:<24} {}", level, check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            println!("{:<8} {:<24} fix: {}", "", "", fix);
+        }
+    }
+    let failed = checks.iter().filter(|c| c.level == doctor::Level::Failed).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, checks.len());
+    }
+    Ok(())
+}
+
+/// Close the overlay on SIGINT/SIGTERM before exiting
+///
+/// Overlays run their Wayland loop on a blocking thread, so exiting straight
+/// away could leave the compositor with a layer surface still holding the
+/// keyboard. The overlay's cancel source wakes its loop, which destroys the
+/// surface and releases the grab; the process exits once that is done.
+fn spawn_signal_handler() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::spawn(async move {
+        let code = tokio::select! {
+            _ = interrupt.recv() => 130,
+            _ = terminate.recv() => 143,
+        };
+        if overlay::cancel() || overlay::is_shown() {
+            info!("Closing the overlay before exiting");
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(1);
+            while overlay::is_shown() && tokio::time::Instant::now() < deadline {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        }
+        ydotoold::shutdown();
+        std::process::exit(code);
+    });
+    Ok(())
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+async fn main() -> Result<std::process::ExitCode> {
+    let cli = Cli::parse_from(expand_mode_list(std::env::args()));
 
     // Initialize logging
     let log_level = match cli.verbose {
@@ -72,150 +539,577 @@ async fn main() -> Result<()> {
         .init();
 
     // Load config
-    let config = if let Some(path) = cli.config {
+    let mut config = if let Some(path) = &cli.config {
         Config::load_from_path(path.into())?
     } else {
         Config::load()
     };
+    config.colors.check_contrast();
+    let overrides = cli.configure(&mut config);
+    // Serving commands check the power state again for each mode they run
+    if config.power.enabled && !cli.command.as_ref().is_some_and(Commands::serves) {
+        power::throttle(&mut config, power::detect().await);
+    }
+    atspi::set_name_concurrency(config.scan.name_concurrency);
+    ydotoold::set_managed(config.click.ydotool.manage_daemon);
+    #[cfg(feature = "lua")]
+    if let Err(e) = plugins::load(&plugins::dir()) {
+        warn!("Plugins not loaded: {:#}", e);
+    }
 
     info!("vimium-linux starting...");
+    spawn_signal_handler()?;
 
-    match cli.command {
+    // Export metrics even when the command fails, failures are what they count
+    let result = run_command(cli.command, &config, &cli.fallback_mode, &overrides).await;
+
+    if let Some(path) = &config.metrics.textfile {
+        if let Err(e) = metrics::write_textfile(path) {
+            warn!("Failed to export metrics: {}", e);
+        }
+    }
+
+    if cli.timings {
+        eprint!("{}", timings::report());
+    }
+
+    ydotoold::shutdown();
+    info!("vimium-linux done");
+    match result {
+        Ok(mut outcome) => {
+            match &mut outcome {
+                Some(outcome) if cli.json_result => {
+                    outcome.resolve_name().await;
+                    println!("{}", outcome.json());
+                }
+                // Without --json-result, `pick` prints just the element
+                Some(Outcome::Selected { element, mode, .. }) if mode == PICK_MODE => {
+                    println!("{}", outcome::element_json(element));
+                }
+                _ => {}
+            }
+            Ok(outcome.map_or(std::process::ExitCode::SUCCESS, |o| o.exit_code().into()))
+        }
+        Err(e) => {
+            if cli.json_result {
+                println!("{}", outcome::failure_json(&e));
+            }
+            eprintln!("Error: {:?}", e);
+            Ok(outcome::EXIT_FAILURE.into())
+        }
+    }
+}
+
+/// Run the selected subcommand, returning how a mode ended
+///
+/// `overrides` are already applied to `config`; `trigger` passes them on to the daemon.
+async fn run_command(
+    command: Option<Commands>,
+    config: &Config,
+    fallbacks: &[ActionMode],
+    overrides: &config::Overrides,
+) -> Result<Option<Outcome>> {
+    let outcome = match command {
         Some(Commands::InitConfig) => {
             Config::default().save()?;
             println!("Config file created at: {:?}", Config::config_path());
-            return Ok(());
+            None
         }
         Some(Commands::ShowConfig) => {
-            println!("{}", toml::to_string_pretty(&config)?);
-            return Ok(());
+            println!("{}", toml::to_string_pretty(config)?);
+            None
+        }
+        Some(Commands::Protocols) => {
+            let advertised = protocols::advertised()?;
+            for support in protocols::report(advertised) {
+                match support.provided_by {
+                    Some(global) => println!("ok       {:<32} {}", support.feature, global),
+                    None => println!("missing  {:<32} needs {}", support.feature, support.needs.join(" or ")),
+                }
+            }
+            None
+        }
+        Some(Commands::Doctor) => {
+            print_checks(&doctor::run().await)?;
+            None
+        }
+        Some(Commands::EnableA11y) => {
+            let result = print_checks(&doctor::enable_accessibility().await);
+            println!("\nRestart running applications to pick this up. Some need a setting of their own:");
+            for (app, step) in doctor::MANUAL_STEPS {
+                println!("  {}: {}", app, step);
+            }
+            result?;
+            None
+        }
+        Some(Commands::Status { watch, json }) => {
+            print_status(watch, json).await?;
+            None
+        }
+        Some(Commands::Keybindings { compositor, daemon }) => {
+            let compositor = compositor_or_detect(compositor)?;
+            print!("{}", keybinds::mode_bindings(compositor, &bindable_modes(daemon), &config.hotkey.bindings, daemon));
+            None
+        }
+        Some(Commands::InstallKeybinds {
+            compositor,
+            daemon,
+            write,
+        }) => {
+            let compositor = compositor_or_detect(compositor)?;
+            if write {
+                let path = keybinds::write(compositor, daemon)?;
+                println!("Keybindings added to {:?}; reload your compositor config", path);
+            } else {
+                print!("{}", keybinds::snippet(compositor, daemon));
+            }
+            None
+        }
+        Some(Commands::InstallService { socket, no_enable }) => {
+            let dir = service::install(socket, !no_enable)?;
+            println!("Units written to {:?}", dir);
+            None
+        }
+        Some(Commands::Click { args }) => {
+            Some(modes::run_modes(config, ActionMode::Click, &args, fallbacks).await?)
+        }
+        Some(Commands::RightClick { args }) => {
+            Some(modes::run_modes(config, ActionMode::RightClick, &args, fallbacks).await?)
+        }
+        Some(Commands::MiddleClick { args }) => {
+            Some(modes::run_modes(config, ActionMode::MiddleClick, &args, fallbacks).await?)
+        }
+        Some(Commands::Activate { args }) => {
+            Some(modes::run_modes(config, ActionMode::Activate, &args, fallbacks).await?)
+        }
+        Some(Commands::Pick { args }) => {
+            let args = HintArgs { print: true, ..args };
+            Some(modes::run_modes(config, ActionMode::Click, &args, fallbacks).await?)
+        }
+        Some(Commands::Press { at: (x, y), button }) => {
+            click::press_at(x, y, button)?;
+            None
+        }
+        Some(Commands::ClickAt { x, y, button, local }) => {
+            // Compositor IPC runs commands, kept off the async workers
+            let (dx, dy) = if local {
+                tokio::task::spawn_blocking(focus::focused_output_origin).await?
+            } else {
+                (0, 0)
+            };
+            click::press_at(x + dx, y + dy, button)?;
+            None
+        }
+        Some(Commands::SendKeys { keys }) => {
+            keys::send_sequence(&keys)?;
+            None
+        }
+        Some(Commands::Move { at: Some((x, y)), .. }) => {
+            click::move_cursor_to(x, y)?;
+            None
+        }
+        Some(Commands::Move { at: None, args }) => Some(modes::run_move_mode(config, &args).await?),
+        Some(Commands::NativeHost { .. }) => {
+            browser::run_native_host().await?;
+            None
+        }
+        Some(Commands::List { format, filter }) => {
+            run_list(config, format, filter.as_ref()).await?;
+            None
+        }
+        Some(Commands::Act { id, action }) => {
+            modes::run_act(config, &id, action).await?;
+            None
+        }
+        Some(Commands::Run { script: path, wait }) => {
+            let mut steps = script::Script::load(&path)?;
+            if let Some(wait) = wait {
+                steps.set_default_timeout(wait);
+            }
+            info!("Running {} steps from {}", steps.steps.len(), path.display());
+            script::run(&steps, config).await?;
+            None
+        }
+        Some(Commands::Batch) => {
+            run_batch(config).await?;
+            None
+        }
+        Some(Commands::Daemon) => {
+            let (hotkey_tx, hotkey_rx) = tokio::sync::mpsc::unbounded_channel();
+            let mut evdev = !config.hotkey.bindings.is_empty();
+            if config.hotkey.portal {
+                match portal::register(&config.hotkey.bindings, hotkey_tx.clone()).await {
+                    Ok(()) => evdev = false,
+                    Err(e) => warn!("GlobalShortcuts portal unavailable: {:#}", e),
+                }
+            }
+            if config.auto_text.enabled {
+                tokio::spawn(daemon::watch_prompts(config.clone()));
+            }
+            if evdev {
+                hotkey::listen(&config.hotkey.bindings, hotkey_tx)?;
+            }
+            export_metrics_while_running(config);
+            daemon::run(hotkey_rx, |request| async move {
+                let args = HintArgs {
+                    filter: request.filter,
+                    ..HintArgs::default()
+                };
+                let mut config = config.clone();
+                config.apply_overrides(request.overrides);
+                throttle_for_power(&mut config).await;
+                modes::run_modes(&config, request.mode, &args, fallbacks).await.map(drop)
+            })
+            .await?;
+            None
+        }
+        Some(Commands::DbusService) => {
+            export_metrics_while_running(config);
+            dbus::serve(|request| async move {
+                let args = HintArgs {
+                    filter: request.filter,
+                    ..HintArgs::default()
+                };
+                let mut config = config.clone();
+                throttle_for_power(&mut config).await;
+                modes::run_modes(&config, request.mode, &args, fallbacks).await.map(drop)
+            })
+            .await?;
+            None
         }
-        Some(Commands::Click { filter }) => {
-            run_click_mode(&config, ActionMode::Click, filter.as_deref()).await?;
+        #[cfg(feature = "http")]
+        Some(Commands::ServeHttp { listen }) => {
+            export_metrics_while_running(config);
+            http::serve(listen, config.scroll.scroll_step, |request| async move {
+                let mut config = config.clone();
+                throttle_for_power(&mut config).await;
+                let config = &config;
+                match request {
+                    http::Request::Elements => {
+                        let elements = list_elements(config, None).await?;
+                        let apps = element_apps(&elements).await;
+                        Ok(serde_json::to_value(listed(&elements, &apps))?)
+                    }
+                    http::Request::Hints { mode, filter } => {
+                        let args = HintArgs {
+                            filter,
+                            ..HintArgs::default()
+                        };
+                        let mut outcome = modes::run_modes(config, mode, &args, fallbacks).await?;
+                        outcome.resolve_name().await;
+                        Ok(outcome.json())
+                    }
+                    http::Request::Act { id, action } => {
+                        modes::run_act(config, &id, action).await?;
+                        Ok(serde_json::json!({}))
+                    }
+                }
+            })
+            .await?;
+            None
         }
-        Some(Commands::RightClick { filter }) => {
-            run_click_mode(&config, ActionMode::RightClick, filter.as_deref()).await?;
+        Some(Commands::Trigger { mode, filter }) => {
+            let request = daemon::TriggerRequest {
+                mode,
+                filter,
+                overrides: overrides.clone(),
+            };
+            daemon::trigger(&request).await?;
+            None
         }
-        Some(Commands::MiddleClick { filter }) => {
-            run_click_mode(&config, ActionMode::MiddleClick, filter.as_deref()).await?;
+        Some(Commands::Scroll { focused: true }) => Some(modes::run_focused_scroll(config).await?),
+        Some(Commands::Scroll { focused: false }) => {
+            Some(modes::run_modes(config, ActionMode::Scroll, &HintArgs::default(), fallbacks).await?)
         }
-        Some(Commands::Scroll) => {
-            run_scroll_mode(&config).await?;
+        Some(Commands::Text {
+            wait,
+            then_type,
+            enter,
+            caret,
+        }) => {
+            let args = HintArgs {
+                wait,
+                ..HintArgs::default()
+            };
+            let outcome = modes::run_modes(config, ActionMode::Text, &args, fallbacks).await?;
+            if let Outcome::Selected { element, mode, .. } = &outcome {
+                if *mode == ActionMode::Text.name() {
+                    if let Some(text) = then_type {
+                        modes::type_into(element, &text, enter).await?;
+                    }
+                    if caret {
+                        modes::run_caret_mode(config, element, false).await?;
+                    }
+                }
+            }
+            Some(outcome)
+        }
+        Some(Commands::Mark { letter }) => {
+            modes::set_mark(letter)?;
+            None
+        }
+        Some(Commands::Goto { letter, action }) => Some(modes::run_goto(config, letter, action).await?),
+        Some(Commands::RepeatLast) => Some(modes::run_repeat_last(config).await?),
+        Some(Commands::Bookmark { name: Some(name) }) => Some(modes::run_bookmark(config, &name).await?),
+        Some(Commands::Bookmark { name: None }) => {
+            modes::list_bookmarks(config);
+            None
         }
-        Some(Commands::Text) => {
-            run_text_mode(&config).await?;
+        Some(Commands::Window) => Some(modes::run_window_mode(config).await?),
+        Some(Commands::Tabs) => Some(modes::run_tabs_mode(config).await?),
+        Some(Commands::Read) => Some(modes::run_read_mode(config).await?),
+        Some(Commands::OpenLink) => Some(modes::run_open_link_mode(config).await?),
+        Some(Commands::Select { copy }) => Some(modes::run_select_mode(config, copy).await?),
+        Some(Commands::Yank) => Some(modes::run_yank_mode(config).await?),
+        Some(Commands::Search { query, scroll_only }) => {
+            let query = match query {
+                Some(query) => Some(query),
+                None => {
+                    let config = config.clone();
+                    tokio::task::spawn_blocking(move || prompt::run_prompt("/", &config)).await??
+                }
+            };
+            match query {
+                Some(query) => Some(modes::run_search(config, &query, scroll_only).await?),
+                None => Some(Outcome::Cancelled),
+            }
         }
         None => {
             // Default to click mode
-            run_click_mode(&config, config.behavior.default_mode, None).await?;
+            let mode = config.behavior.default_mode;
+            Some(modes::run_modes(config, mode, &HintArgs::default(), fallbacks).await?)
         }
-    }
+    };
 
-    info!("vimium-linux done");
-    Ok(())
+    Ok(outcome)
 }
 
-/// Run click mode with hints
-async fn run_click_mode(config: &Config, action: ActionMode, filter: Option<&str>) -> Result<()> {
-    // 1. Query AT-SPI for clickable elements
-    let mut elements = atspi::get_clickable_elements().await?;
-    info!("Found {} clickable elements", elements.len());
-
-    // Apply filter if specified
-    if let Some(role_filter) = filter {
-        let role_filter = role_filter.to_lowercase();
-        elements.retain(|e| e.role.to_lowercase().contains(&role_filter));
-        info!("After filtering: {} elements", elements.len());
-    }
-
-    if elements.is_empty() {
-        warn!("No clickable elements found");
-        println!("No clickable elements found. Make sure:");
-        println!("  - The target application supports AT-SPI accessibility");
-        println!("  - For Firefox: set accessibility.force_disabled = 0 in about:config");
-        println!("  - For Chrome/Electron: launch with --force-renderer-accessibility");
-        return Ok(());
+/// Keep `[metrics] textfile` current while a resident command runs, not only on exit
+fn export_metrics_while_running(config: &Config) {
+    if let Some(path) = &config.metrics.textfile {
+        metrics::spawn_textfile_writer(path.clone());
     }
+}
 
-    // 2. Generate hints for elements
-    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
+/// The given compositor, or the one running this session
+fn compositor_or_detect(compositor: Option<keybinds::Compositor>) -> Result<keybinds::Compositor> {
+    compositor
+        .or_else(keybinds::Compositor::detect)
+        .ok_or_else(|| anyhow::anyhow!("Could not detect the compositor; name it (hyprland, sway or river)"))
+}
 
-    // 3. Show overlay and wait for user input
-    let result = overlay::show_and_select(hinted_elements, config.clone()).await?;
+/// Modes a key can start: those with a subcommand, or all of them through `trigger`
+fn bindable_modes(daemon: bool) -> Vec<String> {
+    use clap::CommandFactory;
+    let cli = Cli::command();
+    ActionMode::value_variants()
+        .iter()
+        .map(|mode| mode.name())
+        .filter(|name| daemon || cli.find_subcommand(name).is_some())
+        .collect()
+}
 
-    // 4. Perform action on selected element
-    if let Some((element, modifier_action)) = result {
-        let (x, y) = element.click_position();
+/// Run commands from stdin in this process, printing a JSON result line for each
+///
+/// Lines are split like a shell would, so `click --filter 'link,button'`
+/// works. Blank lines and `#` comments are skipped; `exit` stops early.
+/// Every line is answered as with `--json-result`.
+async fn run_batch(config: &Config) -> Result<()> {
+    use tokio::io::AsyncBufReadExt;
 
-        // Modifier overrides the mode
-        let final_action = modifier_action.unwrap_or(action);
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "exit" || line == "quit" {
+            break;
+        }
 
-        match final_action {
-            ActionMode::Click => {
-                info!("Clicking element at ({}, {})", x, y);
-                click::click_at(x, y)?;
-            }
-            ActionMode::RightClick => {
-                info!("Right-clicking element at ({}, {})", x, y);
-                click::right_click_at(x, y)?;
-            }
-            ActionMode::MiddleClick => {
-                info!("Middle-clicking element at ({}, {})", x, y);
-                click::middle_click_at(x, y)?;
+        let reply = match run_batch_line(line, config).await {
+            Ok(Some(mut outcome)) => {
+                outcome.resolve_name().await;
+                outcome.json()
             }
-            _ => {
-                click::click_at(x, y)?;
+            Ok(None) => serde_json::json!({ "result": "ok" }),
+            Err(e) => {
+                warn!("{}: {:#}", line, e);
+                outcome::failure_json(&e)
             }
-        }
+        };
+        println!("{}", reply);
     }
-
     Ok(())
 }
 
-/// Run scroll mode - select a scrollable area then scroll with hjkl
-async fn run_scroll_mode(config: &Config) -> Result<()> {
-    // Get scrollable elements
-    let elements = atspi::get_scrollable_elements().await?;
-    info!("Found {} scrollable elements", elements.len());
+/// Parse and run one `batch` line
+async fn run_batch_line(line: &str, config: &Config) -> Result<Option<Outcome>> {
+    let words = shlex::split(line).ok_or_else(|| anyhow::anyhow!("Unbalanced quotes"))?;
+    let args = expand_mode_list(std::iter::once("vimium-linux".to_string()).chain(words));
+    let cli = Cli::try_parse_from(args).map_err(|e| {
+        let message = e.render().to_string();
+        let first = message.lines().next().unwrap_or_default();
+        anyhow::anyhow!("{}", first.trim_start_matches("error: "))
+    })?;
+    if cli.command.as_ref().is_some_and(Commands::keeps_running) {
+        anyhow::bail!("Not available in batch mode, it doesn't finish");
+    }
+    info!("Batch: {}", line);
+    // Global options on the line apply to it alone
+    let mut config = config.clone();
+    let overrides = cli.configure(&mut config);
+    let timed = cli.timings.then(timings::mark);
+    let result = Box::pin(run_command(cli.command, &config, &cli.fallback_mode, &overrides)).await;
+    if let Some(mark) = timed {
+        eprint!("{}", timings::report_since(mark));
+    }
+    result
+}
 
-    if elements.is_empty() {
-        warn!("No scrollable elements found");
-        println!("No scrollable elements found.");
-        return Ok(());
+/// Throttle `config` for the power state right now, per `[power]`
+///
+/// Serving commands run for days, across plugging and unplugging the charger,
+/// so they call this for every mode they run instead of once at startup.
+async fn throttle_for_power(config: &mut Config) {
+    if config.power.enabled {
+        power::throttle(config, power::detect().await);
     }
+    atspi::set_name_concurrency(config.scan.name_concurrency);
+}
+
+/// Print the published status once, or on every change with `watch`
+async fn print_status(watch: bool, json: bool) -> Result<()> {
+    let mut last = None;
+    loop {
+        let current = status::read();
+        if last.as_ref() != Some(&current) {
+            let line = if json {
+                serde_json::to_string(&current)?
+            } else {
+                current.waybar().to_string()
+            };
+            println!("{}", line);
+            if !watch {
+                return Ok(());
+            }
+            last = Some(current);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
 
-    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
-    let result = overlay::show_and_select(hinted_elements, config.clone()).await?;
+/// Print the clickable elements and their IDs
+async fn run_list(config: &Config, format: ListFormat, filter: Option<&filter::RoleFilter>) -> Result<()> {
+    let elements = list_elements(config, filter).await?;
+    let apps = element_apps(&elements).await;
 
-    if let Some((element, _)) = result {
-        let (x, y) = element.click_position();
-        // Enter scroll mode at this position
-        scroll::run_scroll_mode(x, y, config).await?;
+    match format {
+        ListFormat::Text => {
+            for e in &elements {
+                println!(
+                    "{}  {:<16} {:>5},{:<5} {:>4}x{:<4} {:<16} {}",
+                    e.id(),
+                    e.role,
+                    e.x,
+                    e.y,
+                    e.width,
+                    e.height,
+                    apps.get(&e.bus_name).map_or("-", String::as_str),
+                    e.name
+                );
+            }
+        }
+        ListFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&listed(&elements, &apps))?);
+        }
     }
 
+    // Kept off stdout for JSON, so the output stays one array
+    let silent = modes::silent_app_notes().await;
+    match format {
+        ListFormat::Text if !silent.is_empty() => {
+            println!();
+            silent.iter().for_each(|note| println!("{}", note));
+        }
+        ListFormat::Text => {}
+        ListFormat::Json => silent.iter().for_each(|note| eprintln!("{}", note)),
+    }
     Ok(())
 }
 
-/// Run text input mode - focus on text fields
-async fn run_text_mode(config: &Config) -> Result<()> {
-    // Get only text input elements
-    let elements = atspi::get_text_elements().await?;
-    info!("Found {} text input elements", elements.len());
-
-    if elements.is_empty() {
-        warn!("No text input elements found");
-        println!("No text input fields found.");
-        return Ok(());
+/// Clickable elements with their names, for listing
+async fn list_elements(
+    config: &Config,
+    filter: Option<&filter::RoleFilter>,
+) -> Result<Vec<atspi::ClickableElement>> {
+    let mut elements = provider::collect(ElementKind::Clickable, config).await?;
+    if let Some(filter) = filter {
+        elements.retain(|e| filter.matches(&e.role));
     }
+    atspi::resolve_names(&mut elements).await?;
+    modes::apply_exclusions(&mut elements, config).await?;
+    Ok(elements)
+}
+
+/// Names of the applications owning `elements`, by bus name
+///
+/// Listing goes on without them when they can't be read.
+async fn element_apps(elements: &[atspi::ClickableElement]) -> std::collections::HashMap<String, String> {
+    let bus_names = hints::app_order(elements);
+    atspi::app_names(&bus_names).await.unwrap_or_else(|e| {
+        warn!("Failed to read application names: {:#}", e);
+        Default::default()
+    })
+}
+
+/// Elements in the JSON shape printed by `list --format json`
+fn listed<'a>(
+    elements: &'a [atspi::ClickableElement],
+    apps: &'a std::collections::HashMap<String, String>,
+) -> Vec<ListedElement<'a>> {
+    elements
+        .iter()
+        .map(|e| ListedElement {
+            id: e.id(),
+            role: &e.role,
+            name: &e.name,
+            app: apps.get(&e.bus_name).map(String::as_str),
+            x: e.x,
+            y: e.y,
+            width: e.width,
+            height: e.height,
+        })
+        .collect()
+}
 
-    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
-    let result = overlay::show_and_select(hinted_elements, config.clone()).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if let Some((element, _)) = result {
-        let (x, y) = element.click_position();
-        // Click to focus the text field
-        click::click_at(x, y)?;
+    #[test]
+    fn test_negative_points() {
+        let cli = Cli::try_parse_from(["vimium-linux", "press", "--at", "-10,20"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Press { at: (-10, 20), .. })));
+
+        let cli = Cli::try_parse_from(["vimium-linux", "move", "--at", "5,-1080"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Move { at: Some((5, -1080)), .. })));
     }
 
-    Ok(())
+    #[test]
+    fn test_click_at() {
+        let cli = Cli::try_parse_from(["vimium-linux", "click-at", "-1920", "-40", "--button", "right"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::ClickAt { x: -1920, y: -40, button: click::ClickButton::Right, local: false })
+        ));
+
+        let cli = Cli::try_parse_from(["vimium-linux", "click-at", "--local", "800", "600"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::ClickAt { x: 800, y: 600, button: click::ClickButton::Left, local: true })
+        ));
+
+        assert!(Cli::try_parse_from(["vimium-linux", "click-at", "800"]).is_err());
+    }
 }
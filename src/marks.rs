@@ -0,0 +1,118 @@
+use crate::atspi::ClickableElement;
+use crate::history::Entry;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// An element saved under a letter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mark {
+    pub name: String,
+    pub role: String,
+    /// Center of the element when it was marked, in global coordinates
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Mark {
+    pub fn new(element: &ClickableElement) -> Self {
+        let (x, y) = element.center();
+        Mark {
+            name: element.name.clone(),
+            role: element.role.clone(),
+            x,
+            y,
+        }
+    }
+
+    /// The mark as a history entry, for finding its element again with `history::locate`
+    pub fn entry(&self, app: &str) -> Entry {
+        Entry {
+            app: app.to_string(),
+            name: self.name.clone(),
+            role: self.role.clone(),
+            x: self.x,
+            y: self.y,
+            mode: "click".to_string(),
+        }
+    }
+}
+
+/// Marks by application name, then letter
+pub type Marks = BTreeMap<String, BTreeMap<char, Mark>>;
+
+/// File the marks are kept in
+pub fn path() -> PathBuf {
+    crate::history::path().with_file_name("marks.json")
+}
+
+/// Saved marks; empty when none were saved
+pub fn load() -> Marks {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Whether `letter` can name a mark
+pub fn valid_letter(letter: char) -> bool {
+    letter.is_ascii_alphanumeric()
+}
+
+/// Save `mark` under `letter` for `app`, replacing the app's old mark of that letter
+pub fn set(app: &str, letter: char, mark: Mark) -> Result<()> {
+    if !valid_letter(letter) {
+        anyhow::bail!("Marks are named by a letter or digit, not {:?}", letter);
+    }
+    let mut marks = load();
+    marks.entry(app.to_string()).or_default().insert(letter, mark);
+
+    let path = path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&marks)?).with_context(|| format!("Failed to write {:?}", tmp))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Every application's mark named `letter`, as history entries
+pub fn named(marks: &Marks, letter: char) -> Vec<Entry> {
+    marks
+        .iter()
+        .filter_map(|(app, letters)| letters.get(&letter).map(|mark| mark.entry(app)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named() {
+        let element = ClickableElement {
+            name: "Send".to_string(),
+            role: "PushButton".to_string(),
+            x: 100,
+            y: 200,
+            width: 40,
+            height: 20,
+            ..Default::default()
+        };
+        let mark = Mark::new(&element);
+        assert_eq!((mark.x, mark.y), (120, 210));
+
+        let mut marks = Marks::new();
+        marks.entry("thunderbird".to_string()).or_default().insert('s', mark.clone());
+        marks.entry("gedit".to_string()).or_default().insert('o', mark);
+        let found = named(&marks, 's');
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].app, "thunderbird");
+        assert!(found[0].covers(&element));
+        assert!(named(&marks, 'x').is_empty());
+
+        assert!(valid_letter('a') && valid_letter('7'));
+        assert!(!valid_letter('\''));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::{ActionMode, Picker};
+use crate::hints::{find_exact_match, HintedElement};
+use crate::tty;
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+/// Program and arguments showing a menu read from stdin
+fn command(picker: Picker) -> Option<(&'static str, &'static [&'static str])> {
+    match picker {
+        Picker::Rofi => Some(("rofi", &["-dmenu", "-i", "-p", "hint"])),
+        Picker::Dmenu => Some(("dmenu", &["-i", "-l", "20", "-p", "hint"])),
+        Picker::Fuzzel => Some(("fuzzel", &["--dmenu", "--prompt", "hint> "])),
+        Picker::Overlay | Picker::Tui => None,
+    }
+}
+
+/// One "hint  name  role" line per element, hints padded to the same width
+fn menu_lines(elements: &[HintedElement]) -> String {
+    let hint_width = elements.iter().map(|h| h.hint.len()).max().unwrap_or(0);
+    elements
+        .iter()
+        .map(|h| format!("{:<w$}  {}  {}\n", h.hint, h.element.name, h.element.role, w = hint_width))
+        .collect()
+}
+
+/// The element whose hint starts the chosen line
+fn parse_choice<'a>(line: &str, elements: &'a [HintedElement]) -> Option<&'a HintedElement> {
+    let hint = line.split_whitespace().next()?;
+    find_exact_match(elements, hint)
+}
+
+/// Pick an element from a rofi, dmenu or fuzzel menu instead of the overlay
+///
+/// Closing the menu, or entering text that is not a hint, cancels.
+pub async fn select(
+    mut elements: Vec<HintedElement>,
+    picker: Picker,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    let (program, args) = command(picker).with_context(|| format!("{:?} is not a menu program", picker))?;
+    tty::resolve_names(&mut elements).await?;
+
+    debug!("Picking from {} elements with {}", elements.len(), program);
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {} (is it installed?)", program))?;
+
+    let mut stdin = child.stdin.take().context("No stdin for the menu")?;
+    stdin.write_all(menu_lines(&elements).as_bytes()).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    let choice = String::from_utf8_lossy(&output.stdout);
+    let selected = parse_choice(&choice, &elements).cloned();
+    if selected.is_none() && !choice.trim().is_empty() {
+        info!("'{}' is not a hint, cancelling", choice.trim());
+    }
+    Ok(selected.map(|h| (h, None)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atspi::ClickableElement;
+    use crate::hints::assign_hints;
+
+    #[test]
+    fn test_menu_lines() {
+        let element = |name: &str, role: &str| ClickableElement {
+            name: name.to_string(),
+            role: role.to_string(),
+            ..Default::default()
+        };
+        let elements = assign_hints(&[element("Save", "PushButton"), element("Home", "Link")], "ab");
+        assert_eq!(menu_lines(&elements), "a  Save  PushButton\nb  Home  Link\n");
+
+        assert_eq!(parse_choice("b  Home  Link\n", &elements).unwrap().element.name, "Home");
+        assert_eq!(parse_choice("A\n", &elements).unwrap().element.name, "Save");
+        assert!(parse_choice("Save", &elements).is_none());
+        assert!(parse_choice("", &elements).is_none());
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// Duration of AT-SPI tree walks
+pub const SCAN_SECONDS: &str = "vimium_scan_duration_seconds";
+/// Elements found by the most recent scan
+pub const SCAN_ELEMENTS: &str = "vimium_scan_elements";
+/// Pointer backend invocations, labelled by backend and result
+pub const BACKEND_ATTEMPTS: &str = "vimium_backend_attempts_total";
+/// Accessibility bus connection lookups, labelled hit or miss
+pub const CONNECTION_CACHE: &str = "vimium_a11y_connection_cache_total";
+/// Scans restarted on a new connection after the accessibility bus dropped
+pub const RECONNECTS: &str = "vimium_a11y_reconnects_total";
+
+/// How often resident commands such as the daemon rewrite the textfile
+const TEXTFILE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Process-wide metrics, rendered in the Prometheus text format
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(&'static str, String), u64>,
+    gauges: BTreeMap<&'static str, f64>,
+    /// Sum and count of observed durations
+    summaries: BTreeMap<&'static str, (f64, u64)>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    counters: BTreeMap::new(),
+    gauges: BTreeMap::new(),
+    summaries: BTreeMap::new(),
+});
+
+fn help(name: &str) -> &'static str {
+    match name {
+        SCAN_SECONDS => "Time spent walking the accessibility tree",
+        SCAN_ELEMENTS => "Elements found by the most recent scan",
+        BACKEND_ATTEMPTS => "Pointer backend invocations by backend and result",
+        CONNECTION_CACHE => "Accessibility bus connection cache lookups",
+        RECONNECTS => "Scans restarted after the accessibility bus dropped",
+        _ => "",
+    }
+}
+
+/// Increment a counter with the given labels
+pub fn inc_counter(name: &'static str, labels: &[(&str, &str)]) {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v))
+        .collect::<Vec<_>>()
+        .join(",");
+    if let Ok(mut registry) = REGISTRY.lock() {
+        *registry.counters.entry((name, labels)).or_default() += 1;
+    }
+}
+
+/// Set a gauge to `value`
+pub fn set_gauge(name: &'static str, value: f64) {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        registry.gauges.insert(name, value);
+    }
+}
+
+/// Record a duration in seconds
+pub fn observe_seconds(name: &'static str, seconds: f64) {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        let entry = registry.summaries.entry(name).or_default();
+        entry.0 += seconds;
+        entry.1 += 1;
+    }
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    let Ok(registry) = REGISTRY.lock() else {
+        return String::new();
+    };
+    let mut out = String::new();
+
+    let mut last_counter = "";
+    for ((name, labels), value) in &registry.counters {
+        if *name != last_counter {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help(name), name);
+            last_counter = name;
+        }
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+
+    for (name, value) in &registry.gauges {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help(name), name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    for (name, (sum, count)) in &registry.summaries {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} summary", name, help(name), name);
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+
+    out
+}
+
+/// Write metrics for the node_exporter textfile collector
+///
+/// The file is replaced atomically so the collector never reads a partial file.
+pub fn write_textfile(path: &Path) -> Result<()> {
+    let tmp = path.with_extension("prom.tmp");
+    fs::write(&tmp, render()).with_context(|| format!("Failed to write metrics to {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to move metrics to {:?}", path))?;
+    Ok(())
+}
+
+/// Rewrite the textfile every 15 seconds for as long as the process runs, so
+/// the collector sees a resident process's metrics before it exits
+pub fn spawn_textfile_writer(path: PathBuf) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TEXTFILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = write_textfile(&path) {
+                warn!("Failed to export metrics: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_format() {
+        inc_counter(BACKEND_ATTEMPTS, &[("backend", "ydotool"), ("result", "success")]);
+        inc_counter(BACKEND_ATTEMPTS, &[("backend", "ydotool"), ("result", "success")]);
+        observe_seconds(SCAN_SECONDS, 0.25);
+        set_gauge(SCAN_ELEMENTS, 42.0);
+
+        let text = render();
+        assert!(text.contains("# TYPE vimium_backend_attempts_total counter"));
+        assert!(text.contains(
+            "vimium_backend_attempts_total{backend=\"ydotool\",result=\"success\"} 2"
+        ));
+        assert!(text.contains("vimium_scan_duration_seconds_count 1"));
+        assert!(text.contains("vimium_scan_elements 42"));
+    }
+}
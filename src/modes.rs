@@ -0,0 +1,1129 @@
+use crate::config::{ActionMode, Config, Picker};
+use crate::hints::{self, HintedElement, NameMatch, NameMatcher};
+use crate::outcome::Outcome;
+use crate::provider::{self, ElementKind};
+use crate::{
+    atspi, caret, click, clipboard, dbus, doctor, filter, focus, history, hooks, keys, links, marks, menu, nudge, overlay,
+    picker, protocols, scroll, search, style, terminal, tty, window,
+};
+use anyhow::Result;
+use clap::Args;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Mode of the outcome when the element was only picked, see `pick`
+pub const PICK_MODE: &str = "pick";
+
+/// Options shared by the click modes
+#[derive(Args, Clone, Default)]
+pub struct HintArgs {
+    /// Filter by element role: `button`, `link,button` or `!table_cell`
+    #[arg(short, long)]
+    pub filter: Option<filter::RoleFilter>,
+    /// Pick the element class (buttons, links, ...) with one key before hinting
+    #[arg(long)]
+    pub pick_role: bool,
+    /// Skip hints and act on the only element with this name, failing if none or several match
+    #[arg(long, conflicts_with = "near")]
+    pub name: Option<String>,
+    /// How `--name` is compared with element names
+    #[arg(long, value_enum, default_value_t = NameMatch::Exact, requires = "name")]
+    pub name_match: NameMatch,
+    /// Only hint elements whose name matches this regular expression, e.g. `^Download`
+    #[arg(long, value_name = "REGEX")]
+    pub name_filter: Option<String>,
+    /// Skip hints and act on the element closest to x,y
+    #[arg(long, value_parser = parse_point)]
+    pub near: Option<(i32, i32)>,
+    /// Print the selected element as JSON instead of acting on it
+    #[arg(long)]
+    pub print: bool,
+    /// Keep scanning up to this many seconds until a matching element appears
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub wait: Option<Duration>,
+}
+
+/// Parse an "x,y" screen position
+pub fn parse_point(s: &str) -> Result<(i32, i32), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y but got '{}'", s))?;
+    let x = x.trim().parse().map_err(|_| format!("invalid x coordinate '{}'", x))?;
+    let y = y.trim().parse().map_err(|_| format!("invalid y coordinate '{}'", y))?;
+    Ok((x, y))
+}
+
+/// Parse a number of seconds such as `5` or `0.5`
+pub fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.trim()
+        .parse()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds '{}'", s))
+}
+
+/// Scan again after this long while `--wait` has time left
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Whether `--wait` allows another scan before `deadline`, sleeping until it may start
+async fn wait_again(deadline: Option<std::time::Instant>) -> bool {
+    match deadline {
+        Some(deadline) if std::time::Instant::now() < deadline => {
+            debug!("Nothing found yet, scanning again");
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Run `mode`, then each fallback in turn until one finds elements
+pub async fn run_modes(
+    config: &Config,
+    mode: ActionMode,
+    args: &HintArgs,
+    fallbacks: &[ActionMode],
+) -> Result<Outcome> {
+    let mut outcome = run_mode(config, mode, args).await?;
+    for &fallback in fallbacks {
+        if !matches!(outcome, Outcome::NoElements) {
+            break;
+        }
+        info!("Nothing to hint, falling back to {:?} mode", fallback);
+        outcome = run_mode(config, fallback, args).await?;
+    }
+    outcome.resolve_name().await;
+    run_hook(config, &outcome).await;
+    record_history(config, &outcome).await;
+    Ok(outcome)
+}
+
+/// Remember a selection for `repeat-last`, when it was a pointer action
+async fn record_history(config: &Config, outcome: &Outcome) {
+    let Outcome::Selected { element, position, mode } = outcome else {
+        return;
+    };
+    if config.history.size == 0 {
+        return;
+    }
+    let element = element.clone();
+    let apps = atspi::app_names(std::slice::from_ref(&element.bus_name)).await.unwrap_or_default();
+    let entry = history::Entry {
+        app: apps.get(&element.bus_name).unwrap_or(&element.bus_name).clone(),
+        name: element.name,
+        role: element.role,
+        x: position.0,
+        y: position.1,
+        mode: mode.clone(),
+    };
+    if entry.action().is_none() {
+        return;
+    }
+    if let Err(e) = history::record(entry, config.history.size) {
+        warn!("Failed to record history: {:#}", e);
+    }
+}
+
+/// Scan for the element of the last selection and take the same action on it
+pub async fn run_repeat_last(config: &Config) -> Result<Outcome> {
+    let entry = history::last().ok_or_else(|| anyhow::anyhow!("Nothing to repeat yet"))?;
+    let action = entry
+        .action()
+        .ok_or_else(|| anyhow::anyhow!("Cannot repeat {:?}", entry.mode))?;
+    let element = locate_entries(config, std::slice::from_ref(&entry))
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("{} {:?} in {} is no longer on screen", entry.role, entry.name, entry.app))?;
+    act_on_located(config, element, action).await
+}
+
+/// Scan for the element marked `letter` in any application and take `action` on it
+pub async fn run_goto(config: &Config, letter: char, action: ActionMode) -> Result<Outcome> {
+    let entries = marks::named(&marks::load(), letter);
+    if entries.is_empty() {
+        anyhow::bail!("No mark {:?}", letter);
+    }
+    let element = locate_entries(config, &entries)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No element marked {:?} is on screen", letter))?;
+    act_on_located(config, element, action).await
+}
+
+/// Scan for the element a `[bookmarks]` entry names and take its action on it
+pub async fn run_bookmark(config: &Config, name: &str) -> Result<Outcome> {
+    let bookmark = config
+        .bookmarks
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("No bookmark {:?} in [bookmarks]", name))?;
+    let matcher = NameMatcher::new(&bookmark.name, bookmark.name_match)?;
+    let mut elements = provider::collect(ElementKind::Clickable, config).await?;
+    if !bookmark.role.is_empty() {
+        elements.retain(|e| bookmark.role.matches(&e.role));
+    }
+    if !bookmark.app.is_empty() {
+        let apps = atspi::app_names(&hints::app_order(&elements)).await.unwrap_or_default();
+        elements.retain(|e| bookmark.in_app(apps.get(&e.bus_name).unwrap_or(&e.bus_name)));
+    }
+    atspi::resolve_names(&mut elements).await?;
+    let mut element = elements
+        .into_iter()
+        .find(|e| matcher.matches(&e.name))
+        .ok_or_else(|| anyhow::anyhow!("Bookmark {:?} ({:?}) is not on screen", name, bookmark.name))?;
+    provider::to_global(std::slice::from_mut(&mut element)).await;
+    act_on_located(config, element, bookmark.action).await
+}
+
+/// Print the `[bookmarks]` entries
+pub fn list_bookmarks(config: &Config) {
+    if config.bookmarks.is_empty() {
+        println!("No bookmarks; add them under [bookmarks] in {}", Config::config_path().display());
+    }
+    for (name, bookmark) in &config.bookmarks {
+        let app = if bookmark.app.is_empty() { "any app" } else { &bookmark.app };
+        println!("{}\t{:?} in {}", name, bookmark.name, app);
+    }
+}
+
+/// Save the last selection under `letter`, for its application
+pub fn set_mark(letter: char) -> Result<()> {
+    let entry = history::last().ok_or_else(|| anyhow::anyhow!("Nothing selected yet (or history.size is 0)"))?;
+    let mark = marks::Mark {
+        name: entry.name,
+        role: entry.role,
+        x: entry.x,
+        y: entry.y,
+    };
+    marks::set(&entry.app, letter, mark.clone())?;
+    println!("Marked {} {:?} in {} as {}", mark.role, mark.name, entry.app, letter);
+    Ok(())
+}
+
+/// Scan for the element of the first of `entries` still on screen, in global coordinates
+async fn locate_entries(config: &Config, entries: &[history::Entry]) -> Result<Option<atspi::ClickableElement>> {
+    let mut elements = provider::collect(provider::ElementKind::Clickable, config).await?;
+    // Entries are remembered in global coordinates
+    provider::to_global(&mut elements).await;
+    if entries.iter().any(|entry| !entry.name.is_empty()) {
+        atspi::resolve_names(&mut elements).await?;
+    }
+    let apps = atspi::app_names(&hints::app_order(&elements)).await.unwrap_or_default();
+    Ok(entries.iter().find_map(|entry| history::locate(entry, &elements, &apps)).cloned())
+}
+
+/// Take `action` on an element found again (in global coordinates), then run the hook and record it
+async fn act_on_located(config: &Config, element: atspi::ClickableElement, action: ActionMode) -> Result<Outcome> {
+    let position = element.center();
+    act_on_element(&element, position, action, config).await?;
+    let mut outcome = Outcome::Selected {
+        element,
+        position,
+        mode: action.name(),
+    };
+    outcome.resolve_name().await;
+    run_hook(config, &outcome).await;
+    record_history(config, &outcome).await;
+    Ok(outcome)
+}
+
+/// Start the `[hooks]` command for how the mode ended
+async fn run_hook(config: &Config, outcome: &Outcome) {
+    if let (Some(keys), Outcome::Selected { .. }) = (&config.hooks.on_select_keys, outcome) {
+        if let Err(e) = keys::send_sequence(keys) {
+            warn!("{:#}", e);
+        }
+    }
+    if config.hooks.on_select.is_none() && config.hooks.on_cancel.is_none() {
+        return;
+    }
+    if let Err(e) = hooks::run(&config.hooks, outcome) {
+        warn!("{:#}", e);
+    }
+}
+
+/// Run a single mode
+async fn run_mode(config: &Config, mode: ActionMode, args: &HintArgs) -> Result<Outcome> {
+    dbus::mode_started(mode).await;
+    match mode {
+        ActionMode::Scroll => run_scroll_mode(config).await,
+        ActionMode::Text => run_text_mode(config, args.wait).await,
+        action => run_click_mode(config, action, args).await,
+    }
+}
+
+/// Hint the windows on visible workspaces, then move/resize the one picked
+pub async fn run_window_mode(config: &Config) -> Result<Outcome> {
+    let mut windows = window::list()?;
+    info!("Found {} windows", windows.len());
+    let picked = match windows.len() {
+        0 => {
+            eprintln!("No windows found");
+            return Ok(Outcome::NoElements);
+        }
+        1 => windows.remove(0),
+        _ => {
+            // Windows are global, the overlay draws on the focused output
+            let (ox, oy) = focus::focused_output_origin();
+            let elements: Vec<_> = windows
+                .iter()
+                .map(|w| atspi::ClickableElement {
+                    x: w.x - ox,
+                    y: w.y - oy,
+                    ..w.element()
+                })
+                .collect();
+            let hinted = hints::assign_hints(&elements, &config.hints.chars);
+            let Some((picked, _)) = choose_hint(hinted.clone(), config).await? else {
+                info!("Cancelled");
+                return Ok(Outcome::Cancelled);
+            };
+            let index = hinted
+                .iter()
+                .position(|h| h.hint == picked.hint)
+                .expect("picked hint comes from the list");
+            windows.swap_remove(index)
+        }
+    };
+
+    let element = picked.element();
+    let config = config.clone();
+    tokio::task::spawn_blocking(move || window::run_adjust_overlay(picked, &config)).await??;
+    Ok(Outcome::Selected {
+        position: element.center(),
+        element,
+        mode: "window".to_string(),
+    })
+}
+
+/// Hint the tabs of the focused window, then click the one picked
+pub async fn run_tabs_mode(config: &Config) -> Result<Outcome> {
+    let mut config = config.clone();
+    config.scan.focused_only = true;
+    config.hints.font_size = config.tabs.font_size;
+    let mut elements = provider::collect(ElementKind::Tab, &config).await?;
+    apply_exclusions(&mut elements, &config).await?;
+    info!("Found {} tabs", elements.len());
+    if elements.is_empty() {
+        eprintln!("No tabs found in the focused window");
+        return Ok(Outcome::NoElements);
+    }
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((picked, action)) = choose_hint(hinted, &config).await? else {
+        info!("Cancelled");
+        return Ok(Outcome::Cancelled);
+    };
+    act_on_located(&config, picked.element, action.unwrap_or(ActionMode::Click)).await
+}
+
+/// Hint headings and text, then put the caret at the start of the one picked
+///
+/// Applications that don't take the caret there get the pointer moved over it
+/// instead. Holding a modifier clicks it like in click mode.
+pub async fn run_read_mode(config: &Config) -> Result<Outcome> {
+    let mut elements = atspi::get_heading_elements(&config.scan).await?;
+    apply_exclusions(&mut elements, config).await?;
+    info!("Found {} headings and text elements", elements.len());
+    if elements.is_empty() {
+        eprintln!("No headings or text found");
+        return Ok(Outcome::NoElements);
+    }
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((picked, action)) = choose_hint(hinted, config).await? else {
+        info!("Cancelled");
+        return Ok(Outcome::Cancelled);
+    };
+    let position = picked.click_position();
+    let element = picked.element;
+    if let Some(action) = action {
+        act_on_element(&element, position, action, config).await?;
+        return Ok(Outcome::Selected {
+            element,
+            position,
+            mode: action.name(),
+        });
+    }
+    if let Err(e) = atspi::place_caret(&element).await {
+        info!("Moving the pointer instead of the caret: {:#}", e);
+        click::move_cursor_to(position.0, position.1)?;
+    }
+    Ok(Outcome::Selected {
+        element,
+        position,
+        mode: "read".to_string(),
+    })
+}
+
+/// Hint links, then open the URI of the one picked with `links.open_command`
+pub async fn run_open_link_mode(config: &Config) -> Result<Outcome> {
+    let mut elements = atspi::get_link_elements(&config.scan).await?;
+    apply_exclusions(&mut elements, config).await?;
+    info!("Found {} links", elements.len());
+    if elements.is_empty() {
+        eprintln!("No links found");
+        return Ok(Outcome::NoElements);
+    }
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((picked, _)) = choose_hint(hinted, config).await? else {
+        info!("Cancelled");
+        return Ok(Outcome::Cancelled);
+    };
+    let position = picked.click_position();
+    let element = picked.element;
+    let uri = atspi::link_uri(&element).await?;
+    links::open(&config.links.open_command, &uri)?;
+    Ok(Outcome::Selected {
+        element,
+        position,
+        mode: "open-link".to_string(),
+    })
+}
+
+/// Hint elements showing text, then copy the text of the one picked to the clipboard
+///
+/// Elements without a Text interface, such as most buttons, give their name instead.
+pub async fn run_yank_mode(config: &Config) -> Result<Outcome> {
+    let mut elements = atspi::get_yank_elements(&config.scan).await?;
+    apply_exclusions(&mut elements, config).await?;
+    info!("Found {} elements with text", elements.len());
+    if elements.is_empty() {
+        eprintln!("No elements with text found");
+        return Ok(Outcome::NoElements);
+    }
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((picked, _)) = choose_hint(hinted, config).await? else {
+        info!("Cancelled");
+        return Ok(Outcome::Cancelled);
+    };
+    let position = picked.click_position();
+    let mut element = picked.element;
+    let text = match atspi::get_text(&element).await {
+        Ok(text) if !text.trim().is_empty() => text,
+        _ => {
+            atspi::resolve_names(std::slice::from_mut(&mut element)).await?;
+            element.name.clone()
+        }
+    };
+    if text.is_empty() {
+        anyhow::bail!("The element has no text to copy");
+    }
+    clipboard::copy(&text)?;
+    info!("Copied {} characters", text.chars().count());
+    Ok(Outcome::Selected {
+        element,
+        position,
+        mode: "yank".to_string(),
+    })
+}
+
+/// Insert `text` into a focused field, then press Enter if asked
+///
+/// The text goes through the field's EditableText interface when it has one,
+/// and is typed with wtype, dotool or ydotool otherwise.
+pub async fn type_into(element: &atspi::ClickableElement, text: &str, enter: bool) -> Result<()> {
+    if let Err(e) = atspi::insert_text(element, text).await {
+        debug!("Typing instead of inserting the text: {:#}", e);
+        keys::type_text(text)?;
+    }
+    if enter {
+        keys::send(&"enter".parse().map_err(anyhow::Error::msg)?)?;
+    }
+    Ok(())
+}
+
+/// Move the caret of an element's text with vim motions until the user is done
+///
+/// A selection made with `v` is set on the element, and copied to the
+/// clipboard when it ends with `y` or `copy` is set.
+pub async fn run_caret_mode(config: &Config, element: &atspi::ClickableElement, copy: bool) -> Result<()> {
+    let text = atspi::get_text(element).await?;
+    let start = atspi::caret_offset(element).await.unwrap_or(0).max(0) as usize;
+    let length = text.chars().count() as i32;
+    let (config, element, runtime) = (config.clone(), element.clone(), tokio::runtime::Handle::current());
+    let move_caret = move |offset: usize, anchor: Option<usize>| {
+        runtime.block_on(async {
+            let offset = offset as i32;
+            if let Err(e) = atspi::set_caret_offset(&element, offset).await {
+                warn!("Failed to move the caret: {:#}", e);
+            }
+            if let Some(anchor) = anchor.map(|anchor| anchor as i32).filter(|&anchor| anchor != offset) {
+                if let Err(e) = atspi::set_selection(&element, anchor.min(offset), anchor.max(offset)).await {
+                    warn!("Failed to select text: {:#}", e);
+                }
+            }
+            // Past the last character, draw at the right edge of that character
+            let (x, y, width, height) = if offset < length {
+                atspi::get_text_range_extents(&element, offset, offset + 1).await.ok()?
+            } else {
+                let (x, y, width, height) = atspi::get_text_range_extents(&element, offset - 1, offset).await.ok()?;
+                (x + width, y, 0, height)
+            };
+            (height > 0).then_some((x, y, width, height))
+        })
+    };
+    let shown = text.clone();
+    let end = tokio::task::spawn_blocking(move || caret::run_caret_overlay(&shown, start, &config, move_caret)).await??;
+    if let Some(range) = end.selection().filter(|_| end.yank || copy) {
+        let selected: String = text.chars().skip(range.start).take(range.len()).collect();
+        clipboard::copy(&selected)?;
+        info!("Copied {} characters", range.len());
+    }
+    Ok(())
+}
+
+/// Hint elements showing text, then select some of the picked one's text with caret motions
+pub async fn run_select_mode(config: &Config, copy: bool) -> Result<Outcome> {
+    let mut elements = atspi::get_readable_elements(&config.scan).await?;
+    apply_exclusions(&mut elements, config).await?;
+    info!("Found {} elements with text", elements.len());
+    if elements.is_empty() {
+        eprintln!("No elements with text found");
+        return Ok(Outcome::NoElements);
+    }
+    let hinted = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((picked, _)) = choose_hint(hinted, config).await? else {
+        info!("Cancelled");
+        return Ok(Outcome::Cancelled);
+    };
+    let position = picked.click_position();
+    run_caret_mode(config, &picked.element, copy).await?;
+    Ok(Outcome::Selected {
+        element: picked.element,
+        position,
+        mode: "select".to_string(),
+    })
+}
+
+/// Hint elements, move the pointer over the one picked, then let it be nudged
+/// from the keyboard and optionally clicked
+pub async fn run_move_mode(config: &Config, args: &HintArgs) -> Result<Outcome> {
+    dbus::mode_started(ActionMode::Hover).await;
+    let mut outcome = run_click_mode(config, ActionMode::Hover, args).await?;
+    if let Outcome::Selected { position, mode, .. } = &mut outcome {
+        if !args.print {
+            let (start, config) = (*position, config.clone());
+            let (moved, button) =
+                tokio::task::spawn_blocking(move || nudge::run_nudge_overlay(start, &config)).await??;
+            *position = moved;
+            if let Some(button) = button {
+                info!("Clicking {:?} at ({}, {})", button, moved.0, moved.1);
+                click::press_at(moved.0, moved.1, button)?;
+                let action = match button {
+                    click::ClickButton::Left => ActionMode::Click,
+                    click::ClickButton::Right => ActionMode::RightClick,
+                    click::ClickButton::Middle => ActionMode::MiddleClick,
+                };
+                *mode = action.name();
+            }
+        }
+    }
+    outcome.resolve_name().await;
+    run_hook(config, &outcome).await;
+    record_history(config, &outcome).await;
+    Ok(outcome)
+}
+
+/// Drop elements matching `hints.exclude_names` or hidden by `[[hints.rules]]`,
+/// fetching names first if either matches on them
+pub async fn apply_exclusions(elements: &mut Vec<atspi::ClickableElement>, config: &Config) -> Result<()> {
+    let styles = (!config.hints.rules.is_empty()).then(|| style::StyleRules::new(config));
+    #[cfg(feature = "lua")]
+    let plugins = crate::plugins::filters_elements();
+    #[cfg(not(feature = "lua"))]
+    let plugins = false;
+    if config.hints.exclude_names.is_empty() && styles.is_none() && !plugins {
+        return Ok(());
+    }
+
+    if !config.hints.exclude_names.is_empty()
+        || styles.as_ref().is_some_and(style::StyleRules::matches_names)
+        || plugins
+    {
+        atspi::resolve_names(elements).await?;
+    }
+    if !config.hints.exclude_names.is_empty() {
+        let patterns = hints::compile_name_patterns(&config.hints.exclude_names)?;
+        hints::exclude_by_name(elements, &patterns);
+        info!("After name exclusions: {} elements", elements.len());
+    }
+    if let Some(styles) = styles {
+        elements.retain(|e| !styles.resolve(e).hidden);
+    }
+    #[cfg(feature = "lua")]
+    crate::plugins::filter(elements);
+    Ok(())
+}
+
+/// Show the hint overlay and wait for a selection
+///
+/// With `behavior.restore_focus`, the previously focused window gets keyboard
+/// focus back once the overlay closes. Without a Wayland display (SSH, a
+/// TTY) the hints are listed on stdout and the selection is read from stdin.
+async fn select_hint(
+    hinted_elements: Vec<HintedElement>,
+    mode: ActionMode,
+    config: &Config,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    let result = choose_hint(hinted_elements, config).await?;
+    match &result {
+        Some((hinted, action)) => {
+            dbus::element_selected(action.unwrap_or(mode), &hinted.element, hinted.click_position()).await
+        }
+        None => dbus::mode_cancelled(mode).await,
+    }
+    Ok(result)
+}
+
+/// Show the overlay or the configured picker, falling back to the terminal
+/// without a Wayland display
+async fn choose_hint(
+    hinted_elements: Vec<HintedElement>,
+    config: &Config,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    let mut result = match config.behavior.picker {
+        Picker::Overlay => match overlay::wayland_connection() {
+            Err(e) => {
+                info!("No overlay available ({:#}), picking in the terminal", e);
+                choose_in_terminal(hinted_elements).await?
+            }
+            Ok(_) if !protocols::has(protocols::LAYER_SHELL) => {
+                info!("The compositor lacks {}, picking in the terminal", protocols::LAYER_SHELL);
+                choose_in_terminal(hinted_elements).await?
+            }
+            Ok(_) => return select_with_overlay(hinted_elements, config).await,
+        },
+        Picker::Tui => picker::select(hinted_elements).await?,
+        menu_picker => menu::select(hinted_elements, menu_picker).await?,
+    };
+    // The overlay reports global positions, other pickers hand back the scanned ones
+    if let Some((hinted, _)) = &mut result {
+        provider::to_global(std::slice::from_mut(&mut hinted.element)).await;
+    }
+    Ok(result)
+}
+
+/// The fuzzy picker on an interactive terminal, otherwise hints read from stdin
+async fn choose_in_terminal(
+    hinted_elements: Vec<HintedElement>,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    use std::io::IsTerminal;
+    if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        picker::select(hinted_elements).await
+    } else {
+        tty::select(hinted_elements).await
+    }
+}
+
+/// First stage of two-stage hinting: pick a region, returning its elements
+///
+/// Regions hold at most one element per hint character, so the second
+/// stage's labels are a single character.
+async fn pick_cluster(
+    elements: Vec<atspi::ClickableElement>,
+    config: &Config,
+) -> Result<Option<Vec<atspi::ClickableElement>>> {
+    let chars = if config.hints.chars.is_empty() { hints::DEFAULT_HINT_CHARS } else { &config.hints.chars };
+    let mut clusters = hints::cluster(&elements, chars.chars().count());
+    if clusters.len() <= 1 {
+        return Ok(Some(elements));
+    }
+    info!("Grouped {} elements into {} regions", elements.len(), clusters.len());
+
+    let regions: Vec<_> = clusters.iter().map(|c| hints::bounding_box(c)).collect();
+    let hinted = hints::assign_hints(&regions, chars);
+    let Some((picked, _)) = choose_hint(hinted.clone(), config).await? else {
+        return Ok(None);
+    };
+    let index = hinted
+        .iter()
+        .position(|h| h.hint == picked.hint)
+        .expect("picked hint comes from the list");
+    Ok(Some(clusters.swap_remove(index)))
+}
+
+/// Show the overlay, restoring focus around it with `behavior.restore_focus`
+async fn select_with_overlay(
+    hinted_elements: Vec<HintedElement>,
+    config: &Config,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    let focused = if config.behavior.restore_focus {
+        focus::FocusedWindow::capture()
+    } else {
+        None
+    };
+
+    let result = show_overlay(hinted_elements, config).await;
+
+    if let Some(window) = focused {
+        if let Err(e) = window.restore() {
+            warn!("Failed to restore focus: {}", e);
+        }
+    }
+    result
+}
+
+/// Run the hint overlay
+///
+/// When element names are displayed they are fetched in the background and
+/// streamed into the overlay, so hints appear without waiting for them.
+async fn show_overlay(
+    hinted_elements: Vec<HintedElement>,
+    config: &Config,
+) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    if !config.behavior.show_element_names {
+        return overlay::show_and_select(hinted_elements, config.clone(), None).await;
+    }
+
+    let (name_tx, name_rx) = overlay::name_channel();
+    let elements: Vec<_> = hinted_elements.iter().map(|h| h.element.clone()).collect();
+    let keys: Vec<String> = elements.iter().map(|e| e.key()).collect();
+    let name_fetch = tokio::spawn(async move {
+        let result = atspi::fetch_names(elements, |index, name| {
+            name_tx.send((keys[index].clone(), name)).is_ok()
+        })
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to fetch element names: {}", e);
+        }
+    });
+
+    let result = overlay::show_and_select(hinted_elements, config.clone(), Some(name_rx)).await;
+    name_fetch.abort();
+    result
+}
+
+/// Run click mode with hints
+async fn run_click_mode(config: &Config, action: ActionMode, args: &HintArgs) -> Result<Outcome> {
+    // 1. Query the element providers for clickable elements
+    let started = std::time::Instant::now();
+    let deadline = args.wait.map(|wait| started + wait);
+    let role_filter = args.filter.as_ref().unwrap_or(&config.hints.filter);
+    let name = args
+        .name
+        .as_deref()
+        .map(|name| NameMatcher::new(name, args.name_match))
+        .transpose()?;
+    let name_filter = args
+        .name_filter
+        .as_deref()
+        .map(|pattern| NameMatcher::new(pattern, NameMatch::Regex))
+        .transpose()?;
+    let (found, mut elements) = loop {
+        let mut elements = provider::collect(ElementKind::Clickable, config).await?;
+        info!("Found {} clickable elements", elements.len());
+        let found = config.overlay.debug.then(|| elements.clone());
+
+        // Apply the role filter, from the command line or else the config
+        if !role_filter.is_empty() {
+            elements.retain(|e| role_filter.matches(&e.role));
+            info!("After filtering by '{}': {} elements", role_filter, elements.len());
+        }
+        apply_exclusions(&mut elements, config).await?;
+        if name.is_some() || name_filter.is_some() {
+            atspi::resolve_names(&mut elements).await?;
+        }
+        if let Some(name) = &name {
+            elements.retain(|e| name.matches(&e.name));
+        }
+        if let Some(name_filter) = &name_filter {
+            elements.retain(|e| name_filter.matches(&e.name));
+            info!("After filtering by name: {} elements", elements.len());
+        }
+        if !elements.is_empty() || !wait_again(deadline).await {
+            break (found, elements);
+        }
+    };
+
+    // Acting by name needs exactly one match, there is nobody to pick one
+    if let Some(name) = &args.name {
+        let mut element = match elements.as_slice() {
+            [element] => element.clone(),
+            [] => anyhow::bail!("No element named {:?}", name),
+            many => {
+                let names: Vec<String> = many.iter().take(5).map(|e| format!("{} {:?}", e.role, e.name)).collect();
+                anyhow::bail!("{} elements match {:?}: {}", many.len(), name, names.join(", "));
+            }
+        };
+        info!("Element named {:?} is {} at {:?}", name, element.role, element.center());
+        provider::to_global(std::slice::from_mut(&mut element)).await;
+        return finish_click(element.clone(), element.center(), action, args, config).await;
+    }
+
+    if elements.is_empty() {
+        warn!("No clickable elements found");
+        eprintln!("No clickable elements found. Make sure:");
+        eprintln!("  - The target application supports AT-SPI accessibility");
+        eprintln!("  - For Firefox: set accessibility.force_disabled = 0 in about:config");
+        eprintln!("  - For Chrome/Electron: launch with --force-renderer-accessibility");
+        for note in silent_app_notes().await {
+            eprintln!("  - {}", note);
+        }
+        return Ok(Outcome::NoElements);
+    }
+    let debug = match found {
+        Some(found) => {
+            let scan_time = started.elapsed();
+            Some(debug_info(found, &elements, scan_time, role_filter, name_filter.as_ref(), config).await?)
+        }
+        None => None,
+    };
+
+    // Approximate positions from other tools snap to the closest element
+    if let Some((x, y)) = args.near {
+        let element = hints::nearest_element(&elements, x, y)
+            .ok_or_else(|| anyhow::anyhow!("No element near ({}, {})", x, y))?;
+        info!("Nearest element to ({}, {}) is {} at {:?}", x, y, element.role, element.center());
+        let mut element = element.clone();
+        provider::to_global(std::slice::from_mut(&mut element)).await;
+        return finish_click(element.clone(), element.center(), action, args, config).await;
+    }
+
+    // Dense screens: pick a region first so labels stay short
+    let threshold = config.hints.two_stage_threshold;
+    if threshold > 0 && elements.len() > threshold {
+        match pick_cluster(elements, config).await? {
+            Some(region) => elements = region,
+            None => {
+                dbus::mode_cancelled(action).await;
+                return Ok(Outcome::Cancelled);
+            }
+        }
+    }
+
+    // 2. Generate hints for elements
+    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
+
+    // 3. Show overlay and wait for user input
+    let mut config = config.clone();
+    config.behavior.role_picker |= args.pick_role;
+    if let Some(debug) = debug {
+        overlay::set_debug_info(debug);
+    }
+    let result = select_hint(hinted_elements, action, &config).await?;
+
+    // 4. Perform action on selected element
+    let Some((element, modifier_action)) = result else {
+        return Ok(Outcome::Cancelled);
+    };
+    // Modifier overrides the mode
+    let final_action = modifier_action.unwrap_or(action);
+    let position = element.click_position();
+    finish_click(element.element, position, final_action, args, &config).await
+}
+
+/// Hint the visible occurrences of `query`, then scroll the chosen one into view and click it
+pub async fn run_search(config: &Config, query: &str, scroll_only: bool) -> Result<Outcome> {
+    let occurrences = search::find(query, &config.scan).await?;
+    info!("Found {} visible occurrences of {:?}", occurrences.len(), query);
+    if occurrences.is_empty() {
+        eprintln!("No visible text matches {:?}", query);
+        return Ok(Outcome::NoElements);
+    }
+
+    let elements: Vec<_> = occurrences.iter().map(|o| o.element.clone()).collect();
+    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
+    let Some((chosen, modifier_action)) = select_hint(hinted_elements, ActionMode::Click, config).await? else {
+        return Ok(Outcome::Cancelled);
+    };
+    let occurrence = occurrences
+        .iter()
+        .find(|o| o.element.key() == chosen.element.key())
+        .ok_or_else(|| anyhow::anyhow!("The chosen match is gone"))?;
+
+    // The chosen element is already global, so carry over how far the match moved
+    let scrolled = search::scroll_into_view(occurrence).await?;
+    let mut element = chosen.element;
+    element.x += scrolled.x - occurrence.element.x;
+    element.y += scrolled.y - occurrence.element.y;
+    (element.width, element.height) = (scrolled.width, scrolled.height);
+    let position = element.center();
+    if scroll_only {
+        return Ok(Outcome::Selected {
+            element,
+            position,
+            mode: "scroll".to_string(),
+        });
+    }
+    let action = modifier_action.unwrap_or(ActionMode::Click);
+    act_on_element(&element, position, action, config).await?;
+    Ok(Outcome::Selected {
+        element,
+        position,
+        mode: action.name(),
+    })
+}
+
+/// Diagnostics for `--debug-overlay`: the filter that dropped each found element
+async fn debug_info(
+    mut found: Vec<atspi::ClickableElement>,
+    kept: &[atspi::ClickableElement],
+    scan_time: std::time::Duration,
+    role_filter: &filter::RoleFilter,
+    name_filter: Option<&NameMatcher>,
+    config: &Config,
+) -> Result<overlay::DebugInfo> {
+    atspi::resolve_names(&mut found).await?;
+    let patterns = hints::compile_name_patterns(&config.hints.exclude_names)?;
+    let styles = style::StyleRules::new(config);
+    let apps = hints::app_order(&found);
+    let rejected = found
+        .into_iter()
+        .filter(|e| !kept.iter().any(|k| k.key() == e.key() && (k.x, k.y) == (e.x, e.y)))
+        .map(|e| {
+            let reason = if !role_filter.matches(&e.role) {
+                format!("filter {}", role_filter)
+            } else if patterns.iter().any(|p| p.is_match(&e.name)) {
+                "exclude_names".to_string()
+            } else if name_filter.is_some_and(|f| !f.matches(&e.name)) {
+                "name filter".to_string()
+            } else if styles.resolve(&e).hidden {
+                "hidden by rule".to_string()
+            } else {
+                "not hinted".to_string()
+            };
+            (e, reason)
+        })
+        .collect();
+    Ok(overlay::DebugInfo {
+        scan_time,
+        app_names: atspi::app_names(&apps).await?,
+        rejected,
+    })
+}
+
+/// Act on the chosen element, or print it with `--print`
+async fn finish_click(
+    element: atspi::ClickableElement,
+    position: (i32, i32),
+    action: ActionMode,
+    args: &HintArgs,
+    config: &Config,
+) -> Result<Outcome> {
+    // Printed by the caller, as the element or the whole outcome
+    if args.print {
+        return Ok(Outcome::Selected {
+            element,
+            position,
+            mode: PICK_MODE.to_string(),
+        });
+    }
+    #[cfg(feature = "lua")]
+    let action = crate::plugins::action(&element, action);
+    act_on_element(&element, position, action, config).await?;
+    Ok(Outcome::Selected {
+        element,
+        position,
+        mode: action.name(),
+    })
+}
+
+/// Perform `action` on a selected element, then wait `behavior.post_action_delay_ms`
+async fn act_on_element(
+    element: &atspi::ClickableElement,
+    position: (i32, i32),
+    action: ActionMode,
+    config: &Config,
+) -> Result<()> {
+    // Terminal links are opened or copied rather than clicked
+    if terminal::is_link(element) {
+        terminal::activate_link(element, action, &config.links.open_command)?;
+    } else if action == ActionMode::Activate && element.bus_name.is_empty() {
+        // Elements from the browser extension aren't on the accessibility bus
+        info!("{} has no accessible action, clicking it instead", element.role);
+        perform_click_action(position, ActionMode::Click)?;
+    } else if action == ActionMode::Activate {
+        info!("Activating {} through its default action", element.role);
+        atspi::do_default_action(element).await?;
+    } else if action == ActionMode::Click && config.behavior.use_key_bindings && press_key_binding(element).await {
+        info!("Activated {} through its keyboard shortcut", element.role);
+    } else if action == ActionMode::Click && uses_action(element, config) && toggle(element).await {
+        info!("Toggled {} through its accessible action", element.role);
+    } else {
+        perform_click_action(position, action)?;
+    }
+
+    if config.behavior.post_action_delay_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(config.behavior.post_action_delay_ms)).await;
+    }
+    Ok(())
+}
+
+/// Press the element's own keyboard shortcut, returning whether it has one that was sent
+///
+/// Keys go to the focused window, so this only happens when the element's
+/// application has it.
+async fn press_key_binding(element: &atspi::ClickableElement) -> bool {
+    let Some(combo) = atspi::key_binding(element).await.as_deref().and_then(keys::KeyCombo::from_atspi) else {
+        return false;
+    };
+    if !atspi::focus_for_keys(element).await {
+        debug!("{} is not in the focused window, clicking instead of pressing {}", element.role, combo);
+        return false;
+    }
+    match keys::send(&combo) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to press {}, clicking instead: {:#}", combo, e);
+            false
+        }
+    }
+}
+
+/// Whether `behavior.action_roles` asks for the element to be clicked through its action
+fn uses_action(element: &atspi::ClickableElement, config: &Config) -> bool {
+    let roles = &config.behavior.action_roles;
+    !roles.is_empty() && roles.matches(&element.role)
+}
+
+/// Toggle the element through its Action interface, returning whether that worked
+async fn toggle(element: &atspi::ClickableElement) -> bool {
+    match atspi::do_toggle_action(element).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to toggle {}, clicking instead: {:#}", element.role, e);
+            false
+        }
+    }
+}
+
+/// Click at `position` with the button for `action`
+fn perform_click_action((x, y): (i32, i32), action: ActionMode) -> Result<()> {
+    match action {
+        ActionMode::Click => {
+            info!("Clicking element at ({}, {})", x, y);
+            click::click_at(x, y)?;
+        }
+        ActionMode::RightClick => {
+            info!("Right-clicking element at ({}, {})", x, y);
+            click::right_click_at(x, y)?;
+        }
+        ActionMode::MiddleClick => {
+            info!("Middle-clicking element at ({}, {})", x, y);
+            click::middle_click_at(x, y)?;
+        }
+        ActionMode::Hover => {
+            info!("Moving pointer to element at ({}, {})", x, y);
+            click::move_cursor_to(x, y)?;
+        }
+        ActionMode::Scroll | ActionMode::Text | ActionMode::Drag | ActionMode::Activate => {
+            anyhow::bail!("{} is not a pointer action", action.name());
+        }
+    }
+    Ok(())
+}
+
+/// Act on an element listed earlier, looking it up again by ID
+pub async fn run_act(config: &Config, id: &str, action: ActionMode) -> Result<()> {
+    if matches!(action, ActionMode::Text | ActionMode::Drag) {
+        anyhow::bail!("The {} action needs hints and can't be taken by element ID", action.name());
+    }
+    let elements = provider::collect(ElementKind::Clickable, config).await?;
+    let mut element = elements
+        .into_iter()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| anyhow::anyhow!("No element with ID {} (it may have closed)", id))?;
+    info!("Resolved {} to {} at {:?}", id, element.role, element.center());
+    provider::to_global(std::slice::from_mut(&mut element)).await;
+
+    if action == ActionMode::Scroll {
+        let (x, y) = element.center();
+        return scroll::run_scroll_mode(&element, x, y, config).await;
+    }
+    act_on_element(&element, element.center(), action, config).await
+}
+
+/// Run scroll mode - select a scrollable area then scroll with hjkl
+async fn run_scroll_mode(config: &Config) -> Result<Outcome> {
+    // Get scrollable elements
+    let mut elements = provider::collect(ElementKind::Scrollable, config).await?;
+    info!("Found {} scrollable elements", elements.len());
+    apply_exclusions(&mut elements, config).await?;
+
+    if elements.is_empty() {
+        warn!("No scrollable elements found");
+        eprintln!("No scrollable elements found.");
+        return Ok(Outcome::NoElements);
+    }
+
+    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
+    let result = select_hint(hinted_elements, ActionMode::Scroll, config).await?;
+
+    let Some((element, _)) = result else {
+        return Ok(Outcome::Cancelled);
+    };
+    let (x, y) = element.click_position();
+    // Enter scroll mode at this position
+    scroll::run_scroll_mode(&element.element, x, y, config).await?;
+    Ok(Outcome::Selected {
+        element: element.element,
+        position: (x, y),
+        mode: ActionMode::Scroll.name(),
+    })
+}
+
+/// Scroll the focused window's largest scrollable area directly
+pub async fn run_focused_scroll(config: &Config) -> Result<Outcome> {
+    let Some(mut element) = atspi::get_focused_scrollable(&config.scan).await? else {
+        eprintln!("No focused window found.");
+        return Ok(Outcome::NoElements);
+    };
+    provider::to_global(std::slice::from_mut(&mut element)).await;
+    info!("Scrolling {} at {:?}", element.role, element.center());
+    let (x, y) = element.center();
+    scroll::run_scroll_mode(&element, x, y, config).await?;
+    Ok(Outcome::Selected {
+        element,
+        position: (x, y),
+        mode: ActionMode::Scroll.name(),
+    })
+}
+
+/// Run text input mode - focus on text fields
+async fn run_text_mode(config: &Config, wait: Option<Duration>) -> Result<Outcome> {
+    let deadline = wait.map(|wait| std::time::Instant::now() + wait);
+    let elements = loop {
+        // Get only text input elements
+        let mut elements = provider::collect(ElementKind::Text, config).await?;
+        info!("Found {} text input elements", elements.len());
+        apply_exclusions(&mut elements, config).await?;
+        if !elements.is_empty() || !wait_again(deadline).await {
+            break elements;
+        }
+    };
+
+    if elements.is_empty() {
+        warn!("No text input elements found");
+        eprintln!("No text input fields found.");
+        return Ok(Outcome::NoElements);
+    }
+
+    let hinted_elements = hints::assign_hints(&elements, &config.hints.chars);
+    let result = select_hint(hinted_elements, ActionMode::Text, config).await?;
+
+    let Some((element, _)) = result else {
+        return Ok(Outcome::Cancelled);
+    };
+    // Click to focus the text field
+    act_on_element(&element.element, element.click_position(), ActionMode::Click, config).await?;
+    Ok(Outcome::Selected {
+        position: element.click_position(),
+        element: element.element,
+        mode: ActionMode::Text.name(),
+    })
+}
+
+
+/// A line for each application the last scan found no accessible tree in,
+/// with how to turn accessibility on in it
+///
+/// Background services register without windows and have nothing to expose,
+/// so only applications showing a window are reported when the compositor
+/// can tell.
+pub async fn silent_app_notes() -> Vec<String> {
+    let mut apps = atspi::silent_apps().await;
+    if !apps.is_empty() {
+        if let Ok(Ok(windows)) = tokio::task::spawn_blocking(window::list).await {
+            apps.retain(|(_, pid)| pid.is_none_or(|pid| windows.iter().any(|w| w.pid == Some(pid))));
+        }
+    }
+    apps.into_iter()
+        .map(|(app, _)| format!("{} exposes no AT-SPI tree - {}", app, doctor::enable_hint(&app)))
+        .collect()
+}
@@ -0,0 +1,333 @@
+use crate::click::{self, ClickButton};
+use crate::config::{parse_color, Config};
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::layout::{OutputLayout, OutputRect};
+use crate::overlay::{cancel_source, clear_cancel, wayland_connection};
+use anyhow::{Context, Result};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    reexports::{calloop::EventLoop, calloop_wayland_source::WaylandSource},
+    shm::{Shm, ShmHandler},
+};
+use tracing::{info, warn};
+use wayland_client::{
+    delegate_noop,
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+
+/// Length of each arm of the crosshair around the pointer
+const ARM: i32 = 12;
+/// Space left clear around the pointer, so the target stays visible
+const GAP: i32 = 4;
+/// Thickness of the crosshair
+const THICKNESS: i32 = 2;
+
+/// Where the pointer was left, and the button to click there, if any
+pub type Nudged = ((i32, i32), Option<ClickButton>);
+
+/// Offset a movement key nudges the pointer by
+fn key_offset(key: Keysym, step: i32) -> Option<(i32, i32)> {
+    match key {
+        Keysym::h | Keysym::Left => Some((-step, 0)),
+        Keysym::j | Keysym::Down => Some((0, step)),
+        Keysym::k | Keysym::Up => Some((0, -step)),
+        Keysym::l | Keysym::Right => Some((step, 0)),
+        _ => None,
+    }
+}
+
+/// Button Enter clicks with: Shift for right, Ctrl for middle, as in the hint overlay
+fn enter_button(modifiers: &Modifiers) -> ClickButton {
+    if modifiers.shift {
+        ClickButton::Right
+    } else if modifiers.ctrl {
+        ClickButton::Middle
+    } else {
+        ClickButton::Left
+    }
+}
+
+/// Nudge the pointer from `position` with the keyboard until the user is done
+///
+/// hjkl (or the arrows) move it by `nudge.step`, `nudge.large_step` with Ctrl
+/// held. Enter leaves to click there; Escape or q leave the pointer where it
+/// is. The overlay takes no pointer input, so hover menus stay open.
+pub fn run_nudge_overlay(position: (i32, i32), config: &Config) -> Result<Nudged> {
+    let conn = wayland_connection()?;
+
+    let (globals, event_queue) = registry_queue_init(&conn).context("Failed to init registry")?;
+    let qh = event_queue.handle();
+
+    let compositor = CompositorState::bind(&globals, &qh).context("wl_compositor not available")?;
+    let layer_shell = LayerShell::bind(&globals, &qh).context("layer_shell not available")?;
+    let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
+
+    let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
+    // An empty input region lets the pointer through to the windows below
+    let region = Region::new(&compositor).context("Failed to create an input region")?;
+    surface.set_input_region(Some(region.wl_region()));
+    let layer_surface =
+        layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("vimium-nudge"), None);
+    layer_surface.set_anchor(Anchor::TOP | Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT);
+    layer_surface.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface.commit();
+
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
+
+    let mut state = NudgeState {
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh),
+        shm,
+        buffers,
+        layer_surface: Some(layer_surface),
+        position,
+        click: None,
+        step: config.nudge.step,
+        large_step: config.nudge.large_step,
+        color: parse_color(&config.colors.hint_bg),
+        configured: false,
+        width: 0,
+        height: 0,
+        exit: false,
+        keyboard: None,
+        modifiers: Modifiers::default(),
+        outputs: OutputLayout::default(),
+    };
+
+    let mut event_loop: EventLoop<NudgeState> = EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Move mode cancelled externally");
+            state.exit = true;
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
+    info!("Nudging the pointer. hjkl move, Enter clicks, Escape leaves it.");
+
+    while !state.exit {
+        event_loop.dispatch(None, &mut state).context("Wayland dispatch failed")?;
+    }
+
+    // Same explicit teardown as the hint overlay
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
+
+    Ok((state.position, state.click))
+}
+
+struct NudgeState {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    shm: Shm,
+    buffers: FrameBuffers,
+    layer_surface: Option<LayerSurface>,
+    /// Where the pointer was last moved to, in global coordinates
+    position: (i32, i32),
+    /// Set by Enter
+    click: Option<ClickButton>,
+    step: i32,
+    large_step: i32,
+    color: (u8, u8, u8, u8),
+    configured: bool,
+    width: u32,
+    height: u32,
+    exit: bool,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    modifiers: Modifiers,
+    /// Outputs the overlay is shown on, for translating positions
+    outputs: OutputLayout,
+}
+
+impl NudgeState {
+    /// Draw a crosshair around the pointer on an otherwise clear surface
+    fn draw(&mut self) {
+        if !self.configured || self.width == 0 || self.height == 0 {
+            return;
+        }
+        let Some(layer_surface) = &self.layer_surface else {
+            return;
+        };
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
+        };
+        let (canvas, width, height, scale) = (frame.canvas, frame.width, frame.height, frame.scale as i32);
+        canvas.fill(0);
+
+        let (cx, cy) = self.outputs.to_local(self.position, self.width, self.height);
+        let half = (THICKNESS / 2).max(1);
+        let outer = GAP + ARM;
+        let (r, g, b, a) = self.color;
+        let mut fill = |left: i32, top: i32, right: i32, bottom: i32| {
+            for py in (top / scale).max(0)..(bottom / scale).min(height as i32) {
+                for px in (left / scale).max(0)..(right / scale).min(width as i32) {
+                    let idx = ((py as u32 * width + px as u32) * 4) as usize;
+                    canvas[idx..idx + 4].copy_from_slice(&[b, g, r, a]);
+                }
+            }
+        };
+        fill(cx - outer, cy - half, cx - GAP, cy + half);
+        fill(cx + GAP, cy - half, cx + outer, cy + half);
+        fill(cx - half, cy - outer, cx + half, cy - GAP);
+        fill(cx - half, cy + GAP, cx + half, cy + outer);
+
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
+        layer_surface.commit();
+    }
+
+    fn handle_key(&mut self, key: Keysym) {
+        match key {
+            Keysym::Escape | Keysym::q => {
+                info!("Leaving the pointer at ({}, {})", self.position.0, self.position.1);
+                self.exit = true;
+                return;
+            }
+            Keysym::Return | Keysym::KP_Enter => {
+                self.click = Some(enter_button(&self.modifiers));
+                self.exit = true;
+                return;
+            }
+            _ => {}
+        }
+        let step = if self.modifiers.ctrl { self.large_step } else { self.step };
+        let Some((dx, dy)) = key_offset(key, step) else {
+            return;
+        };
+        let (x, y) = (self.position.0 + dx, self.position.1 + dy);
+        if let Err(e) = click::move_cursor_to(x, y) {
+            warn!("Failed to move the pointer: {}", e);
+            return;
+        }
+        self.position = (x, y);
+    }
+}
+
+impl CompositorHandler for NudgeState {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
+    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.enter(rect);
+            self.draw();
+        }
+    }
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.leave(&rect.name);
+        }
+    }
+}
+
+impl OutputHandler for NudgeState {
+    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl LayerShellHandler for NudgeState {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        self.exit = true;
+    }
+
+    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
+        self.width = configure.new_size.0;
+        self.height = configure.new_size.1;
+        self.configured = true;
+        self.draw();
+    }
+}
+
+impl SeatHandler for NudgeState {
+    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+        }
+    }
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard { self.keyboard = None; }
+    }
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for NudgeState {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        self.handle_key(event.keysym);
+        self.draw();
+    }
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, modifiers: Modifiers, _: u32) {
+        self.modifiers = modifiers;
+    }
+}
+
+impl ShmHandler for NudgeState {
+    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+}
+
+impl ProvidesRegistryState for NudgeState {
+    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(NudgeState);
+delegate_output!(NudgeState);
+delegate_shm!(NudgeState);
+delegate_seat!(NudgeState);
+delegate_keyboard!(NudgeState);
+delegate_layer!(NudgeState);
+delegate_registry!(NudgeState);
+delegate_noop!(NudgeState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(NudgeState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_offset() {
+        assert_eq!(key_offset(Keysym::h, 5), Some((-5, 0)));
+        assert_eq!(key_offset(Keysym::Down, 50), Some((0, 50)));
+        assert_eq!(key_offset(Keysym::x, 5), None);
+
+        let shift = Modifiers {
+            shift: true,
+            ..Default::default()
+        };
+        assert_eq!(enter_button(&Modifiers::default()), ClickButton::Left);
+        assert_eq!(enter_button(&shift), ClickButton::Right);
+    }
+}
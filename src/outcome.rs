@@ -0,0 +1,119 @@
+use crate::atspi::{self, ClickableElement};
+use serde_json::{json, Value};
+use tracing::debug;
+
+/// Exit code when the command failed, e.g. no accessibility bus or no click tool
+pub const EXIT_FAILURE: u8 = 4;
+
+/// How a mode ended, reported through the exit code and `--json-result`
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// An element was picked, then acted on unless only printed
+    Selected {
+        element: ClickableElement,
+        /// Where it was acted on, in global coordinates
+        position: (i32, i32),
+        /// The action taken, e.g. "click" or "right-click"
+        mode: String,
+    },
+    /// The user closed the overlay without picking anything
+    Cancelled,
+    /// There was nothing to hint
+    NoElements,
+}
+
+impl Outcome {
+    /// Process exit code: 0 selected, 1 cancelled, 3 no elements
+    ///
+    /// 2 is left to command-line usage errors and `EXIT_FAILURE` to errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Outcome::Selected { .. } => 0,
+            Outcome::Cancelled => 1,
+            Outcome::NoElements => 3,
+        }
+    }
+
+    /// Fetch the selected element's name, if the pick came before it did
+    ///
+    /// Names are fetched while the hints are shown, so everything reporting
+    /// the outcome (hooks, history, `--json-result`) calls this first.
+    pub async fn resolve_name(&mut self) {
+        let Outcome::Selected { element, .. } = self else {
+            return;
+        };
+        if element.name.is_empty() {
+            if let Err(e) = atspi::resolve_names(std::slice::from_mut(element)).await {
+                debug!("No name for the selected element: {:#}", e);
+            }
+        }
+    }
+
+    /// The outcome as printed by `--json-result`
+    pub fn json(&self) -> Value {
+        match self {
+            Outcome::Selected {
+                element,
+                position,
+                mode,
+            } => json!({
+                "result": "selected",
+                "mode": mode,
+                "position": [position.0, position.1],
+                "element": element_json(element),
+            }),
+            Outcome::Cancelled => json!({ "result": "cancelled" }),
+            Outcome::NoElements => json!({ "result": "no_elements" }),
+        }
+    }
+}
+
+/// An element as printed by `pick` and `--json-result`
+pub fn element_json(element: &ClickableElement) -> Value {
+    json!({
+        "id": element.id(),
+        "role": element.role,
+        "name": element.name,
+        "x": element.x,
+        "y": element.y,
+        "width": element.width,
+        "height": element.height,
+    })
+}
+
+/// The JSON printed by `--json-result` when the command failed
+pub fn failure_json(error: &anyhow::Error) -> Value {
+    json!({ "result": "error", "error": format!("{:#}", error) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome_json() {
+        let selected = Outcome::Selected {
+            element: ClickableElement {
+                name: "Save".to_string(),
+                role: "PushButton".to_string(),
+                x: 10,
+                y: 20,
+                width: 60,
+                height: 24,
+                ..Default::default()
+            },
+            position: (40, 32),
+            mode: "right-click".to_string(),
+        };
+        let json = selected.json();
+        assert_eq!(json["result"], "selected");
+        assert_eq!(json["mode"], "right-click");
+        assert_eq!(json["element"]["name"], "Save");
+        assert_eq!(json["position"], json!([40, 32]));
+        assert_eq!(selected.exit_code(), 0);
+
+        assert_eq!(Outcome::Cancelled.exit_code(), 1);
+        assert_eq!(Outcome::NoElements.json(), json!({ "result": "no_elements" }));
+        assert_eq!(failure_json(&anyhow::anyhow!("no bus"))["error"], "no bus");
+    }
+}
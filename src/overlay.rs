@@ -1,16 +1,29 @@
-use crate::config::{parse_color, ActionMode, Config};
-use crate::hints::{filter_by_prefix, find_exact_match, find_unique_match, HintedElement};
+use crate::activation;
+use crate::history;
+use crate::marks::{self, Mark};
+use crate::config::{parse_color, ActionMode, Config, HintStrategy, PanelPosition, RoleClass};
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::layout::{OutputLayout, OutputRect};
+use crate::atspi::{self, ClickableElement};
+use crate::status::{self, Status};
+use crate::style::{LabelStyle, StyleRules};
+use crate::timings;
+use crate::hints::{
+    app_order, assign_hints_with_strategy, drop_group, filter_by_prefix, find_exact_match, find_unique_match, HintedElement,
+};
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use smithay_client_toolkit::{
+    activation::{ActivationHandler, ActivationState, RequestData},
     compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    delegate_activation, delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
     delegate_registry, delegate_seat, delegate_shm,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
-        pointer::{PointerEvent, PointerHandler},
+        pointer::{PointerEvent, PointerEventKind, PointerHandler},
         Capability, SeatHandler, SeatState,
     },
     shell::{
@@ -20,14 +33,20 @@ use smithay_client_toolkit::{
         },
         WaylandSurface,
     },
-    shm::{slot::SlotPool, Shm, ShmHandler},
+    reexports::{
+        calloop::{channel, ping, EventLoop},
+        calloop_wayland_source::WaylandSource,
+    },
+    shm::{Shm, ShmHandler},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use wayland_client::{
+    delegate_noop,
     globals::registry_queue_init,
-    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface},
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface},
     Connection, QueueHandle,
 };
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
 
 /// Result of the overlay selection
 #[derive(Debug, Clone)]
@@ -36,12 +55,94 @@ pub enum SelectionResult {
     Cancelled,
 }
 
+/// What the overlay waits for after the mark or jump key
+#[derive(Debug, Clone)]
+enum PendingMark {
+    /// The mark key was pressed: the next complete hint picks the element
+    Hint,
+    /// An element was picked to mark, waiting for the letter
+    Set(HintedElement),
+    /// The jump key was pressed, waiting for the letter
+    Jump,
+}
+
+/// Element name resolved after the overlay was shown: (element key, name)
+pub type NameUpdate = (String, String);
+
+/// Element moved while the overlay is shown: (element key, (x, y, width, height))
+type BoundsUpdate = (String, (i32, i32, i32, i32));
+
+/// How long to wait for the compositor to issue an activation token
+const ACTIVATION_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Maximum number of name characters drawn next to a hint
+const MAX_NAME_CHARS: usize = 24;
+
+/// Border colors telling applications apart with `hints.group_by_app`
+const APP_COLORS: [(u8, u8, u8, u8); 8] = [
+    (0x1f, 0x77, 0xb4, 255),
+    (0xd6, 0x27, 0x28, 255),
+    (0x2c, 0xa0, 0x2c, 255),
+    (0x94, 0x67, 0xbd, 255),
+    (0xff, 0x7f, 0x0e, 255),
+    (0x17, 0xbe, 0xcf, 255),
+    (0xe3, 0x77, 0xc2, 255),
+    (0x8c, 0x56, 0x4b, 255),
+];
+
+/// Tooltip lines and characters per line in the `overlay.preview` panel
+const PREVIEW_LINES: usize = 3;
+const PREVIEW_COLUMNS: usize = 60;
+
+/// Sizes of the input box and the mode indicator, and their distance from the edges
+const INPUT_BOX: (u32, u32) = (250, 30);
+const MODE_BOX: (u32, u32) = (180, 25);
+const PANEL_MARGIN: u32 = 10;
+
+/// Top-left corner of a panel, `None` when it is hidden
+pub(crate) type PanelOrigin = Option<(u32, u32)>;
+
+/// Rejected elements this close to a matching hint stay outlined while typing
+const DEBUG_NEAR: i32 = 150;
+
+/// Outline and label color of rejected elements with `overlay.debug`
+const REJECTED_COLOR: (u8, u8, u8, u8) = (0xd6, 0x27, 0x28, 255);
+
+/// Diagnostics drawn over the hints with `overlay.debug` (`--debug-overlay`)
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Time from the start of the scan until the hints were ready
+    pub scan_time: std::time::Duration,
+    /// Application names by bus name
+    pub app_names: HashMap<String, String>,
+    /// Elements found but not hinted, with the filter that dropped each
+    pub rejected: Vec<(ClickableElement, String)>,
+}
+
+/// Diagnostics for the next overlay, see `set_debug_info`
+static DEBUG_INFO: std::sync::Mutex<Option<DebugInfo>> = std::sync::Mutex::new(None);
+
+/// Show `info` in the next overlay, if `overlay.debug` is set
+pub fn set_debug_info(info: DebugInfo) {
+    *DEBUG_INFO.lock().unwrap_or_else(|e| e.into_inner()) = Some(info);
+}
+
+/// Create a channel for streaming element names into a running overlay
+pub fn name_channel() -> (channel::Sender<NameUpdate>, channel::Channel<NameUpdate>) {
+    channel::channel()
+}
+
 /// Show the overlay and wait for user selection
+///
+/// If `names` is given, names arriving on it are filled into the matching
+/// hints while the overlay is up.
 pub async fn show_and_select(
     elements: Vec<HintedElement>,
     config: Config,
+    names: Option<channel::Channel<NameUpdate>>,
 ) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
-    let result = tokio::task::spawn_blocking(move || run_overlay(elements, config)).await??;
+    let result =
+        tokio::task::spawn_blocking(move || run_overlay(elements, config, names)).await??;
 
     match result {
         SelectionResult::Selected(elem, action) => Ok(Some((elem, action))),
@@ -49,18 +150,80 @@ pub async fn show_and_select(
     }
 }
 
-fn run_overlay(elements: Vec<HintedElement>, config: Config) -> Result<SelectionResult> {
+/// Wayland connection reused by every overlay a long-running process shows
+static WAYLAND_CONNECTION: std::sync::Mutex<Option<Connection>> = std::sync::Mutex::new(None);
+
+/// Get the shared Wayland connection, reconnecting if the old one broke
+pub fn wayland_connection() -> Result<Connection> {
+    let mut cached = WAYLAND_CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(conn) = cached.as_ref() {
+        if conn.flush().is_ok() {
+            return Ok(conn.clone());
+        }
+        debug!("Wayland connection broke, reconnecting");
+    }
     let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+    *cached = Some(conn.clone());
+    Ok(conn)
+}
+
+/// Wakes the overlay currently shown, see `cancel`
+static CANCEL: std::sync::Mutex<Option<ping::Ping>> = std::sync::Mutex::new(None);
+
+/// Set from `cancel_source` until the overlay's teardown finished
+static SHOWN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Event source firing when `cancel` is called while an overlay is shown
+pub fn cancel_source() -> Result<ping::PingSource> {
+    let (ping, source) = ping::make_ping().context("Failed to create cancel source")?;
+    *CANCEL.lock().unwrap_or_else(|e| e.into_inner()) = Some(ping);
+    SHOWN.store(true, std::sync::atomic::Ordering::SeqCst);
+    Ok(source)
+}
+
+/// Forget the cancel source of an overlay that closed and was torn down
+pub fn clear_cancel() {
+    CANCEL.lock().unwrap_or_else(|e| e.into_inner()).take();
+    SHOWN.store(false, std::sync::atomic::Ordering::SeqCst);
+}
 
-    let (globals, mut event_queue) =
+/// Whether an overlay is shown or still being torn down
+pub fn is_shown() -> bool {
+    SHOWN.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Close the overlay currently shown, returning whether there was one
+pub fn cancel() -> bool {
+    match CANCEL.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        Some(ping) => {
+            ping.ping();
+            true
+        }
+        None => false,
+    }
+}
+
+fn run_overlay(
+    elements: Vec<HintedElement>,
+    config: Config,
+    names: Option<channel::Channel<NameUpdate>>,
+) -> Result<SelectionResult> {
+    let mut setup = Some(std::time::Instant::now());
+    let conn = wayland_connection()?;
+
+    let (globals, event_queue) =
         registry_queue_init(&conn).context("Failed to init registry")?;
     let qh = event_queue.handle();
 
     let compositor = CompositorState::bind(&globals, &qh).context("wl_compositor not available")?;
     let layer_shell = LayerShell::bind(&globals, &qh).context("layer_shell not available")?;
     let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
+    let activation = ActivationState::bind(&globals, &qh)
+        .inspect_err(|e| debug!("No xdg-activation, programs started for a selection may not get focus: {}", e))
+        .ok();
 
     let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
 
     let layer_surface = layer_shell.create_layer_surface(
         &qh,
@@ -75,48 +238,177 @@ fn run_overlay(elements: Vec<HintedElement>, config: Config) -> Result<Selection
     layer_surface.set_exclusive_zone(-1);
     layer_surface.commit();
 
-    let pool = SlotPool::new(256 * 256 * 4, &shm).context("Failed to create buffer pool")?;
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
 
     let bg_color = parse_color(&config.colors.background);
-    let hint_bg_color = parse_color(&config.colors.hint_bg);
-    let hint_text_color = parse_color(&config.colors.hint_text);
-    let hint_matched_color = parse_color(&config.colors.hint_text_matched);
+    let styles = StyleRules::new(&config);
     let input_bg_color = parse_color(&config.colors.input_bg);
     let input_text_color = parse_color(&config.colors.input_text);
 
+    let candidates: Vec<_> = elements.iter().map(|e| e.element.clone()).collect();
+    let apps = app_order(&candidates);
+
+    // Tooltips are fetched for the best match only, on the runtime that
+    // called `show_and_select`
+    let (tooltip_tx, tooltip_rx) = channel::channel::<NameUpdate>();
+    let tooltip_fetch = config
+        .overlay
+        .preview
+        .then(tokio::runtime::Handle::try_current)
+        .and_then(|handle| handle.ok())
+        .map(|handle| (handle, tooltip_tx));
+
     let mut state = OverlayState {
         registry_state: RegistryState::new(&globals),
         seat_state: SeatState::new(&globals, &qh),
         output_state: OutputState::new(&globals, &qh),
         shm,
-        pool,
+        buffers,
         layer_surface: Some(layer_surface),
         elements,
+        candidates,
+        apps,
+        app_filter: None,
         input_buffer: String::new(),
+        status: status::Publisher::default(),
         result: None,
         configured: false,
         width: 0,
         height: 0,
         exit: false,
+        needs_redraw: false,
+        last_draw: std::time::Instant::now(),
+        picking_role: config.behavior.role_picker,
+        outputs: OutputLayout::default(),
+        cycled_action: None,
+        pending_mark: None,
+        runtime: tokio::runtime::Handle::try_current().ok(),
         keyboard: None,
+        pointer: None,
+        seat: None,
+        key_serial: None,
+        activation,
+        activation_token: None,
         modifiers: Modifiers::default(),
+        debug: if config.overlay.debug {
+            DEBUG_INFO.lock().unwrap_or_else(|e| e.into_inner()).take()
+        } else {
+            None
+        },
+        tooltips: HashMap::new(),
+        tooltip_fetch,
         config,
         bg_color,
-        hint_bg_color,
-        hint_text_color,
-        hint_matched_color,
+        styles,
+        placements: None,
         input_bg_color,
         input_text_color,
     };
 
+    let mut event_loop: EventLoop<OverlayState> =
+        EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+
+    if let Some(names) = names {
+        event_loop
+            .handle()
+            .insert_source(names, |event, _, state| {
+                if let channel::Event::Msg((key, name)) = event {
+                    state.update_name(&key, name);
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to watch name updates: {}", e.error))?;
+    }
+
+    if state.tooltip_fetch.is_some() {
+        event_loop
+            .handle()
+            .insert_source(tooltip_rx, |event, _, state| {
+                if let channel::Event::Msg((key, tooltip)) = event {
+                    state.tooltips.insert(key, Some(tooltip));
+                    state.needs_redraw = true;
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to watch tooltips: {}", e.error))?;
+    }
+
+    // With `overlay.follow_windows`, hints move along with their window
+    let follow = match tokio::runtime::Handle::try_current() {
+        Ok(runtime) if state.config.overlay.follow_windows => {
+            let (bounds_tx, bounds_rx) = channel::channel::<BoundsUpdate>();
+            event_loop
+                .handle()
+                .insert_source(bounds_rx, |event, _, state| {
+                    if let channel::Event::Msg((key, bounds)) = event {
+                        state.update_bounds(&key, bounds);
+                    }
+                })
+                .map_err(|e| anyhow::anyhow!("Failed to watch window moves: {}", e.error))?;
+            let elements = state.candidates.clone();
+            Some(runtime.spawn(async move {
+                let result = atspi::watch_bounds(elements, |key, bounds| bounds_tx.send((key, bounds)).is_ok()).await;
+                if let Err(e) = result {
+                    debug!("Hints won't follow window moves: {:#}", e);
+                }
+            }))
+        }
+        _ => None,
+    };
+
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Overlay cancelled externally");
+            state.result = Some(SelectionResult::Cancelled);
+            state.exit = true;
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
     info!("Overlay started, waiting for input...");
-    info!("Modifiers: Shift=right-click, Ctrl=middle-click");
+    info!("Modifiers: Shift=right-click, Ctrl=middle-click, cycle key switches the action");
 
     while !state.exit {
-        event_queue
-            .blocking_dispatch(&mut state)
+        event_loop
+            .dispatch(state.redraw_delay(), &mut state)
             .context("Wayland dispatch failed")?;
+        if let Some(started) = setup.filter(|_| state.configured) {
+            timings::record(timings::OVERLAY, started.elapsed());
+            setup = None;
+        }
+
+        if state.redraw_delay() == Some(std::time::Duration::ZERO) {
+            state.needs_redraw = false;
+            state.draw(&qh);
+        }
+    }
+
+    if let Some(follow) = follow {
+        follow.abort();
+    }
+
+    // A token for the key press that selected lets a window opened for the
+    // selection (e.g. by a hook or xdg-open) take focus
+    if matches!(state.result, Some(SelectionResult::Selected(..))) && state.request_activation_token(&qh) {
+        let deadline = std::time::Instant::now() + ACTIVATION_TIMEOUT;
+        while state.activation_token.is_none() {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            if left.is_zero() {
+                debug!("The compositor issued no activation token");
+                break;
+            }
+            event_loop.dispatch(left, &mut state).context("Wayland dispatch failed")?;
+        }
     }
+    activation::set_token(state.activation_token.take());
+
+    // Tear down explicitly so the compositor unmaps the surface and frees
+    // the shared memory right away instead of whenever the process exits
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
 
     state.result.ok_or_else(|| anyhow::anyhow!("No result"))
 }
@@ -126,22 +418,60 @@ struct OverlayState {
     seat_state: SeatState,
     output_state: OutputState,
     shm: Shm,
-    pool: SlotPool,
+    buffers: FrameBuffers,
     layer_surface: Option<LayerSurface>,
     elements: Vec<HintedElement>,
+    /// Elements that can be hinted before narrowing to one application
+    candidates: Vec<ClickableElement>,
+    /// Bus names of the applications owning `candidates`
+    apps: Vec<String>,
+    /// Index into `apps` when only one application's hints are shown
+    app_filter: Option<usize>,
     input_buffer: String,
+    /// Mode, input and match count for status bars
+    status: status::Publisher,
     result: Option<SelectionResult>,
     configured: bool,
     width: u32,
     height: u32,
     exit: bool,
+    /// Set when state changed outside of a Wayland event (e.g. a name arrived)
+    needs_redraw: bool,
+    /// When the overlay was last drawn, for `overlay.redraw_interval_ms`
+    last_draw: std::time::Instant,
+    /// Waiting for a role class key before showing hints
+    picking_role: bool,
+    /// Outputs the overlay is shown on, for translating selections
+    outputs: OutputLayout,
+    /// Action chosen with the cycle key, used when no modifier is held
+    cycled_action: Option<ActionMode>,
+    /// Set by the mark or jump key, waiting for a hint or the letter
+    pending_mark: Option<PendingMark>,
+    /// The runtime that called `show_and_select`, for saving and finding marks
+    runtime: Option<tokio::runtime::Handle>,
     keyboard: Option<wl_keyboard::WlKeyboard>,
+    /// Only taken with `overlay.hide_cursor`, to blank the cursor over the overlay
+    pointer: Option<wl_pointer::WlPointer>,
+    /// Seat of the keyboard and serial of its last key press, for activation tokens
+    seat: Option<wl_seat::WlSeat>,
+    key_serial: Option<u32>,
+    activation: Option<ActivationState>,
+    /// Issued after a selection, see `request_activation_token`
+    activation_token: Option<String>,
     modifiers: Modifiers,
+    /// Scan diagnostics, with `overlay.debug`
+    debug: Option<DebugInfo>,
+    /// Tooltips by element key; `None` while fetching or when there is none
+    tooltips: HashMap<String, Option<String>>,
+    /// Where tooltip lookups run and report back, with `overlay.preview`
+    tooltip_fetch: Option<(tokio::runtime::Handle, channel::Sender<NameUpdate>)>,
     config: Config,
     bg_color: (u8, u8, u8, u8),
-    hint_bg_color: (u8, u8, u8, u8),
-    hint_text_color: (u8, u8, u8, u8),
-    hint_matched_color: (u8, u8, u8, u8),
+    /// `[[hints.rules]]`, resolved per element when the hints change
+    styles: StyleRules,
+    /// Label placements by hint at a buffer scale, laid out again when the
+    /// hints or the scale change
+    placements: Option<(u32, HashMap<String, Placement>)>,
     input_bg_color: (u8, u8, u8, u8),
     input_text_color: (u8, u8, u8, u8),
 }
@@ -152,25 +482,18 @@ impl OverlayState {
             return;
         }
 
+        let preview = self.preview_element();
         let layer_surface = match &self.layer_surface {
             Some(ls) => ls,
             None => return,
         };
 
-        let width = self.width;
-        let height = self.height;
-        let stride = width * 4;
-
-        let (buffer, canvas) = match self
-            .pool
-            .create_buffer(width as i32, height as i32, stride as i32, wl_shm::Format::Argb8888)
-        {
-            Ok(b) => b,
-            Err(e) => {
-                debug!("Failed to create buffer: {}", e);
-                return;
-            }
+        self.last_draw = std::time::Instant::now();
+        let action = self.get_action_from_modifiers().unwrap_or(ActionMode::Click);
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
         };
+        let (canvas, width, height, scale) = (frame.canvas, frame.width, frame.height, frame.scale);
 
         // Clear with background color
         let (r, g, b, a) = self.bg_color;
@@ -181,75 +504,446 @@ impl OverlayState {
             pixel[3] = a;
         }
 
+        if self.picking_role {
+            draw_role_picker(
+                canvas,
+                width,
+                height,
+                &self.config.role_picker.classes,
+                self.input_bg_color,
+                self.input_text_color,
+            );
+            self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
+            layer_surface.commit();
+            return;
+        }
+
         // Draw hint labels
         let filtered = filter_by_prefix(&self.elements, &self.input_buffer);
+        self.status.set(Status {
+            mode: Some(action.label().to_lowercase()),
+            input: self.input_buffer.clone(),
+            matches: filtered.len(),
+        });
         let prefix_len = self.input_buffer.len();
-        let padding = self.config.hints.padding;
+        let show_names = self.config.behavior.show_element_names;
+        let uppercase = self.config.hints.uppercase_labels;
+        if !matches!(self.placements, Some((laid_out, _)) if laid_out == scale) {
+            let placements = place_labels(&self.elements, &self.styles, scale, show_names, uppercase);
+            self.placements = Some((scale, placements));
+        }
+        let Some((_, placements)) = &self.placements else {
+            return;
+        };
 
         for elem in &filtered {
-            draw_hint(
+            let Some(placement) = placements.get(&elem.hint) else {
+                continue;
+            };
+            let border = if self.config.hints.group_by_app {
+                self.apps
+                    .iter()
+                    .position(|app| *app == elem.element.bus_name)
+                    .map(|i| APP_COLORS[i % APP_COLORS.len()])
+            } else {
+                None
+            };
+            draw_label(canvas, width, height, elem, placement, prefix_len, show_names, uppercase, border);
+        }
+
+        if let Some(debug) = &self.debug {
+            draw_debug(
+                canvas,
+                width,
+                height,
+                scale,
+                debug,
+                &filtered,
+                self.elements.len(),
+                !self.input_buffer.is_empty(),
+                self.input_text_color,
+            );
+        }
+
+        if let Some(element) = &preview {
+            let tooltip = self.tooltips.get(&element.key()).cloned().flatten();
+            draw_preview(
                 canvas,
                 width,
                 height,
-                elem,
-                prefix_len,
-                padding,
-                self.hint_bg_color,
-                self.hint_text_color,
-                self.hint_matched_color,
+                element,
+                tooltip.as_deref(),
+                self.input_bg_color,
+                self.input_text_color,
             );
         }
 
-        // Draw input display
-        draw_input_display(
-            canvas,
+        let (input_origin, mode_origin) = panel_origins(
+            self.config.overlay.input_position,
+            self.config.overlay.mode_position,
             width,
             height,
-            &self.input_buffer,
-            self.input_bg_color,
-            self.input_text_color,
         );
 
+        // Draw input display, with the application shown when narrowed to one
+        if let Some(origin) = input_origin {
+            let input_text = match self.app_filter {
+                Some(i) => format!("{}_ [app {}/{}]", self.input_buffer, i + 1, self.apps.len()),
+                None => format!("{}_", self.input_buffer),
+            };
+            draw_input_display(
+                canvas,
+                width,
+                height,
+                origin,
+                "Input: ",
+                &input_text,
+                self.input_bg_color,
+                self.input_text_color,
+            );
+        }
+
         // Draw modifier indicator
-        let mode_text = if self.modifiers.shift {
-            "Mode: Right-Click"
-        } else if self.modifiers.ctrl {
-            "Mode: Middle-Click"
-        } else {
-            "Mode: Click"
-        };
-        draw_modifier_indicator(
-            canvas,
-            width,
-            height,
-            mode_text,
-            self.input_bg_color,
-            self.input_text_color,
-        );
+        if let Some(origin) = mode_origin {
+            let mode_text = format!("Mode: {}", action.label());
+            draw_modifier_indicator(
+                canvas,
+                width,
+                height,
+                origin,
+                &mode_text,
+                self.input_bg_color,
+                self.input_text_color,
+            );
+        }
 
-        layer_surface.wl_surface().attach(Some(buffer.wl_buffer()), 0, 0);
-        layer_surface.wl_surface().damage_buffer(0, 0, width as i32, height as i32);
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
         layer_surface.commit();
     }
 
+    /// Fill in a name fetched after the overlay was shown
+    fn update_name(&mut self, key: &str, name: String) {
+        if let Some(elem) = self.elements.iter_mut().find(|e| e.element.key() == key) {
+            debug!("Name for hint {}: {}", elem.hint, name);
+            elem.element.name = name;
+            self.placements = None;
+            self.needs_redraw = self.config.behavior.show_element_names && self.config.overlay.live_names;
+        }
+    }
+
+    /// The best match shown with `overlay.preview`, its tooltip looked up
+    /// in the background the first time it is shown
+    fn preview_element(&mut self) -> Option<ClickableElement> {
+        if !self.config.overlay.preview || self.picking_role {
+            return None;
+        }
+        let element = filter_by_prefix(&self.elements, &self.input_buffer)
+            .into_iter()
+            .find(|e| !self.styles.resolve(&e.element).hidden)?
+            .element
+            .clone();
+        let key = element.key();
+        if let (Some((runtime, tx)), false) = (&self.tooltip_fetch, self.tooltips.contains_key(&key)) {
+            self.tooltips.insert(key.clone(), None);
+            let (tx, element) = (tx.clone(), element.clone());
+            runtime.spawn(async move {
+                match atspi::tooltip(&element).await {
+                    Ok(Some(tooltip)) => {
+                        let _ = tx.send((key, tooltip));
+                    }
+                    Ok(None) => {}
+                    Err(e) => debug!("No tooltip for {}: {:#}", key, e),
+                }
+            });
+        }
+        Some(element)
+    }
+
+    /// Move the hint of an element whose window moved, see `overlay.follow_windows`
+    fn update_bounds(&mut self, key: &str, (x, y, width, height): (i32, i32, i32, i32)) {
+        let hinted = self.elements.iter_mut().map(|h| &mut h.element);
+        for element in hinted.chain(self.candidates.iter_mut()).filter(|e| e.key() == key) {
+            (element.x, element.y, element.width, element.height) = (x, y, width, height);
+            self.placements = None;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Time until a pending redraw may run, throttled by `overlay.redraw_interval_ms`
+    fn redraw_delay(&self) -> Option<std::time::Duration> {
+        if !self.needs_redraw {
+            return None;
+        }
+        let interval = std::time::Duration::from_millis(self.config.overlay.redraw_interval_ms);
+        Some(interval.saturating_sub(self.last_draw.elapsed()))
+    }
+
+    /// Restrict hints to the role class bound to `key`, leaving the role picker
+    fn pick_role_class(&mut self, key: char) {
+        let Some(class) = self.config.role_picker.classes.iter().find(|c| c.key == key) else {
+            return;
+        };
+
+        let elements: Vec<_> = self
+            .elements
+            .iter()
+            .filter(|e| class.matches(&e.element.role))
+            .map(|e| e.element.clone())
+            .collect();
+        if elements.is_empty() {
+            info!("No {} found, keeping role picker open", class.label);
+            return;
+        }
+
+        info!("Picked {}: {} elements", class.label, elements.len());
+        self.apps = app_order(&elements);
+        self.candidates = elements;
+        self.app_filter = None;
+        self.apply_app_filter();
+        self.picking_role = false;
+    }
+
+    /// Show the next application's hints alone, then all of them again
+    fn cycle_app(&mut self) {
+        if self.apps.len() < 2 {
+            return;
+        }
+        self.app_filter = match self.app_filter {
+            None => Some(0),
+            Some(i) if i + 1 < self.apps.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        debug!("Showing hints for app {:?} of {}", self.app_filter, self.apps.len());
+        self.apply_app_filter();
+    }
+
+    /// Re-hint the candidates owned by the selected application
+    fn apply_app_filter(&mut self) {
+        let elements: Vec<_> = match self.app_filter {
+            Some(i) => self
+                .candidates
+                .iter()
+                .filter(|e| e.bus_name == self.apps[i])
+                .cloned()
+                .collect(),
+            None => self.candidates.clone(),
+        };
+        self.elements = assign_hints_with_strategy(&elements, &self.config.hints, self.width);
+        self.placements = None;
+        self.input_buffer.clear();
+    }
+
+    /// Action overriding the mode: held modifiers first, then the cycle key
     fn get_action_from_modifiers(&self) -> Option<ActionMode> {
         if self.modifiers.shift {
             Some(ActionMode::RightClick)
         } else if self.modifiers.ctrl {
             Some(ActionMode::MiddleClick)
         } else {
-            None
+            self.cycled_action
         }
     }
 
+    /// Ask for an xdg-activation token tied to the last key press, returning
+    /// whether one was requested
+    fn request_activation_token(&self, qh: &QueueHandle<Self>) -> bool {
+        let (Some(activation), Some(seat), Some(serial), Some(layer_surface)) =
+            (&self.activation, &self.seat, self.key_serial, &self.layer_surface)
+        else {
+            return false;
+        };
+        activation.request_token(
+            qh,
+            RequestData {
+                app_id: Some("vimium-linux".to_string()),
+                seat_and_serial: Some((seat.clone(), serial)),
+                surface: Some(layer_surface.wl_surface().clone()),
+            },
+        );
+        true
+    }
+
+    /// Finish with `elem`, reporting it in global coordinates
     fn select_element(&mut self, elem: &HintedElement) {
         let action = self.get_action_from_modifiers();
         info!("Selected: {} ({}) with action {:?}", elem.hint, elem.element.name, action);
-        self.result = Some(SelectionResult::Selected(elem.clone(), action));
+        let mut elem = elem.clone();
+        (elem.element.x, elem.element.y) =
+            self.outputs.to_global((elem.element.x, elem.element.y), self.width, self.height);
+        self.result = Some(SelectionResult::Selected(elem, action));
         self.exit = true;
     }
 
+    /// Select a fully typed hint, or pick it to mark after the mark key
+    fn complete_hint(&mut self, elem: &HintedElement) {
+        // Hidden by a rule once its name arrived, so never drawn
+        if self.styles.resolve(&elem.element).hidden {
+            debug!("{} is hidden by a rule", elem.hint);
+            return;
+        }
+        if matches!(self.pending_mark, Some(PendingMark::Hint)) {
+            info!("Marking {}, press a letter", elem.hint);
+            self.pending_mark = Some(PendingMark::Set(elem.clone()));
+            self.input_buffer.clear();
+        } else {
+            self.select_element(elem);
+        }
+    }
+
+    /// `element` with its position in global coordinates
+    fn global(&self, element: &ClickableElement) -> ClickableElement {
+        let mut element = element.clone();
+        (element.x, element.y) = self.outputs.to_global((element.x, element.y), self.width, self.height);
+        element
+    }
+
+    /// Save `elem` as mark `letter` of its application
+    fn set_mark(&mut self, elem: &HintedElement, letter: char) {
+        let Some(runtime) = &self.runtime else {
+            return;
+        };
+        let mut element = self.global(&elem.element);
+        let saved = runtime.block_on(async {
+            atspi::resolve_names(std::slice::from_mut(&mut element)).await?;
+            let apps = atspi::app_names(std::slice::from_ref(&element.bus_name)).await?;
+            let app = apps.get(&element.bus_name).unwrap_or(&element.bus_name);
+            marks::set(app, letter, Mark::new(&element))
+        });
+        match saved {
+            Ok(()) => info!("Marked {} {:?} as {}", element.role, element.name, letter),
+            Err(e) => warn!("Failed to save mark: {:#}", e),
+        }
+    }
+
+    /// Select the element marked `letter` in its application, if it's hinted
+    fn jump_to_mark(&mut self, letter: char) {
+        let entries = marks::named(&marks::load(), letter);
+        let Some(runtime) = self.runtime.as_ref().filter(|_| !entries.is_empty()) else {
+            info!("No mark {}", letter);
+            return;
+        };
+        let apps = runtime.block_on(atspi::app_names(&self.apps)).unwrap_or_default();
+        let found = self.elements.iter().find(|h| {
+            let element = self.global(&h.element);
+            let app = apps.get(&element.bus_name).unwrap_or(&element.bus_name);
+            entries.iter().any(|entry| &entry.app == app && entry.covers(&element))
+        });
+        match found.cloned() {
+            Some(elem) => self.select_element(&elem),
+            None => info!("Mark {} is not among the hints", letter),
+        }
+    }
+
+    /// Select the element under the last selection's position with its action
+    fn repeat_last(&mut self) {
+        let Some(entry) = history::last() else {
+            debug!("No selection to repeat");
+            return;
+        };
+        let found = self.elements.iter().find(|h| entry.covers(&self.global(&h.element)));
+        let Some(elem) = found.cloned() else {
+            info!("{} {:?} is not among the hints", entry.role, entry.name);
+            return;
+        };
+        self.cycled_action = entry.action();
+        self.select_element(&elem);
+    }
+
+    /// Carry out what a plugin's key handler asked for
+    #[cfg(feature = "lua")]
+    fn plugin_key(&mut self, outcome: crate::plugins::KeyOutcome) {
+        use crate::plugins::KeyOutcome;
+        match outcome {
+            KeyOutcome::Handled => {}
+            KeyOutcome::Cancel => {
+                info!("Cancelled by a plugin");
+                self.result = Some(SelectionResult::Cancelled);
+                self.exit = true;
+            }
+            KeyOutcome::Select(hint, action) => {
+                let Some(elem) = self.elements.iter().find(|e| e.hint == hint).cloned() else {
+                    info!("Plugin picked {} which is not among the hints", hint);
+                    return;
+                };
+                if action.is_some() {
+                    self.cycled_action = action;
+                }
+                self.select_element(&elem);
+            }
+        }
+    }
+
     fn handle_key(&mut self, key: Keysym) {
+        if self.picking_role && key != Keysym::Escape {
+            if let Some(ch) = keysym_to_char(key) {
+                self.pick_role_class(ch);
+            }
+            return;
+        }
+
+        match self.pending_mark.take() {
+            Some(PendingMark::Hint) if key == Keysym::Escape => {
+                debug!("Not marking after all");
+                self.input_buffer.clear();
+                return;
+            }
+            // Hint keys are handled as usual until one is complete
+            Some(PendingMark::Hint) => self.pending_mark = Some(PendingMark::Hint),
+            Some(pending) => {
+                match (pending, key.key_char().filter(|&c| marks::valid_letter(c))) {
+                    (PendingMark::Set(elem), Some(letter)) => self.set_mark(&elem, letter),
+                    (_, Some(letter)) => self.jump_to_mark(letter),
+                    (_, None) => debug!("Not a mark letter, dropping the pending mark"),
+                }
+                return;
+            }
+            None if key.key_char() == Some(self.config.behavior.mark_key) => {
+                self.pending_mark = Some(PendingMark::Hint);
+                self.input_buffer.clear();
+                return;
+            }
+            None if key.key_char() == Some(self.config.behavior.jump_key) => {
+                self.pending_mark = Some(PendingMark::Jump);
+                return;
+            }
+            None => {}
+        }
+
+        // Editing sits on Backspace, as Shift and Ctrl with a hint character pick the click button
+        if key == Keysym::BackSpace && (self.modifiers.shift || self.modifiers.ctrl) {
+            if self.modifiers.shift {
+                self.input_buffer.clear();
+            } else {
+                let end = drop_group(&self.elements, &self.input_buffer);
+                self.input_buffer.truncate(end);
+            }
+            debug!("Input now: {}", self.input_buffer);
+            return;
+        }
+
+        if key.key_char() == Some(self.config.behavior.cycle_app_key) {
+            self.cycle_app();
+            return;
+        }
+        if key.key_char() == Some(self.config.behavior.repeat_key) {
+            self.repeat_last();
+            return;
+        }
+        if key.key_char() == Some(self.config.behavior.cycle_action_key) {
+            let next = self.cycled_action.unwrap_or(ActionMode::Click).next_in_cycle();
+            debug!("Pending action is now {:?}", next);
+            self.cycled_action = Some(next);
+            return;
+        }
+        #[cfg(feature = "lua")]
+        if let Some(ch) = key.key_char() {
+            let matching = filter_by_prefix(&self.elements, &self.input_buffer);
+            if let Some(outcome) = crate::plugins::handle_key(ch, &self.input_buffer, &matching) {
+                self.plugin_key(outcome);
+                return;
+            }
+        }
+
         match key {
             Keysym::Escape => {
                 info!("Escape pressed, cancelling");
@@ -266,7 +960,7 @@ impl OverlayState {
                     .cloned();
 
                 if let Some(elem) = selected {
-                    self.select_element(&elem);
+                    self.complete_hint(&elem);
                 }
             }
             _ => {
@@ -277,7 +971,7 @@ impl OverlayState {
                     if self.config.behavior.auto_select {
                         let selected = find_exact_match(&self.elements, &self.input_buffer).cloned();
                         if let Some(elem) = selected {
-                            self.select_element(&elem);
+                            self.complete_hint(&elem);
                         }
                     }
                 }
@@ -288,29 +982,120 @@ impl OverlayState {
 
 // Standalone drawing functions to avoid borrow checker issues
 
-fn draw_hint(
+/// Map a hint's position into a buffer downscaled by `scale`
+pub fn scale_hint(elem: &HintedElement, scale: u32) -> HintedElement {
+    let mut scaled = elem.clone();
+    scaled.element.x /= scale as i32;
+    scaled.element.y /= scale as i32;
+    scaled.element.width /= scale as i32;
+    scaled.element.height /= scale as i32;
+    scaled
+}
+
+/// A hint label's resolved style and its box in buffer pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub style: LabelStyle,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Characters of a label: the hint, and the name when shown
+fn label_chars(elem: &HintedElement, show_name: bool, uppercase: bool) -> (Vec<char>, Vec<char>) {
+    let hint_chars: Vec<char> = if uppercase {
+        elem.hint.to_uppercase().chars().collect()
+    } else {
+        elem.hint.to_lowercase().chars().collect()
+    };
+
+    // Names may still be empty while they are being fetched
+    let name_chars: Vec<char> = if show_name {
+        elem.element.name.chars().take(MAX_NAME_CHARS).collect()
+    } else {
+        Vec::new()
+    };
+    (hint_chars, name_chars)
+}
+
+/// Where `elem`'s label goes when drawn with `style`
+pub fn place_label(elem: &HintedElement, style: LabelStyle, show_name: bool, uppercase: bool) -> Placement {
+    let (hint_chars, name_chars) = label_chars(elem, show_name, uppercase);
+    let label_len = if name_chars.is_empty() {
+        hint_chars.len()
+    } else {
+        hint_chars.len() + 1 + name_chars.len()
+    };
+
+    let glyph = Glyph::at(style.font_size);
+    let width: u32 = style.padding * 2 + (label_len as u32 * glyph.advance);
+    let height: u32 = style.padding * 2 + glyph.height;
+    let (x, y) = style.origin(&elem.element, width, height);
+    Placement {
+        style,
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+/// Placements of every hint not hidden by a rule, by hint, in a buffer of `scale`
+fn place_labels(
+    elements: &[HintedElement],
+    styles: &StyleRules,
+    scale: u32,
+    show_names: bool,
+    uppercase: bool,
+) -> HashMap<String, Placement> {
+    let placements: HashMap<String, Placement> = elements
+        .iter()
+        .filter_map(|elem| {
+            let style = styles.resolve(&elem.element);
+            if style.hidden {
+                return None;
+            }
+            let scaled;
+            let elem = if scale > 1 {
+                scaled = scale_hint(elem, scale);
+                &scaled
+            } else {
+                elem
+            };
+            Some((elem.hint.clone(), place_label(elem, style, show_names, uppercase)))
+        })
+        .collect();
+    debug!("Laid out {} hint labels", placements.len());
+    placements
+}
+
+/// Draw `elem`'s label at a placement worked out by `place_label`
+#[allow(clippy::too_many_arguments)]
+pub fn draw_label(
     canvas: &mut [u8],
     width: u32,
     height: u32,
     elem: &HintedElement,
+    placement: &Placement,
     prefix_len: usize,
-    padding: u32,
-    hint_bg_color: (u8, u8, u8, u8),
-    hint_text_color: (u8, u8, u8, u8),
-    hint_matched_color: (u8, u8, u8, u8),
+    show_name: bool,
+    uppercase: bool,
+    border: Option<(u8, u8, u8, u8)>,
 ) {
-    let x = elem.element.x as u32;
-    let y = elem.element.y as u32;
-
-    let char_width = 8u32;
-    let char_height = 12u32;
-    let box_width: u32 = padding * 2 + (elem.hint.len() as u32 * char_width);
-    let box_height: u32 = padding * 2 + char_height;
-
-    let hint_chars: Vec<char> = elem.hint.chars().collect();
+    let (hint_chars, name_chars) = label_chars(elem, show_name, uppercase);
+    let Placement {
+        style,
+        x,
+        y,
+        width: box_width,
+        height: box_height,
+    } = *placement;
+    let padding = style.padding;
+    let glyph = Glyph::at(style.font_size);
 
     // Draw background
-    let (hr, hg, hb, ha) = hint_bg_color;
+    let (hr, hg, hb, ha) = style.bg;
     for dy in 0..box_height {
         for dx in 0..box_width {
             let px = x.saturating_add(dx);
@@ -328,36 +1113,264 @@ fn draw_hint(
         }
     }
 
+    // Tint the edge of the box with the application's color
+    if let Some((br, bg, bb, ba)) = border {
+        const BORDER: u32 = 2;
+        for dy in 0..box_height {
+            for dx in 0..box_width {
+                let edge = dx < BORDER || dy < BORDER || dx + BORDER >= box_width || dy + BORDER >= box_height;
+                let px = x.saturating_add(dx);
+                let py = y.saturating_add(dy);
+                if edge && px < width && py < height {
+                    let idx = ((py * width + px) * 4) as usize;
+                    if idx + 3 < canvas.len() {
+                        canvas[idx] = bb;
+                        canvas[idx + 1] = bg;
+                        canvas[idx + 2] = br;
+                        canvas[idx + 3] = ba;
+                    }
+                }
+            }
+        }
+    }
+
     // Draw text
     for (i, ch) in hint_chars.iter().enumerate() {
-        let char_x = x + padding + (i as u32 * char_width);
+        let char_x = x + padding + (i as u32 * glyph.advance);
         let char_y = y + padding;
 
-        let (r, g, b) = if i < prefix_len {
-            let (r, g, b, _) = hint_matched_color;
-            (r, g, b)
-        } else {
-            let (r, g, b, _) = hint_text_color;
-            (r, g, b)
-        };
+        let (r, g, b, _) = if i < prefix_len { style.matched } else { style.text };
+
+        draw_glyph(canvas, width, height, char_x, char_y, *ch, glyph, (r, g, b));
+    }
+
+    // Draw element name after the hint, in the dimmer matched color
+    let (r, g, b, _) = style.matched;
+    for (i, ch) in name_chars.iter().enumerate() {
+        let char_x = x + padding + ((hint_chars.len() + 1 + i) as u32 * glyph.advance);
+        draw_glyph(canvas, width, height, char_x, y + padding, *ch, glyph, (r, g, b));
+    }
+}
+
+/// Rejected elements worth outlining: all of them until something is typed,
+/// then only those near a hint that still matches
+fn nearby_rejections<'a>(
+    rejected: &'a [(ClickableElement, String)],
+    matching: &[&HintedElement],
+    typed: bool,
+) -> Vec<&'a (ClickableElement, String)> {
+    rejected
+        .iter()
+        .filter(|(element, _)| {
+            let (x, y) = element.center();
+            !typed
+                || matching.iter().any(|m| {
+                    let (mx, my) = m.element.center();
+                    (x - mx).abs() <= DEBUG_NEAR && (y - my).abs() <= DEBUG_NEAR
+                })
+        })
+        .collect()
+}
+
+/// Draw application names under the matching hints, rejected elements with
+/// the filter that dropped them, and a summary of the scan
+#[allow(clippy::too_many_arguments)]
+fn draw_debug(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    scale: u32,
+    debug: &DebugInfo,
+    matching: &[&HintedElement],
+    hinted: usize,
+    typed: bool,
+    text_color: (u8, u8, u8, u8),
+) {
+    let scale = scale as i32;
+    let panel_bg = (0x20, 0x20, 0x20, 230);
+    for elem in matching {
+        let e = &elem.element;
+        if let Some(app) = debug.app_names.get(&e.bus_name) {
+            let app: String = app.chars().take(MAX_NAME_CHARS).collect();
+            draw_text_box(canvas, width, height, e.x / scale, (e.y + e.height) / scale, &app, panel_bg, text_color);
+        }
+    }
+
+    let shown = nearby_rejections(&debug.rejected, matching, typed);
+    for (e, reason) in &shown {
+        let (x, y, w, h) = (e.x / scale, e.y / scale, e.width / scale, e.height / scale);
+        draw_outline(canvas, width, height, x, y, w, h, REJECTED_COLOR);
+        let label = format!("{}: {}", e.role, reason);
+        draw_text_box(canvas, width, height, x, y + h, &label, REJECTED_COLOR, text_color);
+    }
+
+    let summary = format!(
+        "scan {} ms, {} hinted, {} rejected ({} shown)",
+        debug.scan_time.as_millis(),
+        hinted,
+        debug.rejected.len(),
+        shown.len()
+    );
+    draw_text_box(canvas, width, height, 10, height as i32 - 40, &summary, panel_bg, text_color);
+}
+
+/// Break `text` into at most `max_lines` lines of `columns` characters,
+/// ending the last with "..." when some was left out
+fn wrap_text(text: &str, columns: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while !word.is_empty() {
+            let used = current.chars().count();
+            let space = usize::from(used > 0);
+            if used + space + word.len() <= columns {
+                if space > 0 {
+                    current.push(' ');
+                }
+                current.extend(word.drain(..));
+            } else if used > 0 {
+                lines.push(std::mem::take(&mut current));
+            } else {
+                current.extend(word.drain(..columns));
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = &mut lines[max_lines - 1];
+        let kept: String = last.chars().take(columns.saturating_sub(3)).collect();
+        *last = format!("{}...", kept);
+    }
+    lines
+}
+
+/// Draw the best match's name, role and tooltip in the bottom-right corner
+#[allow(clippy::too_many_arguments)]
+fn draw_preview(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    element: &ClickableElement,
+    tooltip: Option<&str>,
+    bg_color: (u8, u8, u8, u8),
+    text_color: (u8, u8, u8, u8),
+) {
+    let name = if element.name.is_empty() { "(no name)" } else { &element.name };
+    let title: String = format!("{} [{}]", name, element.role).chars().take(PREVIEW_COLUMNS).collect();
+    let mut lines = vec![title];
+    lines.extend(wrap_text(tooltip.unwrap_or_default(), PREVIEW_COLUMNS, PREVIEW_LINES));
+
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+    let line_height = 18u32;
+    let box_width = longest * 8 + 20;
+    let box_height = lines.len() as u32 * line_height + 12;
+    let x = width.saturating_sub(box_width + 10);
+    let y = height.saturating_sub(box_height + 10);
+    fill_rect(canvas, width, height, x, y, box_width, box_height, bg_color);
+
+    let (tr, tg, tb, _) = text_color;
+    for (row, line) in lines.iter().enumerate() {
+        let line_y = y + 6 + row as u32 * line_height;
+        for (i, ch) in line.chars().enumerate() {
+            draw_char(canvas, width, height, x + 10 + (i as u32 * 8), line_y, ch, tr, tg, tb);
+        }
+    }
+}
+
+/// Draw `text` on a filled box with its top-left corner at (x, y)
+#[allow(clippy::too_many_arguments)]
+fn draw_text_box(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    text: &str,
+    bg_color: (u8, u8, u8, u8),
+    text_color: (u8, u8, u8, u8),
+) {
+    let (x, y) = (x.max(0) as u32, y.max(0) as u32);
+    let box_width = 6 + text.chars().count() as u32 * 8;
+    fill_rect(canvas, width, height, x, y, box_width, 18, bg_color);
+    let (tr, tg, tb, _) = text_color;
+    for (i, ch) in text.chars().enumerate() {
+        draw_char(canvas, width, height, x + 3 + (i as u32 * 8), y + 3, ch, tr, tg, tb);
+    }
+}
 
-        draw_char(canvas, width, height, char_x, char_y, *ch, r, g, b);
+/// Draw a 2px rectangle outline
+#[allow(clippy::too_many_arguments)]
+fn draw_outline(canvas: &mut [u8], width: u32, height: u32, x: i32, y: i32, w: i32, h: i32, color: (u8, u8, u8, u8)) {
+    const EDGE: u32 = 2;
+    let (x, y) = (x.max(0) as u32, y.max(0) as u32);
+    let (w, h) = (w.max(EDGE as i32) as u32, h.max(EDGE as i32) as u32);
+    fill_rect(canvas, width, height, x, y, w, EDGE, color);
+    fill_rect(canvas, width, height, x, y + h - EDGE, w, EDGE, color);
+    fill_rect(canvas, width, height, x, y, EDGE, h, color);
+    fill_rect(canvas, width, height, x + w - EDGE, y, EDGE, h, color);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fill_rect(canvas: &mut [u8], width: u32, height: u32, x: u32, y: u32, w: u32, h: u32, color: (u8, u8, u8, u8)) {
+    let (r, g, b, a) = color;
+    for py in y..y.saturating_add(h).min(height) {
+        for px in x..x.saturating_add(w).min(width) {
+            let idx = ((py * width + px) * 4) as usize;
+            if idx + 3 < canvas.len() {
+                canvas[idx] = b;
+                canvas[idx + 1] = g;
+                canvas[idx + 2] = r;
+                canvas[idx + 3] = a;
+            }
+        }
     }
 }
 
-fn draw_input_display(
+/// Where the input box and the mode indicator go
+///
+/// Sharing a corner, they sit side by side with the input box nearest to it.
+pub(crate) fn panel_origins(
+    input: PanelPosition,
+    mode: PanelPosition,
+    width: u32,
+    height: u32,
+) -> (PanelOrigin, PanelOrigin) {
+    let origin = |position: PanelPosition, (w, h): (u32, u32), inset: u32| {
+        let left = PANEL_MARGIN + inset;
+        let right = width.saturating_sub(w + PANEL_MARGIN + inset);
+        let top = PANEL_MARGIN;
+        let bottom = height.saturating_sub(h + PANEL_MARGIN);
+        match position {
+            PanelPosition::TopLeft => Some((left, top)),
+            PanelPosition::TopRight => Some((right, top)),
+            PanelPosition::BottomLeft => Some((left, bottom)),
+            PanelPosition::BottomRight => Some((right, bottom)),
+            PanelPosition::Hidden => None,
+        }
+    };
+    let mode_inset = if mode == input { INPUT_BOX.0 + PANEL_MARGIN } else { 0 };
+    (origin(input, INPUT_BOX, 0), origin(mode, MODE_BOX, mode_inset))
+}
+
+/// Draw `prompt` and the typed text in a box at `start`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_input_display(
     canvas: &mut [u8],
     width: u32,
     height: u32,
+    (start_x, start_y): (u32, u32),
+    prompt: &str,
     input_buffer: &str,
     bg_color: (u8, u8, u8, u8),
     text_color: (u8, u8, u8, u8),
 ) {
-    let text = format!("Input: {}_", input_buffer);
-    let box_width = 250u32;
-    let box_height = 30u32;
-    let start_x = 10u32;
-    let start_y = 10u32;
+    let text = format!("{}{}", prompt, input_buffer);
+    let (box_width, box_height) = INPUT_BOX;
 
     let (ir, ig, ib, ia) = bg_color;
     for dy in 0..box_height {
@@ -382,18 +1395,17 @@ fn draw_input_display(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_modifier_indicator(
     canvas: &mut [u8],
     width: u32,
     height: u32,
+    (start_x, start_y): (u32, u32),
     mode_text: &str,
     bg_color: (u8, u8, u8, u8),
     text_color: (u8, u8, u8, u8),
 ) {
-    let box_width = 180u32;
-    let box_height = 25u32;
-    let start_x = 270u32;
-    let start_y = 10u32;
+    let (box_width, box_height) = MODE_BOX;
 
     let (ir, ig, ib, ia) = bg_color;
     for dy in 0..box_height {
@@ -418,24 +1430,99 @@ fn draw_modifier_indicator(
     }
 }
 
+fn draw_role_picker(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    classes: &[RoleClass],
+    bg_color: (u8, u8, u8, u8),
+    text_color: (u8, u8, u8, u8),
+) {
+    let lines: Vec<String> = classes
+        .iter()
+        .map(|c| format!("{}: {}", c.key, c.label))
+        .collect();
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+
+    let line_height = 20u32;
+    let box_width = longest * 8 + 40;
+    let box_height = lines.len() as u32 * line_height + 30;
+    let start_x = width.saturating_sub(box_width) / 2;
+    let start_y = height.saturating_sub(box_height) / 2;
+
+    let (ir, ig, ib, ia) = bg_color;
+    for dy in 0..box_height {
+        for dx in 0..box_width {
+            let px = start_x + dx;
+            let py = start_y + dy;
+            if px < width && py < height {
+                let idx = ((py * width + px) * 4) as usize;
+                if idx + 3 < canvas.len() {
+                    canvas[idx] = ib;
+                    canvas[idx + 1] = ig;
+                    canvas[idx + 2] = ir;
+                    canvas[idx + 3] = ia;
+                }
+            }
+        }
+    }
+
+    let (tr, tg, tb, _) = text_color;
+    for (row, line) in lines.iter().enumerate() {
+        let y = start_y + 15 + row as u32 * line_height;
+        for (i, ch) in line.chars().enumerate() {
+            draw_char(canvas, width, height, start_x + 20 + (i as u32 * 8), y, ch, tr, tg, tb);
+        }
+    }
+}
+
+/// Font size the 6x6 glyph bitmaps are drawn at unscaled, 6x12 pixels on an 8 pixel advance
+const BASE_FONT_SIZE: u32 = 14;
+
+/// Pixel metrics of a glyph drawn at some font size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Glyph {
+    width: u32,
+    height: u32,
+    /// Distance from one character to the next
+    advance: u32,
+}
+
+impl Glyph {
+    fn at(font_size: u32) -> Self {
+        let scaled = |px: u32| (px * font_size / BASE_FONT_SIZE).max(1);
+        Glyph {
+            width: scaled(6),
+            height: scaled(12),
+            advance: scaled(8),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn draw_char(canvas: &mut [u8], width: u32, height: u32, x: u32, y: u32, ch: char, r: u8, g: u8, b: u8) {
+    draw_glyph(canvas, width, height, x, y, ch, Glyph::at(BASE_FONT_SIZE), (r, g, b));
+}
+
+/// Draw `ch` with its bitmap stretched to `glyph`'s size
+#[allow(clippy::too_many_arguments)]
+fn draw_glyph(canvas: &mut [u8], width: u32, height: u32, x: u32, y: u32, ch: char, glyph: Glyph, (r, g, b): (u8, u8, u8)) {
     let bitmap = get_char_bitmap(ch);
 
-    for (row, &bits) in bitmap.iter().enumerate() {
-        for col in 0..6 {
-            if (bits >> (5 - col)) & 1 == 1 {
-                for sy in 0..2 {
-                    let px = x + col;
-                    let py = y + (row as u32 * 2) + sy;
-
-                    if px < width && py < height {
-                        let idx = ((py * width + px) * 4) as usize;
-                        if idx + 3 < canvas.len() {
-                            canvas[idx] = b;
-                            canvas[idx + 1] = g;
-                            canvas[idx + 2] = r;
-                            canvas[idx + 3] = 255;
-                        }
+    for dy in 0..glyph.height {
+        let bits = bitmap[(dy * 6 / glyph.height) as usize];
+        for dx in 0..glyph.width {
+            if (bits >> (5 - dx * 6 / glyph.width)) & 1 == 1 {
+                let px = x + dx;
+                let py = y + dy;
+
+                if px < width && py < height {
+                    let idx = ((py * width + px) * 4) as usize;
+                    if idx + 3 < canvas.len() {
+                        canvas[idx] = b;
+                        canvas[idx + 1] = g;
+                        canvas[idx + 2] = r;
+                        canvas[idx + 3] = 255;
                     }
                 }
             }
@@ -443,7 +1530,7 @@ fn draw_char(canvas: &mut [u8], width: u32, height: u32, x: u32, y: u32, ch: cha
     }
 }
 
-fn keysym_to_char(key: Keysym) -> Option<char> {
+pub fn keysym_to_char(key: Keysym) -> Option<char> {
     match key {
         Keysym::a => Some('a'),
         Keysym::b => Some('b'),
@@ -487,6 +1574,9 @@ fn keysym_to_char(key: Keysym) -> Option<char> {
 }
 
 fn get_char_bitmap(ch: char) -> [u8; 6] {
+    if ch.is_ascii_lowercase() {
+        return get_lowercase_bitmap(ch);
+    }
     match ch.to_ascii_lowercase() {
         'a' => [0b011100, 0b100010, 0b111110, 0b100010, 0b100010, 0b000000],
         'b' => [0b111100, 0b100010, 0b111100, 0b100010, 0b111100, 0b000000],
@@ -534,6 +1624,39 @@ fn get_char_bitmap(ch: char) -> [u8; 6] {
     }
 }
 
+/// Glyphs for lowercase letters; the last row is used for descenders
+fn get_lowercase_bitmap(ch: char) -> [u8; 6] {
+    match ch {
+        'a' => [0b000000, 0b011100, 0b100100, 0b100100, 0b011110, 0b000000],
+        'b' => [0b100000, 0b111100, 0b100010, 0b100010, 0b111100, 0b000000],
+        'c' => [0b000000, 0b011110, 0b100000, 0b100000, 0b011110, 0b000000],
+        'd' => [0b000010, 0b011110, 0b100010, 0b100010, 0b011110, 0b000000],
+        'e' => [0b000000, 0b011100, 0b111110, 0b100000, 0b011110, 0b000000],
+        'f' => [0b001100, 0b010000, 0b111000, 0b010000, 0b010000, 0b000000],
+        'g' => [0b000000, 0b011110, 0b100010, 0b011110, 0b000010, 0b011100],
+        'h' => [0b100000, 0b111100, 0b100010, 0b100010, 0b100010, 0b000000],
+        'i' => [0b001000, 0b000000, 0b011000, 0b001000, 0b011100, 0b000000],
+        'j' => [0b000100, 0b000000, 0b000100, 0b000100, 0b100100, 0b011000],
+        'k' => [0b100000, 0b100100, 0b111000, 0b100100, 0b100010, 0b000000],
+        'l' => [0b011000, 0b001000, 0b001000, 0b001000, 0b011100, 0b000000],
+        'm' => [0b000000, 0b110100, 0b101010, 0b101010, 0b101010, 0b000000],
+        'n' => [0b000000, 0b111100, 0b100010, 0b100010, 0b100010, 0b000000],
+        'o' => [0b000000, 0b011100, 0b100010, 0b100010, 0b011100, 0b000000],
+        'p' => [0b000000, 0b111100, 0b100010, 0b111100, 0b100000, 0b100000],
+        'q' => [0b000000, 0b011110, 0b100010, 0b011110, 0b000010, 0b000010],
+        'r' => [0b000000, 0b101110, 0b110000, 0b100000, 0b100000, 0b000000],
+        's' => [0b000000, 0b011110, 0b011000, 0b000110, 0b111100, 0b000000],
+        't' => [0b010000, 0b111000, 0b010000, 0b010010, 0b001100, 0b000000],
+        'u' => [0b000000, 0b100010, 0b100010, 0b100110, 0b011010, 0b000000],
+        'v' => [0b000000, 0b100010, 0b100010, 0b010100, 0b001000, 0b000000],
+        'w' => [0b000000, 0b100010, 0b101010, 0b101010, 0b010100, 0b000000],
+        'x' => [0b000000, 0b100010, 0b010100, 0b001000, 0b010100, 0b100010],
+        'y' => [0b000000, 0b100010, 0b100010, 0b011110, 0b000010, 0b011100],
+        'z' => [0b000000, 0b111110, 0b000100, 0b011000, 0b111110, 0b000000],
+        _ => [0b111110, 0b100010, 0b100010, 0b100010, 0b111110, 0b000000],
+    }
+}
+
 // Handler implementations
 
 impl CompositorHandler for OverlayState {
@@ -542,8 +1665,17 @@ impl CompositorHandler for OverlayState {
     fn frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {
         self.draw(qh);
     }
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            debug!("Overlay entered output {:?}", rect);
+            self.outputs.enter(rect);
+        }
+    }
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.leave(&rect.name);
+        }
+    }
 }
 
 impl OutputHandler for OverlayState {
@@ -563,11 +1695,12 @@ impl LayerShellHandler for OverlayState {
         self.height = configure.new_size.1;
         self.configured = true;
 
-        let size = (self.width * self.height * 4) as usize;
-        if self.pool.len() < size {
-            self.pool.resize(size).ok();
+        // Split alphabets depend on where the middle of the screen is
+        if self.config.hints.strategy == HintStrategy::SplitHands && self.input_buffer.is_empty() {
+            let elements: Vec<_> = self.elements.iter().map(|e| e.element.clone()).collect();
+            self.elements = assign_hints_with_strategy(&elements, &self.config.hints, self.width);
+            self.placements = None;
         }
-
         self.draw(qh);
     }
 }
@@ -578,10 +1711,15 @@ impl SeatHandler for OverlayState {
     fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, cap: Capability) {
         if cap == Capability::Keyboard && self.keyboard.is_none() {
             self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+            self.seat = Some(seat.clone());
+        }
+        if cap == Capability::Pointer && self.pointer.is_none() && self.config.overlay.hide_cursor {
+            self.pointer = self.seat_state.get_pointer(qh, &seat).ok();
         }
     }
     fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, cap: Capability) {
         if cap == Capability::Keyboard { self.keyboard = None; }
+        if cap == Capability::Pointer { self.pointer = None; }
     }
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
 }
@@ -589,7 +1727,8 @@ impl SeatHandler for OverlayState {
 impl KeyboardHandler for OverlayState {
     fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
     fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
-    fn press_key(&mut self, _: &Connection, qh: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+    fn press_key(&mut self, _: &Connection, qh: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, serial: u32, event: KeyEvent) {
+        self.key_serial = Some(serial);
         self.handle_key(event.keysym);
         self.draw(qh);
     }
@@ -600,8 +1739,25 @@ impl KeyboardHandler for OverlayState {
     }
 }
 
+impl ActivationHandler for OverlayState {
+    type RequestData = RequestData;
+
+    fn new_token(&mut self, token: String, _: &RequestData) {
+        debug!("Got activation token {}", token);
+        self.activation_token = Some(token);
+    }
+}
+
 impl PointerHandler for OverlayState {
-    fn pointer_frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_pointer::WlPointer, _: &[PointerEvent]) {}
+    fn pointer_frame(&mut self, _: &Connection, _: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
+        // A null cursor surface hides the cursor while it is over the overlay;
+        // the compositor restores it once the overlay is gone
+        for event in events {
+            if let PointerEventKind::Enter { serial } = event.kind {
+                pointer.set_cursor(serial, None, 0, 0);
+            }
+        }
+    }
 }
 
 impl ShmHandler for OverlayState {
@@ -613,6 +1769,7 @@ impl ProvidesRegistryState for OverlayState {
     registry_handlers![OutputState, SeatState];
 }
 
+delegate_activation!(OverlayState);
 delegate_compositor!(OverlayState);
 delegate_output!(OverlayState);
 delegate_shm!(OverlayState);
@@ -621,3 +1778,81 @@ delegate_keyboard!(OverlayState);
 delegate_pointer!(OverlayState);
 delegate_layer!(OverlayState);
 delegate_registry!(OverlayState);
+delegate_noop!(OverlayState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(OverlayState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hints::assign_hints;
+
+    #[test]
+    fn test_nearby_rejections() {
+        let at = |x: i32, y: i32| ClickableElement {
+            x,
+            y,
+            width: 20,
+            height: 20,
+            ..Default::default()
+        };
+        let hinted = assign_hints(&[at(100, 100)], "asdf");
+        let matching: Vec<&HintedElement> = hinted.iter().collect();
+        let rejected = vec![(at(180, 120), "filter link".to_string()), (at(900, 700), "exclude_names".to_string())];
+
+        assert_eq!(nearby_rejections(&rejected, &matching, false).len(), 2);
+        let near = nearby_rejections(&rejected, &matching, true);
+        assert_eq!(near.len(), 1);
+        assert_eq!(near[0].1, "filter link");
+        assert!(nearby_rejections(&rejected, &[], true).is_empty());
+    }
+
+    #[test]
+    fn test_panel_origins() {
+        use PanelPosition::*;
+        assert_eq!(panel_origins(TopLeft, TopLeft, 1920, 1080), (Some((10, 10)), Some((270, 10))));
+        assert_eq!(
+            panel_origins(BottomRight, BottomRight, 1920, 1080),
+            (Some((1660, 1040)), Some((1470, 1045)))
+        );
+        assert_eq!(panel_origins(Hidden, TopRight, 1920, 1080), (None, Some((1730, 10))));
+        assert_eq!(panel_origins(BottomLeft, Hidden, 1920, 1080), (Some((10, 1040)), None));
+    }
+
+    #[test]
+    fn test_place_labels() {
+        let element = |x: i32| ClickableElement {
+            x,
+            y: 40,
+            width: 60,
+            height: 20,
+            ..Default::default()
+        };
+        let hinted = assign_hints(&[element(10), element(200)], "asdf");
+        let config = Config::default();
+        let styles = StyleRules::new(&config);
+        let placements = place_labels(&hinted, &styles, 1, false, false);
+        assert_eq!(placements.len(), 2);
+        assert_eq!((placements[&hinted[1].hint].x, placements[&hinted[1].hint].y), (200, 40));
+        let scaled = place_labels(&hinted, &styles, 2, false, false);
+        assert_eq!((scaled[&hinted[1].hint].x, scaled[&hinted[1].hint].y), (100, 20));
+
+        let mut named = hinted.clone();
+        named[0].element.name = "Save".to_string();
+        let mut style = LabelStyle::base(&Config::default());
+        let placement = place_label(&named[0], style, true, false);
+        assert_eq!((placement.x, placement.y), (10, 40));
+        assert!(placement.width > place_label(&named[0], style, false, false).width);
+        assert_eq!(Glyph::at(14), Glyph { width: 6, height: 12, advance: 8 });
+        style.font_size = 28;
+        let doubled = place_label(&named[0], style, false, false);
+        assert_eq!(doubled.height, style.padding * 2 + 24);
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert!(wrap_text("", 10, 3).is_empty());
+        assert_eq!(wrap_text("Save the  document", 10, 3), vec!["Save the", "document"]);
+        assert_eq!(wrap_text("abcdefghijkl", 5, 3), vec!["abcde", "fghij", "kl"]);
+        assert_eq!(wrap_text("one two three four", 5, 2), vec!["one", "tw..."]);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::config::ActionMode;
+use crate::hints::HintedElement;
+use crate::tty;
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+
+/// Ranking bonus for a query that is exactly an element's hint
+const HINT_BONUS: i32 = 100;
+
+/// A key read from the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Backspace,
+    /// Ctrl+U
+    Clear,
+    /// Ctrl+W
+    DeleteWord,
+    Up,
+    Down,
+    Enter,
+    Escape,
+}
+
+/// Score of `text` for a fuzzy `query`: its characters must appear in order,
+/// runs and word starts score higher
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut from = 0;
+    let mut last: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = from + text[from..].iter().position(|&c| c == q)?;
+        score += 1;
+        if last.is_some_and(|l| l + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(found);
+        from = found + 1;
+    }
+    Some(score)
+}
+
+/// Indices of the elements matching `query`, best first
+fn ranked(elements: &[HintedElement], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(usize, i32)> = elements
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| {
+            let text = format!("{} {} {}", h.hint, h.element.name, h.element.role);
+            let bonus = if h.hint.eq_ignore_ascii_case(query.trim()) { HINT_BONUS } else { 0 };
+            fuzzy_score(query, &text).map(|score| (i, score + bonus))
+        })
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Delete the last word of `query` and the spaces after it, as readline's Ctrl+W does
+pub(crate) fn delete_word(query: &mut String) {
+    let end = query.trim_end().len();
+    let start = query[..end].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    query.truncate(start);
+}
+
+/// The terminal in raw mode on the alternate screen, restored on drop
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enable() -> Result<Self> {
+        // SAFETY: all-zero is a valid termios, filled in by tcgetattr
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: stdin is open for the life of the process
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("stdin is not a terminal");
+        }
+        let original = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        termios.c_iflag &= !(libc::IXON | libc::ICRNL);
+        // Reads give up after 100 ms, telling a lone Escape from an arrow key
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = 1;
+        // SAFETY: as above
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set up the terminal");
+        }
+        print!("\x1b[?1049h");
+        Ok(RawTerminal { original })
+    }
+
+    /// Rows and columns, 24x80 when the terminal doesn't say
+    fn size() -> (usize, usize) {
+        // SAFETY: all-zero is a valid winsize, filled in by the ioctl
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ writes a winsize to the pointer
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        if ok && size.ws_row > 0 && size.ws_col > 0 {
+            (size.ws_row as usize, size.ws_col as usize)
+        } else {
+            (24, 80)
+        }
+    }
+
+    /// Whether the terminal went away, e.g. its window was closed
+    ///
+    /// Reads then return nothing straight away, just like when no key was
+    /// pressed, so only the hangup tells the two apart.
+    fn hung_up() -> bool {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: poll reads and writes the one pollfd passed
+        let ready = unsafe { libc::poll(&mut fd, 1, 0) } > 0;
+        ready && fd.revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        // SAFETY: restores the settings read in `enable`
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+/// Next key pressed, `None` when nothing was pressed for a moment
+fn read_key(input: &mut impl Read) -> Result<Option<Key>> {
+    let mut byte = [0u8; 1];
+    let mut next = |input: &mut dyn Read| -> Result<Option<u8>> {
+        Ok((input.read(&mut byte)? == 1).then_some(byte[0]))
+    };
+    let key = match next(input)? {
+        None => return Ok(None),
+        Some(b'\r' | b'\n') => Key::Enter,
+        Some(0x7f | 0x08) => Key::Backspace,
+        Some(0x15) => Key::Clear,
+        Some(0x17) => Key::DeleteWord,
+        Some(0x0e) => Key::Down,
+        Some(0x10) => Key::Up,
+        Some(0x03) => Key::Escape,
+        Some(0x1b) => match (next(input)?, next(input)?) {
+            (Some(b'[' | b'O'), Some(b'A')) => Key::Up,
+            (Some(b'[' | b'O'), Some(b'B')) => Key::Down,
+            (None, _) => Key::Escape,
+            _ => return Ok(None),
+        },
+        Some(b) if b.is_ascii_graphic() || b == b' ' => Key::Char(b as char),
+        Some(_) => return Ok(None),
+    };
+    Ok(Some(key))
+}
+
+/// Draw the query line, the matches with `selected` highlighted, and a status line
+fn render(
+    out: &mut impl Write,
+    elements: &[HintedElement],
+    matches: &[usize],
+    query: &str,
+    selected: usize,
+) -> Result<()> {
+    let (rows, columns) = RawTerminal::size();
+    let hint_width = elements.iter().map(|h| h.hint.len()).max().unwrap_or(0);
+    let visible = rows.saturating_sub(2);
+    let first = selected.saturating_sub(visible.saturating_sub(1));
+
+    write!(out, "\x1b[H\x1b[2J")?;
+    for (row, &index) in matches.iter().enumerate().skip(first).take(visible) {
+        let h = &elements[index];
+        let line = format!("{:<w$}  {:<16} {}", h.hint, h.element.role, h.element.name, w = hint_width);
+        let line: String = line.chars().take(columns).collect();
+        if row == selected {
+            write!(out, "\x1b[7m{}\x1b[0m\r\n", line)?;
+        } else {
+            write!(out, "{}\r\n", line)?;
+        }
+    }
+    write!(
+        out,
+        "\x1b[{};1H\x1b[2m{}/{}  Enter: select  Esc: cancel\x1b[0m",
+        rows - 1,
+        matches.len(),
+        elements.len()
+    )?;
+    write!(out, "\x1b[{};1H> {}", rows, query)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Pick an element from a list in the terminal, narrowed by fuzzy matching
+/// its hint, name and role
+///
+/// Used when no overlay can be shown but the terminal is interactive (e.g.
+/// over SSH). Up/Down or Ctrl+N/Ctrl+P move the selection, Ctrl+U and Ctrl+W
+/// delete the query or its last word; Escape or Ctrl+C cancels.
+pub async fn select(mut elements: Vec<HintedElement>) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    tty::resolve_names(&mut elements).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let _terminal = RawTerminal::enable()?;
+        let stdin = std::io::stdin();
+        let mut input = stdin.lock();
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        let mut query = String::new();
+        let mut selected = 0;
+        let mut matches = ranked(&elements, &query);
+        render(&mut out, &elements, &matches, &query, selected)?;
+        loop {
+            let Some(key) = read_key(&mut input)? else {
+                if RawTerminal::hung_up() {
+                    return Ok(None);
+                }
+                continue;
+            };
+            match key {
+                Key::Enter => {
+                    if let Some(&index) = matches.get(selected) {
+                        return Ok(Some((elements[index].clone(), None)));
+                    }
+                }
+                Key::Escape => return Ok(None),
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected = (selected + 1).min(matches.len().saturating_sub(1)),
+                Key::Char(c) => query.push(c),
+                Key::Backspace => {
+                    query.pop();
+                }
+                Key::Clear => query.clear(),
+                Key::DeleteWord => delete_word(&mut query),
+            }
+            if matches!(key, Key::Char(_) | Key::Backspace | Key::Clear | Key::DeleteWord) {
+                matches = ranked(&elements, &query);
+                selected = 0;
+            }
+            render(&mut out, &elements, &matches, &query, selected)?;
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atspi::ClickableElement;
+    use crate::hints::assign_hints;
+
+    #[test]
+    fn test_ranked() {
+        assert!(fuzzy_score("sv", "save").is_some());
+        assert!(fuzzy_score("vs", "save").is_none());
+        assert!(fuzzy_score("sa", "save").unwrap() > fuzzy_score("sa", "sofa").unwrap());
+
+        let element = |name: &str, role: &str| ClickableElement {
+            name: name.to_string(),
+            role: role.to_string(),
+            ..Default::default()
+        };
+        let elements = assign_hints(
+            &[element("Open", "PushButton"), element("Save as", "PushButton"), element("Save", "Link")],
+            "abc",
+        );
+        assert_eq!(ranked(&elements, ""), vec![0, 1, 2]);
+        assert_eq!(ranked(&elements, "save"), vec![1, 2]);
+        assert_eq!(ranked(&elements, "save link"), vec![2]);
+        assert_eq!(ranked(&elements, "c")[0], 2);
+        assert!(ranked(&elements, "xyz").is_empty());
+
+        let mut keys: &[u8] = b"a\x1b[B\x7f\r";
+        assert_eq!(read_key(&mut keys).unwrap(), Some(Key::Char('a')));
+        assert_eq!(read_key(&mut keys).unwrap(), Some(Key::Down));
+        assert_eq!(read_key(&mut keys).unwrap(), Some(Key::Backspace));
+        assert_eq!(read_key(&mut keys).unwrap(), Some(Key::Enter));
+        let mut escape: &[u8] = b"\x1b";
+        assert_eq!(read_key(&mut escape).unwrap(), Some(Key::Escape));
+
+        let mut query = "save as link ".to_string();
+        delete_word(&mut query);
+        assert_eq!(query, "save as ");
+        delete_word(&mut query);
+        assert_eq!(query, "save ");
+        let mut query = "save".to_string();
+        delete_word(&mut query);
+        assert!(query.is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::atspi::ClickableElement;
+use crate::config::{ActionMode, Config};
+use crate::hints::HintedElement;
+use anyhow::{Context, Result};
+use mlua::{Function, Lua, Table, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// What an overlay key handler asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyOutcome {
+    /// The handler ran and the overlay carries on
+    Handled,
+    /// Select the hint with this label, optionally with an action overriding the mode
+    Select(String, Option<ActionMode>),
+    /// Close the overlay without selecting
+    Cancel,
+}
+
+/// Lua scripts loaded into one interpreter, and what they registered
+///
+/// Scripts register their callbacks through the global `vimium` table:
+/// `vimium.filter(fn)`, `vimium.action(fn)` and `vimium.key(char, fn)`.
+pub struct Plugins {
+    lua: Lua,
+}
+
+/// Plugins loaded at startup, see `load`
+static PLUGINS: Mutex<Option<Plugins>> = Mutex::new(None);
+
+/// Directory plugins are loaded from: `plugins` next to the default config file
+pub fn dir() -> PathBuf {
+    Config::config_path().with_file_name("plugins")
+}
+
+/// Load every `*.lua` file in `dir`, in name order, for the hooks below
+///
+/// A script that fails to load is skipped with a warning.
+pub fn load(dir: &Path) -> Result<()> {
+    let plugins = Plugins::new()?;
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        debug!("No plugin directory at {:?}", dir);
+        return Ok(());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+        .collect();
+    paths.sort();
+    for path in &paths {
+        let loaded = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {:?}", path))
+            .and_then(|source| plugins.load_source(&path.display().to_string(), &source));
+        match loaded {
+            Ok(()) => info!("Loaded plugin {:?}", path),
+            Err(e) => warn!("Skipping plugin: {:#}", e),
+        }
+    }
+    *PLUGINS.lock().unwrap_or_else(|e| e.into_inner()) = Some(plugins);
+    Ok(())
+}
+
+/// Run `f` with the loaded plugins, if any were loaded
+fn with_plugins<T>(f: impl FnOnce(&Plugins) -> T) -> Option<T> {
+    PLUGINS.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(f)
+}
+
+/// Whether a loaded plugin filters elements, so names must be resolved first
+pub fn filters_elements() -> bool {
+    with_plugins(Plugins::filters_elements).unwrap_or(false)
+}
+
+/// Drop the elements a plugin filter rejects
+pub fn filter(elements: &mut Vec<ClickableElement>) {
+    with_plugins(|plugins| plugins.filter(elements));
+}
+
+/// The action plugins pick for `element`, `action` unless one picks another
+pub fn action(element: &ClickableElement, action: ActionMode) -> ActionMode {
+    with_plugins(|plugins| plugins.action(element, action)).unwrap_or(action)
+}
+
+/// Run the overlay key handler for `key`, if a plugin registered one
+///
+/// `hints` are the hints still matching `input`.
+pub fn handle_key(key: char, input: &str, hints: &[&HintedElement]) -> Option<KeyOutcome> {
+    with_plugins(|plugins| plugins.handle_key(key, input, hints)).flatten()
+}
+
+impl Plugins {
+    /// An interpreter with the `vimium` registration table and nothing registered
+    pub fn new() -> Result<Self> {
+        let lua = Lua::new();
+        lua.load(
+            r#"
+            vimium = { filters = {}, actions = {}, keys = {} }
+            function vimium.filter(f) table.insert(vimium.filters, f) end
+            function vimium.action(f) table.insert(vimium.actions, f) end
+            function vimium.key(key, f) vimium.keys[key] = f end
+            "#,
+        )
+        .set_name("vimium")
+        .exec()
+        .context("Failed to set up the plugin API")?;
+        Ok(Self { lua })
+    }
+
+    /// Run a plugin script, letting it register its callbacks
+    pub fn load_source(&self, name: &str, source: &str) -> Result<()> {
+        self.lua
+            .load(source)
+            .set_name(name)
+            .exec()
+            .with_context(|| format!("Failed to run plugin {}", name))
+    }
+
+    /// Callbacks registered in the `vimium` table under `list`
+    fn registered(&self, list: &str) -> mlua::Result<Table> {
+        self.lua.globals().get::<Table>("vimium")?.get(list)
+    }
+
+    /// Whether any plugin registered a filter
+    pub fn filters_elements(&self) -> bool {
+        self.registered("filters").is_ok_and(|filters| filters.raw_len() > 0)
+    }
+
+    /// Keep the elements every filter returns a true value for
+    ///
+    /// An element a filter fails on is kept.
+    pub fn filter(&self, elements: &mut Vec<ClickableElement>) {
+        let Ok(filters) = self.registered("filters") else {
+            return;
+        };
+        let filters: Vec<Function> = filters.sequence_values().filter_map(|f| f.ok()).collect();
+        if filters.is_empty() {
+            return;
+        }
+        let before = elements.len();
+        elements.retain(|element| {
+            let Ok(table) = element_table(&self.lua, element) else {
+                return true;
+            };
+            filters.iter().all(|f| match f.call::<Value>(table.clone()) {
+                Ok(keep) => !matches!(keep, Value::Nil | Value::Boolean(false)),
+                Err(e) => {
+                    warn!("Plugin filter failed: {}", e);
+                    true
+                }
+            })
+        });
+        if elements.len() < before {
+            info!("Plugins dropped {} elements", before - elements.len());
+        }
+    }
+
+    /// Pass `action` through every action callback, each getting the element
+    /// and the action so far and returning another action name or nothing
+    pub fn action(&self, element: &ClickableElement, mut action: ActionMode) -> ActionMode {
+        let Ok(actions) = self.registered("actions") else {
+            return action;
+        };
+        let Ok(table) = element_table(&self.lua, element) else {
+            return action;
+        };
+        for f in actions.sequence_values::<Function>().filter_map(|f| f.ok()) {
+            match f.call::<Option<String>>((table.clone(), action.name())) {
+                Ok(Some(name)) => match parse_action(&name) {
+                    Some(picked) => action = picked,
+                    None => warn!("Plugin picked unknown action {:?}", name),
+                },
+                Ok(None) => {}
+                Err(e) => warn!("Plugin action failed: {}", e),
+            }
+        }
+        debug!("Plugins picked {:?} for {}", action, element.id());
+        action
+    }
+
+    /// Run the key handler registered for `key`
+    ///
+    /// Handlers get the typed input and the matching hints. They return
+    /// nothing, a hint label to select, `{ hint = ..., action = ... }` or
+    /// `{ cancel = true }`.
+    pub fn handle_key(&self, key: char, input: &str, hints: &[&HintedElement]) -> Option<KeyOutcome> {
+        let handler: Function = self.registered("keys").ok()?.get(key.to_string()).ok()?;
+        let returned = hints_table(&self.lua, hints).and_then(|hints| handler.call::<Value>((input, hints)));
+        let outcome = match returned {
+            Ok(Value::String(hint)) => KeyOutcome::Select(hint.to_string_lossy(), None),
+            Ok(Value::Table(reply)) if reply.get::<bool>("cancel").unwrap_or(false) => KeyOutcome::Cancel,
+            Ok(Value::Table(reply)) => match reply.get::<Option<String>>("hint") {
+                Ok(Some(hint)) => {
+                    let action = reply.get::<Option<String>>("action").ok().flatten();
+                    KeyOutcome::Select(hint, action.as_deref().and_then(parse_action))
+                }
+                _ => KeyOutcome::Handled,
+            },
+            Ok(_) => KeyOutcome::Handled,
+            Err(e) => {
+                warn!("Plugin key {:?} failed: {}", key, e);
+                KeyOutcome::Handled
+            }
+        };
+        Some(outcome)
+    }
+}
+
+/// Action from its command line name, e.g. "right-click"
+fn parse_action(name: &str) -> Option<ActionMode> {
+    use clap::ValueEnum;
+    ActionMode::from_str(name, true).ok()
+}
+
+/// An element as plugins see it: id, name, role and extents
+fn element_table(lua: &Lua, element: &ClickableElement) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    table.set("id", element.id())?;
+    table.set("name", element.name.as_str())?;
+    table.set("role", element.role.as_str())?;
+    table.set("x", element.x)?;
+    table.set("y", element.y)?;
+    table.set("width", element.width)?;
+    table.set("height", element.height)?;
+    Ok(table)
+}
+
+/// Hints as a list of element tables with their `hint` label
+fn hints_table(lua: &Lua, hints: &[&HintedElement]) -> mlua::Result<Table> {
+    let list = lua.create_table()?;
+    for hinted in hints {
+        let table = element_table(lua, &hinted.element)?;
+        table.set("hint", hinted.hint.as_str())?;
+        list.push(table)?;
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(name: &str, role: &str) -> ClickableElement {
+        ClickableElement {
+            name: name.to_string(),
+            role: role.to_string(),
+            width: 10,
+            height: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_and_action() {
+        let plugins = Plugins::new().unwrap();
+        assert!(!plugins.filters_elements());
+        plugins
+            .load_source(
+                "test",
+                r#"
+                vimium.filter(function(e) return not e.name:find("Sponsored") end)
+                vimium.action(function(e, action)
+                    if e.role == "Link" and action == "click" then return "middle-click" end
+                end)
+                "#,
+            )
+            .unwrap();
+        assert!(plugins.filters_elements());
+
+        let mut elements = vec![element("Home", "Link"), element("Sponsored post", "Link"), element("OK", "PushButton")];
+        plugins.filter(&mut elements);
+        let names: Vec<&str> = elements.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Home", "OK"]);
+
+        assert_eq!(plugins.action(&elements[0], ActionMode::Click), ActionMode::MiddleClick);
+        assert_eq!(plugins.action(&elements[0], ActionMode::RightClick), ActionMode::RightClick);
+        assert_eq!(plugins.action(&elements[1], ActionMode::Click), ActionMode::Click);
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let plugins = Plugins::new().unwrap();
+        plugins
+            .load_source(
+                "test",
+                r#"
+                vimium.key("1", function(input, hints) return hints[1].hint end)
+                vimium.key("2", function(input, hints) return { hint = hints[2].hint, action = "hover" } end)
+                vimium.key("3", function() return { cancel = true } end)
+                vimium.key("4", function(input) typed = input end)
+                "#,
+            )
+            .unwrap();
+        let first = HintedElement { hint: "aa".to_string(), element: element("One", "Link") };
+        let second = HintedElement { hint: "as".to_string(), element: element("Two", "Link") };
+        let hints = [&first, &second];
+
+        assert_eq!(plugins.handle_key('1', "a", &hints), Some(KeyOutcome::Select("aa".to_string(), None)));
+        assert_eq!(
+            plugins.handle_key('2', "a", &hints),
+            Some(KeyOutcome::Select("as".to_string(), Some(ActionMode::Hover)))
+        );
+        assert_eq!(plugins.handle_key('3', "a", &hints), Some(KeyOutcome::Cancel));
+        assert_eq!(plugins.handle_key('4', "a", &hints), Some(KeyOutcome::Handled));
+        assert_eq!(plugins.lua.globals().get::<String>("typed").unwrap(), "a");
+        assert_eq!(plugins.handle_key('5', "a", &hints), None);
+    }
+
+    #[test]
+    fn test_load_error() {
+        let plugins = Plugins::new().unwrap();
+        assert!(plugins.load_source("broken", "vimium.filter(").is_err());
+    }
+}
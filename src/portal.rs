@@ -0,0 +1,192 @@
+use crate::config::{ActionMode, HotkeyBinding};
+use crate::hotkey;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, Proxy};
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const GLOBAL_SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+/// A shortcut offered to the portal
+#[derive(Debug, PartialEq)]
+struct Shortcut {
+    /// The mode's name, which comes back on activation
+    id: String,
+    description: String,
+    /// Suggested chord; the user picks one in system settings when absent
+    trigger: Option<String>,
+}
+
+/// One shortcut per bound mode, or one per mode when nothing is bound
+fn shortcuts(bindings: &[HotkeyBinding]) -> Vec<Shortcut> {
+    let bound: Vec<(ActionMode, Option<&str>)> = if bindings.is_empty() {
+        ActionMode::value_variants().iter().map(|&m| (m, None)).collect()
+    } else {
+        bindings.iter().map(|b| (b.mode, Some(b.chord.as_str()))).collect()
+    };
+
+    let mut shortcuts: Vec<Shortcut> = Vec::new();
+    for (mode, chord) in bound {
+        let id = mode.name();
+        if shortcuts.iter().any(|s| s.id == id) {
+            warn!("Only the first chord for {} mode is offered to the portal", id);
+            continue;
+        }
+        let trigger = chord.and_then(|c| match hotkey::portal_trigger(c) {
+            Ok(trigger) => Some(trigger),
+            Err(e) => {
+                warn!("Hotkey {}", e);
+                None
+            }
+        });
+        shortcuts.push(Shortcut {
+            id,
+            description: format!("Show hints ({})", mode.label()),
+            trigger,
+        });
+    }
+    shortcuts
+}
+
+/// Path of the Request object the portal creates for `token`
+fn request_path(conn: &Connection, token: &str) -> Result<String> {
+    let sender = conn.unique_name().context("Not connected to the session bus")?;
+    let sender = sender.trim_start_matches(':').replace('.', "_");
+    Ok(format!("{}/request/{}/{}", PORTAL_PATH, sender, token))
+}
+
+/// Call a portal method and wait for its Request's Response
+async fn request<B>(
+    conn: &Connection,
+    portal: &Proxy<'_>,
+    method: &str,
+    token: &str,
+    body: &B,
+) -> Result<HashMap<String, OwnedValue>>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    // Subscribe before calling so a fast response isn't missed
+    let path = request_path(conn, token)?;
+    let request = Proxy::new(conn, PORTAL, path.as_str(), "org.freedesktop.portal.Request").await?;
+    let mut responses = request.receive_signal("Response").await?;
+
+    let _: OwnedObjectPath = portal
+        .call(method, body)
+        .await
+        .with_context(|| format!("GlobalShortcuts.{} failed", method))?;
+    let message = responses.next().await.context("The portal dropped the request")?;
+    let (code, results): (u32, HashMap<String, OwnedValue>) = message.body().deserialize()?;
+    match code {
+        0 => Ok(results),
+        1 => anyhow::bail!("GlobalShortcuts.{} was cancelled", method),
+        _ => anyhow::bail!("GlobalShortcuts.{} failed with response {}", method, code),
+    }
+}
+
+/// Register shortcuts with xdg-desktop-portal and send the modes they start
+///
+/// The desktop (GNOME, KDE, ...) shows the shortcuts in its settings, where
+/// the user can bind or change them; the chords in `bindings` are only
+/// suggestions. Without bindings, every mode is offered unbound.
+pub async fn register(bindings: &[HotkeyBinding], modes: mpsc::UnboundedSender<ActionMode>) -> Result<()> {
+    let conn = Connection::session().await.context("Failed to connect to session bus")?;
+    let portal = Proxy::new(&conn, PORTAL, PORTAL_PATH, GLOBAL_SHORTCUTS)
+        .await
+        .context("GlobalShortcuts portal not available")?;
+    let token = format!("vimium_linux_{}", std::process::id());
+
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("session_handle_token", Value::from(token.as_str())),
+    ]);
+    let results = request(&conn, &portal, "CreateSession", &token, &(options,)).await?;
+    let session = match results.get("session_handle").map(|v| &**v) {
+        Some(Value::Str(s)) => s.to_string(),
+        Some(Value::ObjectPath(p)) => p.to_string(),
+        _ => anyhow::bail!("The portal returned no session handle"),
+    };
+    let session = ObjectPath::try_from(session).context("Invalid session handle")?;
+    debug!("GlobalShortcuts session {}", session);
+
+    let offered = shortcuts(bindings);
+    let list: Vec<(&str, HashMap<&str, Value>)> = offered
+        .iter()
+        .map(|s| {
+            let mut properties = HashMap::from([("description", Value::from(s.description.as_str()))]);
+            if let Some(trigger) = &s.trigger {
+                properties.insert("preferred_trigger", Value::from(trigger.as_str()));
+            }
+            (s.id.as_str(), properties)
+        })
+        .collect();
+    let bind_token = format!("{}_bind", token);
+    let options = HashMap::from([("handle_token", Value::from(bind_token.as_str()))]);
+    request(&conn, &portal, "BindShortcuts", &bind_token, &(&session, list, "", options)).await?;
+    info!("Registered {} shortcuts with the GlobalShortcuts portal", offered.len());
+
+    let mut activations = portal.receive_signal("Activated").await?;
+    let session = OwnedObjectPath::from(session);
+    tokio::spawn(async move {
+        // The session lasts as long as this connection
+        let _conn = conn;
+        while let Some(message) = activations.next().await {
+            let body = message.body();
+            let Ok((handle, id, _, _)) =
+                body.deserialize::<(OwnedObjectPath, String, u64, HashMap<String, OwnedValue>)>()
+            else {
+                continue;
+            };
+            if handle != session {
+                continue;
+            }
+            let Ok(mode) = ActionMode::from_str(&id, true) else {
+                debug!("Unknown shortcut {}", id);
+                continue;
+            };
+            info!("Portal shortcut activated for {:?} mode", mode);
+            if modes.send(mode).is_err() {
+                break;
+            }
+        }
+        warn!("GlobalShortcuts listener stopped");
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcuts() {
+        let bindings = [
+            HotkeyBinding {
+                chord: "super+semicolon".to_string(),
+                mode: ActionMode::Click,
+            },
+            HotkeyBinding {
+                chord: "super+f".to_string(),
+                mode: ActionMode::Click,
+            },
+            HotkeyBinding {
+                chord: "super+shift+semicolon".to_string(),
+                mode: ActionMode::RightClick,
+            },
+        ];
+        let offered = shortcuts(&bindings);
+        assert_eq!(offered.len(), 2);
+        assert_eq!(offered[0].trigger.as_deref(), Some("LOGO+semicolon"));
+        assert_eq!(offered[1].id, "right-click");
+
+        let all = shortcuts(&[]);
+        assert_eq!(all.len(), ActionMode::value_variants().len());
+        assert!(all.iter().all(|s| s.trigger.is_none()));
+    }
+}
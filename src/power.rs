@@ -0,0 +1,118 @@
+use crate::config::Config;
+use std::time::Duration;
+use tracing::{debug, info};
+use zbus::Connection;
+
+/// How long to wait for UPower before assuming mains power
+const QUERY_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Battery and power-profile state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerState {
+    /// UPower's `OnBattery`
+    pub on_battery: bool,
+    /// power-profiles-daemon's active profile is `power-saver`
+    pub power_saver: bool,
+}
+
+impl PowerState {
+    /// Whether work should be throttled
+    pub fn constrained(&self) -> bool {
+        self.on_battery || self.power_saver
+    }
+}
+
+/// Read the power state from UPower and power-profiles-daemon on the system bus
+///
+/// Anything missing or slow to answer counts as mains power.
+pub async fn detect() -> PowerState {
+    match tokio::time::timeout(QUERY_TIMEOUT, query()).await {
+        Ok(state) => state,
+        Err(_) => {
+            debug!("Timed out reading the power state");
+            PowerState::default()
+        }
+    }
+}
+
+async fn query() -> PowerState {
+    let Ok(conn) = Connection::system().await else {
+        return PowerState::default();
+    };
+    let on_battery = property::<bool>(
+        &conn,
+        "org.freedesktop.UPower",
+        "/org/freedesktop/UPower",
+        "org.freedesktop.UPower",
+        "OnBattery",
+    )
+    .await
+    .unwrap_or(false);
+    let profile = property::<String>(
+        &conn,
+        "net.hadess.PowerProfiles",
+        "/net/hadess/PowerProfiles",
+        "net.hadess.PowerProfiles",
+        "ActiveProfile",
+    )
+    .await;
+    PowerState {
+        on_battery,
+        power_saver: profile.as_deref() == Some("power-saver"),
+    }
+}
+
+async fn property<T>(conn: &Connection, destination: &str, path: &str, interface: &str, name: &str) -> Option<T>
+where
+    T: TryFrom<zbus::zvariant::OwnedValue>,
+    T::Error: Into<zbus::Error>,
+{
+    let proxy = zbus::Proxy::new(conn, destination, path, interface).await.ok()?;
+    match proxy.get_property::<T>(name).await {
+        Ok(value) => Some(value),
+        Err(e) => {
+            debug!("No {}.{}: {}", interface, name, e);
+            None
+        }
+    }
+}
+
+/// Adjust the configuration for the power state, per `[power]`
+pub fn throttle(config: &mut Config, state: PowerState) {
+    if !config.power.enabled || !state.constrained() {
+        return;
+    }
+    info!("Throttling for power state {:?}", state);
+    let power = &config.power;
+    config.scan.name_concurrency = config.scan.name_concurrency.min(power.name_concurrency);
+    config.scan.app_cache_secs = config.scan.app_cache_secs.saturating_mul(power.cache_ttl_factor);
+    config.scroll.smooth &= power.smooth_scroll;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let battery = PowerState {
+            on_battery: true,
+            power_saver: false,
+        };
+
+        let mut config = Config::default();
+        throttle(&mut config, PowerState::default());
+        assert_eq!(config.scan.name_concurrency, 8);
+        assert!(config.scroll.smooth);
+
+        throttle(&mut config, battery);
+        assert_eq!(config.scan.name_concurrency, 1);
+        assert_eq!(config.scan.app_cache_secs, 20);
+        assert!(!config.scroll.smooth);
+
+        let mut config = Config::default();
+        config.power.enabled = false;
+        throttle(&mut config, battery);
+        assert_eq!(config.scan.app_cache_secs, 5);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::config::{parse_color, Config, PanelPosition};
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::overlay::{cancel_source, clear_cancel, draw_input_display, panel_origins, wayland_connection};
+use crate::picker::delete_word;
+use anyhow::{Context, Result};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    reexports::{calloop::EventLoop, calloop_wayland_source::WaylandSource},
+    shm::{Shm, ShmHandler},
+};
+use tracing::info;
+use wayland_client::{
+    delegate_noop,
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+
+/// How a key press ended the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Done {
+    Submit,
+    Cancel,
+}
+
+/// Apply a key press to `text`, returning how it ends the prompt, if it does
+///
+/// Ctrl+U clears the text and Ctrl+W deletes its last word, as in the picker.
+fn edit(text: &mut String, key: Keysym, typed: Option<&str>, ctrl: bool) -> Option<Done> {
+    match key {
+        Keysym::Escape => return Some(Done::Cancel),
+        Keysym::Return | Keysym::KP_Enter => return Some(Done::Submit),
+        Keysym::BackSpace => {
+            text.pop();
+        }
+        Keysym::u if ctrl => text.clear(),
+        Keysym::w if ctrl => delete_word(text),
+        _ if !ctrl => text.extend(typed.unwrap_or_default().chars().filter(|c| !c.is_control())),
+        _ => {}
+    }
+    None
+}
+
+/// Ask for a line of text in a box drawn where `overlay.input_position` puts
+/// the hint overlay's input, e.g. the query for `search`
+///
+/// Returns `None` when cancelled with Escape or submitted empty.
+pub fn run_prompt(prompt: &str, config: &Config) -> Result<Option<String>> {
+    let conn = wayland_connection()?;
+
+    let (globals, event_queue) = registry_queue_init(&conn).context("Failed to init registry")?;
+    let qh = event_queue.handle();
+
+    let compositor = CompositorState::bind(&globals, &qh).context("wl_compositor not available")?;
+    let layer_shell = LayerShell::bind(&globals, &qh).context("layer_shell not available")?;
+    let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
+
+    let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
+    let layer_surface =
+        layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("vimium-prompt"), None);
+    layer_surface.set_anchor(Anchor::TOP | Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT);
+    layer_surface.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface.commit();
+
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
+
+    // A hidden input box would leave nothing to type into
+    let position = match config.overlay.input_position {
+        PanelPosition::Hidden => PanelPosition::TopLeft,
+        position => position,
+    };
+    let mut state = PromptState {
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh),
+        shm,
+        buffers,
+        layer_surface: Some(layer_surface),
+        prompt: prompt.to_string(),
+        text: String::new(),
+        done: None,
+        position,
+        bg_color: parse_color(&config.colors.input_bg),
+        text_color: parse_color(&config.colors.input_text),
+        configured: false,
+        width: 0,
+        height: 0,
+        keyboard: None,
+        modifiers: Modifiers::default(),
+    };
+
+    let mut event_loop: EventLoop<PromptState> = EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Prompt cancelled externally");
+            state.done = Some(Done::Cancel);
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
+    while state.done.is_none() {
+        event_loop.dispatch(None, &mut state).context("Wayland dispatch failed")?;
+    }
+
+    // Same explicit teardown as the hint overlay
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
+
+    Ok((state.done == Some(Done::Submit) && !state.text.is_empty()).then_some(state.text))
+}
+
+struct PromptState {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    shm: Shm,
+    buffers: FrameBuffers,
+    layer_surface: Option<LayerSurface>,
+    prompt: String,
+    text: String,
+    done: Option<Done>,
+    position: PanelPosition,
+    bg_color: (u8, u8, u8, u8),
+    text_color: (u8, u8, u8, u8),
+    configured: bool,
+    width: u32,
+    height: u32,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    modifiers: Modifiers,
+}
+
+impl PromptState {
+    /// Draw the input box on an otherwise clear surface
+    fn draw(&mut self) {
+        if !self.configured || self.width == 0 || self.height == 0 {
+            return;
+        }
+        let Some(layer_surface) = &self.layer_surface else {
+            return;
+        };
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
+        };
+        let (canvas, width, height) = (frame.canvas, frame.width, frame.height);
+        canvas.fill(0);
+
+        let (origin, _) = panel_origins(self.position, PanelPosition::Hidden, width, height);
+        if let Some(origin) = origin {
+            let text = format!("{}_", self.text);
+            draw_input_display(canvas, width, height, origin, &self.prompt, &text, self.bg_color, self.text_color);
+        }
+
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
+        layer_surface.commit();
+    }
+}
+
+impl CompositorHandler for PromptState {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
+    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+}
+
+impl OutputHandler for PromptState {
+    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl LayerShellHandler for PromptState {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        self.done = Some(Done::Cancel);
+    }
+
+    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
+        self.width = configure.new_size.0;
+        self.height = configure.new_size.1;
+        self.configured = true;
+        self.draw();
+    }
+}
+
+impl SeatHandler for PromptState {
+    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+        }
+    }
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard { self.keyboard = None; }
+    }
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for PromptState {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        self.done = edit(&mut self.text, event.keysym, event.utf8.as_deref(), self.modifiers.ctrl);
+        self.draw();
+    }
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, modifiers: Modifiers, _: u32) {
+        self.modifiers = modifiers;
+    }
+}
+
+impl ShmHandler for PromptState {
+    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+}
+
+impl ProvidesRegistryState for PromptState {
+    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(PromptState);
+delegate_output!(PromptState);
+delegate_shm!(PromptState);
+delegate_seat!(PromptState);
+delegate_keyboard!(PromptState);
+delegate_layer!(PromptState);
+delegate_registry!(PromptState);
+delegate_noop!(PromptState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(PromptState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit() {
+        let mut text = String::new();
+        assert_eq!(edit(&mut text, Keysym::a, Some("a"), false), None);
+        edit(&mut text, Keysym::space, Some(" "), false);
+        edit(&mut text, Keysym::B, Some("B"), false);
+        assert_eq!(text, "a B");
+        edit(&mut text, Keysym::BackSpace, Some("\u{8}"), false);
+        assert_eq!(text, "a ");
+        edit(&mut text, Keysym::c, Some("c"), false);
+        edit(&mut text, Keysym::w, Some("\u{17}"), true);
+        assert_eq!(text, "a ");
+        edit(&mut text, Keysym::u, Some("\u{15}"), true);
+        assert_eq!(text, "");
+        assert_eq!(edit(&mut text, Keysym::Return, Some("\r"), false), Some(Done::Submit));
+        assert_eq!(edit(&mut text, Keysym::Escape, None, false), Some(Done::Cancel));
+    }
+}
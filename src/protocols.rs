@@ -0,0 +1,119 @@
+#[cfg(feature = "gui")]
+use crate::overlay::wayland_connection;
+use anyhow::Result;
+#[cfg(feature = "gui")]
+use anyhow::Context;
+#[cfg(feature = "gui")]
+use std::sync::OnceLock;
+use tracing::debug;
+#[cfg(feature = "gui")]
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+#[cfg(feature = "gui")]
+use wayland_client::protocol::wl_registry;
+#[cfg(feature = "gui")]
+use wayland_client::{Connection, Dispatch, QueueHandle};
+
+/// Hint overlay and scroll mode
+pub const LAYER_SHELL: &str = "zwlr_layer_shell_v1";
+/// Pointer control through wlrctl
+pub const VIRTUAL_POINTER: &str = "zwlr_virtual_pointer_manager_v1";
+/// Key sending through wtype
+pub const VIRTUAL_KEYBOARD: &str = "zwp_virtual_keyboard_manager_v1";
+
+/// Features and the globals providing them; any one of the globals is enough
+const FEATURES: &[(&str, &[&str])] = &[
+    ("hint overlay and scroll mode", &[LAYER_SHELL]),
+    ("clicking through wlrctl", &[VIRTUAL_POINTER]),
+    ("sending keys through wtype", &[VIRTUAL_KEYBOARD]),
+    ("screen capture", &["zwlr_screencopy_manager_v1", "ext_image_copy_capture_manager_v1"]),
+    ("window lists", &["zwlr_foreign_toplevel_manager_v1", "ext_foreign_toplevel_list_v1"]),
+    ("reduced-resolution overlays", &["wp_viewporter"]),
+    ("cursor shapes", &["wp_cursor_shape_manager_v1"]),
+    ("focusing programs started for a selection", &["xdg_activation_v1"]),
+];
+
+/// Whether a feature is available and through which global
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Support {
+    pub feature: &'static str,
+    /// Globals that would provide the feature
+    pub needs: &'static [&'static str],
+    /// The advertised one, if any
+    pub provided_by: Option<&'static str>,
+}
+
+#[cfg(feature = "gui")]
+struct Registry;
+
+#[cfg(feature = "gui")]
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Registry {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Interfaces the compositor advertises, read once per process
+#[cfg(feature = "gui")]
+pub fn advertised() -> Result<&'static [String]> {
+    static ADVERTISED: OnceLock<Vec<String>> = OnceLock::new();
+    if let Some(interfaces) = ADVERTISED.get() {
+        return Ok(interfaces);
+    }
+    let conn = wayland_connection()?;
+    let (globals, _queue) = registry_queue_init::<Registry>(&conn).context("Failed to read Wayland globals")?;
+    let interfaces = globals.contents().clone_list().into_iter().map(|g| g.interface).collect();
+    Ok(ADVERTISED.get_or_init(|| interfaces))
+}
+
+/// Without the `gui` feature there is no Wayland client to ask
+#[cfg(not(feature = "gui"))]
+pub fn advertised() -> Result<&'static [String]> {
+    anyhow::bail!("Built without the gui feature")
+}
+
+/// Whether the compositor advertises `interface`
+///
+/// Without a Wayland connection nothing can be checked and this returns
+/// true, so callers fail later with their own error.
+pub fn has(interface: &str) -> bool {
+    match advertised() {
+        Ok(interfaces) => interfaces.iter().any(|i| i == interface),
+        Err(e) => {
+            debug!("Cannot check for {}: {:#}", interface, e);
+            true
+        }
+    }
+}
+
+/// Support for every feature given the advertised interfaces
+pub fn report(advertised: &[String]) -> Vec<Support> {
+    FEATURES
+        .iter()
+        .map(|&(feature, needs)| Support {
+            feature,
+            needs,
+            provided_by: needs.iter().copied().find(|n| advertised.iter().any(|a| a == n)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let advertised = vec![LAYER_SHELL.to_string(), "ext_foreign_toplevel_list_v1".to_string()];
+        let report = report(&advertised);
+        let support = |feature: &str| report.iter().find(|s| s.feature == feature).unwrap().provided_by;
+        assert_eq!(support("hint overlay and scroll mode"), Some(LAYER_SHELL));
+        assert_eq!(support("window lists"), Some("ext_foreign_toplevel_list_v1"));
+        assert_eq!(support("clicking through wlrctl"), None);
+    }
+}
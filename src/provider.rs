@@ -0,0 +1,245 @@
+use crate::atspi::{self, ClickableElement};
+use crate::browser::BrowserProvider;
+use crate::config::Config;
+use crate::focus;
+use crate::terminal::TerminalProvider;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use tracing::{debug, info, warn};
+
+/// Elements whose bounds differ by at most this many pixels are duplicates
+const DEDUPE_TOLERANCE: i32 = 2;
+
+/// The kind of elements a mode asks providers for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElementKind {
+    Clickable,
+    Scrollable,
+    Text,
+    /// Browser and notebook tabs, for `tabs`
+    Tab,
+}
+
+/// Future returned by `ElementProvider::elements`
+pub type ElementFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<ClickableElement>>> + Send + 'a>>;
+
+/// A source of hintable elements (AT-SPI, OCR, compositor window lists, ...)
+pub trait ElementProvider: Send + Sync {
+    /// Name used in `[providers] enabled` and in logs
+    fn name(&self) -> &'static str;
+
+    /// Find elements of `kind` in screen coordinates
+    fn elements<'a>(&'a self, kind: ElementKind, config: &'a Config) -> ElementFuture<'a>;
+}
+
+/// Elements from the AT-SPI accessibility tree
+pub struct AtspiProvider;
+
+impl ElementProvider for AtspiProvider {
+    fn name(&self) -> &'static str {
+        "atspi"
+    }
+
+    fn elements<'a>(&'a self, kind: ElementKind, config: &'a Config) -> ElementFuture<'a> {
+        Box::pin(async move {
+            match kind {
+                ElementKind::Clickable => atspi::get_clickable_elements(&config.scan).await,
+                ElementKind::Scrollable => atspi::get_scrollable_elements(&config.scan).await,
+                ElementKind::Text => atspi::get_text_elements(&config.scan).await,
+                ElementKind::Tab => atspi::get_tab_elements(&config.scan).await,
+            }
+        })
+    }
+}
+
+/// Instantiate the providers listed in `[providers] enabled`, in priority order
+pub fn enabled_providers(config: &Config) -> Vec<Box<dyn ElementProvider>> {
+    let mut providers: Vec<Box<dyn ElementProvider>> = Vec::new();
+    for name in &config.providers.enabled {
+        match name.as_str() {
+            "atspi" => providers.push(Box::new(AtspiProvider)),
+            "browser" => providers.push(Box::new(BrowserProvider)),
+            "terminal" => providers.push(Box::new(TerminalProvider)),
+            other => warn!("Unknown element provider '{}'", other),
+        }
+    }
+    providers
+}
+
+/// Query every enabled provider and merge the results
+///
+/// A failing provider is skipped as long as another one succeeds. When two
+/// providers report the same element, the one listed first wins.
+pub async fn collect(kind: ElementKind, config: &Config) -> Result<Vec<ClickableElement>> {
+    let providers = enabled_providers(config);
+    if providers.is_empty() {
+        anyhow::bail!("No element providers enabled");
+    }
+
+    let mut elements = Vec::new();
+    let mut first_error = None;
+    let mut any_succeeded = false;
+    for provider in &providers {
+        match provider.elements(kind, config).await {
+            Ok(found) => {
+                debug!("Provider {} found {} elements", provider.name(), found.len());
+                elements.extend(found);
+                any_succeeded = true;
+            }
+            Err(e) => {
+                warn!("Provider {} failed: {}", provider.name(), e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    if !any_succeeded {
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+    }
+
+    if config.hints.exclude_passwords {
+        drop_passwords(&mut elements);
+    }
+    let before = elements.len();
+    dedupe(&mut elements);
+    if elements.len() < before {
+        info!("Merged {} duplicate elements", before - elements.len());
+    }
+    #[cfg(feature = "gui")]
+    if config.scan.visible_only {
+        retain_shown(&mut elements).await;
+    }
+    Ok(elements)
+}
+
+/// Drop password fields, for `hints.exclude_passwords`
+pub fn drop_passwords(elements: &mut Vec<ClickableElement>) {
+    let before = elements.len();
+    elements.retain(|e| !atspi::normalize_role_name(&e.role).contains("password"));
+    if elements.len() < before {
+        info!("Dropped {} password fields", before - elements.len());
+    }
+}
+
+/// Drop elements of windows the compositor doesn't show, when its IPC can tell
+#[cfg(feature = "gui")]
+async fn retain_shown(elements: &mut Vec<ClickableElement>) {
+    // Compositor IPC runs commands, kept off the async workers
+    let ipc = tokio::task::spawn_blocking(|| {
+        let windows = crate::window::list()?;
+        anyhow::Ok((windows, focus::focused_output_origin()))
+    });
+    let (windows, origin) = match ipc.await {
+        Ok(Ok(found)) => found,
+        Ok(Err(e)) => {
+            debug!("Not filtering by visible workspace: {:#}", e);
+            return;
+        }
+        Err(e) => {
+            warn!("Window listing failed: {}", e);
+            return;
+        }
+    };
+    let pids = atspi::app_pids(&crate::hints::app_order(elements)).await.unwrap_or_default();
+    let before = elements.len();
+    crate::window::retain_shown(elements, &windows, &pids, origin);
+    if elements.len() < before {
+        info!("Dropped {} elements of windows not shown", before - elements.len());
+    }
+}
+
+/// Move `elements` from the focused output's coordinates into the global
+/// space pointer backends take
+///
+/// The overlay converts what it selects itself; everything that acts on
+/// scanned elements without it goes through here first.
+pub async fn to_global(elements: &mut [ClickableElement]) {
+    // Compositor IPC runs commands, kept off the async workers
+    let (dx, dy) = tokio::task::spawn_blocking(focus::focused_output_origin)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Output lookup failed: {}", e);
+            (0, 0)
+        });
+    for element in elements {
+        element.x += dx;
+        element.y += dy;
+    }
+}
+
+/// Remove elements already reported earlier in the list
+///
+/// Elements are the same if they share an accessibility key or cover the
+/// same rectangle.
+pub fn dedupe(elements: &mut Vec<ClickableElement>) {
+    let mut kept: Vec<ClickableElement> = Vec::with_capacity(elements.len());
+    for element in elements.drain(..) {
+        if !kept.iter().any(|k| is_duplicate(k, &element)) {
+            kept.push(element);
+        }
+    }
+    *elements = kept;
+}
+
+fn is_duplicate(a: &ClickableElement, b: &ClickableElement) -> bool {
+    if !a.bus_name.is_empty() && a.bus_name == b.bus_name && a.path == b.path {
+        return true;
+    }
+    (a.x - b.x).abs() <= DEDUPE_TOLERANCE
+        && (a.y - b.y).abs() <= DEDUPE_TOLERANCE
+        && (a.width - b.width).abs() <= DEDUPE_TOLERANCE
+        && (a.height - b.height).abs() <= DEDUPE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> ClickableElement {
+        ClickableElement {
+            role: "push_button".to_string(),
+            x,
+            y,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dedupe_same_rectangle() {
+        let mut elements = vec![rect(10, 10, 80, 20), rect(11, 9, 80, 21), rect(200, 10, 80, 20)];
+        dedupe(&mut elements);
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].x, 10);
+        assert_eq!(elements[1].x, 200);
+    }
+
+    #[test]
+    fn test_drop_passwords() {
+        let mut password = rect(10, 10, 80, 20);
+        password.role = "PasswordText".to_string();
+        let mut elements = vec![rect(10, 40, 80, 20), password];
+        drop_passwords(&mut elements);
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].role, "push_button");
+    }
+
+    #[test]
+    fn test_dedupe_same_key() {
+        let mut a = rect(10, 10, 80, 20);
+        a.bus_name = ":1.42".to_string();
+        a.path = "/org/a11y/atspi/accessible/7".to_string();
+        let mut b = rect(500, 500, 10, 10);
+        b.bus_name = a.bus_name.clone();
+        b.path = a.path.clone();
+        let mut elements = vec![a, b];
+        dedupe(&mut elements);
+        assert_eq!(elements.len(), 1);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::atspi::{self, ClickableElement};
+use crate::click::{self, ScrollDirection};
+use crate::config::Config;
+use crate::filter::RoleFilter;
+use crate::hints::{NameMatch, NameMatcher};
+use crate::keys::{self, KeySequence};
+use crate::provider::{self, ElementKind};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How often a step waiting for its element scans again
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Steps run in order by `vimium-linux run`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// One step of a script, written as e.g. `{ click = { name = "Save" } }`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// Fail unless a matching element shows up
+    Find(Target),
+    /// Click a matching element
+    Click(Target),
+    RightClick(Target),
+    MiddleClick(Target),
+    /// Move the pointer over a matching element
+    Hover(Target),
+    /// Pause for this many milliseconds
+    Wait(u64),
+    /// Type text into the focused window
+    Type(String),
+    /// Press a key combination such as `ctrl+s`, or a sequence such as `Hello<Enter>`
+    Key(String),
+    Scroll(ScrollStep),
+}
+
+/// The element a step acts on
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Target {
+    /// Regex matched against the accessible name
+    pub name: Option<String>,
+    /// Roles to consider, as in `--filter`
+    pub role: RoleFilter,
+    /// Which match to use when several match, in scan order
+    pub index: usize,
+    /// How long to keep scanning for the element to appear
+    pub timeout_ms: u64,
+}
+
+/// Scroll an area by a number of wheel steps
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScrollStep {
+    pub direction: ScrollDirection,
+    #[serde(default = "default_scroll_amount")]
+    pub amount: i32,
+    /// The area to scroll; the focused window's main scrollable area when absent
+    #[serde(default)]
+    pub target: Option<Target>,
+}
+
+fn default_scroll_amount() -> i32 {
+    3
+}
+
+impl Script {
+    /// Parse a script: JSON when it starts with `{`, TOML otherwise
+    pub fn parse(text: &str) -> Result<Self> {
+        let script: Script = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).context("Invalid JSON script")?
+        } else {
+            toml::from_str(text).context("Invalid TOML script")?
+        };
+        script.validate()?;
+        Ok(script)
+    }
+
+    /// Read a script from a file, or from stdin for `-`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = if path == Path::new("-") {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read the script from stdin")?;
+            text
+        } else {
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+        };
+        Self::parse(&text)
+    }
+
+    /// Let steps that don't set `timeout_ms` wait up to `timeout` for their element
+    pub fn set_default_timeout(&mut self, timeout: Duration) {
+        for step in &mut self.steps {
+            let target = match step {
+                Step::Find(t) | Step::Click(t) | Step::RightClick(t) | Step::MiddleClick(t) | Step::Hover(t) => t,
+                Step::Scroll(ScrollStep { target: Some(t), .. }) => t,
+                _ => continue,
+            };
+            if target.timeout_ms == 0 {
+                target.timeout_ms = timeout.as_millis() as u64;
+            }
+        }
+    }
+
+    /// Check name patterns and key combinations before anything runs
+    fn validate(&self) -> Result<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            let checked = match step {
+                Step::Key(keys) => keys.parse::<KeySequence>().map(drop).map_err(anyhow::Error::msg),
+                _ => step.target().map_or(Ok(()), |t| t.name_pattern().map(drop)),
+            };
+            checked.with_context(|| format!("Step {} ({})", i + 1, step.name()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    /// The step's key in the script
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Find(_) => "find",
+            Step::Click(_) => "click",
+            Step::RightClick(_) => "right_click",
+            Step::MiddleClick(_) => "middle_click",
+            Step::Hover(_) => "hover",
+            Step::Wait(_) => "wait",
+            Step::Type(_) => "type",
+            Step::Key(_) => "key",
+            Step::Scroll(_) => "scroll",
+        }
+    }
+
+    fn target(&self) -> Option<&Target> {
+        match self {
+            Step::Find(t) | Step::Click(t) | Step::RightClick(t) | Step::MiddleClick(t) | Step::Hover(t) => Some(t),
+            Step::Scroll(scroll) => scroll.target.as_ref(),
+            Step::Wait(_) | Step::Type(_) | Step::Key(_) => None,
+        }
+    }
+}
+
+impl Target {
+    fn name_pattern(&self) -> Result<Option<NameMatcher>> {
+        self.name.as_deref().map(|p| NameMatcher::new(p, NameMatch::Regex)).transpose()
+    }
+
+    /// The `index`th element matching the role filter and name pattern
+    fn select(&self, elements: Vec<ClickableElement>, name: Option<&NameMatcher>) -> Option<ClickableElement> {
+        elements
+            .into_iter()
+            .filter(|e| self.role.matches(&e.role))
+            .filter(|e| name.is_none_or(|n| n.matches(&e.name)))
+            .nth(self.index)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(name) = &self.name {
+            parts.push(format!("name {:?}", name));
+        }
+        if !self.role.is_empty() {
+            parts.push(format!("role {}", self.role));
+        }
+        if self.index > 0 {
+            parts.push(format!("index {}", self.index));
+        }
+        if parts.is_empty() {
+            return write!(f, "any element");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Run every step in order, stopping at the first that fails
+pub async fn run(script: &Script, config: &Config) -> Result<()> {
+    for (i, step) in script.steps.iter().enumerate() {
+        info!("Step {}: {}", i + 1, step.name());
+        run_step(step, config)
+            .await
+            .with_context(|| format!("Step {} ({}) failed", i + 1, step.name()))?;
+    }
+    Ok(())
+}
+
+async fn run_step(step: &Step, config: &Config) -> Result<()> {
+    let pointer_action: fn(i32, i32) -> Result<()> = match step {
+        Step::Find(target) => {
+            find(target, ElementKind::Clickable, config).await?;
+            return Ok(());
+        }
+        Step::Wait(ms) => {
+            tokio::time::sleep(Duration::from_millis(*ms)).await;
+            return Ok(());
+        }
+        Step::Type(text) => return keys::type_text(text),
+        Step::Key(keys) => return keys::send_sequence(&keys.parse().map_err(anyhow::Error::msg)?),
+        Step::Scroll(scroll) => return run_scroll(scroll, config).await,
+        Step::Click(_) => click::click_at,
+        Step::RightClick(_) => click::right_click_at,
+        Step::MiddleClick(_) => click::middle_click_at,
+        Step::Hover(_) => click::move_cursor_to,
+    };
+    let target = step.target().expect("pointer steps have a target");
+    let element = find(target, ElementKind::Clickable, config).await?;
+    let (x, y) = element.center();
+    info!("{} {} at ({}, {})", step.name(), element.role, x, y);
+    pointer_action(x, y)?;
+
+    if config.behavior.post_action_delay_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(config.behavior.post_action_delay_ms)).await;
+    }
+    Ok(())
+}
+
+async fn run_scroll(scroll: &ScrollStep, config: &Config) -> Result<()> {
+    let area = match &scroll.target {
+        Some(target) => find(target, ElementKind::Scrollable, config).await?,
+        None => {
+            let mut area = atspi::get_focused_scrollable(&config.scan)
+                .await?
+                .context("No focused window to scroll")?;
+            provider::to_global(std::slice::from_mut(&mut area)).await;
+            area
+        }
+    };
+    let (x, y) = area.center();
+    click::scroll_at(x, y, scroll.direction, scroll.amount)
+}
+
+/// Scan until an element matches `target` or its timeout passes, returning
+/// it in global coordinates
+async fn find(target: &Target, kind: ElementKind, config: &Config) -> Result<ClickableElement> {
+    let name = target.name_pattern()?;
+    let deadline = Instant::now() + Duration::from_millis(target.timeout_ms);
+    loop {
+        let mut elements = provider::collect(kind, config).await?;
+        if name.is_some() {
+            atspi::resolve_names(&mut elements).await?;
+        }
+        debug!("Looking for {} among {} elements", target, elements.len());
+        if let Some(mut element) = target.select(elements, name.as_ref()) {
+            provider::to_global(std::slice::from_mut(&mut element)).await;
+            return Ok(element);
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("No element matching {}", target);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let toml = r#"
+            [[steps]]
+            click = { name = "^Save", role = "button", timeout_ms = 2000 }
+
+            [[steps]]
+            wait = 500
+
+            [[steps]]
+            type = "hello"
+
+            [[steps]]
+            scroll = { direction = "down", amount = 5 }
+        "#;
+        let script = Script::parse(toml).unwrap();
+        let Step::Click(target) = &script.steps[0] else {
+            panic!("expected a click step");
+        };
+        assert_eq!(target.timeout_ms, 2000);
+        assert_eq!(script.steps[1], Step::Wait(500));
+        assert_eq!(script.steps[3].name(), "scroll");
+
+        let mut waiting = script.clone();
+        waiting.set_default_timeout(Duration::from_secs(5));
+        assert_eq!(waiting.steps[0], script.steps[0]);
+
+        let json = r#"{"steps": [{"key": "ctrl+s"}, {"right_click": {"role": "link", "index": 1}}]}"#;
+        let script = Script::parse(json).unwrap();
+        assert_eq!(script.steps[0], Step::Key("ctrl+s".to_string()));
+        let mut waiting = script.clone();
+        waiting.set_default_timeout(Duration::from_millis(1500));
+        assert!(matches!(&waiting.steps[1], Step::RightClick(t) if t.timeout_ms == 1500));
+
+        let element = |name: &str, role: &str| ClickableElement {
+            name: name.to_string(),
+            role: role.to_string(),
+            ..Default::default()
+        };
+        let elements = vec![element("Save as", "PushButton"), element("Home", "Link"), element("Save", "PushButton")];
+        let name = target.name_pattern().unwrap();
+        let found = target.select(elements.clone(), name.as_ref()).unwrap();
+        assert_eq!(found.name, "Save as");
+        let second = Target {
+            index: 1,
+            ..target.clone()
+        };
+        assert_eq!(second.select(elements, name.as_ref()).unwrap().name, "Save");
+
+        assert!(Script::parse("[[steps]]\nkey = \"hyper+x\"").is_err());
+        assert!(Script::parse("[[steps]]\nclick = { name = \"(\" }").is_err());
+        assert!(Script::parse("[[steps]]\nfly = 1").is_err());
+    }
+}
@@ -1,5 +1,12 @@
-use crate::click::{scroll_at, ScrollDirection};
-use crate::config::Config;
+use crate::atspi::{self, ClickableElement};
+use crate::click::{click_at, pinch_at, scroll_at, swipe_at, ScrollDirection, Zoom};
+use crate::config::{parse_color, AccelCurve, Config, HintPosition, ScrollConfig};
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::layout::{OutputLayout, OutputRect};
+use crate::hints::{assign_hints, filter_by_prefix, find_exact_match, find_unique_match, HintedElement};
+use crate::overlay::{cancel_source, clear_cancel, draw_label, keysym_to_char, place_label, scale_hint, wayland_connection};
+use crate::status::{self, Status};
+use crate::style::LabelStyle;
 use anyhow::{Context, Result};
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState},
@@ -20,25 +27,91 @@ use smithay_client_toolkit::{
         },
         WaylandSurface,
     },
-    shm::{slot::SlotPool, Shm, ShmHandler},
+    reexports::{
+        calloop::{channel, EventLoop},
+        calloop_wayland_source::WaylandSource,
+    },
+    shm::{Shm, ShmHandler},
 };
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 use wayland_client::{
+    delegate_noop,
     globals::registry_queue_init,
-    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface},
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface},
     Connection, QueueHandle,
 };
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+
+/// Wait for the application to re-layout before rescanning after a scroll
+const RESCAN_DELAY: Duration = Duration::from_millis(150);
+
+/// Opacity of hints while scrolling, relative to the configured colors
+const FADE: f32 = 0.4;
 
-pub async fn run_scroll_mode(x: i32, y: i32, config: &Config) -> Result<()> {
+/// Scroll `container` at `x`, `y` (global coordinates) until the user exits
+///
+/// With `scroll.show_hints`, the container's clickable elements stay hinted
+/// (faded) and are rescanned after each scroll, so the user can press `f`
+/// and click one as soon as it comes into view.
+pub async fn run_scroll_mode(container: &ClickableElement, x: i32, y: i32, config: &Config) -> Result<()> {
     let config = config.clone();
-    tokio::task::spawn_blocking(move || run_scroll_overlay(x, y, &config)).await??;
-    Ok(())
+    if !config.scroll.show_hints {
+        tokio::task::spawn_blocking(move || run_scroll_overlay(x, y, &config, None)).await??;
+        return Ok(());
+    }
+
+    let initial = atspi::get_clickable_elements_in(container, &config.scan)
+        .await
+        .unwrap_or_default();
+    let (rescan_tx, mut rescan_rx) = mpsc::unbounded_channel::<()>();
+    let (elements_tx, elements_rx) = channel::channel();
+    if elements_tx.send(initial).is_err() {
+        return Ok(());
+    }
+
+    // Rescans are bounded to the scrolled container and coalesced while the
+    // user keeps scrolling
+    let hints = Some(ScrollHints {
+        container: container.clone(),
+        rescan: rescan_tx,
+        elements: elements_rx,
+    });
+    let container = container.clone();
+    let scan = config.scan.clone();
+    let rescans = tokio::spawn(async move {
+        while rescan_rx.recv().await.is_some() {
+            tokio::time::sleep(RESCAN_DELAY).await;
+            while rescan_rx.try_recv().is_ok() {}
+            match atspi::get_clickable_elements_in(&container, &scan).await {
+                Ok(elements) => {
+                    if elements_tx.send(elements).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Failed to rescan scrolled area: {}", e),
+            }
+        }
+    });
+
+    let result = tokio::task::spawn_blocking(move || run_scroll_overlay(x, y, &config, hints)).await?;
+    rescans.abort();
+    result
 }
 
-fn run_scroll_overlay(target_x: i32, target_y: i32, config: &Config) -> Result<()> {
-    let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+/// Channels connecting the scroll overlay to the container rescans
+struct ScrollHints {
+    /// The scrolled area, in global coordinates
+    container: ClickableElement,
+    rescan: mpsc::UnboundedSender<()>,
+    elements: channel::Channel<Vec<ClickableElement>>,
+}
+
+fn run_scroll_overlay(target_x: i32, target_y: i32, config: &Config, hints: Option<ScrollHints>) -> Result<()> {
+    let conn = wayland_connection()?;
 
-    let (globals, mut event_queue) =
+    let (globals, event_queue) =
         registry_queue_init(&conn).context("Failed to init registry")?;
     let qh = event_queue.handle();
 
@@ -47,6 +120,7 @@ fn run_scroll_overlay(target_x: i32, target_y: i32, config: &Config) -> Result<(
     let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
 
     let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
 
     let layer_surface = layer_shell.create_layer_surface(
         &qh,
@@ -61,18 +135,21 @@ fn run_scroll_overlay(target_x: i32, target_y: i32, config: &Config) -> Result<(
     layer_surface.set_exclusive_zone(-1);
     layer_surface.commit();
 
-    let pool = SlotPool::new(256 * 256 * 4, &shm).context("Failed to create buffer pool")?;
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
 
     let mut state = ScrollState {
         registry_state: RegistryState::new(&globals),
         seat_state: SeatState::new(&globals, &qh),
         output_state: OutputState::new(&globals, &qh),
         shm,
-        pool,
+        buffers,
         layer_surface: Some(layer_surface),
         target_x,
         target_y,
         scroll_step: config.scroll.scroll_step,
+        acceleration: Acceleration::new(&config.scroll),
+        batch: Duration::from_millis(config.scroll.batch_ms),
+        pending: None,
         page_step: config.scroll.page_step,
         configured: false,
         width: 0,
@@ -80,27 +157,132 @@ fn run_scroll_overlay(target_x: i32, target_y: i32, config: &Config) -> Result<(
         exit: false,
         keyboard: None,
         modifiers: Modifiers::default(),
+        outputs: OutputLayout::default(),
+        container: None,
+        scanned: Vec::new(),
+        hints: Vec::new(),
+        hint_chars: config.hints.chars.clone(),
+        hinting: false,
+        input_buffer: String::new(),
+        status: status::Publisher::default(),
+        rescan: None,
+        needs_redraw: false,
+        auto_select: config.behavior.auto_select,
+        padding: config.hints.padding,
+        font_size: config.hints.font_size,
+        uppercase_labels: config.hints.uppercase_labels,
+        hint_bg_color: parse_color(&config.colors.hint_bg),
+        hint_text_color: parse_color(&config.colors.hint_text),
+        hint_matched_color: parse_color(&config.colors.hint_text_matched),
     };
 
+    let mut event_loop: EventLoop<ScrollState> =
+        EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+
+    if let Some(hints) = hints {
+        state.rescan = Some(hints.rescan);
+        state.container = Some(hints.container);
+        event_loop
+            .handle()
+            .insert_source(hints.elements, |event, _, state| {
+                if let channel::Event::Msg(elements) = event {
+                    state.update_hints(elements);
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to watch rescans: {}", e.error))?;
+    }
+
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Scroll mode cancelled externally");
+            state.exit = true;
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
     info!("Scroll mode started at ({}, {}). Use hjkl to scroll, Escape to exit.", target_x, target_y);
 
     while !state.exit {
-        event_queue.blocking_dispatch(&mut state).context("Wayland dispatch failed")?;
+        event_loop
+            .dispatch(state.batch_delay(), &mut state)
+            .context("Wayland dispatch failed")?;
+        if state.batch_delay() == Some(Duration::ZERO) {
+            state.flush_scroll();
+        }
+
+        if state.needs_redraw {
+            state.needs_redraw = false;
+            state.draw(&qh);
+        }
     }
 
+    state.flush_scroll();
+
+    // Same explicit teardown as the hint overlay
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
+
     Ok(())
 }
 
+/// Speeds up hjkl presses repeated in one direction, per `scroll.accel_*`
+struct Acceleration {
+    curve: AccelCurve,
+    rate: f32,
+    max: f32,
+    window: Duration,
+    /// Direction and time of the last press, and how many repeats it ended
+    last: Option<(ScrollDirection, Instant, u32)>,
+}
+
+impl Acceleration {
+    fn new(config: &ScrollConfig) -> Self {
+        Acceleration {
+            curve: config.accel_curve,
+            rate: config.accel_rate,
+            max: config.accel_max.max(1.0),
+            window: Duration::from_millis(config.accel_window_ms),
+            last: None,
+        }
+    }
+
+    /// Multiplier for a press in `direction` at `now`; another direction or a
+    /// pause longer than the window starts over at 1
+    fn factor(&mut self, direction: ScrollDirection, now: Instant) -> f32 {
+        let repeats = match self.last {
+            Some((last, at, repeats)) if last == direction && now.duration_since(at) <= self.window => repeats + 1,
+            _ => 0,
+        };
+        self.last = Some((direction, now, repeats));
+        let factor = match self.curve {
+            AccelCurve::Off => 1.0,
+            AccelCurve::Linear => 1.0 + self.rate * repeats as f32,
+            AccelCurve::Exponential => self.rate.powi(repeats as i32),
+        };
+        factor.clamp(1.0, self.max)
+    }
+}
+
 struct ScrollState {
     registry_state: RegistryState,
     seat_state: SeatState,
     output_state: OutputState,
     shm: Shm,
-    pool: SlotPool,
+    buffers: FrameBuffers,
     layer_surface: Option<LayerSurface>,
     target_x: i32,
     target_y: i32,
     scroll_step: i32,
+    acceleration: Acceleration,
+    /// Presses within this long are sent as one scroll (`scroll.batch_ms`)
+    batch: Duration,
+    /// Scroll waiting to be sent, with when its first press came
+    pending: Option<(ScrollDirection, i32, Instant)>,
     page_step: i32,
     configured: bool,
     width: u32,
@@ -108,6 +290,30 @@ struct ScrollState {
     exit: bool,
     keyboard: Option<wl_keyboard::WlKeyboard>,
     modifiers: Modifiers,
+    /// Outputs the overlay is shown on, for translating positions
+    outputs: OutputLayout,
+    /// The scrolled area, in global coordinates
+    container: Option<ClickableElement>,
+    /// Elements from the latest rescan, including scrolled-out ones
+    scanned: Vec<ClickableElement>,
+    /// Clickable elements currently visible in the scrolled area
+    hints: Vec<HintedElement>,
+    hint_chars: String,
+    /// Typing a hint (after `f`) rather than scrolling
+    hinting: bool,
+    input_buffer: String,
+    /// Mode, input and match count for status bars
+    status: status::Publisher,
+    /// Requests a rescan of the scrolled area; `None` without `scroll.show_hints`
+    rescan: Option<mpsc::UnboundedSender<()>>,
+    needs_redraw: bool,
+    auto_select: bool,
+    padding: u32,
+    font_size: u32,
+    uppercase_labels: bool,
+    hint_bg_color: (u8, u8, u8, u8),
+    hint_text_color: (u8, u8, u8, u8),
+    hint_matched_color: (u8, u8, u8, u8),
 }
 
 impl ScrollState {
@@ -121,16 +327,10 @@ impl ScrollState {
             None => return,
         };
 
-        let width = self.width;
-        let height = self.height;
-        let stride = width * 4;
-
-        let (buffer, canvas) = match self.pool.create_buffer(
-            width as i32, height as i32, stride as i32, wl_shm::Format::Argb8888
-        ) {
-            Ok(b) => b,
-            Err(_) => return,
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
         };
+        let (canvas, width, height, scale) = (frame.canvas, frame.width, frame.height, frame.scale);
 
         // Very transparent background
         for pixel in canvas.chunks_exact_mut(4) {
@@ -141,8 +341,9 @@ impl ScrollState {
         }
 
         // Draw crosshair at target position
-        let tx = self.target_x as u32;
-        let ty = self.target_y as u32;
+        let (tx, ty) = self.outputs.to_local((self.target_x, self.target_y), self.width, self.height);
+        let tx = tx.max(0) as u32 / scale;
+        let ty = ty.max(0) as u32 / scale;
 
         // Horizontal line
         if ty < height {
@@ -168,15 +369,177 @@ impl ScrollState {
             }
         }
 
+        // Hints are faded while scrolling and shown fully while typing one
+        let fade = if self.hinting { 1.0 } else { FADE };
+        let style = LabelStyle {
+            bg: faded(self.hint_bg_color, fade),
+            text: faded(self.hint_text_color, fade),
+            matched: faded(self.hint_matched_color, fade),
+            padding: self.padding,
+            font_size: self.font_size,
+            position: HintPosition::TopLeft,
+            hidden: false,
+        };
+        let prefix_len = self.input_buffer.len();
+        let filtered = filter_by_prefix(&self.hints, &self.input_buffer);
+        self.status.set(Status {
+            mode: Some("scroll".to_string()),
+            input: self.input_buffer.clone(),
+            matches: if self.hinting { filtered.len() } else { 0 },
+        });
+        for elem in filtered {
+            let scaled;
+            let elem = if scale > 1 {
+                scaled = scale_hint(elem, scale);
+                &scaled
+            } else {
+                elem
+            };
+            let placement = place_label(elem, style, false, self.uppercase_labels);
+            draw_label(
+                canvas,
+                width,
+                height,
+                elem,
+                &placement,
+                prefix_len,
+                false,
+                self.uppercase_labels,
+                None,
+            );
+        }
+
         // Draw help bar at top
         draw_help_bar(canvas, width, height);
 
-        layer_surface.wl_surface().attach(Some(buffer.wl_buffer()), 0, 0);
-        layer_surface.wl_surface().damage_buffer(0, 0, width as i32, height as i32);
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
         layer_surface.commit();
     }
 
+    /// Replace the hints with a fresh scan of the scrolled area
+    fn update_hints(&mut self, elements: Vec<ClickableElement>) {
+        self.scanned = elements;
+        self.refresh_hints();
+    }
+
+    /// Hint the scanned elements that are inside the scrolled area
+    fn refresh_hints(&mut self) {
+        let visible: Vec<_> = self
+            .scanned
+            .iter()
+            .filter(|e| {
+                let Some(container) = &self.container else {
+                    return true;
+                };
+                let (cx, cy) = self.outputs.to_global(e.center(), self.width, self.height);
+                cx >= container.x
+                    && cy >= container.y
+                    && cx < container.x + container.width
+                    && cy < container.y + container.height
+            })
+            .cloned()
+            .collect();
+        debug!("Scrolled area has {} visible clickable elements", visible.len());
+        self.hints = assign_hints(&visible, &self.hint_chars);
+        self.needs_redraw = true;
+    }
+
+    /// Scroll the target, batching presses with `scroll.batch_ms`
+    fn scroll(&mut self, direction: ScrollDirection, amount: i32) {
+        if self.batch.is_zero() {
+            let _ = scroll_at(self.target_x, self.target_y, direction, amount);
+            return;
+        }
+        match &mut self.pending {
+            Some((pending, total, _)) if *pending == direction => *total += amount,
+            _ => {
+                self.flush_scroll();
+                self.pending = Some((direction, amount, Instant::now()));
+            }
+        }
+    }
+
+    /// `step` for an hjkl press, sped up when it repeats; page steps (Ctrl) stay as they are
+    fn accelerated(&mut self, direction: ScrollDirection, step: i32) -> i32 {
+        if self.modifiers.ctrl {
+            return step;
+        }
+        (step as f32 * self.acceleration.factor(direction, Instant::now())) as i32
+    }
+
+    /// Time until the batched scroll is due
+    fn batch_delay(&self) -> Option<Duration> {
+        let (_, _, since) = self.pending.as_ref()?;
+        Some(self.batch.saturating_sub(since.elapsed()))
+    }
+
+    /// Send the batched scroll, if any
+    fn flush_scroll(&mut self) {
+        if let Some((direction, amount, _)) = self.pending.take() {
+            debug!("Sending batched scroll {:?} by {}", direction, amount);
+            let _ = scroll_at(self.target_x, self.target_y, direction, amount);
+        }
+    }
+
+    /// Ask for a rescan once the scroll has settled
+    fn request_rescan(&self) {
+        if let Some(rescan) = &self.rescan {
+            let _ = rescan.send(());
+        }
+    }
+
+    /// Click the element and leave scroll mode
+    fn select_hint(&mut self, elem: &HintedElement) {
+        self.flush_scroll();
+        let (x, y) = self.outputs.to_global(elem.click_position(), self.width, self.height);
+        info!("Selected: {} at ({}, {})", elem.hint, x, y);
+        if let Err(e) = click_at(x, y) {
+            warn!("Failed to click: {}", e);
+        }
+        self.exit = true;
+    }
+
+    fn handle_hint_key(&mut self, key: Keysym) {
+        match key {
+            Keysym::Escape => {
+                self.hinting = false;
+                self.input_buffer.clear();
+            }
+            Keysym::BackSpace => {
+                self.input_buffer.pop();
+            }
+            Keysym::Return => {
+                let selected = find_exact_match(&self.hints, &self.input_buffer)
+                    .or_else(|| find_unique_match(&self.hints, &self.input_buffer))
+                    .cloned();
+                if let Some(elem) = selected {
+                    self.select_hint(&elem);
+                }
+            }
+            _ => {
+                if let Some(ch) = keysym_to_char(key) {
+                    self.input_buffer.push(ch);
+                    if self.auto_select {
+                        let selected = find_exact_match(&self.hints, &self.input_buffer).cloned();
+                        if let Some(elem) = selected {
+                            self.select_hint(&elem);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     fn handle_key(&mut self, key: Keysym) {
+        if self.hinting {
+            self.handle_hint_key(key);
+            return;
+        }
+        if key == Keysym::f && !self.hints.is_empty() {
+            self.hinting = true;
+            return;
+        }
+
         let step = if self.modifiers.ctrl {
             self.page_step
         } else {
@@ -190,41 +553,71 @@ impl ScrollState {
             }
             Keysym::h | Keysym::Left => {
                 debug!("Scroll left");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Left, step);
+                let step = self.accelerated(ScrollDirection::Left, step);
+                self.scroll(ScrollDirection::Left, step);
             }
             Keysym::j | Keysym::Down => {
                 debug!("Scroll down");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Down, step);
+                let step = self.accelerated(ScrollDirection::Down, step);
+                self.scroll(ScrollDirection::Down, step);
             }
             Keysym::k | Keysym::Up => {
                 debug!("Scroll up");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Up, step);
+                let step = self.accelerated(ScrollDirection::Up, step);
+                self.scroll(ScrollDirection::Up, step);
             }
             Keysym::l | Keysym::Right => {
                 debug!("Scroll right");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Right, step);
+                let step = self.accelerated(ScrollDirection::Right, step);
+                self.scroll(ScrollDirection::Right, step);
             }
             Keysym::d if self.modifiers.ctrl => {
                 debug!("Page down");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Down, self.page_step);
+                self.scroll(ScrollDirection::Down, self.page_step);
             }
             Keysym::u if self.modifiers.ctrl => {
                 debug!("Page up");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Up, self.page_step);
+                self.scroll(ScrollDirection::Up, self.page_step);
             }
             Keysym::g => {
                 debug!("Scroll to top");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Up, 10000);
+                self.scroll(ScrollDirection::Up, 10000);
             }
             Keysym::G => {
                 debug!("Scroll to bottom");
-                let _ = scroll_at(self.target_x, self.target_y, ScrollDirection::Down, 10000);
+                self.scroll(ScrollDirection::Down, 10000);
+            }
+            Keysym::H | Keysym::J | Keysym::K | Keysym::L => {
+                self.flush_scroll();
+                let direction = match key {
+                    Keysym::H => ScrollDirection::Left,
+                    Keysym::J => ScrollDirection::Down,
+                    Keysym::K => ScrollDirection::Up,
+                    _ => ScrollDirection::Right,
+                };
+                if let Err(e) = swipe_at(self.target_x, self.target_y, direction) {
+                    warn!("Failed to swipe: {:#}", e);
+                }
             }
-            _ => {}
+            Keysym::plus | Keysym::equal | Keysym::minus => {
+                self.flush_scroll();
+                let zoom = if key == Keysym::minus { Zoom::Out } else { Zoom::In };
+                if let Err(e) = pinch_at(self.target_x, self.target_y, zoom) {
+                    warn!("Failed to zoom: {:#}", e);
+                }
+            }
+            _ => return,
         }
+        self.request_rescan();
     }
 }
 
+/// Dim a color by `factor`, keeping premultiplied channels consistent
+fn faded((r, g, b, a): (u8, u8, u8, u8), factor: f32) -> (u8, u8, u8, u8) {
+    let scale = |c: u8| (c as f32 * factor) as u8;
+    (scale(r), scale(g), scale(b), scale(a))
+}
+
 fn draw_help_bar(canvas: &mut [u8], width: u32, height: u32) {
     let box_height = 25u32;
     let box_width = 400u32.min(width);
@@ -250,8 +643,17 @@ impl CompositorHandler for ScrollState {
     fn frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {
         self.draw(qh);
     }
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.enter(rect);
+            self.refresh_hints();
+        }
+    }
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.leave(&rect.name);
+        }
+    }
 }
 
 impl OutputHandler for ScrollState {
@@ -270,12 +672,7 @@ impl LayerShellHandler for ScrollState {
         self.width = configure.new_size.0;
         self.height = configure.new_size.1;
         self.configured = true;
-
-        let size = (self.width * self.height * 4) as usize;
-        if self.pool.len() < size {
-            self.pool.resize(size).ok();
-        }
-
+        self.refresh_hints();
         self.draw(qh);
     }
 }
@@ -328,3 +725,34 @@ delegate_keyboard!(ScrollState);
 delegate_pointer!(ScrollState);
 delegate_layer!(ScrollState);
 delegate_registry!(ScrollState);
+delegate_noop!(ScrollState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(ScrollState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acceleration() {
+        let mut acceleration = Acceleration::new(&ScrollConfig::default());
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        assert_eq!(acceleration.factor(ScrollDirection::Down, at(0)), 1.0);
+        assert_eq!(acceleration.factor(ScrollDirection::Down, at(100)), 1.5);
+        assert_eq!(acceleration.factor(ScrollDirection::Down, at(200)), 2.0);
+        // Changing direction or pausing starts over
+        assert_eq!(acceleration.factor(ScrollDirection::Up, at(300)), 1.0);
+        assert_eq!(acceleration.factor(ScrollDirection::Up, at(1000)), 1.0);
+        for i in 1..20 {
+            acceleration.factor(ScrollDirection::Up, at(1000 + i * 100));
+        }
+        assert_eq!(acceleration.factor(ScrollDirection::Up, at(3000)), 4.0);
+
+        let mut off = Acceleration::new(&ScrollConfig {
+            accel_curve: AccelCurve::Off,
+            ..ScrollConfig::default()
+        });
+        off.factor(ScrollDirection::Down, at(0));
+        assert_eq!(off.factor(ScrollDirection::Down, at(100)), 1.0);
+    }
+}
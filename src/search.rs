@@ -0,0 +1,154 @@
+use crate::atspi::{self, ClickableElement};
+use crate::config::ScanConfig;
+use anyhow::Result;
+use tracing::debug;
+
+/// Role given to text matches found by `vimium-linux search`
+pub const MATCH_ROLE: &str = "Match";
+
+/// Characters of surrounding text kept on each side of a match for its name
+const CONTEXT_CHARS: usize = 20;
+
+/// Matches hinted at most
+const MAX_MATCHES: usize = 300;
+
+/// The query found in an element's text
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    /// The element whose text contains the match
+    pub host: ClickableElement,
+    /// Character offsets of the match in the host's text
+    pub start: usize,
+    pub end: usize,
+    /// The match's bounds on screen, named by the text around it
+    pub element: ClickableElement,
+}
+
+/// Character ranges of `query` in `text`
+///
+/// Case is ignored unless the query has capitals, as with vim's `smartcase`.
+pub fn find_matches(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let text: Vec<char> = text.chars().map(fold).collect();
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= text.len() {
+        if text[i..i + query.len()] == query[..] {
+            matches.push((i, i + query.len()));
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+/// The match with the text around it on one line, e.g. "...fix the TODO in main..."
+fn context(text: &[char], start: usize, end: usize) -> String {
+    let from = start.saturating_sub(CONTEXT_CHARS);
+    let to = (end + CONTEXT_CHARS).min(text.len());
+    let snippet: String = text[from..to].iter().collect();
+    let snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    let before = if from > 0 { "..." } else { "" };
+    let after = if to < text.len() { "..." } else { "" };
+    format!("{}{}{}", before, snippet, after)
+}
+
+/// Every visible occurrence of `query` in the text of on-screen elements
+pub async fn find(query: &str, scan: &ScanConfig) -> Result<Vec<Occurrence>> {
+    if query.is_empty() {
+        anyhow::bail!("Nothing to search for");
+    }
+
+    let mut occurrences: Vec<Occurrence> = Vec::new();
+    for host in atspi::get_readable_elements(scan).await? {
+        let text = match atspi::get_text(&host).await {
+            Ok(t) => t,
+            Err(e) => {
+                debug!("No text for {}: {}", host.role, e);
+                continue;
+            }
+        };
+        let chars: Vec<char> = text.chars().collect();
+
+        for (start, end) in find_matches(&text, query) {
+            let Ok((x, y, width, height)) = atspi::get_text_range_extents(&host, start as i32, end as i32).await
+            else {
+                continue;
+            };
+            // Matches scrolled out of the element have no usable bounds
+            let visible = width > 0
+                && height > 0
+                && x >= host.x
+                && y >= host.y
+                && x + width <= host.x + host.width
+                && y + height <= host.y + host.height;
+            // Nested elements (a link in a paragraph) report the same text twice
+            let duplicate = occurrences
+                .iter()
+                .any(|o| (o.element.x, o.element.y, o.element.width, o.element.height) == (x, y, width, height));
+            if !visible || duplicate {
+                continue;
+            }
+
+            occurrences.push(Occurrence {
+                element: ClickableElement {
+                    name: context(&chars, start, end),
+                    role: MATCH_ROLE.to_string(),
+                    x,
+                    y,
+                    width,
+                    height,
+                    bus_name: String::new(),
+                    path: format!("/search/{}", occurrences.len()),
+                },
+                host: host.clone(),
+                start,
+                end,
+            });
+            if occurrences.len() >= MAX_MATCHES {
+                return Ok(occurrences);
+            }
+        }
+    }
+    Ok(occurrences)
+}
+
+/// Scroll the match fully into view, returning it with its new bounds
+pub async fn scroll_into_view(occurrence: &Occurrence) -> Result<ClickableElement> {
+    let (start, end) = (occurrence.start as i32, occurrence.end as i32);
+    let mut element = occurrence.element.clone();
+    if let Err(e) = atspi::scroll_text_into_view(&occurrence.host, start, end).await {
+        debug!("Could not scroll the match into view: {:#}", e);
+        return Ok(element);
+    }
+    let (x, y, width, height) = atspi::get_text_range_extents(&occurrence.host, start, end).await?;
+    if width > 0 && height > 0 {
+        (element.x, element.y, element.width, element.height) = (x, y, width, height);
+    }
+    Ok(element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches() {
+        assert_eq!(find_matches("TODO: todo Todo", "todo"), vec![(0, 4), (6, 10), (11, 15)]);
+        assert_eq!(find_matches("TODO: todo Todo", "Todo"), vec![(11, 15)]);
+        assert_eq!(find_matches("aaaa", "aa"), vec![(0, 2), (2, 4)]);
+        assert_eq!(find_matches("héllo wörld", "wö"), vec![(6, 8)]);
+        assert!(find_matches("text", "").is_empty());
+
+        let text: Vec<char> = "one two\nthree four five six seven eight nine ten eleven".chars().collect();
+        assert_eq!(context(&text, 8, 13), "one two three four five six seven...");
+        assert_eq!(context(&text[..7], 0, 3), "one two");
+    }
+}
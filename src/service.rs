@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::info;
+
+/// Name of the daemon's units
+const UNIT: &str = "vimium-linux";
+
+/// Directories holding systemd user units shipped by packages
+const SYSTEM_UNIT_DIRS: &[&str] = &["/usr/lib/systemd/user", "/usr/local/lib/systemd/user", "/etc/systemd/user"];
+
+/// The ydotool daemon's user unit, whichever name the package installed it under
+fn ydotoold_unit(user_dir: &Path) -> Option<&'static str> {
+    ["ydotool.service", "ydotoold.service"].into_iter().find(|unit| {
+        SYSTEM_UNIT_DIRS
+            .iter()
+            .map(Path::new)
+            .chain([user_dir])
+            .any(|dir| dir.join(unit).exists())
+    })
+}
+
+/// Unit files as (file name, contents)
+fn units(exe: &Path, socket: bool, ydotoold: Option<&str>) -> Vec<(String, String)> {
+    let mut service = String::from("[Unit]\nDescription=vimium-linux daemon\n");
+    service.push_str("PartOf=graphical-session.target\n");
+    match ydotoold {
+        // Clicks go through ydotoold, so start it first
+        Some(ydotoold) => {
+            service.push_str(&format!("Wants={}\n", ydotoold));
+            service.push_str(&format!("After=graphical-session.target {}\n", ydotoold));
+        }
+        None => service.push_str("After=graphical-session.target\n"),
+    }
+    if socket {
+        service.push_str(&format!("Requires={}.socket\n", UNIT));
+    }
+    service.push_str(&format!(
+        "\n[Service]\nExecStart={} daemon\nRestart=on-failure\n\n[Install]\nWantedBy=graphical-session.target\n",
+        exe.display()
+    ));
+
+    let mut units = vec![(format!("{}.service", UNIT), service)];
+    if socket {
+        let socket = format!(
+            "[Unit]\nDescription=vimium-linux daemon socket\n\n[Socket]\nListenStream=%t/{}.sock\n\n[Install]\nWantedBy=sockets.target\n",
+            UNIT
+        );
+        units.push((format!("{}.socket", UNIT), socket));
+    }
+    units
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        anyhow::bail!("systemctl --user {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+/// Write systemd user units for the daemon and optionally enable them
+///
+/// With `socket`, a socket unit starts the daemon on the first `trigger`.
+/// Returns the directory the units were written to.
+pub fn install(socket: bool, enable: bool) -> Result<PathBuf> {
+    let dir = dirs::config_dir()
+        .context("No config directory")?
+        .join("systemd/user");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let exe = std::env::current_exe().context("Cannot find the vimium-linux executable")?;
+
+    let ydotoold = ydotoold_unit(&dir);
+    for (name, contents) in units(&exe, socket, ydotoold) {
+        let path = dir.join(&name);
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {:?}", path))?;
+        info!("Wrote {:?}", path);
+    }
+
+    if enable {
+        systemctl(&["daemon-reload"])?;
+        let unit = format!("{}.{}", UNIT, if socket { "socket" } else { "service" });
+        systemctl(&["enable", "--now", &unit])?;
+    }
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        let exe = Path::new("/usr/bin/vimium-linux");
+        let plain = units(exe, false, Some("ydotool.service"));
+        assert_eq!(plain.len(), 1);
+        let service = &plain[0].1;
+        assert!(service.contains("ExecStart=/usr/bin/vimium-linux daemon\n"));
+        assert!(service.contains("After=graphical-session.target ydotool.service\n"));
+        assert!(!service.contains("Requires="));
+
+        let activated = units(exe, true, None);
+        assert_eq!(activated[1].0, "vimium-linux.socket");
+        assert!(activated[0].1.contains("Requires=vimium-linux.socket\n"));
+        assert!(activated[1].1.contains("ListenStream=%t/vimium-linux.sock\n"));
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::debug;
+
+/// What the overlay is doing, for status bars
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    /// Mode shown, e.g. "click" or "scroll"; `None` when no overlay is up
+    pub mode: Option<String>,
+    /// Hint characters typed so far
+    pub input: String,
+    /// Hints still matching the input
+    pub matches: usize,
+}
+
+impl Status {
+    /// One-line summary, e.g. `HINT: fj (3 matches)`
+    pub fn text(&self) -> String {
+        match self.mode.as_deref() {
+            None => String::new(),
+            Some("scroll") if self.input.is_empty() => "SCROLL".to_string(),
+            Some(mode) => {
+                let label = if mode == "scroll" { "SCROLL HINT" } else { "HINT" };
+                let plural = if self.matches == 1 { "" } else { "es" };
+                format!("{}: {} ({} match{})", label, self.input, self.matches, plural)
+            }
+        }
+    }
+
+    /// A line for a Waybar custom module with `return-type = "json"`
+    pub fn waybar(&self) -> serde_json::Value {
+        serde_json::json!({
+            "text": self.text(),
+            "alt": self.mode.as_deref().unwrap_or("idle"),
+            "class": self.mode.as_deref().unwrap_or("idle"),
+        })
+    }
+}
+
+/// File the current status is written to
+pub fn path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("vimium-linux-status.json")
+}
+
+/// The last published status; idle when nothing was written
+pub fn read() -> Status {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write(status: &Status) {
+    let path = path();
+    let tmp = path.with_extension("json.tmp");
+    let result = serde_json::to_vec(status)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(&tmp, json))
+        .and_then(|()| std::fs::rename(&tmp, &path));
+    if let Err(e) = result {
+        debug!("Failed to write status: {}", e);
+    }
+}
+
+/// Publishes an overlay's status when it changes, and idle once dropped
+#[derive(Default)]
+pub struct Publisher {
+    last: Option<Status>,
+}
+
+impl Publisher {
+    pub fn set(&mut self, status: Status) {
+        if self.last.as_ref() != Some(&status) {
+            write(&status);
+            self.last = Some(status);
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if self.last.is_some() {
+            write(&Status::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_text() {
+        assert_eq!(Status::default().text(), "");
+        let hint = Status {
+            mode: Some("click".to_string()),
+            input: "fj".to_string(),
+            matches: 3,
+        };
+        assert_eq!(hint.text(), "HINT: fj (3 matches)");
+        assert_eq!(hint.waybar()["class"], "click");
+        let scroll = Status {
+            mode: Some("scroll".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(scroll.text(), "SCROLL");
+    }
+}
@@ -0,0 +1,169 @@
+use crate::atspi::ClickableElement;
+use crate::config::{parse_color, Config, HintPosition, HintRule};
+use regex::Regex;
+use tracing::warn;
+
+type Color = (u8, u8, u8, u8);
+
+/// How one hint label is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelStyle {
+    pub bg: Color,
+    pub text: Color,
+    pub matched: Color,
+    pub padding: u32,
+    /// Label text height in pixels
+    pub font_size: u32,
+    pub position: HintPosition,
+    pub hidden: bool,
+}
+
+impl LabelStyle {
+    /// The style from `[hints]` and `[colors]`, before any rule
+    pub fn base(config: &Config) -> Self {
+        Self {
+            bg: parse_color(&config.colors.hint_bg),
+            text: parse_color(&config.colors.hint_text),
+            matched: parse_color(&config.colors.hint_text_matched),
+            padding: config.hints.padding,
+            font_size: config.hints.font_size,
+            position: HintPosition::TopLeft,
+            hidden: false,
+        }
+    }
+
+    /// Top-left corner of a `box_width` x `box_height` label for `element`
+    pub fn origin(&self, element: &ClickableElement, box_width: u32, box_height: u32) -> (u32, u32) {
+        let (x, y, w, h) = (element.x, element.y, element.width, element.height);
+        let (bw, bh) = (box_width as i32, box_height as i32);
+        let (lx, ly) = match self.position {
+            HintPosition::TopLeft => (x, y),
+            HintPosition::Center => (x + (w - bw) / 2, y + (h - bh) / 2),
+            HintPosition::Left => (x - bw, y + (h - bh) / 2),
+            HintPosition::Right => (x + w, y + (h - bh) / 2),
+            HintPosition::Above => (x, y - bh),
+            HintPosition::Below => (x, y + h),
+        };
+        (lx.max(0) as u32, ly.max(0) as u32)
+    }
+}
+
+/// `[[hints.rules]]` with their name patterns compiled
+pub struct StyleRules {
+    base: LabelStyle,
+    rules: Vec<(HintRule, Option<Regex>)>,
+}
+
+impl StyleRules {
+    pub fn new(config: &Config) -> Self {
+        let rules = config
+            .hints
+            .rules
+            .iter()
+            .filter_map(|rule| match rule.matches.name.as_deref().map(Regex::new).transpose() {
+                Ok(name) => Some((rule.clone(), name)),
+                Err(e) => {
+                    warn!("Ignoring hint rule with invalid name pattern: {}", e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            base: LabelStyle::base(config),
+            rules,
+        }
+    }
+
+    /// Whether a rule matches on accessible names, which must be resolved before `resolve`
+    pub fn matches_names(&self) -> bool {
+        self.rules.iter().any(|(_, name)| name.is_some())
+    }
+
+    /// The style for `element`: the base style with every matching rule applied in order
+    pub fn resolve(&self, element: &ClickableElement) -> LabelStyle {
+        let mut style = self.base;
+        for (rule, name) in &self.rules {
+            let role_matches = rule.matches.role.is_empty() || rule.matches.role.matches(&element.role);
+            let name_matches = name.as_ref().is_none_or(|n| n.is_match(&element.name));
+            if !role_matches || !name_matches {
+                continue;
+            }
+            let set = &rule.style;
+            if let Some(bg) = &set.bg {
+                style.bg = parse_color(bg);
+            }
+            if let Some(text) = &set.text {
+                style.text = parse_color(text);
+            }
+            if let Some(matched) = &set.matched {
+                style.matched = parse_color(matched);
+            }
+            style.padding = set.padding.unwrap_or(style.padding);
+            style.font_size = set.size.unwrap_or(style.font_size);
+            style.position = set.position.unwrap_or(style.position);
+            style.hidden = set.hidden.unwrap_or(style.hidden);
+        }
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HintStyle, RuleMatch};
+
+    #[test]
+    fn test_rules_cascade() {
+        let mut config = Config::default();
+        config.hints.rules = vec![
+            HintRule {
+                matches: RuleMatch {
+                    role: "link".parse().unwrap(),
+                    name: None,
+                },
+                style: HintStyle {
+                    bg: Some("#224".to_string()),
+                    size: Some(20),
+                    position: Some(HintPosition::Left),
+                    ..Default::default()
+                },
+            },
+            HintRule {
+                matches: RuleMatch {
+                    name: Some("^Sponsored".to_string()),
+                    ..Default::default()
+                },
+                style: HintStyle {
+                    hidden: Some(true),
+                    ..Default::default()
+                },
+            },
+        ];
+        let rules = StyleRules::new(&config);
+
+        let mut link = ClickableElement {
+            role: "Link".to_string(),
+            x: 100,
+            y: 50,
+            width: 40,
+            height: 20,
+            ..Default::default()
+        };
+        let style = rules.resolve(&link);
+        assert_eq!(style.bg, (0x22, 0x22, 0x44, 255));
+        assert_eq!(style.font_size, 20);
+        assert!(rules.matches_names());
+        assert_eq!(style.text, LabelStyle::base(&config).text);
+        assert!(!style.hidden);
+        assert_eq!(style.origin(&link, 30, 10), (70, 55));
+
+        link.name = "Sponsored link".to_string();
+        assert!(rules.resolve(&link).hidden);
+
+        let button = ClickableElement {
+            role: "PushButton".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(rules.resolve(&button), LabelStyle::base(&config));
+    }
+}
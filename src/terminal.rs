@@ -0,0 +1,180 @@
+use crate::atspi::{self, ClickableElement};
+use crate::clipboard;
+use crate::config::{ActionMode, Config};
+use crate::links;
+use crate::provider::{ElementFuture, ElementKind, ElementProvider};
+use anyhow::Result;
+use regex::Regex;
+use std::sync::LazyLock;
+use tracing::{debug, info};
+
+/// Role given to URLs found in terminal text
+pub const URL_ROLE: &str = "Url";
+/// Role given to file paths found in terminal text
+pub const PATH_ROLE: &str = "Path";
+
+static URL_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`]+"#).unwrap());
+
+/// Absolute or home-relative paths, starting a word
+static PATH_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:^|[\s"'(=:\[])(~?/[\w.\-+@%]+(?:/[\w.\-+@%]*)*)"#).unwrap());
+
+/// A URL or path in a block of text, with character (not byte) offsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLink {
+    pub start: usize,
+    pub end: usize,
+    pub target: String,
+    pub role: &'static str,
+}
+
+/// Find URLs and file paths in `text`
+pub fn find_links(text: &str) -> Vec<TextLink> {
+    let mut spans: Vec<(usize, usize, &'static str)> = Vec::new();
+
+    for m in URL_PATTERN.find_iter(text) {
+        let url = trim_url(m.as_str());
+        spans.push((m.start(), m.start() + url.len(), URL_ROLE));
+    }
+    for caps in PATH_PATTERN.captures_iter(text) {
+        let m = caps.get(1).expect("path group always participates");
+        let path = m.as_str().trim_end_matches(['.', ',', ':', ';']);
+        let (start, end) = (m.start(), m.start() + path.len());
+        if !spans.iter().any(|&(s, e, _)| start < e && s < end) {
+            spans.push((start, end, PATH_ROLE));
+        }
+    }
+    spans.sort_by_key(|&(start, _, _)| start);
+
+    spans
+        .into_iter()
+        .map(|(start, end, role)| TextLink {
+            start: text[..start].chars().count(),
+            end: text[..end].chars().count(),
+            target: text[start..end].to_string(),
+            role,
+        })
+        .collect()
+}
+
+/// Drop punctuation that ends a sentence rather than the URL
+fn trim_url(url: &str) -> &str {
+    let mut url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        while url.ends_with(close) && url.matches(close).count() > url.matches(open).count() {
+            url = &url[..url.len() - 1];
+        }
+    }
+    url
+}
+
+/// URLs and file paths visible in terminal emulators
+pub struct TerminalProvider;
+
+impl ElementProvider for TerminalProvider {
+    fn name(&self) -> &'static str {
+        "terminal"
+    }
+
+    fn elements<'a>(&'a self, kind: ElementKind, config: &'a Config) -> ElementFuture<'a> {
+        Box::pin(async move {
+            if kind != ElementKind::Clickable {
+                return Ok(Vec::new());
+            }
+
+            let mut elements = Vec::new();
+            for term in atspi::get_terminal_elements(&config.scan).await? {
+                let text = match atspi::get_text(&term).await {
+                    Ok(t) => t,
+                    Err(e) => {
+                        debug!("Failed to read terminal text: {}", e);
+                        continue;
+                    }
+                };
+
+                for link in find_links(&text) {
+                    let Ok((x, y, width, height)) =
+                        atspi::get_text_range_extents(&term, link.start as i32, link.end as i32).await
+                    else {
+                        continue;
+                    };
+                    // Scrollback and wrapped-off text has no usable on-screen extents
+                    let visible = width > 0
+                        && height > 0
+                        && x >= term.x
+                        && y >= term.y
+                        && y + height <= term.y + term.height;
+                    if !visible {
+                        continue;
+                    }
+
+                    elements.push(ClickableElement {
+                        name: link.target,
+                        role: link.role.to_string(),
+                        x,
+                        y,
+                        width,
+                        height,
+                        bus_name: String::new(),
+                        path: format!("/terminal/{}", elements.len()),
+                    });
+                }
+            }
+            Ok(elements)
+        })
+    }
+}
+
+/// Whether the element is a URL or path found by `TerminalProvider`
+pub fn is_link(element: &ClickableElement) -> bool {
+    element.role == URL_ROLE || element.role == PATH_ROLE
+}
+
+/// Open the link with `opener` (`links.open_command`), or copy it for a right-click
+pub fn activate_link(element: &ClickableElement, action: ActionMode, opener: &str) -> Result<()> {
+    if action == ActionMode::RightClick {
+        info!("Copying {}", element.name);
+        return clipboard::copy(&element.name);
+    }
+
+    let target = match element.name.strip_prefix("~/") {
+        Some(rest) if element.role == PATH_ROLE => match dirs::home_dir() {
+            Some(home) => home.join(rest).to_string_lossy().into_owned(),
+            None => element.name.clone(),
+        },
+        _ => element.name.clone(),
+    };
+    links::open(opener, &target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_urls() {
+        let links = find_links("see https://example.org/a_(b). or (http://x.io/y)");
+        let targets: Vec<_> = links.iter().map(|l| l.target.as_str()).collect();
+        assert_eq!(targets, ["https://example.org/a_(b)", "http://x.io/y"]);
+        assert!(links.iter().all(|l| l.role == URL_ROLE));
+    }
+
+    #[test]
+    fn test_find_paths() {
+        let links = find_links("error in /etc/fstab: line 3\nopen ~/notes/todo.md.");
+        let targets: Vec<_> = links.iter().map(|l| l.target.as_str()).collect();
+        assert_eq!(targets, ["/etc/fstab", "~/notes/todo.md"]);
+        assert!(links.iter().all(|l| l.role == PATH_ROLE));
+
+        // Paths inside URLs and mid-word slashes are not paths
+        assert!(find_links("https://a.org/b/c").iter().all(|l| l.role == URL_ROLE));
+        assert!(find_links("and/or").is_empty());
+    }
+
+    #[test]
+    fn test_link_offsets_are_characters() {
+        let links = find_links("→ /tmp");
+        assert_eq!((links[0].start, links[0].end), (2, 6));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+
+/// Connecting to the accessibility bus
+pub const CONNECT: &str = "a11y connect";
+/// Walking the accessibility trees of every application
+pub const TRAVERSAL: &str = "tree traversal";
+/// Looking up element names ahead of name filters
+pub const NAMES: &str = "name lookup";
+/// Generating hint labels
+pub const HINTS: &str = "hint assignment";
+/// From starting the overlay to its first frame
+pub const OVERLAY: &str = "overlay setup";
+
+/// Whether stages are kept for `report` (`--timings`)
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stages in the order they finished
+static STAGES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Keep stage durations for `report`
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Record how long `stage` took
+///
+/// Always logged at debug level; kept for `report` only with `--timings`.
+pub fn record(stage: &'static str, took: Duration) {
+    debug!(stage, ms = took.as_secs_f64() * 1000.0, "Timing");
+    if ENABLED.load(Ordering::Relaxed) {
+        STAGES.lock().unwrap_or_else(|e| e.into_inner()).push((stage, took));
+    }
+}
+
+/// Total time and runs of each stage, in the order stages first finished
+fn totals(stages: &[(&'static str, Duration)]) -> Vec<(&'static str, Duration, usize)> {
+    let mut totals: Vec<(&'static str, Duration, usize)> = Vec::new();
+    for &(stage, took) in stages {
+        match totals.iter_mut().find(|(name, ..)| *name == stage) {
+            Some((_, total, runs)) => {
+                *total += took;
+                *runs += 1;
+            }
+            None => totals.push((stage, took, 1)),
+        }
+    }
+    totals
+}
+
+fn render(stages: &[(&'static str, Duration)]) -> String {
+    let mut out = format!("{:<16} {:>10} {:>5}\n", "stage", "ms", "runs");
+    for (stage, total, runs) in totals(stages) {
+        let _ = writeln!(out, "{:<16} {:>10.1} {:>5}", stage, total.as_secs_f64() * 1000.0, runs);
+    }
+    out
+}
+
+/// Time spent in each recorded stage, as a table
+pub fn report() -> String {
+    report_since(0)
+}
+
+/// How many stages have been recorded, for `report_since`
+pub fn mark() -> usize {
+    STAGES.lock().unwrap_or_else(|e| e.into_inner()).len()
+}
+
+/// Time spent in the stages recorded after `mark`, as a table
+pub fn report_since(mark: usize) -> String {
+    let stages = STAGES.lock().unwrap_or_else(|e| e.into_inner());
+    render(stages.get(mark..).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let stages = [
+            (CONNECT, Duration::from_millis(12)),
+            (TRAVERSAL, Duration::from_millis(100)),
+            (TRAVERSAL, Duration::from_millis(50)),
+        ];
+        let totals = totals(&stages);
+        assert_eq!(totals[0], (CONNECT, Duration::from_millis(12), 1));
+        assert_eq!(totals[1], (TRAVERSAL, Duration::from_millis(150), 2));
+
+        let report = render(&stages);
+        assert_eq!(report.lines().count(), 3);
+        assert!(report.lines().nth(2).unwrap().starts_with("tree traversal"));
+        assert!(report.contains("150.0"));
+    }
+}
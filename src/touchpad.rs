@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info};
+
+// From linux/input-event-codes.h and linux/uinput.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const BTN_LEFT: u16 = 0x110;
+const BTN_TOOL_FINGER: u16 = 0x145;
+const BTN_TOUCH: u16 = 0x14a;
+const BTN_TOOL_DOUBLETAP: u16 = 0x14d;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_MT_SLOT: u16 = 0x2f;
+const ABS_MT_POSITION_X: u16 = 0x35;
+const ABS_MT_POSITION_Y: u16 = 0x36;
+const ABS_MT_TRACKING_ID: u16 = 0x39;
+const INPUT_PROP_POINTER: u16 = 0x00;
+const BUS_VIRTUAL: u16 = 0x06;
+
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c_5503;
+const UI_ABS_SETUP: libc::c_ulong = 0x401c_5504;
+const UI_SET_EVBIT: libc::c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x4004_5565;
+const UI_SET_ABSBIT: libc::c_ulong = 0x4004_5567;
+const UI_SET_PROPBIT: libc::c_ulong = 0x4004_556e;
+
+/// Touchpad surface in device units, 100x70 mm at `RESOLUTION`
+const PAD_WIDTH: i32 = 2000;
+const PAD_HEIGHT: i32 = 1400;
+/// Device units per millimeter
+const RESOLUTION: i32 = 20;
+
+/// Frames per gesture and the time between them
+const FRAMES: usize = 20;
+const FRAME_INTERVAL: Duration = Duration::from_millis(8);
+
+/// How long libinput takes to pick up a new device
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// A two-finger gesture, in touchpad units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Gesture {
+    /// Fingers moving apart (true) or together (false)
+    Pinch(bool),
+    /// Both fingers moving by (dx, dy) over the gesture
+    Swipe(i32, i32),
+}
+
+impl Gesture {
+    /// Positions of both fingers in every frame
+    fn frames(self) -> Vec<[(i32, i32); 2]> {
+        let (cx, cy) = (PAD_WIDTH / 2, PAD_HEIGHT / 2);
+        (0..=FRAMES)
+            .map(|i| {
+                let t = i as i32;
+                let n = FRAMES as i32;
+                match self {
+                    Gesture::Pinch(apart) => {
+                        let (from, to) = if apart { (100, 600) } else { (600, 100) };
+                        let half = from + (to - from) * t / n;
+                        [(cx - half, cy), (cx + half, cy)]
+                    }
+                    Gesture::Swipe(dx, dy) => {
+                        let (x, y) = (cx - dx / 2 + dx * t / n, cy - dy / 2 + dy * t / n);
+                        [(x - 150, y), (x + 150, y)]
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// A virtual multitouch touchpad on /dev/uinput
+pub(crate) struct Touchpad {
+    file: File,
+}
+
+impl Touchpad {
+    /// Create the device; fails without write access to /dev/uinput
+    pub(crate) fn create() -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")
+            .context("Cannot open /dev/uinput (add a udev rule or join the input group)")?;
+        let pad = Touchpad { file };
+        for event in [EV_SYN, EV_KEY, EV_ABS] {
+            pad.ioctl(UI_SET_EVBIT, event as libc::c_ulong)?;
+        }
+        for key in [BTN_LEFT, BTN_TOOL_FINGER, BTN_TOUCH, BTN_TOOL_DOUBLETAP] {
+            pad.ioctl(UI_SET_KEYBIT, key as libc::c_ulong)?;
+        }
+        pad.ioctl(UI_SET_PROPBIT, INPUT_PROP_POINTER as libc::c_ulong)?;
+
+        let axes = [
+            (ABS_X, PAD_WIDTH, RESOLUTION),
+            (ABS_Y, PAD_HEIGHT, RESOLUTION),
+            (ABS_MT_SLOT, 1, 0),
+            (ABS_MT_POSITION_X, PAD_WIDTH, RESOLUTION),
+            (ABS_MT_POSITION_Y, PAD_HEIGHT, RESOLUTION),
+            (ABS_MT_TRACKING_ID, 0xffff, 0),
+        ];
+        for (code, maximum, resolution) in axes {
+            pad.ioctl(UI_SET_ABSBIT, code as libc::c_ulong)?;
+            // SAFETY: all-zero is a valid value of this plain C struct
+            let mut setup: libc::uinput_abs_setup = unsafe { std::mem::zeroed() };
+            setup.code = code;
+            setup.absinfo.maximum = maximum;
+            setup.absinfo.resolution = resolution;
+            pad.ioctl_ptr(UI_ABS_SETUP, &setup)?;
+        }
+
+        // SAFETY: as above
+        let mut setup: libc::uinput_setup = unsafe { std::mem::zeroed() };
+        setup.id.bustype = BUS_VIRTUAL;
+        setup.id.vendor = 0x1234;
+        setup.id.product = 0x5678;
+        for (dst, src) in setup.name.iter_mut().zip(b"vimium-linux touchpad") {
+            *dst = *src as libc::c_char;
+        }
+        pad.ioctl_ptr(UI_DEV_SETUP, &setup)?;
+        pad.ioctl(UI_DEV_CREATE, 0)?;
+        info!("Created a virtual touchpad for gestures");
+        thread::sleep(SETTLE_TIME);
+        Ok(pad)
+    }
+
+    fn ioctl(&self, request: libc::c_ulong, arg: libc::c_ulong) -> Result<()> {
+        // SAFETY: uinput requests taking an integer argument on our own fd
+        let ret = unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, arg) };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("uinput ioctl {:#x} failed", request));
+        }
+        Ok(())
+    }
+
+    fn ioctl_ptr<T>(&self, request: libc::c_ulong, arg: &T) -> Result<()> {
+        // SAFETY: `arg` is the struct the request's size encodes, and outlives the call
+        let ret = unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, arg as *const T) };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("uinput ioctl {:#x} failed", request));
+        }
+        Ok(())
+    }
+
+    /// Write events followed by a SYN_REPORT
+    fn frame(&mut self, events: &[(u16, u16, i32)]) -> Result<()> {
+        let mut bytes = Vec::new();
+        for &(kind, code, value) in events.iter().chain([&(EV_SYN, SYN_REPORT, 0)]) {
+            // SAFETY: as above; the kernel fills in the timestamp
+            let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+            event.type_ = kind;
+            event.code = code;
+            event.value = value;
+            // SAFETY: input_event is plain data without padding between fields
+            let raw = unsafe {
+                std::slice::from_raw_parts(
+                    &event as *const libc::input_event as *const u8,
+                    std::mem::size_of::<libc::input_event>(),
+                )
+            };
+            bytes.extend_from_slice(raw);
+        }
+        self.file.write_all(&bytes).context("Failed to write touchpad events")
+    }
+
+    /// Perform a gesture with two fingers
+    pub(crate) fn perform(&mut self, gesture: Gesture) -> Result<()> {
+        debug!("Touchpad gesture {:?}", gesture);
+        let frames = gesture.frames();
+        for (i, fingers) in frames.iter().enumerate() {
+            let mut events = Vec::new();
+            for (slot, &(x, y)) in fingers.iter().enumerate() {
+                events.push((EV_ABS, ABS_MT_SLOT, slot as i32));
+                if i == 0 {
+                    events.push((EV_ABS, ABS_MT_TRACKING_ID, slot as i32 + 1));
+                }
+                events.push((EV_ABS, ABS_MT_POSITION_X, x));
+                events.push((EV_ABS, ABS_MT_POSITION_Y, y));
+            }
+            if i == 0 {
+                events.push((EV_KEY, BTN_TOUCH, 1));
+                events.push((EV_KEY, BTN_TOOL_DOUBLETAP, 1));
+            }
+            events.push((EV_ABS, ABS_X, fingers[0].0));
+            events.push((EV_ABS, ABS_Y, fingers[0].1));
+            self.frame(&events)?;
+            thread::sleep(FRAME_INTERVAL);
+        }
+        self.frame(&[
+            (EV_ABS, ABS_MT_SLOT, 0),
+            (EV_ABS, ABS_MT_TRACKING_ID, -1),
+            (EV_ABS, ABS_MT_SLOT, 1),
+            (EV_ABS, ABS_MT_TRACKING_ID, -1),
+            (EV_KEY, BTN_TOUCH, 0),
+            (EV_KEY, BTN_TOOL_DOUBLETAP, 0),
+        ])
+    }
+}
+
+impl Drop for Touchpad {
+    fn drop(&mut self) {
+        let _ = self.ioctl(UI_DEV_DESTROY, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gesture_frames() {
+        let spread = |f: &[(i32, i32); 2]| f[1].0 - f[0].0;
+        let frames = Gesture::Pinch(true).frames();
+        assert_eq!(frames.len(), FRAMES + 1);
+        assert!(spread(&frames[0]) < spread(&frames[FRAMES]));
+        let frames = Gesture::Pinch(false).frames();
+        assert!(spread(&frames[0]) > spread(&frames[FRAMES]));
+
+        let frames = Gesture::Swipe(0, 400).frames();
+        assert_eq!(frames[FRAMES][0].1 - frames[0][0].1, 400);
+        assert_eq!(spread(&frames[0]), spread(&frames[FRAMES]));
+        assert!(frames.iter().flatten().all(|&(x, y)| (0..=PAD_WIDTH).contains(&x) && (0..=PAD_HEIGHT).contains(&y)));
+    }
+}
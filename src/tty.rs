@@ -0,0 +1,89 @@
+use crate::atspi;
+use crate::config::ActionMode;
+use crate::hints::{find_exact_match, HintedElement};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::io::{BufRead, Write};
+
+/// Pick an element by typing its hint on stdin, for use without a Wayland display
+///
+/// The hint may be followed by an action, e.g. `fj right-click`. An empty
+/// line or end of input cancels.
+pub async fn select(mut elements: Vec<HintedElement>) -> Result<Option<(HintedElement, Option<ActionMode>)>> {
+    resolve_names(&mut elements).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        for h in &elements {
+            let e = &h.element;
+            writeln!(out, "{:<4} {:<16} {:>5},{:<5} {}", h.hint, e.role, e.x, e.y, e.name)?;
+        }
+
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            write!(out, "Hint: ")?;
+            out.flush()?;
+            let Some(line) = lines.next() else {
+                return Ok(None);
+            };
+            let line = line.context("Failed to read selection")?;
+            match parse_selection(&line, &elements) {
+                Ok(Some((index, action))) => return Ok(Some((elements[index].clone(), action))),
+                Ok(None) => return Ok(None),
+                Err(e) => writeln!(out, "{}", e)?,
+            }
+        }
+    })
+    .await?
+}
+
+/// Fetch every hinted element's name; without an overlay the names are all
+/// there is to go on
+pub(crate) async fn resolve_names(elements: &mut [HintedElement]) -> Result<()> {
+    let mut plain: Vec<_> = elements.iter().map(|h| h.element.clone()).collect();
+    atspi::resolve_names(&mut plain).await?;
+    for (hinted, element) in elements.iter_mut().zip(plain) {
+        hinted.element.name = element.name;
+    }
+    Ok(())
+}
+
+/// Index of the element picked by `line` and the action given after the hint
+fn parse_selection(line: &str, elements: &[HintedElement]) -> Result<Option<(usize, Option<ActionMode>)>, String> {
+    let mut words = line.split_whitespace();
+    let Some(hint) = words.next() else {
+        return Ok(None);
+    };
+    let action = match words.next() {
+        Some(action) => Some(ActionMode::from_str(action, true)?),
+        None => None,
+    };
+    let selected = find_exact_match(elements, hint).ok_or_else(|| format!("No hint '{}'", hint))?;
+    let index = elements
+        .iter()
+        .position(|h| std::ptr::eq(h, selected))
+        .expect("match comes from the same slice");
+    Ok(Some((index, action)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atspi::ClickableElement;
+    use crate::hints::assign_hints;
+
+    #[test]
+    fn test_parse_selection() {
+        let elements = assign_hints(&[ClickableElement::default(), ClickableElement::default()], "ab");
+        assert_eq!(parse_selection("b", &elements), Ok(Some((1, None))));
+        assert_eq!(
+            parse_selection(" A right-click ", &elements),
+            Ok(Some((0, Some(ActionMode::RightClick))))
+        );
+        assert_eq!(parse_selection("", &elements), Ok(None));
+        assert!(parse_selection("c", &elements).is_err());
+        assert!(parse_selection("a sideways", &elements).is_err());
+    }
+}
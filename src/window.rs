@@ -0,0 +1,635 @@
+use crate::atspi::ClickableElement;
+use crate::config::{parse_color, Config};
+use crate::focus::run_json;
+use crate::framebuffer::{create_viewport, FrameBuffers};
+use crate::layout::{OutputLayout, OutputRect};
+use crate::overlay::{cancel_source, clear_cancel, wayland_connection};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    reexports::{calloop::EventLoop, calloop_wayland_source::WaylandSource},
+    shm::{Shm, ShmHandler},
+};
+use std::collections::HashMap;
+use std::process::Command;
+use tracing::{debug, info, warn};
+use wayland_client::{
+    delegate_noop,
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+
+/// Width of the frame drawn around the selected window
+const FRAME: i32 = 3;
+
+/// How the compositor's IPC addresses a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowId {
+    /// Hyprland window address
+    Hyprland(String),
+    /// Sway container ID
+    Sway(i64),
+}
+
+/// A toplevel window on a visible workspace, in global coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub id: WindowId,
+    pub title: String,
+    /// Hyprland class or sway app_id
+    pub app: String,
+    /// Process owning the window, when the compositor tells
+    pub pid: Option<u32>,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A change to a window made through the compositor's IPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    /// Move by dx, dy pixels
+    Move(i32, i32),
+    /// Grow (or shrink, when negative) by dw, dh pixels
+    Resize(i32, i32),
+    /// Switch between tiled and floating; tiled windows can't be moved freely
+    ToggleFloating,
+    /// Switch between maximized and the previous size
+    ToggleMaximized,
+    /// Send to the workspace with this number, staying on the current one
+    MoveToWorkspace(u8),
+    /// Ask the window to close
+    Close,
+}
+
+impl Adjustment {
+    /// Whether the window is gone from the screen afterwards
+    fn removes(self) -> bool {
+        matches!(self, Adjustment::MoveToWorkspace(_) | Adjustment::Close)
+    }
+}
+
+impl Window {
+    /// The window as an element the hint overlay can label
+    pub fn element(&self) -> ClickableElement {
+        let name = if self.app.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{}: {}", self.app, self.title)
+        };
+        ClickableElement {
+            name,
+            role: "Window".to_string(),
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            ..Default::default()
+        }
+    }
+
+    /// Ask the compositor to make `adjustment`
+    pub fn adjust(&self, adjustment: Adjustment) -> Result<()> {
+        let (program, args) = command(&self.id, adjustment);
+        debug!("Running {} {:?}", program, args);
+        let status = Command::new(program)
+            .args(&args)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run {}", program))?;
+        if !status.success() {
+            anyhow::bail!("{} refused to adjust the window", program);
+        }
+        Ok(())
+    }
+
+    /// Expected geometry after a move or resize, without asking the compositor
+    fn apply(&mut self, adjustment: Adjustment) {
+        match adjustment {
+            Adjustment::Move(dx, dy) => {
+                self.x += dx;
+                self.y += dy;
+            }
+            Adjustment::Resize(dw, dh) => {
+                self.width = (self.width + dw).max(1);
+                self.height = (self.height + dh).max(1);
+            }
+            Adjustment::ToggleFloating
+            | Adjustment::ToggleMaximized
+            | Adjustment::MoveToWorkspace(_)
+            | Adjustment::Close => {}
+        }
+    }
+}
+
+/// Windows on the visible workspaces, from Hyprland's or sway's IPC
+pub fn list() -> Result<Vec<Window>> {
+    if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+        let clients = run_json("hyprctl", &["clients", "-j"]).context("Failed to list windows with hyprctl")?;
+        let monitors = run_json("hyprctl", &["monitors", "-j"]).context("Failed to list monitors with hyprctl")?;
+        return Ok(hyprland_windows(&clients, &monitors));
+    }
+    if std::env::var("SWAYSOCK").is_ok() {
+        let tree = run_json("swaymsg", &["-t", "get_tree", "-r"]).context("Failed to list windows with swaymsg")?;
+        let mut windows = Vec::new();
+        sway_windows(&tree, &mut windows);
+        return Ok(windows);
+    }
+    anyhow::bail!("Window mode needs Hyprland or sway IPC")
+}
+
+/// Drop elements of windows that aren't shown: those of applications without
+/// a window on a visible workspace, and those outside every shown window of
+/// their application (e.g. its second window on another workspace)
+///
+/// `pids` maps bus names to processes; elements of unknown processes are kept.
+/// Elements are in the coordinates of the output at `origin`, windows in
+/// global ones.
+pub fn retain_shown(
+    elements: &mut Vec<ClickableElement>,
+    windows: &[Window],
+    pids: &HashMap<String, u32>,
+    origin: (i32, i32),
+) {
+    elements.retain(|e| {
+        let Some(&pid) = pids.get(&e.bus_name) else {
+            return true;
+        };
+        let (x, y) = e.center();
+        let (x, y) = (x + origin.0, y + origin.1);
+        windows
+            .iter()
+            .filter(|w| w.pid == Some(pid))
+            .any(|w| (w.x..w.x + w.width).contains(&x) && (w.y..w.y + w.height).contains(&y))
+    });
+}
+
+/// Mapped clients on the workspaces shown on some monitor
+fn hyprland_windows(clients: &Value, monitors: &Value) -> Vec<Window> {
+    let shown: Vec<i64> = monitors
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|m| ["activeWorkspace", "specialWorkspace"].map(|key| m[key]["id"].as_i64()))
+        .flatten()
+        .filter(|&id| id != 0)
+        .collect();
+    let pair = |value: &Value| Some((value[0].as_i64()? as i32, value[1].as_i64()? as i32));
+
+    clients
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|c| c["mapped"].as_bool() == Some(true) && c["hidden"].as_bool() != Some(true))
+        .filter(|c| c["workspace"]["id"].as_i64().is_some_and(|id| shown.contains(&id)))
+        .filter_map(|c| {
+            let (x, y) = pair(&c["at"])?;
+            let (width, height) = pair(&c["size"])?;
+            Some(Window {
+                id: WindowId::Hyprland(c["address"].as_str()?.to_string()),
+                title: c["title"].as_str().unwrap_or_default().to_string(),
+                app: c["class"].as_str().unwrap_or_default().to_string(),
+                pid: c["pid"].as_u64().and_then(|pid| u32::try_from(pid).ok()),
+                x,
+                y,
+                width,
+                height,
+            })
+        })
+        .collect()
+}
+
+/// Visible application containers in a sway tree
+fn sway_windows(node: &Value, windows: &mut Vec<Window>) {
+    if node.get("pid").is_some() && node["visible"].as_bool() == Some(true) {
+        let rect = &node["rect"];
+        let field = |key: &str| rect[key].as_i64().unwrap_or_default() as i32;
+        if let Some(id) = node["id"].as_i64() {
+            let app = node["app_id"]
+                .as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .unwrap_or_default();
+            windows.push(Window {
+                id: WindowId::Sway(id),
+                title: node["name"].as_str().unwrap_or_default().to_string(),
+                app: app.to_string(),
+                pid: node["pid"].as_u64().and_then(|pid| u32::try_from(pid).ok()),
+                x: field("x"),
+                y: field("y"),
+                width: field("width"),
+                height: field("height"),
+            });
+        }
+    }
+    for key in ["nodes", "floating_nodes"] {
+        for child in node[key].as_array().into_iter().flatten() {
+            sway_windows(child, windows);
+        }
+    }
+}
+
+/// Program and arguments making `adjustment` to window `id`
+fn command(id: &WindowId, adjustment: Adjustment) -> (&'static str, Vec<String>) {
+    match id {
+        WindowId::Hyprland(address) => {
+            let target = format!("address:{}", address);
+            let (dispatcher, arg) = match adjustment {
+                Adjustment::Move(dx, dy) => ("movewindowpixel", format!("{} {},{}", dx, dy, target)),
+                Adjustment::Resize(dw, dh) => ("resizewindowpixel", format!("{} {},{}", dw, dh, target)),
+                Adjustment::ToggleFloating => ("togglefloating", target),
+                Adjustment::MoveToWorkspace(n) => ("movetoworkspacesilent", format!("{},{}", n, target)),
+                Adjustment::Close => ("closewindow", target),
+                // fullscreen only acts on the focused window
+                Adjustment::ToggleMaximized => {
+                    let batch = format!("dispatch focuswindow {}; dispatch fullscreen 1", target);
+                    return ("hyprctl", vec!["--batch".to_string(), batch]);
+                }
+            };
+            ("hyprctl", vec!["dispatch".to_string(), dispatcher.to_string(), arg])
+        }
+        WindowId::Sway(id) => {
+            let commands = match adjustment {
+                Adjustment::Move(dx, dy) => [
+                    sway_step(dx, "move right", "move left"),
+                    sway_step(dy, "move down", "move up"),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+                Adjustment::Resize(dw, dh) => [
+                    sway_step(dw, "resize grow width", "resize shrink width"),
+                    sway_step(dh, "resize grow height", "resize shrink height"),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+                Adjustment::ToggleFloating => vec!["floating toggle".to_string()],
+                Adjustment::ToggleMaximized => vec!["fullscreen toggle".to_string()],
+                Adjustment::MoveToWorkspace(n) => vec![format!("move container to workspace number {}", n)],
+                Adjustment::Close => vec!["kill".to_string()],
+            };
+            ("swaymsg", vec![format!("[con_id={}] {}", id, commands.join(", "))])
+        }
+    }
+}
+
+/// A sway command moving or resizing by `delta` pixels, if it isn't zero
+fn sway_step(delta: i32, positive: &str, negative: &str) -> Option<String> {
+    match delta {
+        0 => None,
+        d if d > 0 => Some(format!("{} {} px", positive, d)),
+        d => Some(format!("{} {} px", negative, -d)),
+    }
+}
+
+/// Move and resize `window` from the keyboard until the user exits
+///
+/// hjkl move the window, HJKL shrink and grow it, f toggles floating, m
+/// maximizing and Ctrl uses `window.large_step`. 1-9 send the window to that
+/// workspace and x closes it, which ends the mode like Escape, Enter or q.
+pub fn run_adjust_overlay(window: Window, config: &Config) -> Result<()> {
+    let conn = wayland_connection()?;
+
+    let (globals, event_queue) = registry_queue_init(&conn).context("Failed to init registry")?;
+    let qh = event_queue.handle();
+
+    let compositor = CompositorState::bind(&globals, &qh).context("wl_compositor not available")?;
+    let layer_shell = LayerShell::bind(&globals, &qh).context("layer_shell not available")?;
+    let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
+
+    let surface = compositor.create_surface(&qh);
+    let viewport = create_viewport(&globals, &qh, &surface);
+    let layer_surface =
+        layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("vimium-window"), None);
+    layer_surface.set_anchor(Anchor::TOP | Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT);
+    layer_surface.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface.commit();
+
+    let buffers = FrameBuffers::new(&shm, viewport, config.overlay.max_buffer_bytes())?;
+
+    let mut state = WindowState {
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh),
+        shm,
+        buffers,
+        layer_surface: Some(layer_surface),
+        window,
+        step: config.window.step,
+        large_step: config.window.large_step,
+        frame_color: parse_color(&config.colors.hint_bg),
+        configured: false,
+        width: 0,
+        height: 0,
+        exit: false,
+        keyboard: None,
+        modifiers: Modifiers::default(),
+        outputs: OutputLayout::default(),
+    };
+
+    let mut event_loop: EventLoop<WindowState> = EventLoop::try_new().context("Failed to create event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| anyhow::anyhow!("Failed to watch Wayland events: {}", e.error))?;
+    event_loop
+        .handle()
+        .insert_source(cancel_source()?, |_, _, state| {
+            info!("Window mode cancelled externally");
+            state.exit = true;
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch cancellation: {}", e.error))?;
+
+    info!(
+        "Adjusting {:?}. hjkl move, HJKL resize, f floats, m maximizes, 1-9 send to a workspace, x closes, Escape exits.",
+        state.window.title
+    );
+
+    while !state.exit {
+        event_loop.dispatch(None, &mut state).context("Wayland dispatch failed")?;
+    }
+
+    // Same explicit teardown as the hint overlay
+    state.layer_surface.take();
+    state.buffers.release();
+    conn.flush().ok();
+    clear_cancel();
+
+    Ok(())
+}
+
+struct WindowState {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    shm: Shm,
+    buffers: FrameBuffers,
+    layer_surface: Option<LayerSurface>,
+    /// The window being adjusted, with its expected geometry
+    window: Window,
+    step: i32,
+    large_step: i32,
+    frame_color: (u8, u8, u8, u8),
+    configured: bool,
+    width: u32,
+    height: u32,
+    exit: bool,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    modifiers: Modifiers,
+    /// Outputs the overlay is shown on, for translating positions
+    outputs: OutputLayout,
+}
+
+impl WindowState {
+    /// Draw a frame around the window on an otherwise clear surface
+    fn draw(&mut self) {
+        if !self.configured || self.width == 0 || self.height == 0 {
+            return;
+        }
+        let Some(layer_surface) = &self.layer_surface else {
+            return;
+        };
+        let Some(frame) = self.buffers.next_frame(self.width, self.height) else {
+            return;
+        };
+        let (canvas, width, height, scale) = (frame.canvas, frame.width, frame.height, frame.scale as i32);
+        canvas.fill(0);
+
+        let (x, y) = self.outputs.to_local((self.window.x, self.window.y), self.width, self.height);
+        let (left, top) = ((x - FRAME) / scale, (y - FRAME) / scale);
+        let right = (x + self.window.width + FRAME) / scale;
+        let bottom = (y + self.window.height + FRAME) / scale;
+        let thickness = (FRAME / scale).max(1);
+        let (r, g, b, a) = self.frame_color;
+        for py in top.max(0)..bottom.min(height as i32) {
+            for px in left.max(0)..right.min(width as i32) {
+                let edge = px < left + thickness
+                    || px >= right - thickness
+                    || py < top + thickness
+                    || py >= bottom - thickness;
+                if edge {
+                    let idx = ((py as u32 * width + px as u32) * 4) as usize;
+                    canvas[idx..idx + 4].copy_from_slice(&[b, g, r, a]);
+                }
+            }
+        }
+
+        self.buffers.attach(layer_surface.wl_surface(), self.width, self.height);
+        layer_surface.commit();
+    }
+
+    fn handle_key(&mut self, key: Keysym) {
+        let step = if self.modifiers.ctrl { self.large_step } else { self.step };
+        let adjustment = match key {
+            Keysym::Escape | Keysym::Return | Keysym::q => {
+                info!("Exiting window mode");
+                self.exit = true;
+                return;
+            }
+            Keysym::h | Keysym::Left => Adjustment::Move(-step, 0),
+            Keysym::j | Keysym::Down => Adjustment::Move(0, step),
+            Keysym::k | Keysym::Up => Adjustment::Move(0, -step),
+            Keysym::l | Keysym::Right => Adjustment::Move(step, 0),
+            Keysym::H => Adjustment::Resize(-step, 0),
+            Keysym::J => Adjustment::Resize(0, step),
+            Keysym::K => Adjustment::Resize(0, -step),
+            Keysym::L => Adjustment::Resize(step, 0),
+            Keysym::f => Adjustment::ToggleFloating,
+            Keysym::m => Adjustment::ToggleMaximized,
+            Keysym::x => Adjustment::Close,
+            key => match key.key_char().and_then(|c| c.to_digit(10)) {
+                Some(n @ 1..=9) => Adjustment::MoveToWorkspace(n as u8),
+                _ => return,
+            },
+        };
+        if let Err(e) = self.window.adjust(adjustment) {
+            warn!("Failed to adjust window: {}", e);
+            return;
+        }
+        if adjustment.removes() {
+            info!("Window {:?}, exiting window mode", adjustment);
+            self.exit = true;
+        } else if matches!(adjustment, Adjustment::ToggleFloating | Adjustment::ToggleMaximized) {
+            // The compositor picks the new geometry, so read it back
+            let refreshed = list().ok().and_then(|windows| windows.into_iter().find(|w| w.id == self.window.id));
+            if let Some(window) = refreshed {
+                self.window = window;
+            }
+        } else {
+            self.window.apply(adjustment);
+        }
+    }
+}
+
+impl CompositorHandler for WindowState {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
+    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.enter(rect);
+            self.draw();
+        }
+    }
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(rect) = self.output_state.info(output).as_ref().and_then(OutputRect::from_info) {
+            self.outputs.leave(&rect.name);
+        }
+    }
+}
+
+impl OutputHandler for WindowState {
+    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl LayerShellHandler for WindowState {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        self.exit = true;
+    }
+
+    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
+        self.width = configure.new_size.0;
+        self.height = configure.new_size.1;
+        self.configured = true;
+        self.draw();
+    }
+}
+
+impl SeatHandler for WindowState {
+    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+        }
+    }
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, cap: Capability) {
+        if cap == Capability::Keyboard { self.keyboard = None; }
+    }
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for WindowState {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        self.handle_key(event.keysym);
+        self.draw();
+    }
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, modifiers: Modifiers, _: u32) {
+        self.modifiers = modifiers;
+    }
+}
+
+impl ShmHandler for WindowState {
+    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+}
+
+impl ProvidesRegistryState for WindowState {
+    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(WindowState);
+delegate_output!(WindowState);
+delegate_shm!(WindowState);
+delegate_seat!(WindowState);
+delegate_keyboard!(WindowState);
+delegate_layer!(WindowState);
+delegate_registry!(WindowState);
+delegate_noop!(WindowState: ignore wp_viewporter::WpViewporter);
+delegate_noop!(WindowState: ignore wp_viewport::WpViewport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_and_commands() {
+        let clients: Value = serde_json::from_str(
+            r#"[
+                {"address": "0xa1", "mapped": true, "hidden": false, "at": [10, 20], "size": [800, 600],
+                 "workspace": {"id": 1}, "class": "firefox", "title": "Docs", "pid": 300},
+                {"address": "0xb2", "mapped": true, "hidden": false, "at": [0, 0], "size": [100, 100],
+                 "workspace": {"id": 2}, "class": "foot", "title": "shell", "pid": 400}
+            ]"#,
+        )
+        .unwrap();
+        let monitors: Value =
+            serde_json::from_str(r#"[{"activeWorkspace": {"id": 1}, "specialWorkspace": {"id": 0}}]"#).unwrap();
+        let windows = hyprland_windows(&clients, &monitors);
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].element().name, "firefox: Docs");
+
+        let element = |bus: &str, x: i32| ClickableElement {
+            x,
+            y: 100,
+            width: 20,
+            height: 20,
+            bus_name: bus.to_string(),
+            ..Default::default()
+        };
+        let pids = HashMap::from([(":1.3".to_string(), 300), (":1.4".to_string(), 400)]);
+        let mut elements = vec![element(":1.3", 50), element(":1.3", 900), element(":1.4", 50), element(":1.9", 50)];
+        retain_shown(&mut elements, &windows, &pids, (0, 0));
+        let kept: Vec<_> = elements.iter().map(|e| (e.bus_name.as_str(), e.x)).collect();
+        assert_eq!(kept, vec![(":1.3", 50), (":1.9", 50)]);
+        let mut elements = vec![element(":1.3", 50), element(":1.3", 900)];
+        retain_shown(&mut elements, &windows, &pids, (-850, 0));
+        let kept: Vec<_> = elements.iter().map(|e| e.x).collect();
+        assert_eq!(kept, vec![900]);
+        assert_eq!(
+            command(&windows[0].id, Adjustment::Move(-20, 0)),
+            ("hyprctl", vec!["dispatch".to_string(), "movewindowpixel".to_string(), "-20 0,address:0xa1".to_string()])
+        );
+
+        let tree: Value = serde_json::from_str(
+            r#"{"id": 1, "nodes": [{"id": 4, "nodes": [
+                {"id": 7, "pid": 100, "visible": true, "app_id": "foot", "name": "shell",
+                 "rect": {"x": 0, "y": 30, "width": 640, "height": 480}, "nodes": []},
+                {"id": 8, "pid": 101, "visible": false, "name": "hidden", "rect": {}, "nodes": []}
+            ]}]}"#,
+        )
+        .unwrap();
+        let mut windows = Vec::new();
+        sway_windows(&tree, &mut windows);
+        assert_eq!(windows.len(), 1);
+        assert_eq!((windows[0].y, windows[0].height), (30, 480));
+        assert_eq!(
+            command(&windows[0].id, Adjustment::Resize(0, -20)).1,
+            vec!["[con_id=7] resize shrink height 20 px".to_string()]
+        );
+        assert_eq!(
+            command(&windows[0].id, Adjustment::MoveToWorkspace(3)).1,
+            vec!["[con_id=7] move container to workspace number 3".to_string()]
+        );
+        assert_eq!(
+            command(&WindowId::Hyprland("0xa1".to_string()), Adjustment::ToggleMaximized).1,
+            vec!["--batch".to_string(), "dispatch focuswindow address:0xa1; dispatch fullscreen 1".to_string()]
+        );
+    }
+}
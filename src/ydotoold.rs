@@ -0,0 +1,210 @@
+use crate::click::on_path;
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, Once, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How long ydotoold gets to create its socket
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the ydotoold we started is checked on
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether to start ydotoold ourselves (`click.ydotool.manage_daemon`)
+static MANAGE: AtomicBool = AtomicBool::new(false);
+
+/// The ydotoold we started, until `shutdown`
+static MANAGED: Mutex<Option<Managed>> = Mutex::new(None);
+
+/// Socket of the ydotoold we started, passed to every ydotool command
+static SOCKET: OnceLock<PathBuf> = OnceLock::new();
+
+struct Managed {
+    child: Child,
+    socket: PathBuf,
+}
+
+/// Start a private ydotoold when ydotool is first used and none is running
+pub fn set_managed(manage: bool) {
+    MANAGE.store(manage, Ordering::Relaxed);
+}
+
+/// Sockets ydotool may talk to ydotoold on, most specific first
+pub(crate) fn sockets(env: Option<PathBuf>, runtime_dir: Option<PathBuf>) -> Vec<PathBuf> {
+    match env {
+        Some(socket) => vec![socket],
+        None => runtime_dir
+            .map(|dir| dir.join(".ydotool_socket"))
+            .into_iter()
+            .chain([PathBuf::from("/tmp/.ydotool_socket")])
+            .collect(),
+    }
+}
+
+/// Whether this user may connect to the socket at `path`
+pub(crate) fn writable(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string for the duration of the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Whether a ydotoold we may use listens where ydotool looks
+fn running() -> bool {
+    let env = std::env::var_os("YDOTOOL_SOCKET").map(PathBuf::from);
+    sockets(env, dirs::runtime_dir())
+        .iter()
+        .any(|socket| socket.exists() && writable(socket))
+}
+
+/// Start ydotoold on `socket` and wait for it to listen
+///
+/// It gets SIGTERM when the thread starting it exits, so it can't outlive us
+/// even when we are killed.
+fn spawn(socket: &Path) -> Result<Child> {
+    let _ = std::fs::remove_file(socket);
+    let mut command = Command::new("ydotoold");
+    command
+        .arg(format!("--socket-path={}", socket.display()))
+        .arg("--socket-perm=0600")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: prctl is async-signal-safe and touches no memory of ours
+    unsafe {
+        command.pre_exec(|| {
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().context("Failed to start ydotoold")?;
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while !socket.exists() {
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("ydotoold exited ({}); it needs write access to /dev/uinput", status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("ydotoold did not create {} in time", socket.display());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    Ok(child)
+}
+
+/// Start ydotoold, report its socket, then restart it whenever it dies
+///
+/// Runs on its own thread for the life of the process, which is what ties
+/// ydotoold's lifetime to ours.
+fn supervise(socket: PathBuf, ready: mpsc::Sender<Result<()>>) {
+    let child = match spawn(&socket) {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    info!("Started ydotoold on {}", socket.display());
+    *MANAGED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Managed {
+        child,
+        socket: socket.clone(),
+    });
+    let _ = ready.send(Ok(()));
+
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+        let mut managed = MANAGED.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(current) = managed.as_mut() else {
+            return;
+        };
+        if let Ok(Some(status)) = current.child.try_wait() {
+            warn!("ydotoold exited ({}), restarting it", status);
+            match spawn(&socket) {
+                Ok(child) => current.child = child,
+                Err(e) => {
+                    warn!("Failed to restart ydotoold: {:#}", e);
+                    *managed = None;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Socket for ydotool commands, starting our own ydotoold the first time if
+/// management is on and none is running
+pub(crate) fn socket() -> Option<&'static Path> {
+    static STARTED: Once = Once::new();
+    if !MANAGE.load(Ordering::Relaxed) {
+        return None;
+    }
+    STARTED.call_once(|| {
+        if running() {
+            debug!("ydotoold is already running");
+            return;
+        }
+        if !on_path("ydotoold") {
+            warn!("click.ydotool.manage_daemon is set but ydotoold is not installed");
+            return;
+        }
+        let socket = dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("vimium-linux-ydotool.socket");
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let thread = {
+            let socket = socket.clone();
+            thread::Builder::new()
+                .name("ydotoold".to_string())
+                .spawn(move || supervise(socket, ready_tx))
+        };
+        if let Err(e) = thread {
+            warn!("Failed to start ydotoold: {}", e);
+            return;
+        }
+        match ready_rx.recv() {
+            Ok(Ok(())) => {
+                let _ = SOCKET.set(socket);
+            }
+            Ok(Err(e)) => warn!("Failed to start ydotoold: {:#}", e),
+            Err(_) => warn!("Failed to start ydotoold"),
+        }
+    });
+    SOCKET.get().map(PathBuf::as_path)
+}
+
+/// Stop the ydotoold we started, if any
+pub fn shutdown() {
+    let Some(mut managed) = MANAGED.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let _ = managed.child.kill();
+    let _ = managed.child.wait();
+    let _ = std::fs::remove_file(&managed.socket);
+    debug!("Stopped ydotoold");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sockets() {
+        let runtime = Some(PathBuf::from("/run/user/1000"));
+        assert_eq!(
+            sockets(None, runtime.clone()),
+            vec![PathBuf::from("/run/user/1000/.ydotool_socket"), PathBuf::from("/tmp/.ydotool_socket")]
+        );
+        assert_eq!(sockets(Some("/tmp/y.sock".into()), runtime), vec![PathBuf::from("/tmp/y.sock")]);
+        assert_eq!(sockets(None, None), vec![PathBuf::from("/tmp/.ydotool_socket")]);
+        assert!(!writable(Path::new("/nonexistent/ydotool.socket")));
+    }
+}